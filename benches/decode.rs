@@ -0,0 +1,139 @@
+use clap::Parser;
+use computer_enhance::{
+    decoder::{decode, decode_with},
+    environment::Environment,
+    failure::{Failed, Failure, EXIT_STATUSES},
+    metrics::ticks_to_seconds,
+    repetition_tester::{Bench, BenchArgs, Results},
+};
+use std::{
+    fs,
+    hint::black_box,
+    io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Time the decoder over the course listings and a large synthetic binary, repeating each
+/// until it stops getting faster, and report instructions and bytes decoded a second. Run with
+/// `cargo bench --bench decode`, and its options after `--`.
+#[derive(Parser)]
+#[command(name = "decode", after_help = EXIT_STATUSES)]
+struct Args {
+    /// Binaries to decode, each timed alone. The course listings in --listings by default.
+    inputs: Vec<PathBuf>,
+
+    /// Directory of course listings: the files in it without an extension.
+    #[arg(long, value_name = "DIR", default_value = "listings")]
+    listings: PathBuf,
+
+    /// Also decode the inputs repeated end to end up to this many bytes, or not at all if 0.
+    #[arg(long, value_name = "BYTES", default_value_t = 16 << 20)]
+    synthetic: usize,
+
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Passed by `cargo bench`, to tell benchmarks from tests with the same harness.
+    #[arg(long, hide = true)]
+    bench: bool,
+}
+
+/// The binaries in `listings`: every file without an extension, in name order.
+fn course_listings(listings: &Path) -> Result<Vec<PathBuf>, Failed> {
+    let failed = |error: io::Error| Failure::Io.failed(format!("{}: {error}", listings.display()));
+    let entries = fs::read_dir(listings).map_err(failed)?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(failed)?.path();
+        if path.is_file() && path.extension().is_none() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Times decoding `bytes` into a `Vec`, and with `decode_with` visiting each instruction
+/// instead, printing how many instructions a second each managed at its fastest.
+fn bench_decoding(bench: &mut Bench, name: &str, bytes: &[u8]) -> Result<(), Failed> {
+    let instructions = decode(bytes)
+        .map_err(|error| Failure::from(&error).failed(format!("{name}: {error}")))?
+        .len() as u64;
+    let size = bytes.len() as u64;
+    let collected = bench.run(&format!("{name} (collect)"), size, || {
+        black_box(decode(black_box(bytes)).ok());
+    });
+    print_rate(collected, instructions);
+    let visited = bench.run(&format!("{name} (visit)"), size, || {
+        let mut count = 0u64;
+        let _ = decode_with(black_box(bytes), |_, _| {
+            count += 1;
+            ControlFlow::<()>::Continue(())
+        });
+        black_box(count);
+    });
+    print_rate(visited, instructions);
+    Ok(())
+}
+
+fn print_rate(results: Option<Results>, instructions: u64) {
+    let Some(results) = results else { return };
+    let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+    if seconds > 0.0 {
+        let rate = instructions as f64 / seconds;
+        println!(
+            "Best: {:.2}M instructions/s ({instructions} instructions)",
+            rate / 1e6
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    println!("{}", Environment::detect());
+    let inputs = if args.inputs.is_empty() {
+        match course_listings(&args.listings) {
+            Ok(paths) => paths,
+            Err(failed) => return failed.report(),
+        }
+    } else {
+        args.inputs.clone()
+    };
+    let mut files = Vec::new();
+    for path in &inputs {
+        match fs::read(path) {
+            Ok(bytes) => files.push((path.display().to_string(), bytes)),
+            Err(error) => {
+                eprintln!("{}: {error}", path.display());
+                return Failure::Io.into();
+            }
+        }
+    }
+
+    let mut bench = args.bench_args.bench();
+    let mut status = None;
+    let mut report = |result: Result<(), Failed>| {
+        if let Err(failed) = result {
+            status = status.or(Some(failed.report()));
+        }
+    };
+    for (name, bytes) in &files {
+        report(bench_decoding(&mut bench, name, bytes));
+    }
+
+    // Whole files end on instruction boundaries, so repeating them decodes as cleanly as
+    // they do on their own.
+    let corpus: Vec<u8> = files.iter().flat_map(|(_, bytes)| bytes).copied().collect();
+    if args.synthetic != 0 && !corpus.is_empty() {
+        let mut synthetic = Vec::with_capacity(args.synthetic + corpus.len());
+        while synthetic.len() < args.synthetic {
+            synthetic.extend_from_slice(&corpus);
+        }
+        let name = format!("synthetic {}KB", synthetic.len() / 1024);
+        report(bench_decoding(&mut bench, &name, &synthetic));
+    }
+    let finished = bench.finish();
+    status.unwrap_or(finished)
+}
@@ -0,0 +1,428 @@
+#![allow(non_snake_case)]
+
+use computer_enhance::{
+    decoder::Decoder,
+    instruction::{Instruction, Operand, Register, Rep},
+};
+use std::{
+    ffi::{c_char, c_void, CStr},
+    ptr, slice,
+};
+
+// The structures and functions of sim86_shared.h, the header of the course's reference decoder,
+// at the version below. C code including that header can link against this library instead.
+const SIM86_VERSION: u32 = 4;
+
+pub const INST_LOCK: u32 = 0x1;
+pub const INST_REP: u32 = 0x2;
+pub const INST_SEGMENT: u32 = 0x4;
+pub const INST_WIDE: u32 = 0x8;
+pub const INST_FAR: u32 = 0x10;
+pub const INST_REPNE: u32 = 0x20;
+
+pub const ADDRESS_EXPLICIT_SEGMENT: u32 = 0x1;
+
+pub const IMMEDIATE_RELATIVE_JUMP_DISPLACEMENT: u32 = 0x1;
+
+pub const OPERAND_NONE: u32 = 0;
+pub const OPERAND_REGISTER: u32 = 1;
+pub const OPERAND_MEMORY: u32 = 2;
+pub const OPERAND_IMMEDIATE: u32 = 3;
+
+/// Part or all of a register: `count` bytes from byte `offset`, so AH is offset 1, count 1.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegisterAccess {
+    pub index: u32,
+    pub offset: u32,
+    pub count: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EffectiveAddressTerm {
+    pub register: RegisterAccess,
+    pub scale: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EffectiveAddressExpression {
+    pub terms: [EffectiveAddressTerm; 2],
+    /// The segment of a direct far CALL or JMP, with `ADDRESS_EXPLICIT_SEGMENT`.
+    pub explicit_segment: u32,
+    pub displacement: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Immediate {
+    pub value: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union OperandPayload {
+    pub address: EffectiveAddressExpression,
+    pub register: RegisterAccess,
+    pub immediate: Immediate,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InstructionOperand {
+    /// One of the `OPERAND_` kinds, which says which member of the payload is set.
+    pub kind: u32,
+    pub payload: OperandPayload,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Sim86Instruction {
+    pub address: u32,
+    pub size: u32,
+    /// An index into the operation types, or 0 if nothing could be decoded.
+    pub op: u32,
+    pub flags: u32,
+    pub operands: [InstructionOperand; 2],
+    /// The register index of a segment override, with `INST_SEGMENT`.
+    pub segment_override: u32,
+}
+
+/// The reference's table of encodings, which this library doesn't have.
+#[repr(C)]
+pub struct InstructionTable {
+    pub encodings: *const c_void,
+    pub encoding_count: u32,
+    pub max_instruction_byte_count: u32,
+}
+
+/// The operation types, in the reference's order, named by their mnemonics; the first is none.
+/// Its prefixes have types of their own, and the rest have the same order as `Mnemonic`.
+const OPERATIONS: &[&CStr] = &[
+    c"", c"mov", c"push", c"pop", c"xchg", c"in", c"out", c"xlat", c"lea", c"lds", c"les", c"lahf",
+    c"sahf", c"pushf", c"popf", c"add", c"adc", c"inc", c"aaa", c"daa", c"sub", c"sbb", c"dec",
+    c"neg", c"cmp", c"aas", c"das", c"mul", c"imul", c"aam", c"div", c"idiv", c"aad", c"cbw",
+    c"cwd", c"not", c"shl", c"shr", c"sar", c"rol", c"ror", c"rcl", c"rcr", c"and", c"test", c"or",
+    c"xor", c"rep", c"movs", c"cmps", c"scas", c"lods", c"stos", c"call", c"jmp", c"ret", c"retf",
+    c"je", c"jl", c"jle", c"jb", c"jbe", c"jp", c"jo", c"js", c"jne", c"jnl", c"jg", c"jnb", c"ja",
+    c"jnp", c"jno", c"jns", c"loop", c"loopz", c"loopnz", c"jcxz", c"int", c"int3", c"into",
+    c"iret", c"clc", c"cmc", c"stc", c"cld", c"std", c"cli", c"sti", c"hlt", c"wait", c"esc",
+    c"lock", c"segment",
+];
+
+/// Register names by index: the low byte, the high byte, and the whole register.
+const REGISTER_NAMES: [[&CStr; 3]; 15] = [
+    [c"", c"", c""],
+    [c"al", c"ah", c"ax"],
+    [c"bl", c"bh", c"bx"],
+    [c"cl", c"ch", c"cx"],
+    [c"dl", c"dh", c"dx"],
+    [c"sp"; 3],
+    [c"bp"; 3],
+    [c"si"; 3],
+    [c"di"; 3],
+    [c"es"; 3],
+    [c"cs"; 3],
+    [c"ss"; 3],
+    [c"ds"; 3],
+    [c"ip"; 3],
+    [c"flags"; 3],
+];
+
+fn register_access(register: Register) -> RegisterAccess {
+    use Register::*;
+    let (index, offset, count) = match register {
+        Al => (1, 0, 1),
+        Ah => (1, 1, 1),
+        Ax => (1, 0, 2),
+        Bl => (2, 0, 1),
+        Bh => (2, 1, 1),
+        Bx => (2, 0, 2),
+        Cl => (3, 0, 1),
+        Ch => (3, 1, 1),
+        Cx => (3, 0, 2),
+        Dl => (4, 0, 1),
+        Dh => (4, 1, 1),
+        Dx => (4, 0, 2),
+        Sp => (5, 0, 2),
+        Bp => (6, 0, 2),
+        Si => (7, 0, 2),
+        Di => (8, 0, 2),
+        Es => (9, 0, 2),
+        Cs => (10, 0, 2),
+        Ss => (11, 0, 2),
+        Ds => (12, 0, 2),
+    };
+    RegisterAccess {
+        index,
+        offset,
+        count,
+    }
+}
+
+impl InstructionOperand {
+    const NONE: InstructionOperand = InstructionOperand {
+        kind: OPERAND_NONE,
+        payload: OperandPayload {
+            address: EffectiveAddressExpression {
+                terms: [EffectiveAddressTerm {
+                    register: RegisterAccess {
+                        index: 0,
+                        offset: 0,
+                        count: 0,
+                    },
+                    scale: 0,
+                }; 2],
+                explicit_segment: 0,
+                displacement: 0,
+                flags: 0,
+            },
+        },
+    };
+
+    fn new(operand: Option<Operand>) -> InstructionOperand {
+        let mut converted = InstructionOperand::NONE;
+        let (kind, payload) = (&mut converted.kind, &mut converted.payload);
+        match operand {
+            None => {}
+            Some(Operand::Register(register)) => {
+                *kind = OPERAND_REGISTER;
+                payload.register = register_access(register);
+            }
+            Some(Operand::Memory(address)) => {
+                let mut terms = [EffectiveAddressTerm::default(); 2];
+                for (term, &register) in terms.iter_mut().zip(address.base.registers()) {
+                    term.register = register_access(register);
+                    term.scale = 1;
+                }
+                *kind = OPERAND_MEMORY;
+                payload.address = EffectiveAddressExpression {
+                    terms,
+                    displacement: address.displacement as i32,
+                    ..Default::default()
+                };
+            }
+            Some(Operand::Immediate(value)) => {
+                *kind = OPERAND_IMMEDIATE;
+                payload.immediate = Immediate { value, flags: 0 };
+            }
+            Some(Operand::Relative(displacement)) => {
+                *kind = OPERAND_IMMEDIATE;
+                payload.immediate = Immediate {
+                    value: displacement as i32,
+                    flags: IMMEDIATE_RELATIVE_JUMP_DISPLACEMENT,
+                };
+            }
+            Some(Operand::Far { segment, offset }) => {
+                *kind = OPERAND_MEMORY;
+                payload.address = EffectiveAddressExpression {
+                    explicit_segment: segment as u32,
+                    displacement: offset as i32,
+                    flags: ADDRESS_EXPLICIT_SEGMENT,
+                    ..Default::default()
+                };
+            }
+        }
+        converted
+    }
+}
+
+impl Sim86Instruction {
+    /// What's written when nothing could be decoded.
+    const NONE: Sim86Instruction = Sim86Instruction {
+        address: 0,
+        size: 0,
+        op: 0,
+        flags: 0,
+        operands: [InstructionOperand::NONE; 2],
+        segment_override: 0,
+    };
+
+    fn new(instruction: &Instruction) -> Sim86Instruction {
+        let name = instruction.mnemonic.name();
+        let op = OPERATIONS
+            .iter()
+            .position(|operation| operation.to_bytes() == name.as_bytes())
+            .unwrap_or(0);
+        let flag = |set: bool, flag: u32| if set { flag } else { 0 };
+        Sim86Instruction {
+            address: instruction.offset as u32,
+            size: instruction.length as u32,
+            op: op as u32,
+            flags: flag(instruction.lock, INST_LOCK)
+                | flag(instruction.rep == Some(Rep::Rep), INST_REP)
+                | flag(instruction.rep == Some(Rep::Repne), INST_REPNE)
+                | flag(instruction.segment_override.is_some(), INST_SEGMENT)
+                | flag(instruction.wide, INST_WIDE)
+                | flag(
+                    instruction.far
+                        || (instruction.operands.iter())
+                            .any(|operand| matches!(operand, Some(Operand::Far { .. }))),
+                    INST_FAR,
+                ),
+            operands: instruction.operands.map(InstructionOperand::new),
+            segment_override: instruction
+                .segment_override
+                .map_or(0, |register| register_access(register).index),
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Sim86_GetVersion() -> u32 {
+    SIM86_VERSION
+}
+
+/// Decodes the instruction at the start of the `source_size` bytes at `source` into `dest`, or
+/// writes one whose `op` is 0 if there isn't a whole instruction there.
+///
+/// # Safety
+/// `source` points to `source_size` readable bytes, and `dest` to an instruction to write.
+#[no_mangle]
+pub unsafe extern "C" fn Sim86_Decode8086Instruction(
+    source_size: u32,
+    source: *const u8,
+    dest: *mut Sim86Instruction,
+) {
+    let bytes = if source.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(source, source_size as usize)
+    };
+    let decoded = match Decoder::new(bytes).next_instruction() {
+        Ok(Some(instruction)) => Sim86Instruction::new(&instruction),
+        _ => Sim86Instruction::NONE,
+    };
+    dest.write(decoded);
+}
+
+/// The name of the register, or part of one, that `access` refers to.
+///
+/// # Safety
+/// `access` points to a register access.
+#[no_mangle]
+pub unsafe extern "C" fn Sim86_RegisterNameFromOperand(
+    access: *const RegisterAccess,
+) -> *const c_char {
+    let access = &*access;
+    let names = REGISTER_NAMES[access.index as usize % REGISTER_NAMES.len()];
+    let name = if access.count == 2 {
+        names[2]
+    } else {
+        names[access.offset as usize & 1]
+    };
+    name.as_ptr()
+}
+
+/// The mnemonic of operation type `op`, or an empty string if there's no such type.
+#[no_mangle]
+pub extern "C" fn Sim86_MnemonicFromOperationType(op: u32) -> *const c_char {
+    OPERATIONS.get(op as usize).unwrap_or(&c"").as_ptr()
+}
+
+/// Fills in `dest` with no encodings, since this decoder isn't driven by a table, and the
+/// longest instruction without prefixes.
+///
+/// # Safety
+/// `dest` points to a table to write.
+#[no_mangle]
+pub unsafe extern "C" fn Sim86_Get8086InstructionTable(dest: *mut InstructionTable) {
+    dest.write(InstructionTable {
+        encodings: ptr::null(),
+        encoding_count: 0,
+        max_instruction_byte_count: 6,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Sim86Instruction {
+        let mut decoded = Sim86Instruction::NONE;
+        unsafe { Sim86_Decode8086Instruction(bytes.len() as u32, bytes.as_ptr(), &mut decoded) };
+        decoded
+    }
+
+    fn mnemonic(op: u32) -> &'static str {
+        unsafe { CStr::from_ptr(Sim86_MnemonicFromOperationType(op)) }
+            .to_str()
+            .unwrap()
+    }
+
+    fn register_name(access: RegisterAccess) -> &'static str {
+        unsafe { CStr::from_ptr(Sim86_RegisterNameFromOperand(&access)) }
+            .to_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn instructions_are_laid_out_as_the_header_says() {
+        // mov cx, bx
+        let decoded = decode(&[0x89, 0xD9]);
+        assert_eq!((decoded.size, mnemonic(decoded.op)), (2, "mov"));
+        assert_eq!(decoded.flags, INST_WIDE);
+        assert_eq!(decoded.operands[0].kind, OPERAND_REGISTER);
+        assert_eq!(
+            register_name(unsafe { decoded.operands[0].payload.register }),
+            "cx"
+        );
+        assert_eq!(
+            register_name(unsafe { decoded.operands[1].payload.register }),
+            "bx"
+        );
+
+        // es: mov ah, [bp + di - 37]
+        let decoded = decode(&[0x26, 0x8A, 0x63, 0xDB]);
+        assert_eq!(decoded.flags, INST_SEGMENT);
+        assert_eq!(
+            register_name(unsafe { decoded.operands[0].payload.register }),
+            "ah"
+        );
+        let address = unsafe { decoded.operands[1].payload.address };
+        assert_eq!(decoded.operands[1].kind, OPERAND_MEMORY);
+        assert_eq!(register_name(address.terms[0].register), "bp");
+        assert_eq!(register_name(address.terms[1].register), "di");
+        assert_eq!(address.displacement, -37);
+        assert_eq!(decoded.segment_override, 9);
+
+        // rep movsw
+        let decoded = decode(&[0xF3, 0xA5]);
+        assert_eq!(mnemonic(decoded.op), "movs");
+        assert_eq!(decoded.flags, INST_REP | INST_WIDE);
+
+        // jmp 0x1234:0x5678
+        let decoded = decode(&[0xEA, 0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(decoded.flags & INST_FAR, INST_FAR);
+        let address = unsafe { decoded.operands[0].payload.address };
+        assert_eq!(address.flags, ADDRESS_EXPLICIT_SEGMENT);
+        assert_eq!(
+            (address.explicit_segment, address.displacement),
+            (0x1234, 0x5678)
+        );
+
+        // jne $-4
+        let immediate = unsafe { decode(&[0x75, 0xFC]).operands[0].payload.immediate };
+        assert_eq!(immediate.value, -4);
+        assert_eq!(immediate.flags, IMMEDIATE_RELATIVE_JUMP_DISPLACEMENT);
+
+        // Half an instruction.
+        assert_eq!(decode(&[0x89]).op, 0);
+    }
+
+    #[test]
+    fn every_mnemonic_has_an_operation_type() {
+        for opcode in 0..=0xFF {
+            for modrm in [0x00, 0x06, 0xC0, 0xD8] {
+                let bytes = [opcode, modrm, 0, 0, 0, 0];
+                if let Ok(Some(instruction)) = Decoder::new(&bytes).next_instruction() {
+                    let decoded = decode(&bytes);
+                    assert_eq!(mnemonic(decoded.op), instruction.mnemonic.name());
+                }
+            }
+        }
+    }
+}
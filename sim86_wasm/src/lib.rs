@@ -0,0 +1,100 @@
+use computer_enhance::{
+    decoder::Decoder,
+    export::{write_json, write_json_string},
+    formatter::InstructionFormatter,
+    simulator::{Cpu, Registers},
+};
+use std::{fmt::Write, ops::Range};
+use wasm_bindgen::prelude::*;
+
+/// Decodes `bytes` into the JSON `ce disasm --format json` writes: an array with an object per
+/// instruction.
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8]) -> Result<String, JsError> {
+    let mut decoder = Decoder::new(bytes);
+    let mut instructions = Vec::new();
+    while let Some(instruction) = decoder.next_instruction()? {
+        instructions.push(instruction);
+    }
+    let mut json = Vec::new();
+    write_json(
+        &mut json,
+        &instructions,
+        bytes,
+        0,
+        &InstructionFormatter::default(),
+    )?;
+    Ok(String::from_utf8(json)?)
+}
+
+/// A program loaded at address 0, run an instruction at a time, as `ce exec` runs the course's
+/// listings. It's finished once IP leaves the program.
+#[wasm_bindgen]
+pub struct Simulator {
+    cpu: Cpu,
+    program: Range<usize>,
+}
+
+#[wasm_bindgen]
+impl Simulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(code: &[u8]) -> Simulator {
+        let mut cpu = Cpu::new();
+        cpu.memory.load(0, code);
+        Simulator {
+            cpu,
+            program: 0..code.len(),
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cpu.exit_code.is_some() || !self.program.contains(&self.cpu.instruction_address())
+    }
+
+    /// Executes the next instruction, returning false instead if the program has finished.
+    pub fn step(&mut self) -> Result<bool, JsError> {
+        if self.finished() {
+            return Ok(false);
+        }
+        let Some(instruction) = self.cpu.fetch()? else {
+            return Ok(false);
+        };
+        self.cpu.execute(&instruction)?;
+        Ok(true)
+    }
+
+    /// The next instruction as NASM would write it, or `undefined` if there's none.
+    #[wasm_bindgen(js_name = nextInstruction)]
+    pub fn next_instruction(&self) -> Option<String> {
+        if self.finished() {
+            return None;
+        }
+        let instruction = self.cpu.fetch().ok()??;
+        Some(instruction.to_string())
+    }
+
+    /// The registers, IP, and flags, as a JSON object such as
+    /// `{"ax": 1, ..., "ds": 0, "ip": 3, "flags": "Z"}`.
+    pub fn state(&self) -> String {
+        let mut json = String::from("{");
+        for register in Registers::ALL {
+            let _ = write!(
+                json,
+                "\"{}\": {}, ",
+                register.name(),
+                self.cpu.registers.get(register)
+            );
+        }
+        let _ = write!(json, "\"ip\": {}, \"flags\": ", self.cpu.ip);
+        let mut flags = Vec::new();
+        let _ = write_json_string(&mut flags, &self.cpu.flags.to_string());
+        json.push_str(&String::from_utf8_lossy(&flags));
+        json.push('}');
+        json
+    }
+
+    /// `length` bytes of memory from physical `address`, cut short at the end of memory.
+    pub fn memory(&self, address: usize, length: usize) -> Vec<u8> {
+        self.cpu.memory.slice(address, length).to_vec()
+    }
+}
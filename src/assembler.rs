@@ -0,0 +1,1022 @@
+use crate::{
+    encoder::{data, encode, encode_sized, is_count_or_port, register_code},
+    instruction::{AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register, Rep},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    str::FromStr,
+};
+
+/// Passes to give up after if label addresses are still moving, which can only happen when an
+/// instruction sized by a label's value moves that label.
+const MAX_PASSES: usize = 32;
+
+/// Why a line of source couldn't be assembled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+/// A BYTE or WORD keyword.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Size {
+    Byte,
+    Word,
+}
+
+/// A SHORT or NEAR keyword on a jump target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Distance {
+    Short,
+    Near,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A mnemonic, register, keyword, or symbol, as written.
+    Word(String),
+    Number(i64),
+    /// A quoted string, or character constant.
+    Text(Vec<u8>),
+    Symbol(char),
+}
+
+/// A number written with a `0x`, `0b`, `0o`, or `0d` prefix, an `h` suffix, or in decimal.
+fn number(word: &str) -> Result<i64, String> {
+    let lower = word.to_ascii_lowercase().replace('_', "");
+    let (digits, radix) = if let Some(digits) = lower.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_suffix('h') {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix("0b") {
+        (digits, 2)
+    } else if let Some(digits) = lower.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = lower.strip_prefix("0d") {
+        (digits, 10)
+    } else {
+        (lower.as_str(), 10)
+    };
+    digits
+        .bytes()
+        .all(|digit| digit.is_ascii_hexdigit())
+        .then(|| i64::from_str_radix(digits, radix).ok())
+        .flatten()
+        .ok_or_else(|| format!("invalid number {word:?}"))
+}
+
+fn is_word_character(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"_.?@$#~".contains(&c)
+}
+
+/// Splits a line into tokens, up to any `;` comment.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while let Some(&c) = bytes.get(index) {
+        match c {
+            b';' => break,
+            _ if c.is_ascii_whitespace() => index += 1,
+            b'\'' | b'"' | b'`' => {
+                let length = text[index + 1..]
+                    .find(c as char)
+                    .ok_or("unterminated string")?;
+                let contents = &text[index + 1..index + 1 + length];
+                tokens.push(Token::Text(contents.as_bytes().to_vec()));
+                index += length + 2;
+            }
+            _ if is_word_character(c) => {
+                let length = bytes[index..]
+                    .iter()
+                    .position(|&c| !is_word_character(c))
+                    .unwrap_or(bytes.len() - index);
+                let word = &text[index..index + length];
+                index += length;
+                tokens.push(match word.strip_prefix('$') {
+                    _ if c.is_ascii_digit() => Token::Number(number(word)?),
+                    // `$0ff` is a hex number, and `$name` a symbol that might otherwise be
+                    // taken for a keyword.
+                    Some(hex) if hex.starts_with(|c: char| c.is_ascii_digit()) => {
+                        Token::Number(number(&format!("0x{hex}"))?)
+                    }
+                    Some(name) if !name.is_empty() && name != "$" => Token::Word(name.to_string()),
+                    _ => Token::Word(word.to_string()),
+                });
+            }
+            b'[' | b']' | b',' | b':' | b'+' | b'-' => {
+                tokens.push(Token::Symbol(c as char));
+                index += 1;
+            }
+            _ => {
+                let c = text[index..].chars().next().unwrap_or('?');
+                return Err(format!("unexpected {c:?}"));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+    /// `$`, the address of the line's first byte.
+    Here,
+    /// `$$`, the address the code starts at.
+    Start,
+}
+
+/// Terms added together, each with its sign: `label_0 + 4`, `$-2`.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Expression(Vec<(i64, Term)>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Register(Register),
+    Memory {
+        segment: Option<Register>,
+        registers: Vec<Register>,
+        displacement: Expression,
+    },
+    Expression(Expression),
+    /// `segment:offset`.
+    Far {
+        segment: Expression,
+        offset: Expression,
+    },
+}
+
+/// An operand as written, with any keywords before it.
+#[derive(Clone, Debug, PartialEq)]
+struct Argument {
+    value: Value,
+    size: Option<Size>,
+    distance: Option<Distance>,
+    far: bool,
+}
+
+/// An instruction as written.
+#[derive(Clone, Debug, PartialEq)]
+struct Source {
+    mnemonic: Mnemonic,
+    /// Set by the `B` or `W` ending of a string mnemonic.
+    string_wide: Option<bool>,
+    arguments: Vec<Argument>,
+    lock: bool,
+    rep: Option<Rep>,
+    segment: Option<Register>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Data {
+    Text(Vec<u8>),
+    Expression(Expression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Org(Expression),
+    Equ(String, Expression),
+    /// `db` or, if wide, `dw`.
+    Data {
+        wide: bool,
+        items: Vec<Data>,
+    },
+    Instruction(Source),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Line {
+    number: usize,
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+/// The mnemonic a name spells, NASM's alternative spellings included, with the width the
+/// ending of a string instruction gives it.
+fn mnemonic(name: &str) -> Option<(Mnemonic, Option<bool>)> {
+    use Mnemonic::*;
+    let string = match name {
+        "movsb" | "movsw" => Some(Movs),
+        "cmpsb" | "cmpsw" => Some(Cmps),
+        "scasb" | "scasw" => Some(Scas),
+        "lodsb" | "lodsw" => Some(Lods),
+        "stosb" | "stosw" => Some(Stos),
+        _ => None,
+    };
+    if let Some(string) = string {
+        return Some((string, Some(name.ends_with('w'))));
+    }
+    let mnemonic = match name {
+        "mov" => Mov,
+        "push" => Push,
+        "pop" => Pop,
+        "xchg" | "nop" => Xchg,
+        "in" => In,
+        "out" => Out,
+        "xlat" | "xlatb" => Xlat,
+        "lea" => Lea,
+        "lds" => Lds,
+        "les" => Les,
+        "lahf" => Lahf,
+        "sahf" => Sahf,
+        "pushf" => Pushf,
+        "popf" => Popf,
+        "add" => Add,
+        "adc" => Adc,
+        "inc" => Inc,
+        "aaa" => Aaa,
+        "daa" => Daa,
+        "sub" => Sub,
+        "sbb" => Sbb,
+        "dec" => Dec,
+        "neg" => Neg,
+        "cmp" => Cmp,
+        "aas" => Aas,
+        "das" => Das,
+        "mul" => Mul,
+        "imul" => Imul,
+        "aam" => Aam,
+        "div" => Div,
+        "idiv" => Idiv,
+        "aad" => Aad,
+        "cbw" => Cbw,
+        "cwd" => Cwd,
+        "not" => Not,
+        "shl" | "sal" => Shl,
+        "shr" => Shr,
+        "sar" => Sar,
+        "rol" => Rol,
+        "ror" => Ror,
+        "rcl" => Rcl,
+        "rcr" => Rcr,
+        "and" => And,
+        "test" => Test,
+        "or" => Or,
+        "xor" => Xor,
+        "call" => Call,
+        "jmp" => Jmp,
+        "ret" | "retn" => Ret,
+        "retf" => Retf,
+        "je" | "jz" => Je,
+        "jl" | "jnge" => Jl,
+        "jle" | "jng" => Jle,
+        "jb" | "jnae" | "jc" => Jb,
+        "jbe" | "jna" => Jbe,
+        "jp" | "jpe" => Jp,
+        "jo" => Jo,
+        "js" => Js,
+        "jne" | "jnz" => Jne,
+        "jnl" | "jge" => Jnl,
+        "jg" | "jnle" => Jg,
+        "jnb" | "jae" | "jnc" => Jnb,
+        "ja" | "jnbe" => Ja,
+        "jnp" | "jpo" => Jnp,
+        "jno" => Jno,
+        "jns" => Jns,
+        "loop" => Loop,
+        "loopz" | "loope" => Loopz,
+        "loopnz" | "loopne" => Loopnz,
+        "jcxz" => Jcxz,
+        "int" => Int,
+        "int3" => Int3,
+        "into" => Into,
+        "iret" => Iret,
+        "clc" => Clc,
+        "cmc" => Cmc,
+        "stc" => Stc,
+        "cld" => Cld,
+        "std" => Std,
+        "cli" => Cli,
+        "sti" => Sti,
+        "hlt" => Hlt,
+        "wait" | "fwait" => Wait,
+        "esc" => Esc,
+        _ => return None,
+    };
+    Some((mnemonic, None))
+}
+
+fn register(token: Option<&Token>) -> Option<Register> {
+    match token {
+        Some(Token::Word(word)) => Register::from_str(word).ok(),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn at_end(&self) -> bool {
+        self.position == self.tokens.len()
+    }
+
+    /// The next token in lower case, if it's a word.
+    fn keyword(&self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        self.position += found as usize;
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => Err(format!("expected `{symbol}`")),
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let token = self.peek().ok_or("expected a value")?;
+        let term = match token {
+            Token::Number(value) => Term::Number(*value),
+            // A character constant, stored little-endian as NASM does.
+            Token::Text(text) if text.len() <= 2 => Term::Number(
+                text.iter()
+                    .rev()
+                    .fold(0, |value, &byte| value << 8 | byte as i64),
+            ),
+            Token::Word(word) if word == "$" => Term::Here,
+            Token::Word(word) if word == "$$" => Term::Start,
+            Token::Word(word) if register(Some(token)).is_none() => Term::Symbol(word.clone()),
+            token => return Err(format!("unexpected {}", describe(token))),
+        };
+        self.position += 1;
+        Ok(term)
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut terms = Vec::new();
+        let mut sign = match () {
+            _ if self.eat('-') => -1,
+            _ => {
+                self.eat('+');
+                1
+            }
+        };
+        loop {
+            terms.push((sign, self.term()?));
+            sign = match () {
+                _ if self.eat('+') => 1,
+                _ if self.eat('-') => -1,
+                _ => return Ok(Expression(terms)),
+            };
+        }
+    }
+
+    /// The inside of `[...]`, after the bracket.
+    fn memory(&mut self, mut segment: Option<Register>) -> Result<Value, String> {
+        if let Some(register) = register(self.peek()).filter(|register| register.is_segment()) {
+            if self.tokens.get(self.position + 1) == Some(&Token::Symbol(':')) {
+                segment = Some(register);
+                self.position += 2;
+            }
+        }
+        let mut registers = Vec::new();
+        let mut displacement = Vec::new();
+        let mut sign = if self.eat('-') { -1 } else { 1 };
+        loop {
+            match register(self.peek()) {
+                Some(register) if sign > 0 => {
+                    registers.push(register);
+                    self.position += 1;
+                }
+                Some(register) => {
+                    return Err(format!("{} can't be subtracted", register.name()));
+                }
+                None => displacement.push((sign, self.term()?)),
+            }
+            sign = match () {
+                _ if self.eat('+') => 1,
+                _ if self.eat('-') => -1,
+                _ => break,
+            };
+        }
+        self.expect(']')?;
+        Ok(Value::Memory {
+            segment,
+            registers,
+            displacement: Expression(displacement),
+        })
+    }
+
+    fn argument(&mut self) -> Result<Argument, String> {
+        let mut argument = Argument {
+            value: Value::Expression(Expression::default()),
+            size: None,
+            distance: None,
+            far: false,
+        };
+        loop {
+            match self.keyword().as_deref() {
+                Some("byte") => argument.size = Some(Size::Byte),
+                Some("word") => argument.size = Some(Size::Word),
+                Some("short") => argument.distance = Some(Distance::Short),
+                Some("near") => argument.distance = Some(Distance::Near),
+                Some("far") => argument.far = true,
+                Some("strict") => {}
+                _ => break,
+            }
+            self.position += 1;
+        }
+
+        argument.value = if let Some(register) = register(self.peek()) {
+            self.position += 1;
+            if register.is_segment() && self.eat(':') {
+                self.expect('[')?;
+                self.memory(Some(register))?
+            } else {
+                Value::Register(register)
+            }
+        } else if self.eat('[') {
+            self.memory(None)?
+        } else {
+            let expression = self.expression()?;
+            if self.eat(':') {
+                Value::Far {
+                    segment: expression,
+                    offset: self.expression()?,
+                }
+            } else {
+                Value::Expression(expression)
+            }
+        };
+        Ok(argument)
+    }
+
+    fn data(&mut self, wide: bool) -> Result<Statement, String> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Text(text)) if text.len() > 2 || !wide && text.len() > 1 => {
+                    items.push(Data::Text(text.clone()));
+                    self.position += 1;
+                }
+                _ => items.push(Data::Expression(self.expression()?)),
+            }
+            if !self.eat(',') {
+                return Ok(Statement::Data { wide, items });
+            }
+        }
+    }
+
+    fn instruction(&mut self) -> Result<Statement, String> {
+        let (mut lock, mut rep, mut segment) = (false, None, None);
+        let name = loop {
+            let name = self.keyword().ok_or("expected an instruction")?;
+            let followed_by_word =
+                matches!(self.tokens.get(self.position + 1), Some(Token::Word(_)));
+            match name.as_str() {
+                "lock" => lock = true,
+                "rep" | "repe" | "repz" => rep = Some(Rep::Rep),
+                "repne" | "repnz" => rep = Some(Rep::Repne),
+                "es" | "cs" | "ss" | "ds" if followed_by_word => segment = register(self.peek()),
+                _ => break name,
+            }
+            self.position += 1;
+        };
+        let (mnemonic, string_wide) =
+            mnemonic(&name).ok_or_else(|| format!("unknown instruction {name:?}"))?;
+        self.position += 1;
+
+        let mut arguments = Vec::new();
+        while !self.at_end() {
+            arguments.push(self.argument()?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect_end()?;
+        // NOP is XCHG AX, AX under another name.
+        if name == "nop" && arguments.is_empty() {
+            let ax = Argument {
+                value: Value::Register(Register::Ax),
+                size: None,
+                distance: None,
+                far: false,
+            };
+            arguments = vec![ax.clone(), ax];
+        }
+        Ok(Statement::Instruction(Source {
+            mnemonic,
+            string_wide,
+            arguments,
+            lock,
+            rep,
+            segment,
+        }))
+    }
+
+    fn statement(&mut self, label: &mut Option<String>) -> Result<Option<Statement>, String> {
+        if self.at_end() {
+            return Ok(None);
+        }
+        if self.tokens.get(self.position + 1).is_some_and(
+            |token| matches!(token, Token::Word(word) if word.eq_ignore_ascii_case("equ")),
+        ) {
+            let Some(Token::Word(name)) = self.peek() else {
+                return Err(String::from("expected a name before `equ`"));
+            };
+            self.position += 2;
+            return Ok(Some(Statement::Equ(name.clone(), self.expression()?)));
+        }
+        let keyword = self.keyword();
+        let statement = match keyword.as_deref() {
+            Some("equ") => {
+                self.position += 1;
+                let name = label.take().ok_or("expected a name before `equ`")?;
+                Statement::Equ(name, self.expression()?)
+            }
+            Some("bits") => {
+                self.position += 1;
+                if self.peek() != Some(&Token::Number(16)) {
+                    return Err(String::from("only `bits 16` code can be assembled"));
+                }
+                self.position += 1;
+                return Ok(None);
+            }
+            // Only an 8086's instructions are assembled whatever the CPU.
+            Some("cpu") => return Ok(None),
+            Some("org") => {
+                self.position += 1;
+                Statement::Org(self.expression()?)
+            }
+            Some(directive @ ("db" | "dw")) => {
+                self.position += 1;
+                self.data(directive == "dw")?
+            }
+            _ => return self.instruction().map(Some),
+        };
+        self.expect_end()?;
+        Ok(Some(statement))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("{word:?}"),
+        Token::Number(value) => value.to_string(),
+        Token::Text(_) => String::from("string"),
+        Token::Symbol(symbol) => format!("`{symbol}`"),
+    }
+}
+
+fn parse_line(text: &str, number: usize) -> Result<Line, AssembleError> {
+    let failed = |message| AssembleError {
+        line: number,
+        message,
+    };
+    let mut tokens = tokenize(text).map_err(failed)?;
+    // NASM's primitive form of a directive, `[bits 16]`.
+    if tokens.first() == Some(&Token::Symbol('[')) && tokens.last() == Some(&Token::Symbol(']')) {
+        tokens.pop();
+        tokens.remove(0);
+    }
+
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+    };
+    let mut label = None;
+    if let (Some(Token::Word(name)), Some(Token::Symbol(':'))) = (tokens.first(), tokens.get(1)) {
+        if register(tokens.first()).is_none() {
+            label = Some(name.clone());
+            parser.position = 2;
+        }
+    }
+    let statement = parser.statement(&mut label).map_err(failed)?;
+    Ok(Line {
+        number,
+        label,
+        statement,
+    })
+}
+
+/// The width of an instruction whose operands don't give one: the one instructions that only
+/// come in one width have, as the decoder records it.
+fn implied_width(mnemonic: Mnemonic, has_operands: bool) -> Option<bool> {
+    use Mnemonic::*;
+    match mnemonic {
+        Push | Pop | Call | Jmp | Lea | Lds | Les | Esc | Ret | Retf | Iret | Cwd | Pushf
+        | Popf => Some(true),
+        Int => Some(false),
+        _ if mnemonic.is_conditional_jump() || !has_operands => Some(false),
+        _ => None,
+    }
+}
+
+/// The addressing mode summing `registers`, in any order.
+fn address_base(registers: &[Register]) -> Result<AddressBase, String> {
+    use Register::*;
+    let mut sorted = registers.to_vec();
+    sorted.sort_by_key(|register| register_code(*register));
+    Ok(match sorted.as_slice() {
+        [] => AddressBase::Direct,
+        [Bx, Si] => AddressBase::BxSi,
+        [Bx, Di] => AddressBase::BxDi,
+        [Bp, Si] => AddressBase::BpSi,
+        [Bp, Di] => AddressBase::BpDi,
+        [Si] => AddressBase::Si,
+        [Di] => AddressBase::Di,
+        [Bp] => AddressBase::Bp,
+        [Bx] => AddressBase::Bx,
+        _ => return Err(String::from("invalid effective address")),
+    })
+}
+
+/// One pass over the lines, laying out the code with the symbol values found by the pass before.
+struct Pass<'a> {
+    previous: &'a HashMap<String, i64>,
+    symbols: HashMap<String, i64>,
+    /// Indices of the lines with a JMP written without SHORT or NEAR found not to reach as a
+    /// short one, kept from pass to pass.
+    near_jumps: &'a mut HashSet<usize>,
+    origin: i64,
+    code: Vec<u8>,
+    /// The address of the line being assembled.
+    here: i64,
+    /// Whether a JMP was made near this pass, moving what follows it.
+    lengthened: bool,
+    /// The first symbol used but not yet defined.
+    undefined: Option<(usize, String)>,
+}
+
+impl Pass<'_> {
+    fn evaluate(&mut self, expression: &Expression, line: usize) -> i64 {
+        let mut value = 0;
+        for (sign, term) in &expression.0 {
+            let term = match term {
+                Term::Number(number) => *number,
+                Term::Here => self.here,
+                Term::Start => self.origin,
+                Term::Symbol(name) => {
+                    match self.symbols.get(name).or_else(|| self.previous.get(name)) {
+                        Some(value) => *value,
+                        None => {
+                            self.undefined.get_or_insert((line, name.clone()));
+                            // Good enough for a first guess at the layout.
+                            self.here
+                        }
+                    }
+                }
+            };
+            value += sign * term;
+        }
+        value
+    }
+
+    /// Evaluates `expression` to a 16-bit value, signed or not.
+    fn word(&mut self, expression: &Expression, line: usize) -> Result<u16, String> {
+        match self.evaluate(expression, line) {
+            value @ -0x8000..=0xFFFF => Ok(value as u16),
+            value => Err(format!("{value} doesn't fit in 16 bits")),
+        }
+    }
+
+    fn define(&mut self, name: &str, value: i64) -> Result<(), String> {
+        match self.symbols.insert(name.to_string(), value) {
+            Some(_) => Err(format!("{name} is defined more than once")),
+            None => Ok(()),
+        }
+    }
+
+    fn width(&self, source: &Source, operands: &[Option<Operand>; 2]) -> Result<bool, String> {
+        if let Some(wide) = source.string_wide {
+            return Ok(wide);
+        }
+        let mut wide = None;
+        let mut immediate = None;
+        for (index, (operand, argument)) in operands.iter().zip(&source.arguments).enumerate() {
+            let written = match operand {
+                Some(Operand::Register(register)) if !is_count_or_port(source.mnemonic, index) => {
+                    Some(register.is_word())
+                }
+                Some(Operand::Memory(_)) => argument.size.map(|size| size == Size::Word),
+                Some(Operand::Immediate(_)) => {
+                    immediate = argument.size.map(|size| size == Size::Word);
+                    None
+                }
+                _ => None,
+            };
+            match (wide, written) {
+                (Some(wide), Some(written)) if wide != written => {
+                    return Err(String::from("mismatch in operand sizes"));
+                }
+                (None, written) => wide = written,
+                _ => {}
+            }
+        }
+        wide.or(immediate)
+            .or_else(|| implied_width(source.mnemonic, !source.arguments.is_empty()))
+            .ok_or_else(|| String::from("operation size not specified"))
+    }
+
+    fn instruction(
+        &mut self,
+        index: usize,
+        line: usize,
+        source: &Source,
+    ) -> Result<Vec<u8>, String> {
+        use Mnemonic::*;
+        let mnemonic = source.mnemonic;
+        if source.arguments.len() > 2 {
+            return Err(String::from("too many operands"));
+        }
+        let branch = mnemonic.is_conditional_jump() || matches!(mnemonic, Call | Jmp);
+
+        let mut instruction = Instruction {
+            offset: self.here as usize,
+            length: 0,
+            mnemonic,
+            operands: [None, None],
+            wide: false,
+            far: source.arguments.iter().any(|argument| argument.far),
+            lock: source.lock,
+            rep: source.rep,
+            segment_override: source.segment,
+        };
+        let mut target = None;
+        let mut immediate = None;
+        for (operand, argument) in instruction.operands.iter_mut().zip(&source.arguments) {
+            *operand = Some(match &argument.value {
+                Value::Register(register) => Operand::Register(*register),
+                Value::Memory {
+                    segment,
+                    registers,
+                    displacement,
+                } => {
+                    if segment.is_some() {
+                        instruction.segment_override = *segment;
+                    }
+                    Operand::Memory(EffectiveAddress::new(
+                        address_base(registers)?,
+                        self.word(displacement, line)? as i16,
+                    ))
+                }
+                Value::Expression(_) if argument.far => {
+                    return Err(String::from("a far branch needs a segment:offset target"));
+                }
+                Value::Expression(expression) if branch => {
+                    target = Some((self.evaluate(expression, line), argument.distance));
+                    Operand::Relative(0)
+                }
+                Value::Expression(expression) => {
+                    immediate = argument.size;
+                    Operand::Immediate(self.word(expression, line)? as i16 as i32)
+                }
+                Value::Far { segment, offset } => Operand::Far {
+                    segment: self.word(segment, line)?,
+                    offset: self.word(offset, line)?,
+                },
+            });
+        }
+        instruction.wide = self.width(source, &instruction.operands)?;
+
+        let Some((target, distance)) = target else {
+            let sign_extended = immediate.map(|size| size == Size::Byte);
+            return encode_sized(&instruction, sign_extended).map_err(|error| error.to_string());
+        };
+        let mut short = match (mnemonic, distance) {
+            (Call, Some(Distance::Short)) => return Err(String::from("CALL has no short form")),
+            (Call, _) => false,
+            (Jmp, None) => !self.near_jumps.contains(&index),
+            (Jmp, Some(distance)) => distance == Distance::Short,
+            (_, Some(Distance::Near)) => {
+                return Err(String::from("the 8086's conditional jumps are only short"));
+            }
+            _ => true,
+        };
+        loop {
+            instruction.wide = !short;
+            let length = encode(&instruction)
+                .map_err(|error| error.to_string())?
+                .len();
+            let displacement = target - (self.here + length as i64);
+            if short && i8::try_from(displacement).is_err() {
+                if mnemonic == Jmp && distance.is_none() {
+                    self.near_jumps.insert(index);
+                    self.lengthened = true;
+                    short = false;
+                    continue;
+                }
+                return Err(format!(
+                    "short jump out of range: the target is {displacement} bytes away"
+                ));
+            }
+            // Near displacements wrap around the segment, as IP does.
+            instruction.operands[0] = Some(Operand::Relative(displacement as i16));
+            return encode(&instruction).map_err(|error| error.to_string());
+        }
+    }
+
+    fn line(&mut self, index: usize, line: &Line) -> Result<(), String> {
+        self.here = self.origin + self.code.len() as i64;
+        if let Some(label) = &line.label {
+            self.define(label, self.here)?;
+        }
+        match &line.statement {
+            None => {}
+            Some(Statement::Org(expression)) => {
+                if !self.code.is_empty() {
+                    return Err(String::from("org must come before the code"));
+                }
+                self.origin = self.evaluate(expression, line.number);
+            }
+            Some(Statement::Equ(name, expression)) => {
+                let value = self.evaluate(expression, line.number);
+                self.define(name, value)?;
+            }
+            Some(Statement::Data { wide, items }) => {
+                for item in items {
+                    match item {
+                        Data::Text(text) => {
+                            self.code.extend(text);
+                            if *wide && text.len() % 2 == 1 {
+                                self.code.push(0);
+                            }
+                        }
+                        Data::Expression(expression) => {
+                            let value = self.word(expression, line.number)? as i32;
+                            data(&mut self.code, value, *wide)
+                                .map_err(|error| error.to_string())?;
+                        }
+                    }
+                }
+            }
+            Some(Statement::Instruction(source)) => {
+                let bytes = self.instruction(index, line.number, source)?;
+                self.code.extend(bytes);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assembles NASM source for the 8086 into machine code, as `nasm -f bin` would: the subset of
+/// NASM that `ce disasm` writes, in any of its `--syntax nasm` or `ndisasm` options, and course
+/// listings like it. That's instructions with their prefixes and `byte`/`word`/`short`/`near`/
+/// `far` keywords, labels, `equ`, `org`, `bits 16`, `db` and `dw`, and sums of numbers,
+/// symbols, and `$`.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| parse_line(text, index + 1))
+        .collect::<Result<Vec<Line>, AssembleError>>()?;
+
+    // Each pass lays the code out with the addresses the one before found, until they settle.
+    let mut previous = HashMap::new();
+    let mut near_jumps = HashSet::new();
+    for _ in 0..MAX_PASSES {
+        let mut pass = Pass {
+            previous: &previous,
+            symbols: HashMap::new(),
+            near_jumps: &mut near_jumps,
+            origin: 0,
+            code: Vec::new(),
+            here: 0,
+            lengthened: false,
+            undefined: None,
+        };
+        let mut error = None;
+        for (index, line) in lines.iter().enumerate() {
+            if let Err(message) = pass.line(index, line) {
+                error.get_or_insert(AssembleError {
+                    line: line.number,
+                    message,
+                });
+            }
+        }
+        let Pass {
+            symbols,
+            code,
+            lengthened,
+            undefined,
+            ..
+        } = pass;
+        if !lengthened && symbols == previous {
+            if let Some((line, name)) = undefined {
+                return Err(AssembleError {
+                    line,
+                    message: format!("{name} isn't defined"),
+                });
+            }
+            return error.map_or(Ok(code), Err);
+        }
+        previous = symbols;
+    }
+    Err(AssembleError {
+        line: lines.len(),
+        message: String::from("label addresses don't settle"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        assemble(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn instructions_take_their_shortest_encoding() {
+        let source = "\
+            org 0x100\n\
+            count equ 0x1234\n\
+            start: mov ax, [count]\n\
+            add ax, 5\n\
+            add ax, word 5\n\
+            add al, 5\n\
+            mov [bp], cl\n\
+            es movsb\n\
+            lock inc byte cs:[bx + si - 2]\n\
+            call far [bx]\n\
+            jmp 0x1234:0x5678\n\
+            jne start\n\
+            db 'hi', 0\n";
+        assert_eq!(
+            assemble(source).unwrap(),
+            [
+                0xA1, 0x34, 0x12, // mov ax, [count]
+                0x83, 0xC0, 0x05, // add ax, 5
+                0x05, 0x05, 0x00, // add ax, word 5
+                0x04, 0x05, // add al, 5
+                0x88, 0x4E, 0x00, // mov [bp], cl
+                0x26, 0xA4, // es movsb
+                0xF0, 0x2E, 0xFE, 0x40, 0xFE, // lock inc byte cs:[bx + si - 2]
+                0xFF, 0x1F, // call far [bx]
+                0xEA, 0x78, 0x56, 0x34, 0x12, // jmp 0x1234:0x5678
+                0x75, 0xE2, // jne start
+                b'h', b'i', 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn jumps_grow_to_reach_their_targets() {
+        let near = format!("jmp end\n{}end:\n", "nop\n".repeat(200));
+        assert_eq!(assemble(&near).unwrap()[..3], [0xE9, 0xC8, 0x00]);
+
+        let short = format!("jmp end\n{}end:\n", "nop\n".repeat(100));
+        assert_eq!(assemble(&short).unwrap()[..2], [0xEB, 0x64]);
+
+        let forced = format!("jmp near end\n{}end:\n", "nop\n".repeat(100));
+        assert_eq!(assemble(&forced).unwrap()[..3], [0xE9, 0x64, 0x00]);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(error("nop\njmp nowhere"), "line 2: nowhere isn't defined");
+        assert_eq!(error("inc [bx]"), "line 1: operation size not specified");
+        assert_eq!(error("mov al, bx"), "line 1: mismatch in operand sizes");
+        assert_eq!(error("mov ax, 1, 2"), "line 1: too many operands");
+        assert_eq!(error("\n\nfrob ax"), "line 3: unknown instruction \"frob\"");
+        assert_eq!(
+            error("mov [bx + cx], ax"),
+            "line 1: invalid effective address"
+        );
+        assert_eq!(
+            error("a: nop\na: nop"),
+            "line 2: a is defined more than once"
+        );
+        let far = format!("je end\n{}end:\n", "nop\n".repeat(200));
+        assert_eq!(
+            error(&far),
+            "line 1: short jump out of range: the target is 198 bytes away"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    disasm::FormatArgs,
+    exec::{boot, Boot, StateArgs},
+    one_of, parse_address,
+};
+use computer_enhance::{
+    dos, failure::Failure, instruction::Instruction, metadata::timing, simulator::Model,
+};
+use std::{collections::BTreeMap, io, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    #[command(flatten)]
+    state_args: StateArgs,
+
+    /// Size of simulated memory in bytes.
+    #[arg(long, default_value = "0x100000", value_parser = parse_address)]
+    memory: usize,
+
+    /// Emulate the DOS services tiny programs use to print and exit, as for `exec`.
+    #[arg(long)]
+    dos: bool,
+
+    /// Processor whose bus the clocks are estimated for: 8086 or 8088.
+    #[arg(long, default_value = "8086", value_parser = one_of::<Model>(&["8086", "8088"]))]
+    model: Model,
+
+    /// Stop after executing this many instructions, in case the program never ends.
+    #[arg(long, value_name = "N", default_value_t = 10_000_000)]
+    max_instructions: u64,
+}
+
+/// What running the program made of one instruction.
+#[derive(Default)]
+struct Tally {
+    runs: u64,
+    clocks: u64,
+    /// Runs that went somewhere other than the next instruction.
+    taken: u64,
+    penalty: u64,
+}
+
+/// One line of the report: an instruction from the listing, or one that only turned up while
+/// running, such as from code written at run time.
+struct Row {
+    instruction: Instruction,
+    estimate: Option<u32>,
+    tally: Tally,
+}
+
+/// Why an instruction's simulated clocks aren't its estimate times its runs.
+fn reasons(row: &Row, model: Model) -> Vec<String> {
+    let timing = timing(&row.instruction);
+    let tally = &row.tally;
+    let mut reasons = Vec::new();
+    if row.estimate.is_none() {
+        reasons.push(String::from("not in the listing"));
+    }
+    if timing.not_taken.is_some() {
+        reasons.push(format!("taken {} of {}", tally.taken, tally.runs));
+    }
+    if timing.per_repeat != 0 {
+        reasons.push(String::from("repeat count"));
+    }
+    if tally.penalty != 0 {
+        let transfers = match model {
+            Model::I8086 => "odd-address transfers",
+            Model::I8088 => "word transfers split over the 8-bit bus",
+        };
+        reasons.push(format!("+{} {transfers}", tally.penalty));
+    }
+    reasons
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let Boot {
+        listing,
+        mut cpu,
+        program,
+        ..
+    } = match boot(&args.input, &args.format_args, args.memory) {
+        Ok(booted) => booted,
+        Err(failed) => return failed.report(),
+    };
+    let formatter = match args.format_args.formatter(&listing.instructions) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+    args.state_args.apply(&mut cpu);
+    if args.dos {
+        dos::install(&mut cpu, io::stdout());
+    }
+
+    let mut rows: BTreeMap<usize, Row> = listing
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let row = Row {
+                instruction: *instruction,
+                estimate: Some(timing(instruction).total()),
+                tally: Tally::default(),
+            };
+            (instruction.offset, row)
+        })
+        .collect();
+
+    let mut result = Ok(());
+    let (mut executed, mut total) = (0, 0);
+    while cpu.exit_code.is_none() && program.contains(&cpu.instruction_address()) {
+        if executed == args.max_instructions {
+            result = Err(Failure::Simulation.failed(format!(
+                "stopped after {executed} instructions (--max-instructions)"
+            )));
+            break;
+        }
+        let instruction = match cpu.fetch() {
+            Ok(Some(instruction)) => instruction,
+            Ok(None) => break,
+            Err(error) => {
+                result = Err(Failure::from(&error).failed(error.to_string()));
+                break;
+            }
+        };
+        let clocks = cpu.clocks(&instruction, args.model);
+        if let Err(error) = cpu.execute(&instruction) {
+            result = Err(Failure::Simulation.failed(error.to_string()));
+            break;
+        }
+        executed += 1;
+        total += clocks.total() as u64;
+
+        let row = rows.entry(instruction.offset).or_insert_with(|| Row {
+            instruction,
+            estimate: None,
+            tally: Tally::default(),
+        });
+        let tally = &mut row.tally;
+        tally.runs += 1;
+        tally.clocks += clocks.total() as u64;
+        tally.penalty += clocks.penalty as u64;
+        if cpu.ip as usize != instruction.end() {
+            tally.taken += 1;
+        }
+    }
+
+    println!("  ip    estimate  runs  simulated  instruction");
+    for (offset, row) in &rows {
+        let tally = &row.tally;
+        let estimate = row
+            .estimate
+            .map_or(String::from("-"), |clocks| clocks.to_string());
+        let simulated = match tally.runs {
+            0 => String::from("-"),
+            _ => tally.clocks.to_string(),
+        };
+        let expected = row.estimate.map(|clocks| clocks as u64 * tally.runs);
+        let differs = tally.runs != 0 && expected != Some(tally.clocks);
+
+        let mut line = format!(
+            "{} {offset:04x}  {estimate:>8}  {:>4}  {simulated:>9}  ",
+            if differs { '!' } else { ' ' },
+            tally.runs,
+        );
+        let _ = formatter.write(&mut line, &row.instruction);
+        if differs {
+            let _ = formatter.write_comment(&mut line, &reasons(row, args.model).join(", "));
+        }
+        println!("{line}");
+    }
+
+    let estimate: u64 = rows
+        .values()
+        .filter_map(|row| row.estimate)
+        .map(u64::from)
+        .sum();
+    println!();
+    println!("Estimate: {estimate} clocks for one pass through the listing");
+    println!("Simulated: {total} clocks over {executed} instructions");
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failed) => failed
+            .failure
+            .failed(format!("{}: {failed}", args.input))
+            .report(),
+    }
+}
@@ -0,0 +1,167 @@
+use computer_enhance::failure::Failure;
+use std::{collections::BTreeMap, fs, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// A trace printed by `exec`, in either --trace format, or a reference sim86 output.
+    first: String,
+    second: String,
+
+    /// Instructions to show before the one where the traces diverge.
+    #[arg(short = 'C', long, default_value_t = 3)]
+    context: usize,
+}
+
+/// What a trace has shown of the machine so far. Registers it hasn't mentioned are still 0, and
+/// IP is only known once it reports IP changes, which the early reference listings don't.
+#[derive(Clone, Default)]
+struct State {
+    registers: BTreeMap<String, u16>,
+    ip: Option<u16>,
+    flags: String,
+}
+
+/// One instruction line of a trace and the state after it.
+struct Step {
+    line: String,
+    state: State,
+}
+
+/// The name and new value of every change in a comment, in either trace format:
+/// `ax: 0x0000 -> 0x0001, flags: -> Z` or `ax:0x0->0x1 flags:->Z `. Clocks come before a `|`
+/// and are skipped.
+fn changes(comment: &str) -> Vec<(String, String)> {
+    let comment = comment.rsplit('|').next().unwrap_or_default();
+    // Close up the annotated format's spaces so each change is one word, as in the reference.
+    let joined = comment
+        .replace(", ", " ")
+        .replace(": ", ":")
+        .replace(" ->", "->")
+        .replace("-> ", "->");
+    joined
+        .split_whitespace()
+        .filter_map(|change| {
+            let (name, values) = change.split_once(':')?;
+            let (_, new) = values.split_once("->")?;
+            Some((name.to_string(), new.to_string()))
+        })
+        .collect()
+}
+
+fn parse_word(text: &str) -> Option<u16> {
+    let hex = text.strip_prefix("0x")?;
+    u16::from_str_radix(hex, 16).ok()
+}
+
+/// The instructions of a trace, up to its final registers.
+fn parse_trace(text: &str) -> Vec<Step> {
+    let mut state = State::default();
+    let mut steps = Vec::new();
+    for line in text.lines() {
+        if line.trim() == "Final registers:" {
+            break;
+        }
+        // Anything else without a comment is a heading or a blank line.
+        let Some((_, comment)) = line.split_once(';') else {
+            continue;
+        };
+        for (name, new) in changes(comment) {
+            match name.as_str() {
+                "flags" => state.flags = new,
+                "ip" => state.ip = parse_word(&new).or(state.ip),
+                _ => {
+                    if let Some(value) = parse_word(&new) {
+                        state.registers.insert(name, value);
+                    }
+                }
+            }
+        }
+        steps.push(Step {
+            line: line.trim_end().to_string(),
+            state: state.clone(),
+        });
+    }
+    steps
+}
+
+/// How `second` differs from `first`, one line per register, IP, or flags.
+fn differences(first: &State, second: &State) -> Vec<String> {
+    let mut names: Vec<&String> = first.registers.keys().collect();
+    names.extend(second.registers.keys());
+    names.sort();
+    names.dedup();
+
+    let mut differences = Vec::new();
+    for name in names {
+        let value = |state: &State| state.registers.get(name).copied().unwrap_or(0);
+        let (a, b) = (value(first), value(second));
+        if a != b {
+            differences.push(format!("{name:>5}: {a:#06x} vs {b:#06x}"));
+        }
+    }
+    if let (Some(a), Some(b)) = (first.ip, second.ip) {
+        if a != b {
+            differences.push(format!("{:>5}: {a:#06x} vs {b:#06x}", "ip"));
+        }
+    }
+    // Each format lists flags in the same order, so only their set matters.
+    let sorted = |flags: &str| {
+        let mut letters: Vec<char> = flags.chars().collect();
+        letters.sort_unstable();
+        letters
+    };
+    if sorted(&first.flags) != sorted(&second.flags) {
+        differences.push(format!(
+            "{:>5}: {:?} vs {:?}",
+            "flags", first.flags, second.flags
+        ));
+    }
+    differences
+}
+
+fn line_at(steps: &[Step], index: usize) -> &str {
+    match steps.get(index) {
+        Some(step) => &step.line,
+        None => "(end of trace)",
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let read = |path: &str| {
+        fs::read_to_string(path).map_err(|error| Failure::Io.failed(format!("{path}: {error}")))
+    };
+    let (first, second) = match read(&args.first).and_then(|first| Ok((first, read(&args.second)?)))
+    {
+        Ok((first, second)) => (parse_trace(&first), parse_trace(&second)),
+        Err(failed) => return failed.report(),
+    };
+
+    let divergence = (0..first.len().max(second.len())).find_map(|index| {
+        let (a, b) = (first.get(index), second.get(index));
+        let differences = match (a, b) {
+            (Some(a), Some(b)) => differences(&a.state, &b.state),
+            _ => Vec::new(),
+        };
+        (a.is_none() || b.is_none() || !differences.is_empty()).then_some((index, differences))
+    });
+    let Some((index, differences)) = divergence else {
+        return ExitCode::SUCCESS;
+    };
+
+    println!("--- {}", args.first);
+    println!("+++ {}", args.second);
+    for (number, step) in first
+        .iter()
+        .enumerate()
+        .take(index)
+        .skip(index.saturating_sub(args.context))
+    {
+        println!("  {:>5}  {}", number + 1, step.line);
+    }
+    println!("- {:>5}  {}", index + 1, line_at(&first, index));
+    println!("+ {:>5}  {}", index + 1, line_at(&second, index));
+    for difference in differences {
+        println!("  {difference}");
+    }
+    Failure::Mismatch.into()
+}
@@ -0,0 +1,614 @@
+use crate::{
+    disasm::FormatArgs,
+    exec::{boot, changes, Boot, StateArgs, Trace},
+    gdb, parse_address,
+};
+use computer_enhance::{
+    atomic_file::write_atomically,
+    decoder::Decoder,
+    failure::Failure,
+    flags::Flags,
+    formatter::{InstructionFormatter, Labels},
+    instruction::Register,
+    listing::Listing,
+    simulator::{physical_address, Cpu, Registers},
+    snapshot::Snapshot,
+};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    ops::Range,
+    path::Path,
+    process::ExitCode,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    #[command(flatten)]
+    state_args: StateArgs,
+
+    /// Size of simulated memory in bytes.
+    #[arg(long, default_value = "0x100000", value_parser = parse_address)]
+    memory: usize,
+
+    /// Show a full-screen view of the code, registers, and memory instead of a prompt.
+    #[arg(long)]
+    tui: bool,
+
+    /// Serve the GDB remote protocol on this address, e.g. localhost:1234, instead of a
+    /// prompt, for gdb or another front-end to connect to.
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "tui")]
+    gdb: Option<String>,
+
+    /// Stop before executing the instruction at this offset; may be repeated.
+    #[arg(long = "break", value_name = "OFFSET", value_parser = parse_address)]
+    breakpoints: Vec<usize>,
+}
+
+const HELP: &str = "\
+step [N]          execute the next N instructions (default 1)
+stepback [N]      undo the last N instructions (default 1)
+continue          run until the program ends or reaches a breakpoint or watchpoint
+break [OFFSET]    stop before the instruction at OFFSET, or list breakpoints
+delete OFFSET     remove the breakpoint at OFFSET
+watch REG         stop when a register changes
+watch ADDR [LEN]  stop when LEN bytes at physical address ADDR (default 1) are read or written
+watch             list watchpoints
+unwatch N         remove watchpoint N
+regs              show the registers and flags
+mem ADDR LEN      dump LEN bytes of memory from physical address ADDR
+disasm [N]        disassemble N instructions from IP (default 8)
+save PATH         write the registers, flags, IP, and memory to a file
+restore PATH      load state written by save, forgetting what stepback could undo
+quit              leave the debugger
+Commands can be shortened to their first letter.";
+
+/// Instructions `disasm` lists when not told how many.
+const DISASM_DEFAULT: usize = 8;
+
+/// An IP value, which must fit in 16 bits.
+fn parse_offset(text: &str) -> Result<u16, String> {
+    let offset = parse_address(text)?;
+    u16::try_from(offset).map_err(|_| format!("offset {offset:#x} doesn't fit in 16 bits"))
+}
+
+enum Watch {
+    Register(Register),
+    /// Physical addresses.
+    Memory(Range<usize>),
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Register(register) => f.write_str(register.name()),
+            Watch::Memory(range) if range.len() == 1 => write!(f, "{:#07x}", range.start),
+            Watch::Memory(range) => write!(f, "{:#07x}..{:#07x}", range.start, range.end),
+        }
+    }
+}
+
+impl Watch {
+    fn parse(arguments: &[&str]) -> Result<Watch, String> {
+        match arguments {
+            [name] if name.parse::<Register>().is_ok() => Ok(Watch::Register(name.parse()?)),
+            [address] => {
+                let address = parse_address(address)?;
+                Ok(Watch::Memory(address..address + 1))
+            }
+            [address, length] => {
+                let (address, length) = (parse_address(address)?, parse_address(length)?);
+                if length == 0 {
+                    return Err(String::from("a watched range needs at least one byte"));
+                }
+                Ok(Watch::Memory(address..address + length))
+            }
+            _ => Err(String::from("usage: watch REG or watch ADDR [LEN]")),
+        }
+    }
+
+    /// Why the instruction just executed, which started with `registers`, triggers this
+    /// watchpoint, if it does.
+    fn triggered(&self, registers: &Registers, cpu: &Cpu) -> Option<String> {
+        match self {
+            Watch::Register(register) => {
+                let (old, new) = (registers.get(*register), cpu.registers.get(*register));
+                (old != new).then(|| format!("{} {old:#06x} -> {new:#06x}", register.name()))
+            }
+            Watch::Memory(range) => cpu
+                .accesses
+                .iter()
+                .find(|access| access.overlaps(range))
+                .map(|access| {
+                    let kind = if access.write { "write" } else { "read" };
+                    let plural = if access.size == 1 { "" } else { "s" };
+                    let address = access.address;
+                    format!("{kind} of {} byte{plural} at {address:#07x}", access.size)
+                }),
+        }
+    }
+}
+
+/// What interrupted `Session::resume` before the program ended.
+pub enum Stop {
+    Breakpoint(u16),
+    /// The watchpoint's number, why it triggered, and the trace line of the instruction that
+    /// triggered it.
+    Watchpoint(usize, String, String),
+    Error(String),
+    /// Whoever is driving the session asked to break in.
+    Interrupted,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(offset) => write!(f, "breakpoint at {offset:#06x}"),
+            Stop::Watchpoint(n, reason, line) => write!(f, "watchpoint {n}: {reason}\n{line}"),
+            Stop::Error(message) => write!(f, "error: {message}"),
+            Stop::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// Instructions `Session::resume_until` runs between asking whether it's been interrupted.
+const INTERRUPT_POLL: usize = 4096;
+
+pub struct Session {
+    pub cpu: Cpu,
+    /// Physical addresses the program was loaded into; leaving them ends it.
+    pub program: Range<usize>,
+    /// The program's code, decoded again whenever it's written to.
+    pub listing: Listing,
+    pub formatter: InstructionFormatter,
+    /// IP values to stop at before executing.
+    pub breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watch>,
+    /// How to undo recent instructions, oldest first.
+    history: VecDeque<Undo>,
+}
+
+/// Instructions `stepback` can undo; the oldest are forgotten past this.
+const HISTORY_LIMIT: usize = 10_000;
+
+/// The state an instruction changed, as it was before.
+struct Undo {
+    registers: Registers,
+    flags: Flags,
+    ip: u16,
+    exit_code: Option<u8>,
+    /// Memory writes, as `(address, size, previous)`.
+    writes: Vec<(usize, usize, u16)>,
+}
+
+impl Session {
+    pub fn running(&self) -> bool {
+        self.cpu.exit_code.is_none() && self.program.contains(&self.cpu.instruction_address())
+    }
+
+    /// Runs until the program ends or something stops it, returning how many instructions
+    /// were executed and what stopped them, if anything did. Steps at least once, so resuming
+    /// from a breakpoint gets past it.
+    pub fn resume(&mut self) -> (usize, Option<Stop>) {
+        self.resume_until(|| false)
+    }
+
+    /// `resume`, also stopping with `Stop::Interrupted` once `interrupted` returns true. It's
+    /// asked every `INTERRUPT_POLL` instructions, so it can afford to look for input.
+    pub fn resume_until(&mut self, mut interrupted: impl FnMut() -> bool) -> (usize, Option<Stop>) {
+        let mut executed = 0;
+        while self.running() {
+            if executed % INTERRUPT_POLL == INTERRUPT_POLL - 1 && interrupted() {
+                return (executed, Some(Stop::Interrupted));
+            }
+            let registers = self.cpu.registers;
+            let line = match self.step() {
+                Ok(line) => line,
+                Err(message) => return (executed, Some(Stop::Error(message))),
+            };
+            executed += 1;
+            let triggered = self
+                .watchpoints
+                .iter()
+                .enumerate()
+                .find_map(|(n, watch)| Some((n, watch.triggered(&registers, &self.cpu)?)));
+            if let Some((n, reason)) = triggered {
+                return (executed, Some(Stop::Watchpoint(n, reason, line)));
+            }
+            if self.breakpoints.contains(&self.cpu.ip) {
+                return (executed, Some(Stop::Breakpoint(self.cpu.ip)));
+            }
+        }
+        (executed, None)
+    }
+
+    // Decodes the listing again after its code has changed, and the labels with it.
+    fn redecode(&mut self) {
+        self.listing.redecode(&self.cpu.memory);
+        self.formatter.labels =
+            Labels::with_symbols(&self.listing.instructions, &self.formatter.symbols);
+    }
+
+    /// Undoes up to `count` instructions, returning how many were undone.
+    pub fn step_back(&mut self, count: usize) -> usize {
+        let code = self.listing.range();
+        let mut patched = false;
+        for undone in 0..count {
+            let Some(undo) = self.history.pop_back() else {
+                if patched {
+                    self.redecode();
+                }
+                return undone;
+            };
+            patched |= undo
+                .writes
+                .iter()
+                .any(|&(address, size, _)| address < code.end && code.start < address + size);
+            // Later writes may overlap earlier ones, so put memory back in reverse.
+            for &(address, size, previous) in undo.writes.iter().rev() {
+                if size == 2 {
+                    self.cpu.memory.write_u16(address, previous);
+                } else {
+                    self.cpu.memory.write_u8(address, previous as u8);
+                }
+            }
+            self.cpu.registers = undo.registers;
+            self.cpu.flags = undo.flags;
+            self.cpu.ip = undo.ip;
+            self.cpu.exit_code = undo.exit_code;
+        }
+        if patched {
+            self.redecode();
+        }
+        count
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, |out| self.cpu.snapshot().write(out))
+    }
+
+    /// Loads state written by `save`. The instructions that led to it aren't known, so nothing
+    /// can be stepped back past it.
+    pub fn restore(&mut self, path: &Path) -> io::Result<()> {
+        let snapshot = Snapshot::read(&mut BufReader::new(File::open(path)?))?;
+        self.cpu.restore(&snapshot);
+        self.history.clear();
+        self.redecode();
+        Ok(())
+    }
+
+    /// Executes one instruction, returning its trace line.
+    pub fn step(&mut self) -> Result<String, String> {
+        let instruction = match self.cpu.fetch() {
+            Ok(Some(instruction)) => instruction,
+            Ok(None) => return Err(String::from("no instruction at IP")),
+            Err(error) => return Err(error.to_string()),
+        };
+        let before = (self.cpu.registers, self.cpu.flags, self.cpu.ip);
+        let exit_code = self.cpu.exit_code;
+        self.cpu
+            .execute(&instruction)
+            .map_err(|error| error.to_string())?;
+
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        let writes = self.cpu.accesses.iter().filter(|access| access.write);
+        self.history.push_back(Undo {
+            registers: before.0,
+            flags: before.1,
+            ip: before.2,
+            exit_code,
+            writes: writes
+                .map(|access| (access.address, access.size, access.previous))
+                .collect(),
+        });
+
+        let mut line = format!("{:04x}: ", instruction.offset);
+        let _ = self.formatter.write(&mut line, &instruction);
+        let comment = Trace::Annotated.join(&changes(Trace::Annotated, before, &self.cpu));
+        let _ = self.formatter.write_comment(&mut line, &comment);
+        if let Some(write) = self.listing.refresh(&self.cpu) {
+            self.formatter.labels =
+                Labels::with_symbols(&self.listing.instructions, &self.formatter.symbols);
+            line += &format!(
+                "\nwarning: writes to code at {:#07x}; decoding it again",
+                write.address
+            );
+        }
+        Ok(line)
+    }
+
+    /// `=> 0003: MOV BX, 1000` for the instruction about to run, or a note that the program
+    /// has ended.
+    fn write_next(&self, out: &mut impl Write) -> io::Result<()> {
+        if !self.running() {
+            return writeln!(out, "program ended, ip {:#06x}", self.cpu.ip);
+        }
+        match self.cpu.fetch() {
+            Ok(Some(instruction)) => {
+                let text = self.formatter.display(&instruction);
+                writeln!(out, "=> {:04x}: {text}", instruction.offset)
+            }
+            Ok(None) => writeln!(out, "=> {:04x}: (end of memory)", self.cpu.ip),
+            Err(error) => writeln!(out, "=> {:04x}: {error}", self.cpu.ip),
+        }
+    }
+
+    fn write_registers(&self, out: &mut impl Write) -> io::Result<()> {
+        for register in Registers::ALL {
+            let value = self.cpu.registers.get(register);
+            writeln!(out, "  {}: {value:#06x} ({value})", register.name())?;
+        }
+        writeln!(out, "  ip: {:#06x} ({})", self.cpu.ip, self.cpu.ip)?;
+        writeln!(out, "  flags: {}", self.cpu.flags)
+    }
+
+    /// A hex dump, 16 bytes to a line with printable ASCII alongside.
+    fn write_memory(&self, out: &mut impl Write, address: usize, length: usize) -> io::Result<()> {
+        let bytes = self.cpu.memory.slice(address, length);
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            write!(out, "{:05x}:", address + row * 16)?;
+            for byte in chunk {
+                write!(out, " {byte:02x}")?;
+            }
+            let text: String = chunk
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(
+                out,
+                "{:width$}  |{text}|",
+                "",
+                width = 3 * (16 - chunk.len())
+            )?;
+        }
+        if bytes.len() < length {
+            writeln!(out, "(memory ends at {:#x})", self.cpu.memory.len())?;
+        }
+        Ok(())
+    }
+
+    /// Decodes from memory rather than the loaded listing, so code the program has written
+    /// shows as it now is.
+    fn write_disassembly(&self, out: &mut impl Write, count: usize) -> io::Result<()> {
+        let address = physical_address(self.cpu.registers.get(Register::Cs), self.cpu.ip);
+        let code = self.cpu.memory.slice(address, count * 16);
+        let mut decoder = Decoder::with_origin(code, self.cpu.ip as usize);
+        for index in 0..count {
+            match decoder.next_instruction() {
+                Ok(Some(instruction)) => {
+                    let marker = if index == 0 { "=>" } else { "  " };
+                    let text = self.formatter.display(&instruction);
+                    writeln!(out, "{marker} {:04x}: {text}", instruction.offset)?;
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    writeln!(out, "   {error}")?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Carries out one command line, returning false once it's time to quit.
+    fn command(&mut self, out: &mut impl Write, line: &str) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let arguments: Vec<&str> = words.collect();
+        let count = |default| match arguments.first() {
+            Some(text) => parse_address(text),
+            None => Ok(default),
+        };
+
+        match command {
+            "step" | "s" => match count(1) {
+                Ok(count) => {
+                    for _ in 0..count {
+                        if !self.running() {
+                            break;
+                        }
+                        match self.step() {
+                            Ok(line) => writeln!(out, "{line}")?,
+                            Err(message) => {
+                                writeln!(out, "error: {message}")?;
+                                break;
+                            }
+                        }
+                    }
+                    self.write_next(out)?;
+                }
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "stepback" | "sb" => match count(1) {
+                Ok(count) => {
+                    let undone = self.step_back(count);
+                    let plural = if undone == 1 { "" } else { "s" };
+                    writeln!(out, "stepped back {undone} instruction{plural}")?;
+                    self.write_next(out)?;
+                }
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "continue" | "c" => {
+                let (executed, stop) = self.resume();
+                if let Some(stop) = stop {
+                    writeln!(out, "{stop}")?;
+                }
+                let plural = if executed == 1 { "" } else { "s" };
+                writeln!(out, "executed {executed} instruction{plural}")?;
+                self.write_next(out)?;
+            }
+            "break" | "b" => match arguments.first() {
+                Some(text) => match parse_offset(text) {
+                    Ok(offset) => {
+                        self.breakpoints.insert(offset);
+                        writeln!(out, "breakpoint at {offset:#06x}")?;
+                    }
+                    Err(message) => writeln!(out, "{message}")?,
+                },
+                None if self.breakpoints.is_empty() => writeln!(out, "no breakpoints")?,
+                None => {
+                    for offset in &self.breakpoints {
+                        writeln!(out, "breakpoint at {offset:#06x}")?;
+                    }
+                }
+            },
+            "delete" => match arguments.first().map(|text| parse_offset(text)) {
+                Some(Ok(offset)) if self.breakpoints.remove(&offset) => {
+                    writeln!(out, "deleted breakpoint at {offset:#06x}")?
+                }
+                Some(Ok(offset)) => writeln!(out, "no breakpoint at {offset:#06x}")?,
+                Some(Err(message)) => writeln!(out, "{message}")?,
+                None => writeln!(out, "usage: delete OFFSET")?,
+            },
+            "regs" | "r" => self.write_registers(out)?,
+            "mem" | "m" => match arguments[..] {
+                [address, length] => match (parse_address(address), parse_address(length)) {
+                    (Ok(address), Ok(length)) => self.write_memory(out, address, length)?,
+                    (Err(message), _) | (_, Err(message)) => writeln!(out, "{message}")?,
+                },
+                _ => writeln!(out, "usage: mem ADDR LEN")?,
+            },
+            "disasm" | "d" => match count(DISASM_DEFAULT) {
+                Ok(count) => self.write_disassembly(out, count)?,
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "watch" | "w" if arguments.is_empty() => {
+                if self.watchpoints.is_empty() {
+                    writeln!(out, "no watchpoints")?;
+                }
+                for (n, watch) in self.watchpoints.iter().enumerate() {
+                    writeln!(out, "watchpoint {n}: {watch}")?;
+                }
+            }
+            "watch" | "w" => match Watch::parse(&arguments) {
+                Ok(watch) => {
+                    writeln!(out, "watchpoint {}: {watch}", self.watchpoints.len())?;
+                    self.watchpoints.push(watch);
+                }
+                Err(message) => writeln!(out, "{message}")?,
+            },
+            "unwatch" => match arguments.first().map(|text| text.parse::<usize>()) {
+                Some(Ok(n)) if n < self.watchpoints.len() => {
+                    let watch = self.watchpoints.remove(n);
+                    writeln!(out, "deleted watchpoint {n}: {watch}")?;
+                }
+                Some(_) => writeln!(out, "no such watchpoint")?,
+                None => writeln!(out, "usage: unwatch N")?,
+            },
+            "save" => match arguments[..] {
+                [path] => match self.save(Path::new(path)) {
+                    Ok(()) => writeln!(out, "saved state to {path}")?,
+                    Err(error) => writeln!(out, "{path}: {error}")?,
+                },
+                _ => writeln!(out, "usage: save PATH")?,
+            },
+            "restore" => match arguments[..] {
+                [path] => match self.restore(Path::new(path)) {
+                    Ok(()) => {
+                        writeln!(out, "restored state from {path}")?;
+                        self.write_next(out)?;
+                    }
+                    Err(error) => writeln!(out, "{path}: {error}")?,
+                },
+                _ => writeln!(out, "usage: restore PATH")?,
+            },
+            "help" | "h" | "?" => writeln!(out, "{HELP}")?,
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(out, "unknown command {command:?}; try help")?,
+        }
+        Ok(true)
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let Boot {
+        image,
+        listing,
+        mut cpu,
+        program,
+    } = match boot(&args.input, &args.format_args, args.memory) {
+        Ok(booted) => booted,
+        Err(failed) => return failed.report(),
+    };
+    args.state_args.apply(&mut cpu);
+    let formatter = match args.format_args.formatter(&listing.instructions) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+    let mut session = Session {
+        cpu,
+        program: program.clone(),
+        listing,
+        formatter,
+        breakpoints: BTreeSet::new(),
+        watchpoints: Vec::new(),
+        history: VecDeque::new(),
+    };
+    for offset in args.breakpoints {
+        match u16::try_from(offset) {
+            Ok(offset) => session.breakpoints.insert(offset),
+            Err(_) => {
+                return Failure::Usage
+                    .failed(format!("--break {offset:#x}: offsets are 16 bits"))
+                    .report();
+            }
+        };
+    }
+
+    if let Some(address) = &args.gdb {
+        return match gdb::serve(&mut session, address) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => Failure::Io.failed(format!("{address}: {error}")).report(),
+        };
+    }
+
+    if args.tui {
+        #[cfg(feature = "tui")]
+        return match crate::tui::run(&mut session) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => Failure::Io.failed(error.to_string()).report(),
+        };
+        #[cfg(not(feature = "tui"))]
+        return Failure::Usage
+            .failed("--tui needs ce built with the tui feature")
+            .report();
+    }
+
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(
+        stdout,
+        "{}: {} bytes at {:#x}; type help for commands",
+        args.input,
+        image.len(),
+        program.start,
+    );
+    let _ = session.write_next(&mut stdout);
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let _ = write!(stdout, "(ce) ");
+        let _ = stdout.flush();
+        let Some(Ok(line)) = lines.next() else {
+            let _ = writeln!(stdout);
+            break;
+        };
+        match session.command(&mut stdout, &line) {
+            Ok(true) => {}
+            Ok(false) | Err(_) => break,
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,185 @@
+use crate::disasm::{load, FormatArgs};
+use computer_enhance::{failure::Failure, formatter::LabelStyle, instruction::Instruction};
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    old: String,
+    new: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    /// Unchanged instructions to show around each difference.
+    #[arg(short = 'U', long, default_value_t = 3)]
+    context: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The shortest edit script turning `old` into `new` (Myers, "An O(ND) Difference Algorithm").
+fn shortest_edit<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let index = |k: isize| (k + max as isize) as usize;
+
+    // furthest[k] is the furthest x reached on diagonal k = x - y; one snapshot per edit count.
+    let mut furthest = vec![0isize; 2 * max + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max as isize {
+        trace.push(furthest.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+                furthest[index(k + 1)]
+            } else {
+                furthest[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let (d, k) = (d as isize, x - y);
+        let previous_k = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = furthest[index(previous_k)];
+        let previous_y = previous_x - previous_k;
+
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == previous_x {
+                edits.push(Edit::Added(previous_y as usize));
+            } else {
+                edits.push(Edit::Removed(previous_x as usize));
+            }
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    edits.reverse();
+    edits
+}
+
+fn address_column(instruction: Option<&Instruction>) -> String {
+    match instruction {
+        Some(instruction) => format!("{:04x}", instruction.offset),
+        None => String::from("    "),
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let origin = args.format_args.origin();
+    let loaded = load(&args.old, origin).and_then(|old| Ok((old, load(&args.new, origin)?)));
+    let ((_, _, old), (_, _, new)) = match loaded {
+        Ok(loaded) => loaded,
+        Err(failed) => return failed.report(),
+    };
+
+    let mut formatter = match args.format_args.formatter(&old) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+    // Jumps written relative to themselves still match after code around them moves.
+    formatter.label_style = LabelStyle::Relative;
+    let text = |instructions: &[Instruction]| -> Vec<String> {
+        instructions
+            .iter()
+            .map(|instruction| formatter.display(instruction).to_string())
+            .collect()
+    };
+    let (old_text, new_text) = (text(&old), text(&new));
+
+    let edits = shortest_edit(&old_text, &new_text);
+    if edits.iter().all(|edit| matches!(edit, Edit::Same(..))) {
+        return ExitCode::SUCCESS;
+    }
+
+    // Show unchanged instructions only within `context` of a difference.
+    let near_change = |position: usize| {
+        let start = position.saturating_sub(args.context);
+        let end = (position + args.context + 1).min(edits.len());
+        edits[start..end]
+            .iter()
+            .any(|edit| !matches!(edit, Edit::Same(..)))
+    };
+
+    println!("--- {}", args.old);
+    println!("+++ {}", args.new);
+    let mut elided = false;
+    let mut position = 0;
+    while position < edits.len() {
+        match edits[position] {
+            Edit::Same(i, j) => {
+                if near_change(position) {
+                    let (a, b) = (address_column(Some(&old[i])), address_column(Some(&new[j])));
+                    println!("  {a} {b}  {}", old_text[i]);
+                    elided = false;
+                } else if !elided {
+                    println!("  ...");
+                    elided = true;
+                }
+                position += 1;
+            }
+            _ => {
+                // A run of removals and additions; pair them up as changed lines.
+                let end = edits[position..]
+                    .iter()
+                    .position(|edit| matches!(edit, Edit::Same(..)))
+                    .map_or(edits.len(), |length| position + length);
+                let run = &edits[position..end];
+                let removed: Vec<usize> = run
+                    .iter()
+                    .filter_map(|edit| match edit {
+                        Edit::Removed(i) => Some(*i),
+                        _ => None,
+                    })
+                    .collect();
+                let added: Vec<usize> = run
+                    .iter()
+                    .filter_map(|edit| match edit {
+                        Edit::Added(j) => Some(*j),
+                        _ => None,
+                    })
+                    .collect();
+
+                for line in 0..removed.len().max(added.len()) {
+                    let before = removed.get(line).map(|&i| &old[i]);
+                    let after = added.get(line).map(|&j| &new[j]);
+                    let (a, b) = (address_column(before), address_column(after));
+                    match (removed.get(line), added.get(line)) {
+                        (Some(&i), Some(&j)) => {
+                            println!("~ {a} {b}  {}  =>  {}", old_text[i], new_text[j])
+                        }
+                        (Some(&i), None) => println!("- {a} {b}  {}", old_text[i]),
+                        (None, Some(&j)) => println!("+ {a} {b}  {}", new_text[j]),
+                        (None, None) => unreachable!(),
+                    }
+                }
+                elided = false;
+                position = end;
+            }
+        }
+    }
+    Failure::Mismatch.into()
+}
@@ -0,0 +1,847 @@
+use crate::{
+    gdb::parse_hex_bytes,
+    one_of, parse_address,
+    progress::{Progress, ProgressReader},
+    symbols::{read_symbols, write_symbols},
+    watch::{watch, WatchArgs},
+};
+use clap::ValueEnum;
+use computer_enhance::{
+    atomic_file::write_atomically,
+    decoder::{decode_at, decode_chunked, decode_parallel, DecodeError, Disagreement},
+    exe::{is_exe, Exe},
+    explain::write_explanation,
+    export::{write_csv, write_json, write_ndisasm},
+    failure::{Failed, Failure},
+    formatter::{Case, InstructionFormatter, LabelStyle, Labels, Radix, SizeKeywords, Syntax},
+    instruction::Instruction,
+    metadata::timing,
+    os_memory::FileView,
+};
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Read, Write},
+    ops::{ControlFlow, Deref},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Assembly source in the chosen --syntax.
+    Text,
+    /// An array of structured instruction objects.
+    Json,
+    /// One row per instruction: offset, length, bytes, mnemonic, dst, src, clocks.
+    Csv,
+    /// Laid out as `ndisasm -b 16` writes it, offset, bytes, then instruction, so the two can
+    /// be compared with diff. Formatting options are ignored.
+    Ndisasm,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Color {
+    /// Color when writing text to a terminal and NO_COLOR isn't set.
+    Auto,
+    Always,
+    Never,
+}
+
+/// Where a .COM image starts, after the 256-byte program segment prefix.
+pub const COM_ORIGIN: usize = 0x100;
+
+/// Where a BIOS loads a boot sector.
+pub const BOOT_ORIGIN: usize = 0x7C00;
+
+/// Options shared by every subcommand that produces assembly text.
+#[derive(clap::Args)]
+pub struct FormatArgs {
+    /// Address the input is loaded at, e.g. 0x100 for a .COM image. MZ executables are placed
+    /// by their header instead.
+    #[arg(long, default_value = "0", value_parser = parse_address)]
+    pub origin: usize,
+
+    /// Treat the input as a .COM image: loaded at 0x100, and simulated in a 64KB segment with
+    /// CS=DS=ES=SS and SP=0xFFFE.
+    #[arg(long, conflicts_with = "origin")]
+    pub com: bool,
+
+    /// Treat the input as a boot sector: 512 bytes ending in the 0xAA55 signature, loaded at
+    /// 0x7C00 and simulated with the registers a BIOS leaves.
+    #[arg(long, conflicts_with_all = ["origin", "com"])]
+    pub boot: bool,
+
+    /// Letter case of mnemonics and registers: upper or lower.
+    #[arg(long, default_value = "upper", value_parser = one_of::<Case>(&["upper", "lower"]))]
+    pub case: Case,
+
+    /// Immediates and displacements: decimal, hex (0x1234), or suffix (1234h).
+    #[arg(
+        long,
+        default_value = "decimal",
+        value_parser = one_of::<Radix>(&["decimal", "hex", "suffix"])
+    )]
+    pub radix: Radix,
+
+    /// When to write BYTE/WORD on memory operands: auto (only when ambiguous), always, or never.
+    #[arg(
+        long,
+        default_value = "auto",
+        value_parser = one_of::<SizeKeywords>(&["auto", "always", "never"])
+    )]
+    pub size_keywords: SizeKeywords,
+
+    /// Write SHORT/NEAR on branch targets so they reassemble to the original encoding.
+    #[arg(long)]
+    pub distance_keywords: bool,
+
+    /// Assembler dialect: nasm, masm, or ndisasm (NASM as ndisasm writes it).
+    #[arg(
+        long,
+        default_value = "nasm",
+        value_parser = one_of::<Syntax>(&["nasm", "masm", "ndisasm"])
+    )]
+    pub syntax: Syntax,
+
+    /// File of `offset=name` lines naming labels and direct memory addresses.
+    #[arg(long)]
+    pub symbols: Option<String>,
+}
+
+impl FormatArgs {
+    /// Where the input is loaded within its segment.
+    pub fn origin(&self) -> usize {
+        if self.com {
+            COM_ORIGIN
+        } else if self.boot {
+            BOOT_ORIGIN
+        } else {
+            self.origin
+        }
+    }
+
+    /// Builds the formatter these options describe, reading `--symbols` if given.
+    pub fn formatter(&self, instructions: &[Instruction]) -> Result<InstructionFormatter, Failed> {
+        let symbols = match &self.symbols {
+            Some(path) => read_symbols(path)?,
+            None => BTreeMap::new(),
+        };
+        Ok(InstructionFormatter {
+            syntax: self.syntax,
+            case: self.case,
+            radix: self.radix,
+            size_keywords: self.size_keywords,
+            distance_keywords: self.distance_keywords,
+            label_style: LabelStyle::Named,
+            labels: Labels::with_symbols(instructions, &symbols),
+            symbols,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The files to disassemble, one after another, or `-` for standard input.
+    #[arg(required_unless_present = "bytes")]
+    inputs: Vec<String>,
+
+    /// Decode these bytes, written in hex like "89 d9 b1 05", instead of a file.
+    #[arg(long, value_name = "HEX", conflicts_with_all = ["inputs", "mmap", "chunk"])]
+    bytes: Option<String>,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Color mnemonics, registers, numbers, labels, and comments.
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+
+    #[command(flatten)]
+    annotations: Annotations,
+
+    /// Also write the generated labels as `offset=name` lines, readable by --symbols.
+    #[arg(long, value_name = "PATH")]
+    emit_symbols: Option<PathBuf>,
+
+    /// Write to this file instead of stdout. It is replaced only once output is complete.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write each input to its own file in this directory, named after it with an extension
+    /// for the --format, such as listing_0037.asm.
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+
+    /// Decode from this many bytes into each input, taking the bytes there as plain code even
+    /// in an MZ executable. Addresses still count from the start of the input.
+    #[arg(long, value_parser = parse_address)]
+    offset: Option<usize>,
+
+    /// Decode at most this many bytes.
+    #[arg(long, value_parser = parse_address)]
+    length: Option<usize>,
+
+    /// Map the input into memory rather than reading it, so it isn't copied however big it is.
+    #[arg(long)]
+    mmap: bool,
+
+    /// Read and decode the input this many bytes at a time, so inputs bigger than memory can
+    /// be disassembled. Only text can be written this way, and without labels.
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["format", "mmap", "emit_symbols"])]
+    chunk: Option<usize>,
+
+    /// Decode on this many threads, each starting at an even share of the input. Where one
+    /// starts part-way through an instruction it's brought back into step, and noted.
+    #[arg(long, conflicts_with = "chunk")]
+    threads: Option<usize>,
+
+    #[command(flatten)]
+    watch_args: WatchArgs,
+
+    /// Assemble the --source with this NASM executable rather than the built-in assembler.
+    #[arg(long, requires = "source")]
+    nasm: Option<PathBuf>,
+}
+
+/// Extra detail written as comments alongside the text output.
+#[derive(Clone, Copy, Default, clap::Args)]
+pub struct Annotations {
+    /// Append an estimated clock count and running total to each line.
+    #[arg(long)]
+    pub clocks: bool,
+
+    /// Break each instruction's bytes down into their fields (OPCODE, D, W, MOD, REG, ...).
+    #[arg(long)]
+    pub explain: bool,
+}
+
+/// Reads and decodes `input`, reporting failures as `input: error`. Returns the bytes decoded,
+/// the origin they were decoded at, and the listing.
+pub fn load(input: &str, origin: usize) -> Result<(Vec<u8>, usize, Vec<Instruction>), Failed> {
+    let file =
+        read_input(input).map_err(|error| Failure::Io.failed(format!("{input}: {error}")))?;
+    decode_file(input, file, origin)
+}
+
+/// Reports `error` from decoding `input`.
+fn decode_failed(input: &str, error: &DecodeError) -> Failed {
+    Failure::from(error).failed(format!("{input}: {error}"))
+}
+
+/// The input path that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// What the output calls the bytes given by `--bytes`.
+pub const BYTES: &str = "--bytes";
+
+/// Bytes written in hex, with any whitespace between them.
+fn parse_bytes(hex: &str) -> Result<Vec<u8>, Failed> {
+    let digits: String = hex.split_whitespace().collect();
+    parse_hex_bytes(&digits)
+        .ok_or_else(|| Failure::Usage.failed(format!("{BYTES}: invalid hex bytes {hex:?}")))
+}
+
+/// The whole of `input`: the file at that path, or standard input if it's `-`.
+pub fn read_input(input: &str) -> io::Result<Vec<u8>> {
+    if input == STDIN {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        fs::read(input)
+    }
+}
+
+/// Decodes `file`, the contents of `input`, as `load` does. An MZ executable is decoded from
+/// its entry point rather than its header, with offsets within its code segment, whatever
+/// `origin` says.
+pub fn decode_file(
+    input: &str,
+    file: Vec<u8>,
+    origin: usize,
+) -> Result<(Vec<u8>, usize, Vec<Instruction>), Failed> {
+    let (code, origin) = code(input, &file, origin)?;
+    let image = if is_exe(&file) { code.to_vec() } else { file };
+    let instructions = decode_at(&image, origin).map_err(|error| decode_failed(input, &error))?;
+    log_decoded(input, &image, origin, &instructions);
+    Ok((image, origin, instructions))
+}
+
+/// Logs how much of `input` was decoded.
+pub fn log_decoded(input: &str, image: &[u8], origin: usize, instructions: &[Instruction]) {
+    tracing::info!(
+        "{input}: decoded {} instructions from {} bytes at {origin:#x}",
+        instructions.len(),
+        image.len()
+    );
+}
+
+/// The code in `file`, the contents of `input`, and the origin to decode it at: the entry point
+/// onwards of an MZ executable, or else all of it at `origin`.
+fn code<'a>(input: &str, file: &'a [u8], origin: usize) -> Result<(&'a [u8], usize), Failed> {
+    if is_exe(file) {
+        let exe = Exe::parse(file)
+            .map_err(|error| Failure::Invalid.failed(format!("{input}: {error}")))?;
+        let code = exe.module.get(exe.entry()..).unwrap_or_default();
+        tracing::info!(
+            "{input}: MZ executable, decoding from its entry point at {:#x} in the file",
+            exe.entry()
+        );
+        Ok((code, exe.header.ip as usize))
+    } else {
+        Ok((file, origin))
+    }
+}
+
+impl Args {
+    /// Whether `--offset` or `--length` select part of each input.
+    fn selects(&self) -> bool {
+        self.offset.is_some() || self.length.is_some()
+    }
+
+    /// The part of `file`, the contents of `input`, that `--offset` and `--length` select,
+    /// and the origin to decode it at, given the whole file's.
+    fn select<'a>(
+        &self,
+        input: &str,
+        file: &'a [u8],
+        origin: usize,
+    ) -> Result<(&'a [u8], usize), Failed> {
+        let offset = self.offset.unwrap_or(0);
+        let Some(rest) = file.get(offset..) else {
+            return Err(Failure::Usage.failed(format!(
+                "{input}: offset {offset:#x} is past the end ({:#x} bytes)",
+                file.len()
+            )));
+        };
+        let length = self.length.unwrap_or(rest.len()).min(rest.len());
+        Ok((&rest[..length], origin + offset))
+    }
+}
+
+/// An input file's bytes, either read into memory or mapped with `--mmap`.
+enum Input {
+    Read(Vec<u8>),
+    Mapped(FileView),
+}
+
+impl Input {
+    fn open(input: &str, mmap: bool) -> Result<Input, Failed> {
+        let file = if mmap && input == STDIN {
+            Err(io::Error::other("standard input can't be mapped"))
+        } else if mmap {
+            File::open(input)
+                .and_then(|file| FileView::new(&file))
+                .map(Input::Mapped)
+        } else {
+            read_input(input).map(Input::Read)
+        };
+        file.map_err(|error| Failure::Io.failed(format!("{input}: {error}")))
+    }
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Read(bytes) => bytes,
+            Input::Mapped(view) => view,
+        }
+    }
+}
+
+/// Writes a complete source file for the formatter's assembler, labels included.
+///
+/// With `clocks`, each line ends in `; Clocks: +N = total (base + EAea)`, with the total running
+/// through the file in listing order. With `explain`, each instruction is followed by comment
+/// lines breaking its bytes (from `image`, which was loaded at `origin`) into fields.
+pub fn write_text<'a>(
+    out: &mut dyn Write,
+    input: &str,
+    image: &[u8],
+    origin: usize,
+    instructions: impl IntoIterator<Item = &'a Instruction>,
+    formatter: &InstructionFormatter,
+    annotations: Annotations,
+) -> io::Result<()> {
+    let mut text = TextWriter::new(formatter, annotations);
+    text.begin(out, input, origin)?;
+    for instruction in instructions {
+        let start = instruction.offset - origin;
+        text.instruction(out, instruction, &image[start..instruction.end() - origin])?;
+    }
+    text.end(out)
+}
+
+/// Writes `write_text`'s output an instruction at a time, so it can follow a decoder.
+struct TextWriter<'a> {
+    formatter: &'a InstructionFormatter,
+    annotations: Annotations,
+    line: String,
+    explanation: String,
+    /// Clocks of the instructions so far.
+    total: u32,
+}
+
+impl<'a> TextWriter<'a> {
+    fn new(formatter: &'a InstructionFormatter, annotations: Annotations) -> Self {
+        TextWriter {
+            formatter,
+            annotations,
+            line: String::new(),
+            explanation: String::new(),
+            total: 0,
+        }
+    }
+
+    fn begin(&self, out: &mut dyn Write, input: &str, origin: usize) -> io::Result<()> {
+        let formatter = self.formatter;
+        writeln!(out, "; {input}")?;
+        match formatter.syntax {
+            Syntax::Nasm | Syntax::Ndisasm => {
+                writeln!(out, "bits 16")?;
+                if origin != 0 {
+                    writeln!(out, "org {origin:#x}")?;
+                }
+            }
+            Syntax::Masm => {
+                writeln!(out, ".8086")?;
+                writeln!(out, "_TEXT segment byte public 'CODE'")?;
+                writeln!(out, "assume cs:_TEXT, ds:_TEXT, es:_TEXT, ss:_TEXT")?;
+                if origin != 0 {
+                    writeln!(out, "org 0{origin:x}h")?;
+                }
+            }
+        }
+
+        // Symbols that don't name an instruction have nothing to label, so they become
+        // constants.
+        for (&offset, name) in &formatter.symbols {
+            if formatter.labels.name(offset).is_none() {
+                match formatter.syntax {
+                    Syntax::Nasm | Syntax::Ndisasm => writeln!(out, "{name} equ {offset:#x}")?,
+                    Syntax::Masm => writeln!(out, "{name} equ 0{offset:x}h")?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `instruction`, whose encoding is `bytes`, and the annotations asked for.
+    fn instruction(
+        &mut self,
+        out: &mut dyn Write,
+        instruction: &Instruction,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let formatter = self.formatter;
+        let line = &mut self.line;
+        line.clear();
+        // Writing into a String can't fail.
+        let _ = formatter.write_label(line, instruction.offset);
+        let _ = formatter.write_encoded(line, instruction, bytes);
+        if self.annotations.clocks {
+            let timing = timing(instruction);
+            self.total += timing.total();
+            let mut comment = format!("Clocks: +{} = {}", timing.total(), self.total);
+            if timing.ea != 0 {
+                comment += &format!(" ({} + {}ea)", timing.base, timing.ea);
+            }
+            let _ = formatter.write_comment(line, &comment);
+        }
+        if self.annotations.explain {
+            self.explanation.clear();
+            let _ = write_explanation(&mut self.explanation, bytes, instruction);
+            for part in self.explanation.lines() {
+                line.push_str("\n   ");
+                let _ = formatter.write_comment(line, part);
+            }
+        }
+        writeln!(out, "{line}")
+    }
+
+    fn end(&self, out: &mut dyn Write) -> io::Result<()> {
+        if self.formatter.syntax == Syntax::Masm {
+            writeln!(out, "_TEXT ends")?;
+            writeln!(out, "end")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `input`'s text as `write_text` does, reading and decoding `reader` a chunk at a time,
+/// so however big it is only a chunk is ever in memory. Returns whether decoding succeeded,
+/// or the error writing to `out`.
+fn write_chunked(
+    out: &mut dyn Write,
+    input: &str,
+    reader: impl Read,
+    chunk: usize,
+    origin: usize,
+    formatter: &InstructionFormatter,
+    annotations: Annotations,
+) -> io::Result<Result<(), DecodeError>> {
+    let mut text = TextWriter::new(formatter, annotations);
+    text.begin(out, input, origin)?;
+    let decoded = decode_chunked(reader, chunk, origin, |instruction, bytes| {
+        match text.instruction(out, instruction, bytes) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(error) => ControlFlow::Break(error),
+        }
+    });
+    match decoded {
+        Ok(ControlFlow::Continue(())) => text.end(out).map(Ok),
+        Ok(ControlFlow::Break(error)) => Err(error),
+        Err(error) => Ok(Err(error)),
+    }
+}
+
+/// Writes what `render` does to `output`, or to stdout if there's no file.
+fn write_output(
+    output: Option<&Path>,
+    render: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    match output {
+        Some(path) => write_atomically(path, |out| render(out)),
+        // Standard output flushes every line, which dominates the time to print a big listing.
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            render(&mut out).and_then(|()| out.flush())
+        }
+    }
+}
+
+/// Reports an error from `write_output`.
+fn output_failed(output: Option<&Path>, error: io::Error) -> ExitCode {
+    match output {
+        Some(path) => eprintln!("{}: {error}", path.display()),
+        None => eprintln!("failed to write output: {error}"),
+    }
+    Failure::Io.into()
+}
+
+impl Args {
+    /// Whether the output goes to stdout rather than files.
+    fn to_stdout(&self) -> bool {
+        self.output.is_none() && self.output_dir.is_none()
+    }
+
+    fn color(&self) -> bool {
+        match self.color {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => {
+                matches!(self.format, Format::Text)
+                    && self.to_stdout()
+                    && env::var_os("NO_COLOR").is_none()
+                    && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+fn report_disagreement(input: &str, disagreement: Disagreement) {
+    let boundary = disagreement.boundary;
+    match disagreement.resynchronized {
+        Some(offset) => tracing::warn!(
+            "{input}: the thread starting at {boundary:#x} was out of step until {offset:#x}"
+        ),
+        None => tracing::warn!(
+            "{input}: the thread starting at {boundary:#x} never fell into step, so its share \
+             was decoded again"
+        ),
+    }
+}
+
+/// Writes the blank line between one input's output and the next, unless this is the first.
+fn separate(out: &mut dyn Write, first: &mut bool) -> io::Result<()> {
+    if !std::mem::take(first) {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Disassembles `input` to `out` with `--chunk`. An error reading or decoding it is returned
+/// inside, once any output it got as far as is written.
+fn disassemble_chunked(
+    out: &mut dyn Write,
+    first: &mut bool,
+    args: &Args,
+    input: &str,
+    chunk: usize,
+) -> io::Result<Result<(), Failed>> {
+    // An MZ executable is decoded from the entry point its header gives, so needs it in full.
+    let opened = if input == STDIN {
+        Ok(Box::new(io::stdin().lock()) as Box<dyn Read>)
+    } else {
+        File::open(input).map(|file| Box::new(file) as Box<dyn Read>)
+    };
+    let mut origin = args.format_args.origin();
+    let opened = opened.and_then(|mut file| {
+        if args.selects() {
+            let offset = args.offset.unwrap_or(0);
+            io::copy(&mut (&mut file).take(offset as u64), &mut io::sink())?;
+            origin += offset;
+            // Selected bytes are plain code, so there's no header to look for.
+            let length = args.length.map_or(u64::MAX, |length| length as u64);
+            return Ok((Vec::new(), Box::new(file.take(length)) as Box<dyn Read>));
+        }
+        let mut magic = Vec::new();
+        (&mut file).take(2).read_to_end(&mut magic)?;
+        Ok((magic, file))
+    });
+    let (magic, file) = match opened {
+        Ok(opened) => opened,
+        Err(error) => return Ok(Err(Failure::Io.failed(format!("{input}: {error}")))),
+    };
+    if is_exe(&magic) {
+        return Ok(Err(Failure::Usage.failed(format!(
+            "{input}: MZ executables can't be decoded in chunks"
+        ))));
+    }
+    let mut formatter = match args.format_args.formatter(&[]) {
+        Ok(formatter) => formatter,
+        Err(message) => return Ok(Err(message)),
+    };
+    // Without the whole listing there are no labels, so branches are relative to themselves.
+    formatter.label_style = LabelStyle::Relative;
+    formatter.color = args.color();
+
+    let size = match input {
+        STDIN => None,
+        path => fs::metadata(path).ok().map(|metadata| metadata.len()),
+    };
+    let total = size.map(|size| {
+        let rest = size.saturating_sub(args.offset.unwrap_or(0) as u64);
+        args.length.map_or(rest, |length| rest.min(length as u64))
+    });
+
+    separate(out, first)?;
+    let reader = ProgressReader {
+        reader: magic.chain(file),
+        progress: Progress::new(input, total, "bytes", args.to_stdout()),
+    };
+    Ok(write_chunked(
+        out,
+        input,
+        reader,
+        chunk,
+        origin,
+        &formatter,
+        args.annotations,
+    )?
+    .map_err(|error| decode_failed(input, &error)))
+}
+
+/// Disassembles `input` to `out`. An error reading or decoding it is returned inside, and
+/// nothing is written for it.
+fn disassemble(
+    out: &mut dyn Write,
+    first: &mut bool,
+    args: &Args,
+    input: &str,
+) -> io::Result<Result<(), Failed>> {
+    let origin = args.format_args.origin();
+    let file = match &args.bytes {
+        Some(hex) => parse_bytes(hex).map(Input::Read),
+        None => Input::open(input, args.mmap),
+    };
+    let file = match file {
+        Ok(file) => file,
+        Err(message) => return Ok(Err(message)),
+    };
+    let selected = if args.selects() {
+        args.select(input, &file, origin)
+    } else {
+        code(input, &file, origin)
+    };
+    let decoded = selected.and_then(|(image, origin)| {
+        let instructions = match args.threads {
+            Some(threads) => decode_parallel(image, origin, threads).map(|decoded| {
+                for disagreement in decoded.disagreements {
+                    report_disagreement(input, disagreement);
+                }
+                decoded.instructions
+            }),
+            None => decode_at(image, origin),
+        };
+        let instructions = instructions.map_err(|error| decode_failed(input, &error))?;
+        log_decoded(input, image, origin, &instructions);
+        Ok((image, origin, instructions))
+    });
+    let (image, origin, instructions) = match decoded {
+        Ok(decoded) => decoded,
+        Err(message) => return Ok(Err(message)),
+    };
+    let mut formatter = match args.format_args.formatter(&instructions) {
+        Ok(formatter) => formatter,
+        Err(message) => return Ok(Err(message)),
+    };
+    formatter.color = args.color();
+    let total = Some(instructions.len() as u64);
+    let mut progress = Progress::new(input, total, "instructions", args.to_stdout());
+
+    if let Some(path) = &args.emit_symbols {
+        if let Err(message) = write_symbols(path, &formatter) {
+            return Ok(Err(message));
+        }
+    }
+
+    separate(out, first)?;
+    match args.format {
+        Format::Text => write_text(
+            out,
+            input,
+            image,
+            origin,
+            instructions.iter().zip(1..).map(|(instruction, written)| {
+                progress.set(written);
+                instruction
+            }),
+            &formatter,
+            args.annotations,
+        )?,
+        Format::Json => write_json(out, &instructions, image, origin, &formatter)?,
+        Format::Csv => write_csv(out, &instructions, image, origin, &formatter)?,
+        Format::Ndisasm => {
+            let formatter = InstructionFormatter {
+                color: formatter.color,
+                ..InstructionFormatter::ndisasm()
+            };
+            write_ndisasm(out, &instructions, image, origin, &formatter)?
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Disassembles `inputs` one after another to `output`, or stdout if there's no file, adding
+/// those that fail to `failed` with how they failed. Returns whether the output was written, or
+/// there was none to write because every input failed.
+fn write_inputs<'a>(
+    args: &Args,
+    inputs: &[&'a str],
+    output: Option<&Path>,
+    failed: &mut Vec<(&'a str, Failure)>,
+) -> bool {
+    let before = failed.len();
+    let result = write_output(output, |out| {
+        let mut first = true;
+        for &input in inputs {
+            let disassembled = match args.chunk {
+                Some(chunk) => disassemble_chunked(out, &mut first, args, input, chunk)?,
+                None => disassemble(out, &mut first, args, input)?,
+            };
+            if let Err(failure) = disassembled {
+                eprintln!("{failure}");
+                failed.push((input, failure.failure));
+            }
+        }
+        if failed.len() - before == inputs.len() {
+            // Fail the write too, so an output file isn't left with nothing or half of one.
+            return Err(io::Error::other("disassembly failed"));
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => true,
+        Err(_) if failed.len() - before == inputs.len() => true,
+        Err(error) => {
+            output_failed(output, error);
+            false
+        }
+    }
+}
+
+/// The file `--output-dir` gets for `input`: its name, with the format's extension.
+fn output_name(input: &str, format: Format) -> PathBuf {
+    let name = match input {
+        STDIN => "stdin".into(),
+        BYTES => "bytes".into(),
+        path => Path::new(path)
+            .file_stem()
+            .map_or_else(|| "output".into(), |stem| stem.to_os_string()),
+    };
+    let extension = match format {
+        Format::Text => "asm",
+        Format::Json => "json",
+        Format::Csv => "csv",
+        Format::Ndisasm => "txt",
+    };
+    Path::new(&name).with_extension(extension)
+}
+
+pub fn run(args: Args) -> ExitCode {
+    if args.watch_args.watch {
+        let inputs: Vec<&str> = args.inputs.iter().map(String::as_str).collect();
+        return watch(&args.watch_args, &inputs, args.nasm.as_deref(), || {
+            run_once(&args)
+        });
+    }
+    run_once(&args)
+}
+
+fn run_once(args: &Args) -> ExitCode {
+    let inputs: Vec<&str> = match args.bytes {
+        Some(_) => vec![BYTES],
+        None => args.inputs.iter().map(String::as_str).collect(),
+    };
+    let several = inputs.len() > 1;
+    // --output takes precedence, so --output-dir can be a default from the configuration.
+    let directory = match args.output {
+        Some(_) => None,
+        None => args.output_dir.as_deref(),
+    };
+    if several && args.emit_symbols.is_some() {
+        return Failure::Usage
+            .failed("--emit-symbols takes a single input")
+            .report();
+    }
+    if several && directory.is_none() && !matches!(args.format, Format::Text) {
+        return Failure::Usage
+            .failed("only text can be written for several inputs, unless to --output-dir")
+            .report();
+    }
+
+    let mut failed = Vec::new();
+    let written = match directory {
+        Some(directory) => {
+            if let Err(error) = fs::create_dir_all(directory) {
+                return Failure::Io
+                    .failed(format!("{}: {error}", directory.display()))
+                    .report();
+            }
+            inputs.iter().fold(true, |written, &input| {
+                let path = directory.join(output_name(input, args.format));
+                write_inputs(args, &[input], Some(&path), &mut failed) && written
+            })
+        }
+        None => write_inputs(args, &inputs, args.output.as_deref(), &mut failed),
+    };
+
+    if several {
+        let succeeded = inputs.len() - failed.len();
+        if failed.is_empty() {
+            eprintln!("disassembled {succeeded} files");
+        } else {
+            let names: Vec<&str> = failed.iter().map(|&(input, _)| input).collect();
+            eprintln!(
+                "disassembled {succeeded} of {} files; failed: {}",
+                inputs.len(),
+                names.join(", ")
+            );
+        }
+    }
+    // The first input to fail says how, if the output was written.
+    match failed.first() {
+        _ if !written => Failure::Io.into(),
+        Some(&(_, failure)) => failure.into(),
+        None => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,794 @@
+use crate::{
+    disasm::{log_decoded, FormatArgs},
+    parse_address,
+    progress::Progress,
+};
+use computer_enhance::{
+    atomic_file::write_atomically,
+    coverage::{Coverage, Use},
+    dos,
+    exe::{is_exe, Exe},
+    export::write_csv_field,
+    failure::{Failed, Failure},
+    flags::Flags,
+    formatter::{Case, InstructionFormatter, LabelStyle, Labels},
+    image::{write_image, ImageFormat},
+    instruction::Register,
+    listing::Listing,
+    memory::Memory,
+    simulator::{physical_address, Clocks, Cpu, Model, PrefetchQueue, Registers},
+    text_mode::write_text_screen,
+    trace_hash::TraceHash,
+};
+use std::{
+    cell::Cell,
+    collections::BTreeSet,
+    env, fs,
+    io::{self, IsTerminal, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    rc::Rc,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    #[command(flatten)]
+    state_args: StateArgs,
+
+    /// Size of simulated memory in bytes.
+    #[arg(long, default_value = "0x100000", value_parser = parse_address)]
+    memory: usize,
+
+    /// How to print the trace of executed instructions.
+    #[arg(long, value_enum, default_value_t)]
+    trace: Trace,
+
+    /// After execution, report which bytes of the program were executed, which were only
+    /// decoded, and which were never touched.
+    #[arg(long)]
+    coverage: bool,
+
+    /// With --coverage, also list every decoded instruction marked with whether it ran.
+    #[arg(long, requires = "coverage")]
+    coverage_listing: bool,
+
+    /// Print only a digest of the trace, covering every instruction executed and the state it
+    /// left, so runs can be checked for changes without keeping whole traces.
+    #[arg(long)]
+    hash: bool,
+
+    /// Estimate the clocks each instruction takes on an 8086, with a running total.
+    #[arg(long = "showclocks")]
+    show_clocks: bool,
+
+    /// With --showclocks, also estimate 8088 clocks, whose 8-bit bus splits every word transfer.
+    #[arg(long = "explicit8088", requires = "show_clocks")]
+    explicit_8088: bool,
+
+    /// With --showclocks, model the prefetch queue: count the clocks spent waiting for
+    /// instruction bytes, such as after each taken branch flushes the queue.
+    #[arg(long, requires = "show_clocks")]
+    prefetch: bool,
+
+    /// With --showclocks, also write each instruction's clocks to this CSV file: IP, mnemonic,
+    /// base, EA, transfer penalty, prefetch wait, and the running total.
+    #[arg(long, value_name = "PATH", requires = "show_clocks")]
+    clocks_csv: Option<PathBuf>,
+
+    /// Emulate the DOS services tiny programs use to print and exit (INT 21h functions 00h,
+    /// 02h, 09h, and 4Ch, and INT 20h), printing to stdout on lines apart from the trace.
+    #[arg(long)]
+    dos: bool,
+
+    /// Stop after executing this many instructions, in case the program never ends.
+    #[arg(long, value_name = "N", default_value_t = 10_000_000)]
+    max_instructions: u64,
+
+    /// Stop once the estimated 8086 clocks reach this many, in case the program never ends.
+    #[arg(long, value_name = "N", default_value_t = 1_000_000_000)]
+    max_clocks: u64,
+
+    /// After execution, show the CGA text screen at 0xB8000, in color on a terminal.
+    #[arg(long)]
+    text_screen: bool,
+
+    /// After execution, write simulated memory to this file.
+    #[arg(long, value_name = "PATH")]
+    dump: Option<PathBuf>,
+
+    /// Dump only START:LENGTH bytes rather than all of memory, e.g. 0x100:0x4000.
+    #[arg(long, value_name = "START:LENGTH", value_parser = parse_range, requires = "dump")]
+    dump_range: Option<(usize, usize)>,
+
+    /// Convert WIDTH x HEIGHT RGBA pixels of memory at OFFSET into an image after execution,
+    /// e.g. 0x100x64x64.
+    #[arg(long, value_name = "OFFSETxWIDTHxHEIGHT", value_parser = parse_render)]
+    render: Option<Render>,
+
+    /// Image written by --render; .png, .bmp, or .ppm.
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = "render.png",
+        requires = "render"
+    )]
+    render_output: PathBuf,
+}
+
+/// Machine state to set up after loading, before the first instruction.
+#[derive(clap::Args)]
+pub struct StateArgs {
+    /// Start a register at a value, e.g. ax=0x1234; may be repeated.
+    #[arg(long = "set", value_name = "REG=VALUE", value_parser = parse_assignment)]
+    registers: Vec<(Register, u16)>,
+
+    /// Start executing at this IP instead of where the program was loaded.
+    #[arg(long, value_name = "OFFSET", value_parser = parse_word)]
+    ip: Option<u16>,
+}
+
+impl StateArgs {
+    pub fn apply(&self, cpu: &mut Cpu) {
+        for &(register, value) in &self.registers {
+            cpu.registers.set(register, value);
+        }
+        if let Some(ip) = self.ip {
+            cpu.ip = ip;
+        }
+    }
+}
+
+/// A value that fits in 16 bits.
+fn parse_word(text: &str) -> Result<u16, String> {
+    let value = parse_address(text)?;
+    u16::try_from(value).map_err(|_| format!("{value:#x} doesn't fit in 16 bits"))
+}
+
+fn parse_assignment(text: &str) -> Result<(Register, u16), String> {
+    let (register, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected REG=VALUE, got {text:?}"))?;
+    let register: Register = register.parse()?;
+    let value = parse_word(value)?;
+    if !register.is_word() && value > 0xFF {
+        return Err(format!("{value:#x} doesn't fit in {}", register.name()));
+    }
+    Ok((register, value))
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Trace {
+    /// Padded values, separated by commas.
+    #[default]
+    Annotated,
+    /// Exactly the text of the reference sim86 outputs, so a run can be compared to them.
+    Reference,
+}
+
+impl Trace {
+    /// `ax: 0x0000 -> 0x0001`, or `ax:0x0->0x1` for the reference.
+    pub fn word_change(self, name: &str, old: u16, new: u16) -> String {
+        match self {
+            Trace::Annotated => format!("{name}: {old:#06x} -> {new:#06x}"),
+            Trace::Reference => format!("{name}:{old:#x}->{new:#x}"),
+        }
+    }
+
+    /// `flags: PZ -> S`, or `flags:PZ->S` for the reference, leaving out either side when no
+    /// flag is set.
+    pub fn flags_change(self, before: Flags, after: Flags) -> String {
+        match self {
+            Trace::Annotated => {
+                let before = if before.is_empty() {
+                    String::new()
+                } else {
+                    format!("{before} ")
+                };
+                format!("flags: {before}-> {after}").trim_end().to_string()
+            }
+            Trace::Reference => format!("flags:{before}->{after}"),
+        }
+    }
+
+    pub fn join(self, changes: &[String]) -> String {
+        match self {
+            Trace::Annotated => changes.join(", "),
+            // The reference leaves a space after every change, the last included.
+            Trace::Reference => changes.iter().map(|change| format!("{change} ")).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Render {
+    offset: usize,
+    width: usize,
+    height: usize,
+}
+
+// Split from the right, so that a `0x` offset's own `x` is left alone.
+fn parse_render(text: &str) -> Result<Render, String> {
+    let mut fields = text.rsplitn(3, 'x');
+    let (Some(height), Some(width), Some(offset)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(format!("expected OFFSETxWIDTHxHEIGHT, got {text:?}"));
+    };
+    let dimension = |field: &str| {
+        field
+            .parse()
+            .map_err(|error| format!("invalid dimension {field:?}: {error}"))
+    };
+    Ok(Render {
+        offset: parse_address(offset)?,
+        width: dimension(width)?,
+        height: dimension(height)?,
+    })
+}
+
+fn render(path: &Path, render: Render, memory: &Memory) -> Result<(), Failed> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let format = extension
+        .and_then(ImageFormat::from_extension)
+        .ok_or_else(|| {
+            Failure::Usage.failed(format!(
+                "{}: expected a .png, .bmp, or .ppm path",
+                path.display()
+            ))
+        })?;
+
+    let length = render.width * render.height * 4;
+    let mut pixels = memory.slice(render.offset, length).to_vec();
+    if pixels.len() < length {
+        return Err(Failure::Usage.failed(format!(
+            "{}: the image runs past the end of memory",
+            path.display()
+        )));
+    }
+    // The homework leaves alpha at zero; treat the image as opaque so viewers show it.
+    if format != ImageFormat::Ppm && pixels.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        pixels.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0xFF);
+    }
+
+    write_atomically(path, |out| {
+        write_image(out, format, render.width, render.height, &pixels)
+    })
+    .map_err(|error| Failure::Io.failed(format!("{}: {error}", path.display())))
+}
+
+fn parse_range(text: &str) -> Result<(usize, usize), String> {
+    let (start, length) = text
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:LENGTH, got {text:?}"))?;
+    Ok((parse_address(start)?, parse_address(length)?))
+}
+
+/// A change for every register that differs between `before` and `after`.
+fn register_changes(trace: Trace, before: &Registers, after: &Registers) -> Vec<String> {
+    Registers::ALL
+        .into_iter()
+        .filter(|&register| before.get(register) != after.get(register))
+        .map(|register| {
+            let (old, new) = (before.get(register), after.get(register));
+            trace.word_change(register.name(), old, new)
+        })
+        .collect()
+}
+
+/// `Clocks: +13 = 17 (8 + 5ea)`, with `p` for unaligned transfer penalties and `q` for prefetch
+/// queue waits, spelling out the parts only when there is more than one.
+fn clocks_change(clocks: Clocks, total: u64) -> String {
+    let mut text = format!("Clocks: +{} = {total}", clocks.total());
+    if clocks.ea != 0 || clocks.penalty != 0 || clocks.wait != 0 {
+        text += &format!(" ({}", clocks.base);
+        if clocks.ea != 0 {
+            text += &format!(" + {}ea", clocks.ea);
+        }
+        if clocks.penalty != 0 {
+            text += &format!(" + {}p", clocks.penalty);
+        }
+        if clocks.wait != 0 {
+            text += &format!(" + {}q", clocks.wait);
+        }
+        text += ")";
+    }
+    text
+}
+
+/// One instruction's row of the --clocks-csv trace.
+struct ClocksRow {
+    ip: u16,
+    mnemonic: String,
+    clocks: Clocks,
+    total: u64,
+}
+
+fn write_clocks_csv(out: &mut impl Write, rows: &[ClocksRow]) -> io::Result<()> {
+    writeln!(out, "ip,mnemonic,base,ea,penalty,wait,total")?;
+    for row in rows {
+        write!(out, "{:#06x},", row.ip)?;
+        write_csv_field(out, &row.mnemonic)?;
+        let clocks = row.clocks;
+        writeln!(
+            out,
+            ",{},{},{},{},{}",
+            clocks.base, clocks.ea, clocks.penalty, clocks.wait, row.total
+        )?;
+    }
+    Ok(())
+}
+
+/// Where a program prints under `--dos`: stdout, which the trace shares, noting whether the
+/// program left a line unfinished.
+struct ProgramOutput {
+    unfinished: Rc<Cell<bool>>,
+}
+
+impl Write for ProgramOutput {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let written = io::stdout().write(bytes)?;
+        if let Some(&last) = bytes[..written].last() {
+            self.unfinished.set(last != b'\n');
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// Ends any line the program left unfinished, so the trace after it starts on its own.
+fn end_program_line(out: &mut impl Write, unfinished: &Cell<bool>) {
+    if unfinished.take() {
+        let _ = writeln!(out);
+    }
+}
+
+/// Each run of the program's bytes used the same way, by physical address, and how much ran.
+fn write_coverage(out: &mut impl Write, coverage: &Coverage) -> io::Result<()> {
+    writeln!(out, "Coverage:")?;
+    for (range, kind) in coverage.runs() {
+        let plural = if range.len() == 1 { "" } else { "s" };
+        writeln!(
+            out,
+            "  {:#07x}..{:#07x} {kind} ({} byte{plural})",
+            range.start,
+            range.end,
+            range.len()
+        )?;
+    }
+    let executed = coverage.count(Use::Executed);
+    let percent = match coverage.len() {
+        0 => 0,
+        length => executed * 100 / length,
+    };
+    writeln!(
+        out,
+        "  executed {executed} of {} bytes ({percent}%)",
+        coverage.len()
+    )
+}
+
+/// The listing with each instruction marked `*` if it ran.
+fn write_coverage_listing(
+    out: &mut impl Write,
+    coverage: &Coverage,
+    listing: &Listing,
+    formatter: &InstructionFormatter,
+) -> io::Result<()> {
+    for instruction in &listing.instructions {
+        let address = listing.address(instruction);
+        let marker = match coverage.use_at(address) {
+            Some(Use::Executed) => '*',
+            _ => ' ',
+        };
+        let text = formatter.display(instruction);
+        writeln!(out, "{marker} {:04x}: {text}", instruction.offset)?;
+    }
+    Ok(())
+}
+
+fn write_state(out: &mut impl Write, cpu: &Cpu) -> io::Result<()> {
+    writeln!(out, "Final registers:")?;
+    for register in Registers::ALL {
+        let value = cpu.registers.get(register);
+        if value != 0 {
+            writeln!(out, "      {}: {value:#06x} ({value})", register.name())?;
+        }
+    }
+    writeln!(out, "      ip: {:#06x} ({})", cpu.ip, cpu.ip)?;
+    if !cpu.flags.is_empty() {
+        writeln!(out, "   flags: {}", cpu.flags)?;
+    }
+    if let Some(code) = cpu.exit_code {
+        writeln!(out, "    exit: {code}")?;
+    }
+    Ok(())
+}
+
+/// The register, IP, and flag changes from the state captured before an instruction to `cpu`.
+pub fn changes(trace: Trace, before: (Registers, Flags, u16), cpu: &Cpu) -> Vec<String> {
+    let (registers, flags, ip) = before;
+    let mut changes = register_changes(trace, &registers, &cpu.registers);
+    // The reference has no memory column; elsewhere, show where each access landed.
+    if trace == Trace::Annotated {
+        changes.extend(cpu.accesses.iter().map(|access| {
+            let digits = 2 + 2 * access.size;
+            let address = access.address;
+            if access.write {
+                let value = match access.size {
+                    2 => cpu.memory.read_u16(address),
+                    _ => cpu.memory.read_u8(address) as u16,
+                };
+                let previous = access.previous;
+                format!("[{address:#07x}]: {previous:#0digits$x} -> {value:#0digits$x}")
+            } else {
+                format!("[{address:#07x}] read {:#0digits$x}", access.previous)
+            }
+        }));
+    }
+    changes.push(trace.word_change("ip", ip, cpu.ip));
+    if flags != cpu.flags {
+        changes.push(trace.flags_change(flags, cpu.flags));
+    }
+    changes
+}
+
+/// Segment a .COM image's program segment prefix is put at: above the interrupt vector table
+/// and the memory DOS itself would use.
+const COM_SEGMENT: u16 = 0x1000;
+
+/// End of the interrupt vector table and BIOS data area, the lowest memory a boot sector's
+/// stack may use.
+const BIOS_DATA_END: u32 = 0x500;
+
+/// Whether `file` is a boot sector: 512 bytes ending in the 0xAA55 signature.
+fn is_boot_sector(file: &[u8]) -> bool {
+    file.len() == 512 && file.ends_with(&[0x55, 0xAA])
+}
+
+/// A program loaded into a CPU, about to run its first instruction.
+pub struct Boot {
+    pub image: Vec<u8>,
+    /// The program decoded from its first instruction, at CS:IP.
+    pub listing: Listing,
+    pub cpu: Cpu,
+    /// Physical addresses the image was loaded into; leaving them ends the program.
+    pub program: Range<usize>,
+}
+
+/// Loads `input` into a CPU with `memory` bytes, as `format_args` describe, with IP pointing
+/// at it. Normally that's at `origin` in segment 0; a .COM image gets a segment of its own, a
+/// boot sector is checked for its signature, and an MZ executable is relocated and started
+/// where its header says.
+pub fn boot(input: &str, format_args: &FormatArgs, memory: usize) -> Result<Boot, Failed> {
+    let file = fs::read(input).map_err(|error| Failure::Io.failed(format!("{input}: {error}")))?;
+    if is_exe(&file) {
+        return boot_exe(input, file, memory);
+    }
+    if format_args.boot && !is_boot_sector(&file) {
+        return Err(Failure::Invalid.failed(format!(
+            "{input}: a boot sector is 512 bytes ending in 0x55 0xAA"
+        )));
+    }
+    let (image, origin) = (file, format_args.origin());
+    let segment = if format_args.com { COM_SEGMENT } else { 0 };
+    let start = physical_address(segment, 0) + origin;
+    if format_args.com && origin + image.len() > 0xFFFE {
+        return Err(Failure::Invalid.failed(format!(
+            "{input}: {} bytes is too big for a .COM image",
+            image.len()
+        )));
+    }
+    let end = if format_args.com {
+        physical_address(segment, 0) + 0x10000
+    } else {
+        start + image.len()
+    };
+    if memory == 0 || end > memory {
+        return Err(Failure::Invalid.failed(format!(
+            "{input}: {} bytes at {start:#x} don't fit in {memory} bytes of memory",
+            image.len(),
+        )));
+    }
+
+    let mut cpu = Cpu::with_memory(memory);
+    cpu.memory.load(start, &image);
+    cpu.ip = origin as u16;
+    // The stack grows down from the top of the segment towards the program.
+    cpu.stack = Some((origin + image.len()) as u32..0x10000);
+    if format_args.com {
+        for register in [Register::Cs, Register::Ds, Register::Es, Register::Ss] {
+            cpu.registers.set(register, segment);
+        }
+        // As DOS leaves it: a RET from the program pops the zero word at the top of the stack
+        // and lands on the INT 20h at the start of the prefix, terminating it.
+        cpu.memory.load(physical_address(segment, 0), &[0xCD, 0x20]);
+        cpu.registers.set(Register::Sp, 0xFFFE);
+    }
+    if format_args.boot {
+        // Everything in segment 0, with the stack just below the boot sector, above the BIOS
+        // data area, and DL naming the drive booted from: the first floppy.
+        cpu.registers.set(Register::Sp, origin as u16);
+        cpu.registers.set(Register::Dl, 0x00);
+        cpu.stack = Some(BIOS_DATA_END..origin as u32);
+    }
+    // Only the listing is decoded up front, and it steps over data: the CPU decodes what it
+    // really runs as it fetches it.
+    let listing = Listing::decode(&cpu.memory, start, origin, image.len());
+    log_decoded(input, &image, origin, &listing.instructions);
+    Ok(Boot {
+        program: start..start + image.len(),
+        listing,
+        image,
+        cpu,
+    })
+}
+
+// `boot` for an MZ executable, whose load module goes just past a program segment prefix at
+// the same segment a .COM image would use.
+fn boot_exe(input: &str, file: Vec<u8>, memory: usize) -> Result<Boot, Failed> {
+    let exe =
+        Exe::parse(&file).map_err(|error| Failure::Invalid.failed(format!("{input}: {error}")))?;
+    let prefix = COM_SEGMENT;
+    let segment = prefix + 0x10;
+    let start = physical_address(segment, 0);
+    let needed = exe.module.len() + exe.header.min_extra_paragraphs as usize * 16;
+    if memory == 0 || start + needed > memory {
+        return Err(Failure::Invalid.failed(format!(
+            "{input}: {needed} bytes at {start:#x} don't fit in {memory} bytes of memory",
+        )));
+    }
+
+    let mut cpu = Cpu::with_memory(memory);
+    exe.load(&mut cpu.memory, segment);
+    cpu.memory.load(physical_address(prefix, 0), &[0xCD, 0x20]);
+    let header = exe.header;
+    cpu.registers
+        .set(Register::Cs, segment.wrapping_add(header.cs));
+    cpu.registers
+        .set(Register::Ss, segment.wrapping_add(header.ss));
+    cpu.registers.set(Register::Sp, header.sp);
+    cpu.registers.set(Register::Ds, prefix);
+    cpu.registers.set(Register::Es, prefix);
+    cpu.ip = header.ip;
+    // Nothing says where the stack ends, so only catch underflows.
+    let top = match header.sp {
+        0 => 0x10000,
+        sp => sp as u32,
+    };
+    cpu.stack = Some(0..top);
+
+    let image = exe.module.to_vec();
+    let entry = exe.entry().min(image.len());
+    let listing = Listing::decode(
+        &cpu.memory,
+        start + entry,
+        header.ip as usize,
+        image.len() - entry,
+    );
+    log_decoded(
+        input,
+        &image[entry..],
+        header.ip as usize,
+        &listing.instructions,
+    );
+    Ok(Boot {
+        program: start..start + image.len(),
+        listing,
+        image,
+        cpu,
+    })
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let Boot {
+        mut listing,
+        mut cpu,
+        program,
+        ..
+    } = match boot(&args.input, &args.format_args, args.memory) {
+        Ok(booted) => booted,
+        Err(failed) => return failed.report(),
+    };
+    let mut formatter = match args.format_args.formatter(&listing.instructions) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+
+    args.state_args.apply(&mut cpu);
+    let unfinished = Rc::new(Cell::new(false));
+    if args.dos {
+        dos::install(
+            &mut cpu,
+            ProgramOutput {
+                unfinished: Rc::clone(&unfinished),
+            },
+        );
+    }
+
+    let mut stdout = io::stdout().lock();
+    if args.trace == Trace::Reference && !args.hash {
+        formatter.case = Case::Lower;
+        formatter.label_style = LabelStyle::Relative;
+        let name = Path::new(&args.input).with_extension("");
+        let _ = writeln!(stdout, "--- {} execution ---", name.display());
+    }
+    let mut result = Ok(());
+    let mut line = String::new();
+    let (mut total_clocks, mut total_clocks_8088) = (0, 0);
+    let mut executed = 0;
+    let mut hash = args.hash.then(TraceHash::new);
+    let mut clocks_rows = Vec::new();
+    let mut coverage = Coverage::new(program.clone());
+    coverage.decoded(&listing);
+    // Instructions that have written to code, which are only warned about once.
+    let mut patched = BTreeSet::new();
+    let mut queue = PrefetchQueue::new(Model::I8086);
+    let mut queue_8088 = PrefetchQueue::new(Model::I8088);
+    let mut progress = Progress::new(&args.input, None, "instructions", true);
+    // Fetch from memory at CS:IP rather than walking the listing, so jumps land wherever they
+    // point. Running off the end of the program, or terminating it, stops the simulation.
+    while cpu.exit_code.is_none() && program.contains(&cpu.instruction_address()) {
+        if executed == args.max_instructions {
+            result = Err(Failure::Simulation.failed(format!(
+                "stopped after {executed} instructions (--max-instructions)"
+            )));
+            break;
+        }
+        if total_clocks >= args.max_clocks {
+            result = Err(Failure::Simulation.failed(format!(
+                "stopped after {total_clocks} clocks (--max-clocks)"
+            )));
+            break;
+        }
+        let instruction = match cpu.fetch() {
+            Ok(Some(instruction)) => instruction,
+            Ok(None) => break,
+            Err(error) => {
+                result = Err(Failure::from(&error).failed(error.to_string()));
+                break;
+            }
+        };
+        let mut clocks = cpu.clocks(&instruction, Model::I8086);
+        let mut clocks_8088 = cpu.clocks(&instruction, Model::I8088);
+        let before = (cpu.registers, cpu.flags, cpu.ip);
+        let address = cpu.instruction_address();
+        if let Some(hash) = &mut hash {
+            hash.instruction(&cpu, &instruction);
+        }
+        if let Err(error) = cpu.execute(&instruction) {
+            result = Err(Failure::Simulation.failed(error.to_string()));
+            break;
+        }
+        tracing::trace!(
+            "{address:#07x}: {}, then IP {:#06x}",
+            formatter.display(&instruction),
+            cpu.ip
+        );
+        if args.prefetch {
+            let branched = cpu.ip as usize != instruction.end();
+            clocks = queue.advance(&instruction, clocks, branched);
+            clocks_8088 = queue_8088.advance(&instruction, clocks_8088, branched);
+        }
+        if let Some(write) = listing.refresh(&cpu) {
+            if patched.insert(instruction.offset) {
+                tracing::warn!(
+                    "{:04x}: writes to code at {:#07x}; decoding it again",
+                    instruction.offset,
+                    write.address
+                );
+            }
+            formatter.labels = Labels::with_symbols(&listing.instructions, &formatter.symbols);
+            coverage.decoded(&listing);
+        }
+        coverage.executed(address, instruction.length);
+        executed += 1;
+        progress.set(executed);
+        total_clocks += clocks.total() as u64;
+        total_clocks_8088 += clocks_8088.total() as u64;
+        if args.clocks_csv.is_some() {
+            let mut mnemonic = String::new();
+            let _ = formatter.write_mnemonic(&mut mnemonic, &instruction);
+            clocks_rows.push(ClocksRow {
+                ip: before.2,
+                mnemonic,
+                clocks,
+                total: total_clocks,
+            });
+        }
+        if let Some(hash) = &mut hash {
+            hash.state(&cpu);
+            continue;
+        }
+
+        line.clear();
+        let _ = formatter.write(&mut line, &instruction);
+        let mut comment = args.trace.join(&changes(args.trace, before, &cpu));
+        if args.explicit_8088 {
+            let clocks = clocks_change(clocks_8088, total_clocks_8088);
+            comment = format!("8088 {clocks} | {comment}");
+        }
+        if args.show_clocks {
+            let clocks = clocks_change(clocks, total_clocks);
+            comment = if args.explicit_8088 {
+                format!("8086 {clocks}, {comment}")
+            } else {
+                format!("{clocks} | {comment}")
+            };
+        }
+        let _ = formatter.write_comment(&mut line, &comment);
+        end_program_line(&mut stdout, &unfinished);
+        let _ = writeln!(stdout, "{line}");
+    }
+    drop(progress);
+    end_program_line(&mut stdout, &unfinished);
+
+    match hash {
+        Some(hash) => {
+            let _ = writeln!(stdout, "{:016x}", hash.digest());
+        }
+        None => {
+            let _ = writeln!(stdout);
+            let _ = write_state(&mut stdout, &cpu);
+        }
+    }
+    if args.text_screen {
+        let color = env::var_os("NO_COLOR").is_none() && stdout.is_terminal();
+        let _ = writeln!(stdout);
+        let _ = write_text_screen(&mut stdout, &cpu.memory, color);
+    }
+    if args.coverage {
+        let _ = writeln!(stdout);
+        let _ = write_coverage(&mut stdout, &coverage);
+        if args.coverage_listing {
+            let _ = writeln!(stdout);
+            let _ = write_coverage_listing(&mut stdout, &coverage, &listing, &formatter);
+        }
+    }
+
+    // Dump even after a failure; the memory at that point is often the interesting part.
+    let mut status = ExitCode::SUCCESS;
+    if let Some(path) = &args.dump {
+        let (start, length) = args.dump_range.unwrap_or((0, cpu.memory.len()));
+        let bytes = cpu.memory.slice(start, length);
+        if bytes.len() < length {
+            tracing::warn!(
+                "{}: only {} of the {length} bytes requested exist",
+                path.display(),
+                bytes.len()
+            );
+        }
+        if let Err(error) = write_atomically(path, |out| out.write_all(bytes)) {
+            eprintln!("{}: {error}", path.display());
+            status = Failure::Io.into();
+        }
+    }
+
+    if let Some(path) = &args.clocks_csv {
+        if let Err(error) = write_atomically(path, |out| write_clocks_csv(out, &clocks_rows)) {
+            eprintln!("{}: {error}", path.display());
+            status = Failure::Io.into();
+        }
+    }
+
+    if let Some(spec) = args.render {
+        if let Err(failed) = render(&args.render_output, spec, &cpu.memory) {
+            status = failed.report();
+        }
+    }
+
+    match result {
+        Ok(()) => status,
+        Err(failed) => failed
+            .failure
+            .failed(format!("{}: {failed}", args.input))
+            .report(),
+    }
+}
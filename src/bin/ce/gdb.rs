@@ -0,0 +1,359 @@
+use crate::debug::{Session, Stop};
+use computer_enhance::{flags::Flags, instruction::Register};
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+/// The registers of GDB's i386 layout, in its order: the general registers as their 16-bit
+/// halves, then EIP and EFLAGS (handled separately), then the segment registers. FS and GS
+/// don't exist on the 8086 and read as 0.
+const REGISTERS: [Option<Register>; 16] = [
+    Some(Register::Ax),
+    Some(Register::Cx),
+    Some(Register::Dx),
+    Some(Register::Bx),
+    Some(Register::Sp),
+    Some(Register::Bp),
+    Some(Register::Si),
+    Some(Register::Di),
+    None,
+    None,
+    Some(Register::Cs),
+    Some(Register::Ss),
+    Some(Register::Ds),
+    Some(Register::Es),
+    None,
+    None,
+];
+const EIP: usize = 8;
+const EFLAGS: usize = 9;
+
+/// Signals reported to GDB for why the target stopped.
+const SIGTRAP: u8 = 5;
+const SIGILL: u8 = 4;
+const SIGINT: u8 = 2;
+
+/// What GDB sends outside any packet to break in on a running target, for Ctrl-C.
+const INTERRUPT: u8 = 0x03;
+
+/// Waits for GDB to connect to `address` and serves it until it detaches or disconnects.
+///
+/// GDB sees the 8086 as an i386 with 16-bit values in 32-bit registers: `set architecture
+/// i8086` and `target remote` it. The PC is IP, so breakpoints are IP values as for `--break`,
+/// but memory addresses are physical.
+pub fn serve(session: &mut Session, address: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    eprintln!("waiting for gdb on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    eprintln!("gdb connected from {peer}");
+    Connection::new(stream)?.serve(session)
+}
+
+struct Connection {
+    input: BufReader<TcpStream>,
+    output: BufWriter<TcpStream>,
+}
+
+/// What to do after replying to a packet.
+enum Next {
+    Continue,
+    Close,
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Bytes written as pairs of hex digits.
+pub fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// `ADDR,LENGTH`, both hex.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((parse_number(address)?, parse_number(length)?))
+}
+
+/// A register value as GDB sends it: 32 bits, little-endian, of which the 8086 has the low 16.
+fn parse_register(text: &str) -> Option<u16> {
+    let bytes = parse_hex_bytes(text)?;
+    Some(u16::from_le_bytes([*bytes.first()?, *bytes.get(1)?]))
+}
+
+fn encode_register(value: u16) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .chain(&[0, 0])
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Connection> {
+        Ok(Connection {
+            input: BufReader::new(stream.try_clone()?),
+            output: BufWriter::new(stream),
+        })
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.input.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Whether GDB has sent an interrupt since the target was resumed, or gone away, checked
+    /// without waiting. Anything else it sent is left for `read_packet`.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if self.input.buffer().is_empty() {
+            self.input.get_ref().set_nonblocking(true)?;
+            let filled = self.input.fill_buf().map(|buffer| buffer.len());
+            self.input.get_ref().set_nonblocking(false)?;
+            match filled {
+                Ok(0) => return Ok(true),
+                Ok(_) => {}
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(error) => return Err(error),
+            }
+        }
+        if self.input.buffer().first() == Some(&INTERRUPT) {
+            self.input.consume(1);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Resumes until the program stops by itself or GDB interrupts it, and the stop reply.
+    fn resume(&mut self, session: &mut Session) -> String {
+        match session.resume_until(|| self.interrupted().unwrap_or(true)) {
+            (_, Some(Stop::Error(_))) => stop_reply(session, SIGILL),
+            (_, Some(Stop::Interrupted)) => stop_reply(session, SIGINT),
+            _ => stop_reply(session, SIGTRAP),
+        }
+    }
+
+    /// The next packet's data, acknowledged, or `None` once GDB disconnects. Acks, stray
+    /// bytes, and interrupt requests while the target is already stopped are skipped; packets
+    /// with a bad checksum are refused so GDB sends them again.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+            let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+            let expected = hex_digit(high).zip(hex_digit(low)).map(|(h, l)| h << 4 | l);
+            if expected != Some(sum) {
+                self.output.write_all(b"-")?;
+                self.output.flush()?;
+                continue;
+            }
+            self.output.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.output, "${data}#{sum:02x}")?;
+        self.output.flush()
+    }
+
+    fn serve(&mut self, session: &mut Session) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            // Kill gets no reply; there's nothing left to debug.
+            if packet.starts_with('k') {
+                break;
+            }
+            // Continuing is handled here, where GDB can be listened to for an interrupt.
+            if packet.starts_with('c') {
+                let reply = self.resume(session);
+                self.write_packet(&reply)?;
+                continue;
+            }
+            let (reply, next) = handle(session, &packet);
+            self.write_packet(&reply)?;
+            if let Next::Close = next {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_register(session: &Session, index: usize) -> Option<u16> {
+    let cpu = &session.cpu;
+    match index {
+        EIP => Some(cpu.ip),
+        EFLAGS => Some(cpu.flags.0),
+        _ => Some(
+            REGISTERS
+                .get(index)?
+                .map_or(0, |register| cpu.registers.get(register)),
+        ),
+    }
+}
+
+fn write_register(session: &mut Session, index: usize, value: u16) -> bool {
+    let cpu = &mut session.cpu;
+    match index {
+        EIP => cpu.ip = value,
+        EFLAGS => cpu.flags = Flags(value & Flags::ALL.0),
+        _ => match REGISTERS.get(index) {
+            Some(Some(register)) => cpu.registers.set(*register, value),
+            // Writes to FS and GS go nowhere.
+            Some(None) => {}
+            None => return false,
+        },
+    }
+    true
+}
+
+/// The stop reply for where the program is now: still running, or exited with its code.
+fn stop_reply(session: &Session, signal: u8) -> String {
+    if session.running() {
+        format!("S{signal:02x}")
+    } else {
+        format!("W{:02x}", session.cpu.exit_code.unwrap_or(0))
+    }
+}
+
+/// The reply to one packet. Anything unrecognized gets the empty reply, which tells GDB it's
+/// unsupported.
+fn handle(session: &mut Session, packet: &str) -> (String, Next) {
+    let ok = || String::from("OK");
+    let error = || String::from("E01");
+    let (command, arguments) = match packet.char_indices().nth(1) {
+        Some((index, _)) => packet.split_at(index),
+        None => (packet, ""),
+    };
+    let reply = match command {
+        "?" => stop_reply(session, SIGTRAP),
+        "g" => (0..REGISTERS.len())
+            .filter_map(|index| read_register(session, index))
+            .map(encode_register)
+            .collect(),
+        "G" => {
+            let values: Option<Vec<u16>> = (0..arguments.len() / 8)
+                .map(|index| {
+                    let value = arguments.get(index * 8..index * 8 + 8);
+                    value.and_then(parse_register)
+                })
+                .collect();
+            match values {
+                Some(values) => {
+                    for (index, value) in values.into_iter().enumerate() {
+                        write_register(session, index, value);
+                    }
+                    ok()
+                }
+                None => error(),
+            }
+        }
+        "p" => match parse_number(arguments).and_then(|index| read_register(session, index)) {
+            Some(value) => encode_register(value),
+            None => error(),
+        },
+        "P" => {
+            let written = arguments.split_once('=').and_then(|(index, value)| {
+                Some(write_register(
+                    session,
+                    parse_number(index)?,
+                    parse_register(value)?,
+                ))
+            });
+            if written == Some(true) {
+                ok()
+            } else {
+                error()
+            }
+        }
+        "m" => match parse_range(arguments) {
+            Some((address, length)) => session
+                .cpu
+                .memory
+                .slice(address, length)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            None => error(),
+        },
+        "M" => {
+            let parsed = arguments.split_once(':').and_then(|(range, data)| {
+                let (address, length) = parse_range(range)?;
+                let bytes = parse_hex_bytes(data)?;
+                (bytes.len() == length).then_some((address, bytes))
+            });
+            match parsed {
+                Some((address, bytes)) => {
+                    session.cpu.memory.load(address, &bytes);
+                    ok()
+                }
+                None => error(),
+            }
+        }
+        "s" if !session.running() => stop_reply(session, SIGTRAP),
+        "s" => match session.step() {
+            Ok(_) => stop_reply(session, SIGTRAP),
+            Err(_) => stop_reply(session, SIGILL),
+        },
+        "Z" | "z" => {
+            // Only software breakpoints, `Z0,ADDR,KIND`.
+            let mut fields = arguments.split(',');
+            let breakpoint = match (fields.next(), fields.next().and_then(parse_number)) {
+                (Some("0"), Some(address)) => u16::try_from(address).ok(),
+                _ => None,
+            };
+            match breakpoint {
+                Some(ip) if command == "Z" => {
+                    session.breakpoints.insert(ip);
+                    ok()
+                }
+                Some(ip) => {
+                    session.breakpoints.remove(&ip);
+                    ok()
+                }
+                None => String::new(),
+            }
+        }
+        "H" | "T" => ok(),
+        "q" => match arguments {
+            _ if arguments.starts_with("Supported") => String::from("PacketSize=4000"),
+            "Attached" => String::from("1"),
+            "C" => String::from("QC1"),
+            "fThreadInfo" => String::from("m1"),
+            "sThreadInfo" => String::from("l"),
+            _ => String::new(),
+        },
+        "D" => return (ok(), Next::Close),
+        _ => String::new(),
+    };
+    (reply, Next::Continue)
+}
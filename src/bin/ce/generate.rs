@@ -0,0 +1,50 @@
+use clap::{Command as ClapCommand, ValueEnum};
+use clap_complete::Shell;
+use clap_mangen::Man;
+use computer_enhance::failure::Failure;
+use std::{
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// What to write to stdout.
+    #[arg(value_enum)]
+    target: Target,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    /// A completion script to source from .bashrc or put in bash-completion's directory.
+    Bash,
+    /// A completion function to put on $fpath as _ce.
+    Zsh,
+    /// Completions to put in ~/.config/fish/completions/ce.fish.
+    Fish,
+    /// A man page in roff, for man -l or a man1 directory.
+    Man,
+}
+
+/// Writes the chosen completions or man page for `command`, the whole command line.
+pub fn run(args: Args, mut command: ClapCommand) -> ExitCode {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let name = command.get_name().to_string();
+    let shell = match args.target {
+        Target::Bash => Some(Shell::Bash),
+        Target::Zsh => Some(Shell::Zsh),
+        Target::Fish => Some(Shell::Fish),
+        Target::Man => None,
+    };
+    let written = match shell {
+        Some(shell) => {
+            clap_complete::generate(shell, &mut command, name, &mut out);
+            Ok(())
+        }
+        None => Man::new(command).render(&mut out),
+    };
+    match written.and_then(|()| out.flush()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => Failure::Io.failed(format!("stdout: {error}")).report(),
+    }
+}
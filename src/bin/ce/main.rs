@@ -0,0 +1,213 @@
+mod clocks;
+mod compare;
+mod debug;
+mod diff;
+mod disasm;
+mod exec;
+mod gdb;
+mod generate;
+mod progress;
+mod results;
+mod roundtrip;
+mod serve;
+mod symbols;
+#[cfg(feature = "tui")]
+mod tui;
+mod verify;
+mod watch;
+
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    ArgAction, Command as ClapCommand, CommandFactory, FromArgMatches, Parser, Subcommand,
+};
+use computer_enhance::{
+    config::{parse_config, Setting},
+    failure::{Failed, Failure, EXIT_STATUSES},
+    log,
+};
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+};
+
+/// Disassembler and tooling for the Computer Enhance 8086 homework.
+#[derive(Parser)]
+#[command(name = "ce", after_help = EXIT_STATUSES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Defaults for options, as `option = value` lines, optionally under a `[subcommand]`
+    /// header to apply to just that one. Options given on the command line override them.
+    /// Without this, ce.toml in the current directory is used if there is one.
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Log more to stderr: -v for what's loaded, -vv for each instruction's decoding, and
+    /// -vvv for each instruction simulated.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Log only errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// The configuration file's name in the current directory.
+const CONFIG_FILE: &str = "ce.toml";
+
+/// The configuration file named by `--config`, which has to be found before the command line
+/// is parsed because it changes how that's parsed.
+fn config_path() -> Option<PathBuf> {
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        } else if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Makes each of `settings` the default of the option it names, in every subcommand that has
+/// that option or just the one its section names.
+fn apply_settings(
+    mut cli: ClapCommand,
+    settings: Vec<Setting>,
+    path: &Path,
+) -> Result<ClapCommand, Failed> {
+    for setting in settings {
+        let context = format!("{}: line {}", path.display(), setting.line);
+        if let Some(section) = &setting.section {
+            if cli.find_subcommand(section).is_none() {
+                return Err(
+                    Failure::Invalid.failed(format!("{context}: there's no {section} subcommand"))
+                );
+            }
+        }
+        // Leaked so clap can keep it for the life of the process, as it does its own defaults.
+        let value: &'static str = Box::leak(setting.value.into_boxed_str());
+        let mut applied = false;
+        for subcommand in cli.get_subcommands_mut() {
+            if (setting.section.as_deref()).is_some_and(|section| section != subcommand.get_name())
+            {
+                continue;
+            }
+            let id = subcommand
+                .get_arguments()
+                .find(|arg| {
+                    (arg.get_long()).is_some_and(|long| long.replace('-', "_") == setting.key)
+                })
+                .map(|arg| arg.get_id().clone());
+            if let Some(id) = id {
+                *subcommand = std::mem::take(subcommand)
+                    .mut_arg(id, |arg| arg.default_value(value).required(false));
+                applied = true;
+            }
+        }
+        if !applied {
+            return Err(Failure::Invalid.failed(format!(
+                "{context}: no subcommand has a --{} option",
+                setting.key
+            )));
+        }
+    }
+    Ok(cli)
+}
+
+/// The command line, with any configuration file's defaults applied.
+fn parse() -> Result<Cli, Failed> {
+    let (path, required) = match config_path() {
+        Some(path) => (path, true),
+        None => (PathBuf::from(CONFIG_FILE), false),
+    };
+    let mut cli = Cli::command();
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            let settings = parse_config(&text)
+                .map_err(|error| Failure::Invalid.failed(format!("{}: {error}", path.display())))?;
+            cli = apply_settings(cli, settings, &path)?;
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {}
+        Err(error) => return Err(Failure::Io.failed(format!("{}: {error}", path.display()))),
+    }
+    let matches = cli.get_matches();
+    Ok(Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit()))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Disassemble an 8086 binary.
+    Disasm(disasm::Args),
+    /// Simulate an 8086 binary, printing each instruction's effect and the final state.
+    Exec(exec::Args),
+    /// Step through an 8086 binary at an interactive prompt.
+    Debug(debug::Args),
+    /// Show an instruction-level diff of two binaries. Exits 0 if they disassemble the same,
+    /// and 1 if they differ.
+    Diff(diff::Args),
+    /// Disassemble, reassemble with NASM, and compare against the original bytes.
+    Verify(verify::Args),
+    /// Disassemble, reassemble with the built-in assembler, and compare against the original
+    /// bytes, listing each instruction that comes out differently. Needs no other tools.
+    Roundtrip(roundtrip::Args),
+    /// Run an 8086 binary and show each instruction's static clock estimate beside the clocks
+    /// simulated for it, marking with `!` those where branches or data made them differ.
+    Clocks(clocks::Args),
+    /// Find the first instruction where two execution traces disagree on registers, flags, or
+    /// IP. Exits 0 if they agree throughout, and 1 if they diverge.
+    CompareTraces(compare::Args),
+    /// Compare two haversine answer files distance by distance, listing those furthest apart.
+    /// Exits 0 if every distance and the average match within epsilon, and 1 if not.
+    CompareResults(results::Args),
+    /// Disassemble over HTTP: POST machine code to /disasm, raw or with ?encoding=base64, and
+    /// an ?origin if it isn't the --origin, to get the JSON `disasm --format json` writes.
+    Serve(serve::Args),
+    /// Write shell completions or a man page, generated from these options.
+    #[command(hide = true)]
+    Generate(generate::Args),
+}
+
+/// Parses one of `values`, listed so that help and completions can offer them, as a `T`.
+pub fn one_of<T>(values: &'static [&'static str]) -> impl TypedValueParser<Value = T>
+where
+    T: FromStr + Clone + Send + Sync + 'static,
+    T::Err: fmt::Debug,
+{
+    PossibleValuesParser::new(values.iter().copied()).map(|text| text.parse().unwrap())
+}
+
+/// Parses `0x`-prefixed hexadecimal or plain decimal.
+pub fn parse_address(text: &str) -> Result<usize, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|error| format!("invalid address {text:?}: {error}"))
+}
+
+fn main() -> ExitCode {
+    let cli = match parse() {
+        Ok(cli) => cli,
+        Err(failed) => return failed.report(),
+    };
+    log::init(log::level_from_verbosity(cli.verbose, cli.quiet));
+    match cli.command {
+        Command::Clocks(args) => clocks::run(args),
+        Command::CompareResults(args) => results::run(args),
+        Command::CompareTraces(args) => compare::run(args),
+        Command::Disasm(args) => disasm::run(args),
+        Command::Debug(args) => debug::run(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Exec(args) => exec::run(args),
+        Command::Generate(args) => generate::run(args, Cli::command()),
+        Command::Roundtrip(args) => roundtrip::run(args),
+        Command::Serve(args) => serve::run(args),
+        Command::Verify(args) => verify::run(args),
+    }
+}
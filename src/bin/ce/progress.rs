@@ -0,0 +1,140 @@
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    time::{Duration, Instant},
+};
+
+/// How often the progress line is redrawn.
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// Calls to `set` between looks at the clock, which costs more than the work between them.
+const CALLS_PER_CHECK: u32 = 1024;
+
+/// A progress line on stderr, redrawn in place as work is done and cleared when it's dropped.
+/// It's only drawn when stderr is a terminal that the output isn't also going to.
+pub struct Progress {
+    label: String,
+    /// What `done` counts up to, if that's known, which gives a bar and an estimate of the time
+    /// left.
+    total: Option<u64>,
+    /// What's being counted, such as "bytes".
+    unit: &'static str,
+    done: u64,
+    start: Instant,
+    drawn: Instant,
+    calls: u32,
+    enabled: bool,
+}
+
+impl Progress {
+    /// Progress through `total` `unit`s of `label`. `to_stdout` says whether the output is
+    /// written to stdout, where a terminal would interleave it with the progress line.
+    pub fn new(label: &str, total: Option<u64>, unit: &'static str, to_stdout: bool) -> Self {
+        let now = Instant::now();
+        Progress {
+            label: label.to_string(),
+            total,
+            unit,
+            done: 0,
+            start: now,
+            drawn: now,
+            calls: 0,
+            enabled: io::stderr().is_terminal() && !(to_stdout && io::stdout().is_terminal()),
+        }
+    }
+
+    /// Records that `done` units are done, for frequent calls such as one per instruction: the
+    /// clock is only looked at every so often, to see whether to redraw the line.
+    pub fn set(&mut self, done: u64) {
+        self.done = done;
+        self.calls += 1;
+        if self.calls >= CALLS_PER_CHECK {
+            self.calls = 0;
+            self.tick();
+        }
+    }
+
+    /// Records that `more` units are done, for calls as infrequent as one per read.
+    pub fn add(&mut self, more: u64) {
+        self.done += more;
+        self.tick();
+    }
+
+    /// Redraws the line if it's been long enough.
+    fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if now - self.drawn >= REDRAW {
+            self.drawn = now;
+            self.draw(now - self.start);
+        }
+    }
+
+    fn draw(&self, elapsed: Duration) {
+        const WIDTH: usize = 30;
+        let rate = self.done as f64 / elapsed.as_secs_f64();
+        let mut line = format!("\r{}: ", self.label);
+        match self.total {
+            Some(total) if total != 0 => {
+                let fraction = (self.done as f64 / total as f64).min(1.0);
+                let filled = (fraction * WIDTH as f64) as usize;
+                let left = (total.saturating_sub(self.done)) as f64 / rate;
+                line += &format!(
+                    "[{}{}] {:3.0}% {} of {} {}, {} left",
+                    "#".repeat(filled),
+                    "-".repeat(WIDTH - filled),
+                    fraction * 100.0,
+                    scaled(self.done as f64),
+                    scaled(total as f64),
+                    self.unit,
+                    clock(left),
+                );
+            }
+            _ => line += &format!("{} {}", scaled(self.done as f64), self.unit),
+        }
+        line += &format!(" at {}/s\x1b[K", scaled(rate));
+        let _ = io::stderr().write_all(line.as_bytes());
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled && self.drawn != self.start {
+            let _ = io::stderr().write_all(b"\r\x1b[K");
+        }
+    }
+}
+
+/// `count` with a k, M, or G suffix.
+fn scaled(count: f64) -> String {
+    match count {
+        count if count >= 1e9 => format!("{:.1}G", count / 1e9),
+        count if count >= 1e6 => format!("{:.1}M", count / 1e6),
+        count if count >= 1e3 => format!("{:.1}k", count / 1e3),
+        count => format!("{count:.0}"),
+    }
+}
+
+/// `seconds` as m:ss.
+fn clock(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return String::from("?:??");
+    }
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Reads from `reader`, reporting the bytes read to a `Progress`.
+pub struct ProgressReader<R> {
+    pub reader: R,
+    pub progress: Progress,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buffer)?;
+        self.progress.add(read as u64);
+        Ok(read)
+    }
+}
@@ -0,0 +1,96 @@
+use computer_enhance::{
+    failure::{Failed, Failure},
+    haversine::{read_answers, Answers},
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Answer files from `haversine_gen --answers` or `haversine_proc --dump`.
+    first: PathBuf,
+    second: PathBuf,
+
+    /// The largest difference between two distances, or the averages, that still counts as a
+    /// match.
+    #[arg(short, long, default_value_t = 0.0)]
+    epsilon: f64,
+
+    /// How many of the most different distances to list.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    worst: usize,
+}
+
+fn read(path: &Path) -> Result<Answers, Failed> {
+    let failed = |failure: Failure, message: String| {
+        failure.failed(format!("{}: {message}", path.display()))
+    };
+    let mut file = File::open(path).map_err(|error| failed(Failure::Io, error.to_string()))?;
+    read_answers(&mut file).map_err(|error| failed(Failure::from(&error), error.to_string()))
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let (first, second) = match read(&args.first).and_then(|first| Ok((first, read(&args.second)?)))
+    {
+        Ok(results) => results,
+        Err(failed) => return failed.report(),
+    };
+    if first.distances.len() != second.distances.len() {
+        eprintln!(
+            "{} has {} distances but {} has {}",
+            args.first.display(),
+            first.distances.len(),
+            args.second.display(),
+            second.distances.len()
+        );
+        return Failure::Mismatch.into();
+    }
+
+    // A NaN never matches, however large epsilon is.
+    let outside = |difference: f64| difference.is_nan() || difference > args.epsilon;
+    let mut offenders: Vec<(usize, f64)> = first
+        .distances
+        .iter()
+        .zip(&second.distances)
+        .map(|(a, b)| (a - b).abs())
+        .enumerate()
+        .filter(|&(_, difference)| outside(difference))
+        .collect();
+    offenders.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let average_difference = (first.average - second.average).abs();
+
+    println!("--- {}", args.first.display());
+    println!("+++ {}", args.second.display());
+    println!(
+        "Pairs: {} ({} differ by more than {:e})",
+        first.distances.len(),
+        offenders.len(),
+        args.epsilon
+    );
+    println!(
+        "Average: {:.16} vs {:.16} (difference {average_difference:e})",
+        first.average, second.average
+    );
+    if !offenders.is_empty() {
+        println!();
+        println!(
+            "{:>10}  {:>22}  {:>22}  difference",
+            "pair", "first", "second"
+        );
+        for &(index, difference) in offenders.iter().take(args.worst) {
+            println!(
+                "{index:>10}  {:>22.16}  {:>22.16}  {difference:e}",
+                first.distances[index], second.distances[index]
+            );
+        }
+    }
+
+    if offenders.is_empty() && !outside(average_difference) {
+        ExitCode::SUCCESS
+    } else {
+        Failure::Mismatch.into()
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    disasm::{load, write_text, Annotations, FormatArgs},
+    verify::first_difference,
+    watch::{watch, WatchArgs},
+};
+use computer_enhance::{
+    assembler::{assemble, AssembleError},
+    failure::Failure,
+    formatter::{InstructionFormatter, Syntax},
+    instruction::{AddressBase, Instruction, Operand},
+};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    process::ExitCode,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    #[command(flatten)]
+    watch_args: WatchArgs,
+}
+
+/// Bytes as space-separated hex.
+fn hex(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    hex.join(" ")
+}
+
+/// `instruction` as `formatter` writes it, assembled on its own at its own offset, with the
+/// label or symbol it names, if any, defined as a constant.
+fn reassemble(
+    formatter: &InstructionFormatter,
+    instruction: &Instruction,
+) -> Result<Vec<u8>, AssembleError> {
+    let mut source = format!("bits 16\norg {:#x}\n", instruction.offset);
+    let target = (instruction.branch_target())
+        .and_then(|target| Some((target, formatter.labels.name(target)?)));
+    let address = instruction
+        .operands
+        .iter()
+        .find_map(|operand| match operand {
+            Some(Operand::Memory(address)) if address.base == AddressBase::Direct => {
+                let address = address.displacement as u16 as usize;
+                Some((address, formatter.symbols.get(&address)?.as_str()))
+            }
+            _ => None,
+        });
+    let names = target
+        .into_iter()
+        .chain(address.filter(|&named| Some(named) != target));
+    for (offset, name) in names {
+        let _ = writeln!(source, "{name} equ {offset:#x}");
+    }
+    let _ = writeln!(source, "{}", formatter.display(instruction));
+    assemble(&source)
+}
+
+pub fn run(args: Args) -> ExitCode {
+    if args.watch_args.watch {
+        return watch(&args.watch_args, &[&args.input], None, || run_once(&args));
+    }
+    run_once(&args)
+}
+
+fn run_once(args: &Args) -> ExitCode {
+    if args.format_args.syntax == Syntax::Masm {
+        return Failure::Usage
+            .failed("roundtrip reassembles NASM and only supports --syntax nasm or ndisasm")
+            .report();
+    }
+    let origin = args.format_args.origin();
+    let (image, origin, instructions) = match load(&args.input, origin) {
+        Ok(loaded) => loaded,
+        Err(failed) => return failed.report(),
+    };
+    let mut formatter = match args.format_args.formatter(&instructions) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+    // Without these a JMP is reassembled short wherever it reaches, whatever it was.
+    formatter.distance_keywords = true;
+
+    let mut source = Vec::new();
+    // Writing into a Vec can't fail.
+    let _ = write_text(
+        &mut source,
+        &args.input,
+        &image,
+        origin,
+        &instructions,
+        &formatter,
+        Annotations::default(),
+    );
+    let reassembled = match assemble(&String::from_utf8_lossy(&source)) {
+        Ok(reassembled) => reassembled,
+        Err(error) => {
+            return Failure::Mismatch
+                .failed(format!(
+                    "{}: the disassembly doesn't reassemble: {error}",
+                    args.input
+                ))
+                .report();
+        }
+    };
+
+    let Some(index) = first_difference(&image, &reassembled) else {
+        println!(
+            "{}: OK ({} bytes, {} instructions)",
+            args.input,
+            image.len(),
+            instructions.len()
+        );
+        return ExitCode::SUCCESS;
+    };
+
+    // Each instruction's text assembled alone, so that every difference is found, not just the
+    // first before the rest are shifted along.
+    let mut differences = Vec::new();
+    for instruction in &instructions {
+        let start = instruction.offset - origin;
+        let original = &image[start..start + instruction.length];
+        let reassembled = match reassemble(&formatter, instruction) {
+            Ok(reassembled) if reassembled == original => continue,
+            Ok(reassembled) => hex(&reassembled),
+            Err(error) => error.to_string(),
+        };
+        differences.push(format!(
+            "  {:#06x} {}\n    original:    {}\n    reassembled: {reassembled}",
+            instruction.offset,
+            formatter.display(instruction),
+            hex(original)
+        ));
+    }
+
+    let mut stdout = io::stdout().lock();
+    let _ = match differences.len() {
+        0 => writeln!(
+            stdout,
+            "{}: reassembly differs at offset {:#06x}",
+            args.input,
+            origin + index
+        ),
+        differing => writeln!(
+            stdout,
+            "{}: {differing} of {} instructions reassemble differently ({} bytes in, {} bytes out)",
+            args.input,
+            instructions.len(),
+            image.len(),
+            reassembled.len()
+        ),
+    };
+    for difference in differences {
+        let _ = writeln!(stdout, "{difference}");
+    }
+    Failure::Mismatch.into()
+}
@@ -0,0 +1,316 @@
+use crate::{disasm::FormatArgs, parse_address};
+use computer_enhance::{
+    decoder::decode_at,
+    export::{write_json, write_json_string},
+    failure::Failure,
+};
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+/// The longest request line or header accepted.
+const MAX_LINE: u64 = 8 * 1024;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Address to listen on. Port 0 picks a free port, which is printed on startup.
+    #[arg(long, default_value = "127.0.0.1:8086")]
+    listen: String,
+
+    /// The most bytes of machine code a request may send.
+    #[arg(long, value_name = "BYTES", default_value = "1048576", value_parser = parse_address)]
+    max_bytes: usize,
+
+    /// How many seconds a client has to send its whole request. Requests are answered one at a
+    /// time, so this is the longest a slow or stalled client can hold up the others.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    timeout: u64,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+}
+
+/// A request's status and JSON body.
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Response {
+        let mut body = b"{\"error\": ".to_vec();
+        // Writing into a Vec can't fail.
+        let _ = write_json_string(&mut body, message);
+        body.extend_from_slice(b"}\n");
+        Response {
+            status,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        _ => "Internal Server Error",
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    content_length: Option<usize>,
+}
+
+/// A connection that can only be read from until `deadline`. A read timeout alone would limit
+/// each read, and a client sending a byte at a time could keep one going indefinitely.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buffer)
+    }
+}
+
+/// The response to a request that couldn't be read.
+fn read_failed(error: io::Error) -> Response {
+    match error.kind() {
+        // A read timeout is reported as either, depending on the platform.
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+            Response::error(408, "the request took too long to send")
+        }
+        _ => Response::error(400, &error.to_string()),
+    }
+}
+
+/// Reads a line of at most `MAX_LINE` bytes, without its line ending.
+fn read_line(reader: &mut impl BufRead) -> Result<String, Response> {
+    let mut line = String::new();
+    reader
+        .take(MAX_LINE)
+        .read_line(&mut line)
+        .map_err(read_failed)?;
+    if !line.ends_with('\n') {
+        return Err(Response::error(400, "request line or header too long"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads the request line and headers.
+fn read_head(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let line = read_line(reader)?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Response::error(400, "malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        content_length: None,
+    };
+    loop {
+        let header = read_line(reader)?;
+        if header.is_empty() {
+            return Ok(request);
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(Response::error(400, "malformed header"));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            let length = value.trim().parse().map_err(|_| {
+                Response::error(400, &format!("invalid Content-Length {:?}", value.trim()))
+            })?;
+            request.content_length = Some(length);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(Response::error(
+                411,
+                "send a Content-Length rather than chunks",
+            ));
+        }
+    }
+}
+
+/// Bytes written in base64, standard or URL-safe, with or without padding.
+fn decode_base64(text: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut bits, mut count) = (0u32, 0);
+    let mut padding = false;
+    for &c in text.iter().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'=' => {
+                padding = true;
+                continue;
+            }
+            _ if padding => return None,
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6 | value as u32) & 0xFFFF;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    // A lone character left over is six bits, not enough for a byte.
+    (count < 6).then_some(bytes)
+}
+
+impl Args {
+    /// Disassembles the body of a request to /disasm with `query`.
+    fn disassemble(&self, query: &str, body: Vec<u8>) -> Response {
+        let mut origin = self.format_args.origin();
+        let mut base64 = false;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            match pair.split_once('=').unwrap_or((pair, "")) {
+                ("origin", value) => match parse_address(value) {
+                    Ok(value) => origin = value,
+                    Err(message) => return Response::error(400, &message),
+                },
+                ("encoding", "base64") => base64 = true,
+                ("encoding", "raw") => base64 = false,
+                (key, _) => {
+                    return Response::error(400, &format!("unknown query parameter {key:?}"))
+                }
+            }
+        }
+        let image = match base64 {
+            true => match decode_base64(&body) {
+                Some(image) => image,
+                None => return Response::error(400, "invalid base64"),
+            },
+            false => body,
+        };
+        if image.len() > self.max_bytes {
+            return Response::error(413, &format!("more than {} bytes", self.max_bytes));
+        }
+
+        let instructions = match decode_at(&image, origin) {
+            Ok(instructions) => instructions,
+            Err(error) => return Response::error(422, &error.to_string()),
+        };
+        let formatter = match self.format_args.formatter(&instructions) {
+            Ok(formatter) => formatter,
+            Err(failed) => return Response::error(500, &failed.message),
+        };
+        let mut body = Vec::new();
+        // Writing into a Vec can't fail.
+        let _ = write_json(&mut body, &instructions, &image, origin, &formatter);
+        Response {
+            status: 200,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        }
+    }
+
+    fn respond(&self, reader: &mut impl BufRead) -> Response {
+        let request = match read_head(reader) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        match (request.method.as_str(), request.path.as_str()) {
+            // A browser's preflight for a cross-origin POST; the headers allowing it are on
+            // every response.
+            ("OPTIONS", _) => Response {
+                status: 204,
+                body: String::new(),
+            },
+            ("POST", "/disasm") => {
+                let Some(length) = request.content_length else {
+                    return Response::error(411, "send a Content-Length");
+                };
+                // Base64 takes four bytes for every three.
+                if length > self.max_bytes / 3 * 4 + 4 {
+                    return Response::error(413, &format!("more than {} bytes", self.max_bytes));
+                }
+                let mut body = vec![0; length];
+                if let Err(error) = reader.read_exact(&mut body) {
+                    return read_failed(error);
+                }
+                self.disassemble(&request.query, body)
+            }
+            (_, "/disasm") => Response::error(405, "POST the bytes to disassemble"),
+            (_, path) => Response::error(404, &format!("nothing at {path}; POST to /disasm")),
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let peer = stream.peer_addr()?;
+        let mut reader = BufReader::new(Deadline {
+            stream: &stream,
+            deadline: Instant::now() + Duration::from_secs(self.timeout),
+        });
+        let response = self.respond(&mut reader);
+        tracing::info!("{peer}: {} {}", response.status, reason(response.status));
+
+        let mut out = &stream;
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\n\
+             Access-Control-Allow-Origin: *\r\n\
+             Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\n\
+             Connection: close\r\n",
+            response.status,
+            reason(response.status)
+        )?;
+        if response.status != 204 {
+            write!(
+                out,
+                "Content-Type: application/json\r\nContent-Length: {}\r\n",
+                response.body.len()
+            )?;
+        }
+        write!(out, "\r\n{}", response.body)?;
+        out.flush()
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(listener) => listener,
+        Err(error) => {
+            return Failure::Io
+                .failed(format!("can't listen on {}: {error}", args.listen))
+                .report()
+        }
+    };
+    match listener.local_addr() {
+        Ok(address) => eprintln!("listening on http://{address}/disasm"),
+        Err(error) => return Failure::Io.failed(error.to_string()).report(),
+    }
+    // One request at a time: each is small, and decoding is quick.
+    for stream in listener.incoming() {
+        let handled = stream.and_then(|stream| args.handle(stream));
+        if let Err(error) = handled {
+            tracing::warn!("{error}");
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,79 @@
+use crate::parse_address;
+use computer_enhance::{
+    atomic_file::write_atomically,
+    failure::{Failed, Failure},
+    formatter::InstructionFormatter,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Write,
+    path::Path,
+};
+
+// Names both NASM and MASM accept: no leading digit, and no leading `$`, which NASM reads as an
+// escape.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '?' | '@' | '$');
+    match chars.next() {
+        Some(first) if valid(first) && !first.is_ascii_digit() && first != '$' => chars.all(valid),
+        _ => false,
+    }
+}
+
+/// Parses `offset=name` lines. Blank lines and lines starting with `;` or `#` are skipped.
+pub fn parse_symbols(text: &str) -> Result<BTreeMap<usize, String>, String> {
+    let mut symbols = BTreeMap::new();
+    let mut names = BTreeSet::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+
+        let number = index + 1;
+        let (offset, name) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {number}: expected offset=name"))?;
+        let offset =
+            parse_address(offset.trim()).map_err(|error| format!("line {number}: {error}"))?;
+        let name = name.trim();
+        if !is_identifier(name) {
+            return Err(format!("line {number}: {name:?} isn't a valid label name"));
+        }
+        if !names.insert(name) {
+            return Err(format!("line {number}: {name:?} is defined more than once"));
+        }
+        if let Some(previous) = symbols.insert(offset, name.to_string()) {
+            return Err(format!(
+                "line {number}: {offset:#x} is already named {previous:?}"
+            ));
+        }
+    }
+    Ok(symbols)
+}
+
+/// Reads and parses a symbols file, reporting failures as `path: error`.
+pub fn read_symbols(path: &str) -> Result<BTreeMap<usize, String>, Failed> {
+    let text =
+        fs::read_to_string(path).map_err(|error| Failure::Io.failed(format!("{path}: {error}")))?;
+    parse_symbols(&text).map_err(|error| Failure::Invalid.failed(format!("{path}: {error}")))
+}
+
+/// Writes every label and symbol `formatter` knows of to `path` in the format `read_symbols`
+/// accepts, so later runs keep the same names.
+pub fn write_symbols(path: &Path, formatter: &InstructionFormatter) -> Result<(), Failed> {
+    let mut names: BTreeMap<usize, &str> = formatter.labels.iter().collect();
+    for (&offset, name) in &formatter.symbols {
+        names.entry(offset).or_insert(name);
+    }
+
+    write_atomically(path, |out| {
+        for (offset, name) in names {
+            writeln!(out, "{offset:#06x}={name}")?;
+        }
+        Ok(())
+    })
+    .map_err(|error| Failure::Io.failed(format!("{}: {error}", path.display())))
+}
@@ -0,0 +1,187 @@
+use crate::debug::Session;
+use computer_enhance::simulator::Registers;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{fmt::Write as _, io};
+
+const CODE_ROWS: usize = 16;
+const MEMORY_ROWS: usize = 8;
+/// Width of the code pane, which the registers sit to the right of.
+const CODE_WIDTH: u16 = 48;
+/// Listed instructions above the current one.
+const CODE_CONTEXT: usize = 5;
+
+const KEYS: &str =
+    "s step  u step back  c continue  b breakpoint at IP  j/k scroll memory  J/K page  q quit";
+
+fn code_lines(session: &Session) -> Vec<Line<'static>> {
+    let instructions = &session.listing.instructions;
+    let ip = session.cpu.ip as usize;
+    let current = instructions
+        .iter()
+        .position(|instruction| instruction.offset == ip);
+    // Keep the current instruction a few lines down so what led to it stays visible.
+    let start = current.map_or(0, |index| index.saturating_sub(CODE_CONTEXT));
+
+    let mut lines = Vec::with_capacity(CODE_ROWS);
+    if current.is_none() {
+        lines.push(Line::raw(if session.running() {
+            format!("   ip {ip:#06x} isn't at a listed instruction")
+        } else {
+            format!("   program ended, ip {ip:#06x}")
+        }));
+    }
+    for instruction in instructions[start..].iter() {
+        if lines.len() == CODE_ROWS {
+            break;
+        }
+        let breakpoint = session.breakpoints.contains(&(instruction.offset as u16));
+        let marker = match (instruction.offset == ip, breakpoint) {
+            (true, _) => "=>",
+            (false, true) => " *",
+            (false, false) => "  ",
+        };
+        let text = session.formatter.display(instruction).to_string();
+        let line = Line::raw(format!("{marker} {:04x}  {text}", instruction.offset));
+        // Reverse video for the instruction about to run.
+        if instruction.offset == ip {
+            lines.push(line.style(Style::new().add_modifier(Modifier::REVERSED)));
+        } else {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+fn register_lines(session: &Session) -> Vec<Line<'static>> {
+    let cpu = &session.cpu;
+    let mut lines: Vec<Line> = Registers::ALL
+        .into_iter()
+        .map(|register| {
+            let value = cpu.registers.get(register);
+            Line::raw(format!("{}: {value:#06x} ({value})", register.name()))
+        })
+        .collect();
+    lines.push(Line::raw(format!("ip: {:#06x} ({})", cpu.ip, cpu.ip)));
+    lines.push(Line::raw(format!("flags: {}", cpu.flags)));
+    lines
+}
+
+fn memory_lines(session: &Session, memory_offset: usize) -> Vec<Line<'static>> {
+    let bytes = session.cpu.memory.slice(memory_offset, MEMORY_ROWS * 16);
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut text = format!("{:05x}:", memory_offset + row * 16);
+            for byte in chunk {
+                let _ = write!(text, " {byte:02x}");
+            }
+            Line::raw(text)
+        })
+        .collect()
+}
+
+fn draw(frame: &mut Frame, session: &Session, memory_offset: usize, message: &str) {
+    let [top, memory, message_area, keys] = Layout::vertical([
+        Constraint::Length(CODE_ROWS as u16 + 1),
+        Constraint::Length(MEMORY_ROWS as u16 + 2),
+        Constraint::Length(2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [code, registers] =
+        Layout::horizontal([Constraint::Length(CODE_WIDTH), Constraint::Fill(1)]).areas(top);
+
+    let title = |text| Block::new().title(Line::raw(text));
+    frame.render_widget(
+        Paragraph::new(code_lines(session)).block(title("Code")),
+        code,
+    );
+    frame.render_widget(
+        Paragraph::new(register_lines(session)).block(title("Registers")),
+        registers,
+    );
+    // A blank line above, as between the panes.
+    frame.render_widget(
+        Paragraph::new(memory_lines(session, memory_offset))
+            .block(title("Memory").padding(ratatui::widgets::Padding::top(1))),
+        memory,
+    );
+    let message: Vec<Line> = message.lines().take(2).map(Line::raw).collect();
+    frame.render_widget(Paragraph::new(message), message_area);
+    frame.render_widget(Paragraph::new(KEYS), keys);
+}
+
+/// Runs the full-screen debugger until the user quits.
+pub fn run(session: &mut Session) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run_in(&mut terminal, session);
+    ratatui::restore();
+    result
+}
+
+fn run_in(terminal: &mut DefaultTerminal, session: &mut Session) -> io::Result<()> {
+    let mut memory_offset = 0usize;
+    let mut message = String::new();
+    let last_row = session.cpu.memory.len().saturating_sub(16) / 16 * 16;
+
+    loop {
+        terminal.draw(|frame| draw(frame, session, memory_offset, &message))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        // Windows reports releases too.
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Ok(()),
+            KeyCode::Char('s') if session.running() => {
+                message = session
+                    .step()
+                    .unwrap_or_else(|error| format!("error: {error}"));
+            }
+            KeyCode::Char('c') if session.running() => {
+                let (executed, stop) = session.resume();
+                message = match stop {
+                    Some(stop) => stop.to_string(),
+                    None => format!("executed {executed} instructions"),
+                };
+            }
+            KeyCode::Char('u') => {
+                message = match session.step_back(1) {
+                    0 => String::from("nothing to step back"),
+                    _ => format!("stepped back to {:#06x}", session.cpu.ip),
+                };
+            }
+            KeyCode::Char('s' | 'c') => message = String::from("the program has ended"),
+            KeyCode::Char('b') => {
+                let ip = session.cpu.ip;
+                message = if session.breakpoints.remove(&ip) {
+                    format!("deleted breakpoint at {ip:#06x}")
+                } else {
+                    session.breakpoints.insert(ip);
+                    format!("breakpoint at {ip:#06x}")
+                };
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                memory_offset = (memory_offset + 16).min(last_row);
+            }
+            KeyCode::Char('k') | KeyCode::Up => memory_offset = memory_offset.saturating_sub(16),
+            KeyCode::Char('J') | KeyCode::PageDown => {
+                memory_offset = (memory_offset + MEMORY_ROWS * 16).min(last_row);
+            }
+            KeyCode::Char('K') | KeyCode::PageUp => {
+                memory_offset = memory_offset.saturating_sub(MEMORY_ROWS * 16);
+            }
+            _ => {}
+        }
+    }
+}
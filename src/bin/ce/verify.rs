@@ -0,0 +1,170 @@
+use crate::{
+    disasm::{load, write_text, Annotations, FormatArgs},
+    watch::{watch, WatchArgs},
+};
+use computer_enhance::{
+    failure::{Failed, Failure},
+    formatter::Syntax,
+};
+use std::{
+    env, fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, ExitCode},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: String,
+
+    #[command(flatten)]
+    format_args: FormatArgs,
+
+    /// NASM executable used to reassemble the disassembly.
+    #[arg(long, default_value = "nasm")]
+    nasm: PathBuf,
+
+    /// Keep the generated .asm and reassembled binary instead of deleting them.
+    #[arg(long)]
+    keep: bool,
+
+    #[command(flatten)]
+    watch_args: WatchArgs,
+}
+
+/// Index of the first byte at which `expected` and `actual` differ, counting a length mismatch
+/// as a difference at the end of the shorter one.
+pub fn first_difference(expected: &[u8], actual: &[u8]) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+}
+
+/// Assembles `source` into `binary` with `nasm`, and reads it back.
+pub fn reassemble(nasm: &Path, source: &Path, binary: &Path) -> Result<Vec<u8>, Failed> {
+    let output = Command::new(nasm)
+        .arg("-f")
+        .arg("bin")
+        .arg("-o")
+        .arg(binary)
+        .arg(source)
+        .output()
+        .map_err(|error| {
+            Failure::Io.failed(format!("failed to run {}: {error}", nasm.display()))
+        })?;
+    if !output.status.success() {
+        return Err(Failure::Mismatch.failed(format!(
+            "{} rejected {}:\n{}",
+            nasm.display(),
+            source.display(),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    fs::read(binary).map_err(|error| Failure::Io.failed(format!("{}: {error}", binary.display())))
+}
+
+pub fn run(args: Args) -> ExitCode {
+    if args.watch_args.watch {
+        return watch(&args.watch_args, &[&args.input], Some(&args.nasm), || {
+            run_once(&args)
+        });
+    }
+    run_once(&args)
+}
+
+fn run_once(args: &Args) -> ExitCode {
+    if args.format_args.syntax != Syntax::Nasm {
+        return Failure::Usage
+            .failed("verify reassembles with NASM and only supports --syntax nasm")
+            .report();
+    }
+    let origin = args.format_args.origin();
+    let (image, origin, instructions) = match load(&args.input, origin) {
+        Ok(loaded) => loaded,
+        Err(failed) => return failed.report(),
+    };
+    let mut formatter = match args.format_args.formatter(&instructions) {
+        Ok(formatter) => formatter,
+        Err(failed) => return failed.report(),
+    };
+    // Without these NASM is free to pick a shorter branch encoding than the original.
+    formatter.distance_keywords = true;
+
+    let directory = env::temp_dir().join(format!("ce-verify-{}", process::id()));
+    let source = directory.join("disassembly.asm");
+    let binary = directory.join("reassembled.bin");
+
+    let written = fs::create_dir_all(&directory).and_then(|()| {
+        let mut out = BufWriter::new(fs::File::create(&source)?);
+        write_text(
+            &mut out,
+            &args.input,
+            &image,
+            origin,
+            &instructions,
+            &formatter,
+            Annotations::default(),
+        )?;
+        out.flush()
+    });
+    let reassembled = match written {
+        Ok(()) => reassemble(&args.nasm, &source, &binary),
+        Err(error) => Err(Failure::Io.failed(format!("{}: {error}", source.display()))),
+    };
+
+    if args.keep {
+        eprintln!("kept {} and {}", source.display(), binary.display());
+    } else {
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    let reassembled = match reassembled {
+        Ok(reassembled) => reassembled,
+        Err(failed) => return failed.report(),
+    };
+
+    match first_difference(&image, &reassembled) {
+        None => {
+            println!(
+                "{}: OK ({} bytes, {} instructions)",
+                args.input,
+                image.len(),
+                instructions.len()
+            );
+            ExitCode::SUCCESS
+        }
+        Some(index) => {
+            let address = origin + index;
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(
+                stdout,
+                "{}: reassembly differs at offset {address:#06x} ({} bytes in, {} bytes out)",
+                args.input,
+                image.len(),
+                reassembled.len()
+            );
+
+            let byte = |bytes: &[u8]| match bytes.get(index) {
+                Some(byte) => format!("{byte:02x}"),
+                None => String::from("end of file"),
+            };
+            let _ = writeln!(stdout, "  original:    {}", byte(&image));
+            let _ = writeln!(stdout, "  reassembled: {}", byte(&reassembled));
+
+            let culprit = instructions
+                .iter()
+                .find(|instruction| (instruction.offset..instruction.end()).contains(&address));
+            if let Some(instruction) = culprit {
+                let _ = writeln!(
+                    stdout,
+                    "  instruction: {:#06x} {}",
+                    instruction.offset,
+                    formatter.display(instruction)
+                );
+            }
+            Failure::Mismatch.into()
+        }
+    }
+}
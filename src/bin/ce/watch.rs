@@ -0,0 +1,118 @@
+use crate::disasm::{BYTES, STDIN};
+use crate::verify::reassemble;
+use computer_enhance::{
+    assembler::assemble,
+    atomic_file::write_atomically,
+    failure::{Failed, Failure},
+};
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often the watched files are looked at.
+const POLL: Duration = Duration::from_millis(200);
+
+#[derive(clap::Args)]
+pub struct WatchArgs {
+    /// Run again whenever an input changes, clearing the screen first, until interrupted.
+    #[arg(long)]
+    pub watch: bool,
+
+    /// With --watch, also watch this assembly source, and assemble it into the input before each
+    /// run.
+    #[arg(long, value_name = "ASM", requires = "watch")]
+    pub source: Option<PathBuf>,
+}
+
+/// When a file was last modified, and its size, or `None` if it can't be found.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamps(paths: &[PathBuf]) -> Vec<Stamp> {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Waits until one of `paths` changes, and then until they've stopped changing, since editors
+/// and assemblers can take more than one write to save a file.
+fn wait_for_change(paths: &[PathBuf]) {
+    let before = stamps(paths);
+    let mut now = before.clone();
+    while now == before {
+        thread::sleep(POLL);
+        now = stamps(paths);
+    }
+    loop {
+        thread::sleep(POLL);
+        let later = stamps(paths);
+        if later == now {
+            return;
+        }
+        now = later;
+    }
+}
+
+/// Assembles `source` into `binary`, with `nasm` if given and otherwise the built-in assembler.
+fn assemble_into(nasm: Option<&Path>, source: &Path, binary: &Path) -> Result<(), Failed> {
+    if let Some(nasm) = nasm {
+        return reassemble(nasm, source, binary).map(drop);
+    }
+    let text = fs::read_to_string(source)
+        .map_err(|error| Failure::Io.failed(format!("{}: {error}", source.display())))?;
+    let code = assemble(&text)
+        .map_err(|error| Failure::Invalid.failed(format!("{}: {error}", source.display())))?;
+    write_atomically(binary, |out| out.write_all(&code))
+        .map_err(|error| Failure::Io.failed(format!("{}: {error}", binary.display())))
+}
+
+/// Calls `run` now and again each time `inputs` or the `--source` change, assembling the source
+/// into the single input first. Only returns if the arguments can't be watched.
+pub fn watch(
+    args: &WatchArgs,
+    inputs: &[&str],
+    nasm: Option<&Path>,
+    mut run: impl FnMut() -> ExitCode,
+) -> ExitCode {
+    if inputs.is_empty() || inputs.iter().any(|&input| input == STDIN || input == BYTES) {
+        return Failure::Usage
+            .failed("--watch needs input files to watch")
+            .report();
+    }
+    if args.source.is_some() && inputs.len() != 1 {
+        return Failure::Usage
+            .failed("--source assembles into a single input")
+            .report();
+    }
+    let mut paths: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+    paths.extend(args.source.clone());
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    loop {
+        if io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[H");
+            let _ = io::stdout().flush();
+        }
+        let assembled = match &args.source {
+            Some(source) => assemble_into(nasm, source, Path::new(inputs[0])),
+            None => Ok(()),
+        };
+        match assembled {
+            Ok(()) => drop(run()),
+            Err(failed) => drop(failed.report()),
+        }
+        eprintln!("\nwatching {}; press Ctrl-C to stop", names.join(", "));
+        wait_for_change(&paths);
+    }
+}
@@ -0,0 +1,110 @@
+use computer_enhance::repetition_tester::BenchArgs;
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// NOPs to put between a 64-byte boundary and the start of the loop; repeat for several.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(0..64),
+        default_values_t = [0, 1, 8, 16, 24, 32, 48, 56, 60, 62, 63],
+    )]
+    nops: Vec<u8>,
+
+    /// Times to go around the loop each trial.
+    #[arg(long, default_value_t = 10_000_000)]
+    iterations: u64,
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    /// Goes `iterations` times around a 17-byte loop placed `NOPS` bytes past a 64-byte
+    /// boundary, returning the loop's address. The NOPs are jumped over, so only where the
+    /// loop lands changes, not what runs.
+    pub fn padded_loop<const NOPS: usize>(iterations: u64) -> usize {
+        assert!(iterations > 0);
+        let start: usize;
+        // SAFETY: the loop only counts in registers.
+        unsafe {
+            std::arch::asm!(
+                "lea {start}, [rip + 3f]",
+                "jmp 3f",
+                ".p2align 6",
+                ".fill {nops}, 1, 0x90",
+                "3:",
+                "add {a}, 1",
+                "add {b}, 1",
+                "add {c}, 1",
+                "dec {count}",
+                "jnz 3b",
+                nops = const NOPS,
+                start = out(reg) start,
+                count = inout(reg) iterations => _,
+                a = inout(reg) 0u64 => _,
+                b = inout(reg) 0u64 => _,
+                c = inout(reg) 0u64 => _,
+                options(nostack, nomem),
+            );
+        }
+        start
+    }
+
+    macro_rules! padded_loops {
+        ($($nops:literal)*) => {
+            [$(padded_loop::<$nops> as fn(u64) -> usize),*]
+        };
+    }
+
+    /// `padded_loop` for every count of NOPs from 0 to 63, in order.
+    pub const PADDED_LOOPS: [fn(u64) -> usize; 64] = padded_loops!(
+        0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+        32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60
+        61 62 63
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn run(args: Args) -> ExitCode {
+    use computer_enhance::metrics::ticks_to_seconds;
+    use std::hint::black_box;
+
+    let iterations = args.iterations.max(1);
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for &nops in &args.nops {
+        let padded_loop = x86::PADDED_LOOPS[nops as usize];
+        let name = format!("{nops} NOPs");
+        let mut start = 0;
+        if let Some(results) = bench.run(&name, 0, || {
+            start = padded_loop(black_box(iterations));
+        }) {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            rows.push((
+                nops,
+                start % 64,
+                1e9 * seconds / iterations as f64,
+                results.min.ticks as f64 / iterations as f64,
+            ));
+        }
+    }
+
+    println!();
+    println!(
+        "{:>6}  {:>12}  {:>10}  {:>12}",
+        "NOPs", "start % 64", "ns/iter", "ticks/iter"
+    );
+    for (nops, alignment, nanoseconds, ticks) in rows {
+        println!("{nops:>6}  {alignment:>12}  {nanoseconds:>10.3}  {ticks:>12.3}");
+    }
+    println!("(Ticks are the CPU timer's, which counts at the base clock, not the current one.)");
+    bench.finish()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn run(_args: Args) -> ExitCode {
+    eprintln!("loop alignment is only measured on x86-64");
+    computer_enhance::failure::Failure::Usage.into()
+}
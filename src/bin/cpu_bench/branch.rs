@@ -0,0 +1,131 @@
+use computer_enhance::{metrics::ticks_to_seconds, repetition_tester::BenchArgs};
+use std::{hint::black_box, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Branches to run each trial, one per byte of the pattern.
+    #[arg(long, default_value_t = 1_000_000)]
+    branches: usize,
+
+    /// Percent chance each branch of a random pattern is taken; repeat for several.
+    #[arg(long, value_name = "PERCENT", default_values_t = [50, 25, 10, 1])]
+    probability: Vec<u32>,
+}
+
+/// When the branch is taken, byte by byte.
+#[derive(Clone, Copy)]
+enum Pattern {
+    Never,
+    Always,
+    Alternating,
+    /// Taken this percent of the time, at random.
+    Random(u32),
+}
+
+impl Pattern {
+    fn name(self) -> String {
+        match self {
+            Pattern::Never => "never".to_string(),
+            Pattern::Always => "always".to_string(),
+            Pattern::Alternating => "alternating".to_string(),
+            Pattern::Random(percent) => format!("random {percent}%"),
+        }
+    }
+
+    /// `count` bytes, nonzero where the branch should be taken.
+    fn bytes(self, count: usize) -> Vec<u8> {
+        // xorshift64*, seeded the same every run so runs see the same pattern.
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut random = || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        };
+        (0..count)
+            .map(|index| {
+                let taken = match self {
+                    Pattern::Never => false,
+                    Pattern::Always => true,
+                    Pattern::Alternating => index % 2 == 1,
+                    Pattern::Random(percent) => random() % 100 < percent as u64,
+                };
+                taken as u8
+            })
+            .collect()
+    }
+}
+
+/// Branches on every byte of `pattern`, returning how many times the branch was taken.
+fn branch(pattern: &[u8]) -> u64 {
+    assert!(!pattern.is_empty());
+    let mut taken = 0u64;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: the loop reads each byte of `pattern` once, and it isn't empty.
+    unsafe {
+        std::arch::asm!(
+            "2:",
+            "cmp byte ptr [{data} + {index}], 0",
+            "je 3f",
+            "inc {taken}",
+            "3:",
+            "inc {index}",
+            "cmp {index}, {len}",
+            "jb 2b",
+            data = in(reg) pattern.as_ptr(),
+            len = in(reg) pattern.len(),
+            index = inout(reg) 0usize => _,
+            taken = inout(reg) taken,
+            options(nostack, readonly),
+        );
+    }
+    // The compiler may well turn this into a conditional move, with nothing to predict.
+    #[cfg(not(target_arch = "x86_64"))]
+    for &byte in pattern {
+        if black_box(byte) != 0 {
+            taken += 1;
+        }
+    }
+    taken
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let branches = args.branches.max(1);
+    let mut patterns = vec![Pattern::Never, Pattern::Always, Pattern::Alternating];
+    patterns.extend(
+        args.probability
+            .iter()
+            .map(|&percent| Pattern::Random(percent.min(100))),
+    );
+
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for pattern in patterns {
+        let bytes = pattern.bytes(branches);
+        let name = pattern.name();
+        if let Some(results) = bench.run(&name, branches as u64, || {
+            black_box(branch(black_box(&bytes)));
+        }) {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            rows.push((
+                name,
+                1e9 * seconds / branches as f64,
+                results.min.ticks as f64 / branches as f64,
+            ));
+        }
+    }
+
+    println!();
+    println!(
+        "{:>12}  {:>10}  {:>14}",
+        "pattern", "ns/branch", "ticks/branch"
+    );
+    for (name, nanoseconds, ticks) in rows {
+        println!("{name:>12}  {nanoseconds:>10.3}  {ticks:>14.3}");
+    }
+    println!("(Ticks are the CPU timer's, which counts at the base clock, not the current one.)");
+    bench.finish()
+}
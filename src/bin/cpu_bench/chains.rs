@@ -0,0 +1,221 @@
+use computer_enhance::repetition_tester::BenchArgs;
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Operations to measure; repeat for several. All of them by default.
+    #[arg(long, value_enum)]
+    op: Vec<Op>,
+
+    /// Times to go around each loop per trial, each time doing 8 operations in every chain.
+    #[arg(long, default_value_t = 1_000_000)]
+    iterations: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Op {
+    Add,
+    Mul,
+    Load,
+}
+
+impl Op {
+    const ALL: [Op; 3] = [Op::Add, Op::Mul, Op::Load];
+
+    fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Mul => "mul",
+            Op::Load => "load",
+        }
+    }
+}
+
+/// Chains the parallel tests run side by side. Four hide a multiply's latency, but not always
+/// a load's, so the loads' throughput is only a lower bound.
+const PARALLEL: usize = 4;
+
+/// Operations each chain does each time around a loop.
+const UNROLL: u64 = 8;
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::PARALLEL;
+
+    /// Each loop goes `iterations` times around 8 operations on each of `CHAINS` registers,
+    /// every operation taking the last one on its register as input. `pointers` are the
+    /// words the loads start from, each holding its own address.
+    pub type Loop = fn(&[usize; PARALLEL], u64);
+
+    /// Adds a register rather than an immediate, since some cores fold chains of immediate
+    /// adds together as they rename them, leaving the chain nothing to wait for.
+    pub fn adds<const CHAINS: usize>(_pointers: &[usize; PARALLEL], iterations: u64) {
+        assert!(iterations > 0);
+        // SAFETY: the loop only counts in registers.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "add {a}, {one}",
+                ".if {chains} >= 2",
+                "add {b}, {one}",
+                "add {c}, {one}",
+                "add {d}, {one}",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                chains = const CHAINS,
+                one = in(reg) 1u64,
+                count = inout(reg) iterations => _,
+                a = inout(reg) 0u64 => _,
+                b = inout(reg) 0u64 => _,
+                c = inout(reg) 0u64 => _,
+                d = inout(reg) 0u64 => _,
+                options(nostack, nomem),
+            );
+        }
+    }
+
+    pub fn muls<const CHAINS: usize>(_pointers: &[usize; PARALLEL], iterations: u64) {
+        assert!(iterations > 0);
+        // SAFETY: the loop only multiplies in registers.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "imul {a}, {one}",
+                ".if {chains} >= 2",
+                "imul {b}, {one}",
+                "imul {c}, {one}",
+                "imul {d}, {one}",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                chains = const CHAINS,
+                one = in(reg) 1u64,
+                count = inout(reg) iterations => _,
+                a = inout(reg) 1u64 => _,
+                b = inout(reg) 1u64 => _,
+                c = inout(reg) 1u64 => _,
+                d = inout(reg) 1u64 => _,
+                options(nostack, nomem),
+            );
+        }
+    }
+
+    pub fn loads<const CHAINS: usize>(pointers: &[usize; PARALLEL], iterations: u64) {
+        assert!(iterations > 0);
+        assert!(pointers
+            .iter()
+            .all(|pointer| *pointer == pointer as *const usize as usize));
+        // SAFETY: each register starts at a word holding its own address, so every load reads
+        // that same word again.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "mov {a}, [{a}]",
+                ".if {chains} >= 2",
+                "mov {b}, [{b}]",
+                "mov {c}, [{c}]",
+                "mov {d}, [{d}]",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                chains = const CHAINS,
+                count = inout(reg) iterations => _,
+                a = inout(reg) pointers[0] => _,
+                b = inout(reg) pointers[1] => _,
+                c = inout(reg) pointers[2] => _,
+                d = inout(reg) pointers[3] => _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    /// The loop for `op`, as one chain or `PARALLEL` of them.
+    pub fn lookup(op: super::Op, parallel: bool) -> Loop {
+        use super::Op;
+        match (op, parallel) {
+            (Op::Add, false) => adds::<1>,
+            (Op::Add, true) => adds::<PARALLEL>,
+            (Op::Mul, false) => muls::<1>,
+            (Op::Mul, true) => muls::<PARALLEL>,
+            (Op::Load, false) => loads::<1>,
+            (Op::Load, true) => loads::<PARALLEL>,
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn run(args: Args) -> ExitCode {
+    use computer_enhance::repetition_tester::Bench;
+    use std::hint::black_box;
+
+    let iterations = args.iterations.max(1);
+    let mut ops = if args.op.is_empty() {
+        Op::ALL.to_vec()
+    } else {
+        args.op
+    };
+    // A chain of adds takes a cycle per add, so it counts how many cycles there are to a
+    // tick. It runs first either way.
+    ops.retain(|&op| op != Op::Add);
+    ops.insert(0, Op::Add);
+
+    let mut pointers = [0usize; PARALLEL];
+    for pointer in &mut pointers {
+        *pointer = pointer as *const usize as usize;
+    }
+    let mut bench = args.bench_args.bench();
+    // Ticks an operation takes in one chain, and operations a tick across several.
+    let ticks = |bench: &mut Bench, op: Op, parallel: bool| {
+        let test = x86::lookup(op, parallel);
+        let chains = if parallel { PARALLEL } else { 1 };
+        let name = format!("{} ({chains} chains)", op.name());
+        let results = bench.run(&name, 0, || {
+            test(black_box(&pointers), black_box(iterations));
+        })?;
+        Some(results.min.ticks as f64 / (iterations * UNROLL * chains as u64) as f64)
+    };
+    let mut rows = Vec::new();
+    for op in ops {
+        let serial = ticks(&mut bench, op, false);
+        let parallel = ticks(&mut bench, op, true);
+        rows.push((op, serial, parallel));
+    }
+
+    let ticks_per_cycle = rows[0].1;
+    let cell = |ticks: Option<f64>, invert: bool| match (ticks, ticks_per_cycle) {
+        (Some(ticks), Some(per_cycle)) if invert => format!("{:.3}", per_cycle / ticks),
+        (Some(ticks), Some(per_cycle)) => format!("{:.3}", ticks / per_cycle),
+        _ => "-".to_string(),
+    };
+    println!();
+    println!(
+        "{:>6}  {:>16}  {:>18}",
+        "op", "latency (cycles)", "throughput (/cycle)"
+    );
+    for (op, serial, parallel) in rows {
+        println!(
+            "{:>6}  {:>16}  {:>18}",
+            op.name(),
+            cell(serial, false),
+            cell(parallel, true)
+        );
+    }
+    println!("(Cycles are counted by the chain of adds, which take one cycle after another.)");
+    bench.finish()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn run(_args: Args) -> ExitCode {
+    eprintln!("dependency chains are only measured on x86-64");
+    computer_enhance::failure::Failure::Usage.into()
+}
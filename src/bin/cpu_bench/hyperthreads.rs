@@ -0,0 +1,216 @@
+use computer_enhance::{
+    failure::Failure,
+    metrics::ticks_to_seconds,
+    os_threads::{physical_cores, pin_thread_to_cpu},
+    repetition_tester::BenchArgs,
+};
+use std::{hint::black_box, process::ExitCode, thread};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes each thread reads per trial in the bandwidth test, from a buffer of its own.
+    #[arg(long, default_value_t = 64 << 20)]
+    size: usize,
+
+    /// Times each thread goes around the add loop per trial in the ALU test.
+    #[arg(long, default_value_t = 10_000_000)]
+    iterations: u64,
+}
+
+/// What each thread does.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Work {
+    /// Reads its buffer, which the core can't keep up with, let alone its threads.
+    Read,
+    /// Runs chains of adds, which keep the core's ALUs busy whether or not memory can.
+    Alu,
+}
+
+impl Work {
+    fn name(self) -> &'static str {
+        match self {
+            Work::Read => "read",
+            Work::Alu => "alu",
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Work::Read => "GB/s",
+            Work::Alu => "Gadds/s",
+        }
+    }
+}
+
+/// Which logical CPUs the threads run on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    /// One thread, for the rest to compare with.
+    Alone,
+    /// Two threads on the two hyperthreads of one core.
+    SameCore,
+    /// Two threads on the first hyperthreads of two cores.
+    SeparateCores,
+}
+
+impl Placement {
+    const ALL: [Placement; 3] = [
+        Placement::Alone,
+        Placement::SameCore,
+        Placement::SeparateCores,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Placement::Alone => "alone",
+            Placement::SameCore => "same core",
+            Placement::SeparateCores => "separate cores",
+        }
+    }
+
+    /// The CPUs to put threads on, if `cores` have the ones it needs.
+    fn cpus(self, cores: &[Vec<usize>]) -> Option<Vec<usize>> {
+        match self {
+            Placement::Alone => Some(vec![cores.first()?[0]]),
+            Placement::SameCore => {
+                let cpus = cores.iter().find(|cpus| cpus.len() >= 2)?;
+                Some(cpus[..2].to_vec())
+            }
+            Placement::SeparateCores => match cores {
+                [first, second, ..] => Some(vec![first[0], second[0]]),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn read(data: &[u8]) {
+    let sum = data.chunks_exact(8).fold(0u64, |sum, word| {
+        sum.wrapping_add(u64::from_ne_bytes(word.try_into().unwrap()))
+    });
+    black_box(sum);
+}
+
+/// Goes `iterations` times around 8 adds to each of 4 independent registers.
+fn adds(iterations: u64) {
+    assert!(iterations > 0);
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: the loop only counts in registers.
+    unsafe {
+        std::arch::asm!(
+            "2:",
+            ".rept 8",
+            "add {a}, {one}",
+            "add {b}, {one}",
+            "add {c}, {one}",
+            "add {d}, {one}",
+            ".endr",
+            "dec {count}",
+            "jnz 2b",
+            one = in(reg) 1u64,
+            count = inout(reg) iterations => _,
+            a = inout(reg) 0u64 => _,
+            b = inout(reg) 0u64 => _,
+            c = inout(reg) 0u64 => _,
+            d = inout(reg) 0u64 => _,
+            options(nostack, nomem),
+        );
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let mut sums = [0u64; 4];
+        for _ in 0..iterations * 8 {
+            for sum in &mut sums {
+                *sum = black_box(*sum).wrapping_add(1);
+            }
+        }
+        black_box(sums);
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let cores = match physical_cores() {
+        Ok(cores) => cores,
+        Err(error) => {
+            eprintln!("can't find the CPUs' cores: {error}");
+            return Failure::Io.into();
+        }
+    };
+    let mut placements = Vec::new();
+    for placement in Placement::ALL {
+        match placement.cpus(&cores) {
+            Some(cpus) => placements.push((placement, cpus)),
+            None => eprintln!(
+                "skipping {}: this machine has no CPUs for it",
+                placement.name()
+            ),
+        }
+    }
+    for (_, cpus) in &placements {
+        for &cpu in cpus {
+            if let Err(error) = thread::spawn(move || pin_thread_to_cpu(cpu))
+                .join()
+                .unwrap()
+            {
+                eprintln!("can't run a thread on CPU {cpu}: {error}");
+                return Failure::Io.into();
+            }
+        }
+    }
+
+    let size = args.size.max(8);
+    let iterations = args.iterations.max(1);
+    let buffers = [vec![1u8; size], vec![1u8; size]];
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for work in [Work::Read, Work::Alu] {
+        let mut alone = None;
+        for (placement, cpus) in &placements {
+            let (per_thread, bytes) = match work {
+                Work::Read => (size as f64 / (1u64 << 30) as f64, size * cpus.len()),
+                Work::Alu => ((iterations * 32) as f64 / 1e9, 0),
+            };
+            let name = format!("{} ({})", work.name(), placement.name());
+            let Some(results) = bench.run(&name, bytes as u64, || {
+                thread::scope(|scope| {
+                    for (&cpu, buffer) in cpus.iter().zip(&buffers) {
+                        scope.spawn(move || {
+                            // Checked above, so it only fails if the CPU went offline since.
+                            let _ = pin_thread_to_cpu(cpu);
+                            match work {
+                                Work::Read => read(buffer),
+                                Work::Alu => adds(iterations),
+                            }
+                        });
+                    }
+                });
+            }) else {
+                continue;
+            };
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            let rate = per_thread / seconds;
+            let combined = rate * cpus.len() as f64;
+            let alone = *alone.get_or_insert(combined);
+            rows.push((work, *placement, rate, combined, combined / alone));
+        }
+    }
+
+    println!();
+    println!(
+        "{:>6}  {:>14}  {:>8}  {:>10}  {:>10}  {:>8}",
+        "work", "placement", "unit", "per thread", "combined", "x alone"
+    );
+    for (work, placement, rate, combined, ratio) in rows {
+        println!(
+            "{:>6}  {:>14}  {:>8}  {rate:>10.2}  {combined:>10.2}  {ratio:>8.2}",
+            work.name(),
+            placement.name(),
+            work.unit(),
+        );
+    }
+    println!("(Each trial starts its threads afresh, so the times include starting them.)");
+    bench.finish()
+}
@@ -0,0 +1,54 @@
+mod alignment;
+mod branch;
+mod chains;
+mod hyperthreads;
+mod ports;
+mod timer;
+
+use clap::{Parser, Subcommand};
+use computer_enhance::{environment::Environment, failure::EXIT_STATUSES};
+use std::process::ExitCode;
+
+/// Measure how the core runs instructions, with loops written to isolate one effect each,
+/// repeated until they stop getting faster.
+#[derive(Parser)]
+#[command(name = "cpu_bench", after_help = EXIT_STATUSES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the same short loop starting different distances past a 64-byte boundary, showing
+    /// what its alignment costs the front end.
+    Alignment(alignment::Args),
+    /// Run a conditional branch over patterns from never taken to random, timing each branch
+    /// to show what the predictor can and can't learn.
+    Branch(branch::Args),
+    /// Run adds, multiplies, and loads as one chain, each waiting for the last, and as
+    /// several independent chains, measuring each operation's latency and throughput.
+    Chains(chains::Args),
+    /// Run two threads of reads or adds on the hyperthreads of one core and on two separate
+    /// cores, comparing their throughput with one thread's.
+    Hyperthreads(hyperthreads::Args),
+    /// Run 1 to 4 independent streams of adds, loads, or stores, showing how many of each the
+    /// core can do in a cycle.
+    Ports(ports::Args),
+    /// Time nothing, then a chain of multiplies, between reads of the CPU timer with each kind
+    /// of fence around them, showing how out-of-order execution skews what it measures.
+    Timer(timer::Args),
+}
+
+fn main() -> ExitCode {
+    let command = Cli::parse().command;
+    println!("{}", Environment::detect());
+    match command {
+        Command::Alignment(args) => alignment::run(args),
+        Command::Branch(args) => branch::run(args),
+        Command::Chains(args) => chains::run(args),
+        Command::Hyperthreads(args) => hyperthreads::run(args),
+        Command::Ports(args) => ports::run(args),
+        Command::Timer(args) => timer::run(args),
+    }
+}
@@ -0,0 +1,222 @@
+use computer_enhance::repetition_tester::BenchArgs;
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Operations to measure; repeat for several. All of them by default.
+    #[arg(long, value_enum)]
+    op: Vec<Op>,
+
+    /// Times to go around each loop per trial, each time doing 8 of every stream's operation.
+    #[arg(long, default_value_t = 1_000_000)]
+    iterations: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Op {
+    Add,
+    Load,
+    Store,
+}
+
+impl Op {
+    const ALL: [Op; 3] = [Op::Add, Op::Load, Op::Store];
+
+    fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Load => "load",
+            Op::Store => "store",
+        }
+    }
+}
+
+/// The most streams of an operation a loop runs side by side.
+const MAX_STREAMS: usize = 4;
+
+/// How many of each stream's operation a loop does each time around.
+const UNROLL: u64 = 8;
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::MAX_STREAMS;
+
+    /// Each loop goes `iterations` times around 8 rounds of `STREAMS` operations, each on its
+    /// own register and so independent of the others, but dependent on its stream's last.
+    pub type Loop = fn(&mut [u64; MAX_STREAMS], u64);
+
+    /// Adds a register rather than an immediate, since some cores fold chains of immediate
+    /// adds together as they rename them, leaving the chain nothing to wait for.
+    pub fn adds<const STREAMS: usize>(_data: &mut [u64; MAX_STREAMS], iterations: u64) {
+        assert!(iterations > 0);
+        // SAFETY: the loop only counts in registers.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "add {a}, {one}",
+                ".if {streams} >= 2",
+                "add {b}, {one}",
+                ".endif",
+                ".if {streams} >= 3",
+                "add {c}, {one}",
+                ".endif",
+                ".if {streams} >= 4",
+                "add {d}, {one}",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                streams = const STREAMS,
+                one = in(reg) 1u64,
+                count = inout(reg) iterations => _,
+                a = inout(reg) 0u64 => _,
+                b = inout(reg) 0u64 => _,
+                c = inout(reg) 0u64 => _,
+                d = inout(reg) 0u64 => _,
+                options(nostack, nomem),
+            );
+        }
+    }
+
+    pub fn loads<const STREAMS: usize>(data: &mut [u64; MAX_STREAMS], iterations: u64) {
+        assert!(iterations > 0);
+        // SAFETY: every load is of one of `data`'s words.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "mov {a}, [{data}]",
+                ".if {streams} >= 2",
+                "mov {b}, [{data} + 8]",
+                ".endif",
+                ".if {streams} >= 3",
+                "mov {c}, [{data} + 16]",
+                ".endif",
+                ".if {streams} >= 4",
+                "mov {d}, [{data} + 24]",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                streams = const STREAMS,
+                data = in(reg) data.as_ptr(),
+                count = inout(reg) iterations => _,
+                a = out(reg) _,
+                b = out(reg) _,
+                c = out(reg) _,
+                d = out(reg) _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    pub fn stores<const STREAMS: usize>(data: &mut [u64; MAX_STREAMS], iterations: u64) {
+        assert!(iterations > 0);
+        // SAFETY: every store is to one of `data`'s words.
+        unsafe {
+            std::arch::asm!(
+                "2:",
+                ".rept 8",
+                "mov [{data}], {count}",
+                ".if {streams} >= 2",
+                "mov [{data} + 8], {count}",
+                ".endif",
+                ".if {streams} >= 3",
+                "mov [{data} + 16], {count}",
+                ".endif",
+                ".if {streams} >= 4",
+                "mov [{data} + 24], {count}",
+                ".endif",
+                ".endr",
+                "dec {count}",
+                "jnz 2b",
+                streams = const STREAMS,
+                data = in(reg) data.as_mut_ptr(),
+                count = inout(reg) iterations => _,
+                options(nostack),
+            );
+        }
+    }
+
+    pub fn lookup(op: super::Op, streams: usize) -> Loop {
+        use super::Op;
+        match (op, streams) {
+            (Op::Add, 1) => adds::<1>,
+            (Op::Add, 2) => adds::<2>,
+            (Op::Add, 3) => adds::<3>,
+            (Op::Add, _) => adds::<4>,
+            (Op::Load, 1) => loads::<1>,
+            (Op::Load, 2) => loads::<2>,
+            (Op::Load, 3) => loads::<3>,
+            (Op::Load, _) => loads::<4>,
+            (Op::Store, 1) => stores::<1>,
+            (Op::Store, 2) => stores::<2>,
+            (Op::Store, 3) => stores::<3>,
+            (Op::Store, _) => stores::<4>,
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn run(args: Args) -> ExitCode {
+    use std::hint::black_box;
+
+    let iterations = args.iterations.max(1);
+    let ops = if args.op.is_empty() {
+        Op::ALL.to_vec()
+    } else {
+        args.op
+    };
+    // One stream of adds is a chain of adds each waiting for the last, which take a cycle
+    // apiece, so it counts how many cycles there are to a tick. It runs first either way.
+    let mut tests = vec![(Op::Add, 1)];
+    for op in ops {
+        for streams in 1..=MAX_STREAMS {
+            if (op, streams) != (Op::Add, 1) {
+                tests.push((op, streams));
+            }
+        }
+    }
+
+    let mut bench = args.bench_args.bench();
+    let mut data = [0u64; MAX_STREAMS];
+    let mut rows = Vec::new();
+    for (op, streams) in tests {
+        let test = x86::lookup(op, streams);
+        let name = format!("{streams}x {}", op.name());
+        if let Some(results) = bench.run(&name, 0, || {
+            test(black_box(&mut data), black_box(iterations));
+        }) {
+            let ops = (iterations * UNROLL * streams as u64) as f64;
+            rows.push((name, ops / results.min.ticks.max(1) as f64));
+        }
+    }
+
+    let cycles_per_tick = match rows.first() {
+        Some((name, ops_per_tick)) if name == "1x add" => Some(*ops_per_tick),
+        _ => None,
+    };
+    println!();
+    println!("{:>10}  {:>10}  {:>10}", "test", "ops/tick", "ops/cycle");
+    for (name, ops_per_tick) in rows {
+        match cycles_per_tick {
+            Some(cycles) => println!(
+                "{name:>10}  {ops_per_tick:>10.3}  {:>10.3}",
+                ops_per_tick / cycles
+            ),
+            None => println!("{name:>10}  {ops_per_tick:>10.3}  {:>10}", "-"),
+        }
+    }
+    println!("(Cycles are counted by the 1x add test, whose adds take one cycle after another.)");
+    bench.finish()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn run(_args: Args) -> ExitCode {
+    eprintln!("execution ports are only measured on x86-64");
+    computer_enhance::failure::Failure::Usage.into()
+}
@@ -0,0 +1,106 @@
+use computer_enhance::metrics::{read_cpu_timer_serialized, Serialization};
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Intervals to time each way.
+    #[arg(long, default_value_t = 1_000_000)]
+    samples: usize,
+
+    /// Multiplies in the chain timed between reads, each waiting for the last.
+    #[arg(long, default_value_t = 100)]
+    multiplies: u64,
+}
+
+/// Runs `multiplies` multiplies one after another, each waiting for the last.
+#[inline(always)]
+fn chain(multiplies: u64) {
+    if multiplies == 0 {
+        return;
+    }
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: the loop only multiplies in registers.
+    unsafe {
+        std::arch::asm!(
+            "2:",
+            "imul {value}, {one}",
+            "dec {count}",
+            "jnz 2b",
+            one = in(reg) 1u64,
+            count = inout(reg) multiplies => _,
+            value = inout(reg) 1u64 => _,
+            options(nostack, nomem),
+        );
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let mut value = 1u64;
+        for _ in 0..multiplies {
+            value = std::hint::black_box(value.wrapping_mul(1));
+        }
+    }
+}
+
+/// The least and the median of `samples` intervals between two reads of `read`, with
+/// `multiplies` multiplies between them.
+#[inline(always)]
+fn intervals(read: impl Fn() -> u64, samples: usize, multiplies: u64) -> (u64, u64) {
+    let mut intervals: Vec<u64> = (0..samples)
+        .map(|_| {
+            let start = read();
+            chain(multiplies);
+            read().wrapping_sub(start)
+        })
+        .collect();
+    intervals.sort_unstable();
+    (intervals[0], intervals[intervals.len() / 2])
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let samples = args.samples.max(1);
+    println!(
+        "{:>8}  {:>10}  {:>12}  {:>10}  {:>12}",
+        "fence", "empty min", "empty median", "chain min", "chain median"
+    );
+    for serialization in Serialization::ALL {
+        if !serialization.is_available() {
+            eprintln!("skipping {}: this CPU can't", serialization.name());
+            continue;
+        }
+        // Matched here so each read is inlined with its fences fixed, not chosen as it runs.
+        let [(empty_min, empty_median), (chain_min, chain_median)] =
+            [0, args.multiplies].map(|multiplies| match serialization {
+                Serialization::None => intervals(
+                    || read_cpu_timer_serialized(Serialization::None),
+                    samples,
+                    multiplies,
+                ),
+                Serialization::Rdtscp => intervals(
+                    || read_cpu_timer_serialized(Serialization::Rdtscp),
+                    samples,
+                    multiplies,
+                ),
+                Serialization::Lfence => intervals(
+                    || read_cpu_timer_serialized(Serialization::Lfence),
+                    samples,
+                    multiplies,
+                ),
+                Serialization::Mfence => intervals(
+                    || read_cpu_timer_serialized(Serialization::Mfence),
+                    samples,
+                    multiplies,
+                ),
+            });
+        println!(
+            "{:>8}  {empty_min:>10}  {empty_median:>12}  {chain_min:>10}  {chain_median:>12}",
+            serialization.name()
+        );
+    }
+    println!(
+        "(Ticks of the CPU timer. A chain of {} multiplies takes about {} cycles; unfenced reads \
+         can overlap it and undercount.)",
+        args.multiplies,
+        3 * args.multiplies
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,285 @@
+use clap::Parser;
+use computer_enhance::{
+    atomic_file::write_atomically,
+    failure::{Failure, EXIT_STATUSES},
+    haversine::{reference_haversine, AnswersWriter, Pair, EARTH_RADIUS},
+};
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    panic,
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+};
+
+/// Generate random point pairs as JSON for the haversine homework, printing the average
+/// distance between them that a processor should arrive at.
+#[derive(Parser)]
+#[command(name = "haversine_gen", after_help = EXIT_STATUSES)]
+struct Args {
+    /// Seed for the random numbers; the same seed always gives the same pairs, however many
+    /// threads generate them.
+    seed: u64,
+
+    /// How many pairs to generate.
+    count: usize,
+
+    /// JSON file to write, replaced only once it's complete.
+    output: PathBuf,
+
+    /// Also write each pair's distance to this file as a little-endian f64, followed by their
+    /// average, after a header with their count and checksum, so a processor can check its
+    /// results exactly.
+    #[arg(long, value_name = "PATH")]
+    answers: Option<PathBuf>,
+
+    /// How the points are spread over the globe.
+    #[arg(long, value_enum, default_value_t)]
+    method: Method,
+
+    /// Generate with this many threads; one per CPU by default.
+    #[arg(long, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Method {
+    /// Anywhere, all equally likely.
+    #[default]
+    Uniform,
+    /// Bunched into 64 clusters of random size, as the course's generator does, so distances
+    /// aren't all near the average.
+    Cluster,
+}
+
+/// Pairs are generated in blocks of this many, each from its own seed, so any number of
+/// threads can share them out and still produce the same file.
+const BLOCK: usize = 1 << 16;
+
+/// How many clusters the pairs are split between with `Method::Cluster`.
+const CLUSTERS: usize = 64;
+
+/// SplitMix64: small, fast, and plenty random for test data.
+struct Random(u64);
+
+impl Random {
+    /// The generator for `stream`, one of many independent sequences from one seed.
+    fn stream(seed: u64, stream: u64) -> Random {
+        let mut mixed = Random(seed ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        Random(mixed.next())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `min..max`.
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        // The top 53 bits fill an f64's mantissa exactly.
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        min + (max - min) * unit
+    }
+}
+
+/// Where one cluster's points fall: within `radius` degrees of `center` on each axis, clamped
+/// to the globe.
+#[derive(Clone, Copy)]
+struct Cluster {
+    center: (f64, f64),
+    radius: (f64, f64),
+}
+
+impl Cluster {
+    fn new(random: &mut Random) -> Cluster {
+        Cluster {
+            center: (random.range(-180.0, 180.0), random.range(-90.0, 90.0)),
+            radius: (random.range(0.0, 180.0), random.range(0.0, 90.0)),
+        }
+    }
+
+    /// A uniform random longitude and latitude within the cluster.
+    fn point(&self, random: &mut Random) -> (f64, f64) {
+        let degree = |random: &mut Random, center: f64, radius: f64, limit: f64| {
+            let min = (center - radius).max(-limit);
+            let max = (center + radius).min(limit);
+            random.range(min, max)
+        };
+        (
+            degree(random, self.center.0, self.radius.0, 180.0),
+            degree(random, self.center.1, self.radius.1, 90.0),
+        )
+    }
+}
+
+/// The whole globe as one cluster, for `Method::Uniform`.
+const GLOBE: Cluster = Cluster {
+    center: (0.0, 0.0),
+    radius: (180.0, 90.0),
+};
+
+/// What to generate: shared by every block.
+#[derive(Clone, Copy)]
+struct Plan {
+    seed: u64,
+    count: usize,
+    method: Method,
+}
+
+impl Plan {
+    /// Which cluster pair `index` is in.
+    fn cluster_of(&self, index: usize) -> usize {
+        match self.method {
+            Method::Uniform => 0,
+            Method::Cluster => index / self.count.div_ceil(CLUSTERS),
+        }
+    }
+
+    /// Where cluster `index` is. Each has its own stream after the blocks', so it's the same
+    /// whichever blocks its pairs land in.
+    fn cluster(&self, index: usize) -> Cluster {
+        match self.method {
+            Method::Uniform => GLOBE,
+            Method::Cluster => {
+                let blocks = self.count.div_ceil(BLOCK);
+                Cluster::new(&mut Random::stream(self.seed, (blocks + index) as u64))
+            }
+        }
+    }
+}
+
+/// One block of pairs, ready to write: their JSON lines and distances.
+struct Block {
+    text: String,
+    distances: Vec<f64>,
+}
+
+/// Generates block `index`: pairs `index * BLOCK` onwards. Each coordinate is written in the
+/// shortest form that reads back as the same f64, so a parser sees exactly the pairs that were
+/// summed.
+fn block(plan: Plan, index: usize) -> Block {
+    let mut random = Random::stream(plan.seed, index as u64);
+    let start = index * BLOCK;
+    let end = (start + BLOCK).min(plan.count);
+    let mut block = Block {
+        text: String::with_capacity((end - start) * 100),
+        distances: Vec::with_capacity(end - start),
+    };
+    let mut cluster = (usize::MAX, GLOBE);
+    for pair_index in start..end {
+        let cluster_index = plan.cluster_of(pair_index);
+        if cluster.0 != cluster_index {
+            cluster = (cluster_index, plan.cluster(cluster_index));
+        }
+        let (x0, y0) = cluster.1.point(&mut random);
+        let (x1, y1) = cluster.1.point(&mut random);
+        let pair = Pair { x0, y0, x1, y1 };
+        block
+            .distances
+            .push(reference_haversine(pair, EARTH_RADIUS));
+        let separator = if pair_index + 1 < plan.count { "," } else { "" };
+        let _ = writeln!(
+            block.text,
+            "    {{\"x0\":{x0}, \"y0\":{y0}, \"x1\":{x1}, \"y1\":{y1}}}{separator}"
+        );
+    }
+    block
+}
+
+/// Writes `plan.count` pairs as `{"pairs":[{"x0":..., "y0":..., "x1":..., "y1":...}, ...]}`,
+/// generated `threads` blocks at a time, returning the sum of their distances. Each distance is
+/// also written to `answers`, if given. The distances are summed in order, as a processor
+/// would, so the average comes out exactly the same.
+fn write_pairs(
+    out: &mut impl Write,
+    mut answers: Option<&mut AnswersWriter<&mut BufWriter<File>>>,
+    plan: Plan,
+    threads: usize,
+) -> io::Result<f64> {
+    let mut sum = 0.0;
+    writeln!(out, "{{\"pairs\":[")?;
+    let blocks: Vec<usize> = (0..plan.count.div_ceil(BLOCK)).collect();
+    for wave in blocks.chunks(threads) {
+        let generated: Vec<Block> = thread::scope(|scope| {
+            let handles: Vec<_> = wave
+                .iter()
+                .map(|&index| scope.spawn(move || block(plan, index)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        for block in generated {
+            out.write_all(block.text.as_bytes())?;
+            for distance in block.distances {
+                sum += distance;
+                if let Some(answers) = &mut answers {
+                    answers.distance(distance)?;
+                }
+            }
+        }
+    }
+    writeln!(out, "]}}")?;
+    Ok(sum)
+}
+
+fn average(sum: f64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let plan = Plan {
+        seed: args.seed,
+        count: args.count,
+        method: args.method,
+    };
+    let threads = args
+        .threads
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let mut sum = 0.0;
+    // The answers are written alongside the JSON, so their errors also say which file failed.
+    let written = write_atomically(&args.output, |out| match &args.answers {
+        Some(path) => write_atomically(path, |answers| {
+            let mut answers = AnswersWriter::new(answers)?;
+            sum = write_pairs(out, Some(&mut answers), plan, threads)?;
+            answers.finish(average(sum, args.count)).map(drop)
+        })
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {error}", path.display()))),
+        None => {
+            sum = write_pairs(out, None, plan, threads)?;
+            Ok(())
+        }
+    });
+    if let Err(error) = written {
+        eprintln!("{}: {error}", args.output.display());
+        return Failure::Io.into();
+    }
+
+    let average = average(sum, args.count);
+    let method = match args.method {
+        Method::Uniform => "uniform",
+        Method::Cluster => "cluster",
+    };
+    println!("Method: {method}");
+    println!("Random seed: {}", args.seed);
+    println!("Pair count: {}", args.count);
+    println!("Expected average: {average:.16}");
+    ExitCode::SUCCESS
+}
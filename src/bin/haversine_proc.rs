@@ -0,0 +1,558 @@
+use clap::Parser;
+use computer_enhance::{
+    atomic_file::write_atomically,
+    failure::{Failed, Failure, EXIT_STATUSES},
+    haversine::{
+        custom_haversine, read_answers, reference_haversine, Answers, AnswersWriter, Pair,
+        EARTH_RADIUS,
+    },
+    haversine_simd::{haversine_simd, TOLERANCE},
+    json::{self, Elements, JsonError, Value},
+    os_metrics::{os_ticks_to_seconds, read_os_timer},
+    profiler, time_bandwidth, time_function,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    num::NonZeroUsize,
+    panic,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+};
+
+/// Read point pairs written by haversine_gen and print the average distance between them.
+#[derive(Parser)]
+#[command(name = "haversine_proc", after_help = EXIT_STATUSES)]
+struct Args {
+    /// JSON file of pairs: `{"pairs":[{"x0":..., "y0":..., "x1":..., "y1":...}, ...]}`.
+    input: PathBuf,
+
+    /// Handle each pair as it's read instead of parsing the whole file first, so memory use
+    /// stays the same however many pairs there are.
+    #[arg(long)]
+    stream: bool,
+
+    /// Where the parsed JSON's values are allocated, without --stream: each on the heap, or
+    /// all in one arena.
+    #[arg(long, value_enum, default_value_t, conflicts_with = "stream")]
+    allocator: Allocator,
+
+    /// Time parsing the input and summing its pairs with this crate's parser, on the heap and
+    /// in an arena, and with serde_json, and report each one's throughput.
+    #[cfg(feature = "serde_bench")]
+    #[arg(long, conflicts_with_all = ["stream", "allocator", "validate"])]
+    compare_serde: bool,
+
+    /// Divide the pairs between this many threads, each summing its share, without --stream.
+    /// Adding up the threads' sums rounds differently to adding every distance in turn, so the
+    /// average can differ in its last digits.
+    #[arg(long, value_name = "N", default_value = "1", conflicts_with = "stream")]
+    threads: NonZeroUsize,
+
+    /// Compute two pairs at a time with SSE2, without --stream, then check every distance
+    /// against the scalar one.
+    #[arg(long, conflicts_with = "stream")]
+    simd: bool,
+
+    /// Whose sine, cosine, arcsine, and square root to compute distances with.
+    #[arg(long, value_enum, default_value_t, conflicts_with = "simd")]
+    math: Math,
+
+    /// Compare each distance, and the average, with an answer file from `haversine_gen
+    /// --answers`.
+    #[arg(long, value_name = "ANSWERS")]
+    validate: Option<PathBuf>,
+
+    /// Write each distance this run computes, and their average, to an answer file, for
+    /// `ce compare-results` to compare with another run's.
+    #[arg(long, value_name = "PATH")]
+    dump: Option<PathBuf>,
+
+    /// Print how long each part of the run took, from the CPU's timestamp counter.
+    #[arg(long)]
+    profile: bool,
+
+    /// Also write every time through every profiled part of the run to this file as Chrome
+    /// trace events, to view in chrome://tracing or Perfetto.
+    #[arg(long, value_name = "PATH")]
+    trace: Option<PathBuf>,
+
+    /// Also write the time spent in each stack of profiled parts of the run to this file as
+    /// folded stacks, to draw as a flamegraph with inferno or flamegraph.pl.
+    #[arg(long, value_name = "PATH")]
+    folded: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Allocator {
+    /// A separate allocation for every array, object, and string.
+    #[default]
+    Heap,
+    /// All values in one allocation and all strings in another.
+    Arena,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Math {
+    /// The standard library's, as the reference answers use.
+    #[default]
+    Std,
+    /// This crate's own polynomial approximations.
+    Custom,
+}
+
+impl Math {
+    fn haversine(self) -> fn(Pair, f64) -> f64 {
+        match self {
+            Math::Std => reference_haversine,
+            Math::Custom => custom_haversine,
+        }
+    }
+}
+
+const NO_PAIRS: &str = "expected an object with a \"pairs\" array";
+
+/// Pair `index` of the input, from its JSON object's numbers as `number` looks them up.
+fn pair(index: usize, number: impl Fn(&str) -> Option<f64>) -> Result<Pair, String> {
+    let coordinate =
+        |name: &str| number(name).ok_or(format!("pair {index} has no number {name:?}"));
+    Ok(Pair {
+        x0: coordinate("x0")?,
+        y0: coordinate("y0")?,
+        x1: coordinate("x1")?,
+        y1: coordinate("y1")?,
+    })
+}
+
+/// Reference values read alongside the pairs, and how the computed ones compare.
+struct Validation {
+    path: PathBuf,
+    answers: Answers,
+    /// How many of the distances have been compared.
+    compared: usize,
+    mismatches: usize,
+    largest_difference: f64,
+}
+
+impl Validation {
+    /// Opens an answer file, checking its header, size, and checksum so a truncated or
+    /// damaged file is refused before any pair is compared with it.
+    fn open(path: &Path) -> Result<Validation, Failed> {
+        let failed = |failure: Failure, message: String| {
+            failure.failed(format!("{}: {message}", path.display()))
+        };
+        let mut file = File::open(path).map_err(|error| failed(Failure::Io, error.to_string()))?;
+        let answers = read_answers(&mut file)
+            .map_err(|error| failed(Failure::from(&error), error.to_string()))?;
+
+        Ok(Validation {
+            path: path.to_path_buf(),
+            answers,
+            compared: 0,
+            mismatches: 0,
+            largest_difference: 0.0,
+        })
+    }
+
+    /// Checks the answers are for `count` pairs, before comparing any of them.
+    fn expect_count(&self, count: usize) -> Result<(), Failed> {
+        if self.answers.distances.len() != count {
+            return Err(Failure::Mismatch.failed(format!(
+                "{}: answers for {} pairs, but the input has {count}",
+                self.path.display(),
+                self.answers.distances.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The next pair's answer.
+    fn expect(&mut self) -> Result<f64, Failed> {
+        let distance = self.answers.distances.get(self.compared).ok_or_else(|| {
+            Failure::Mismatch.failed(format!("{}: too few answers", self.path.display()))
+        })?;
+        self.compared += 1;
+        Ok(*distance)
+    }
+
+    /// The average the answers end with, once every pair's answer has been read.
+    fn reference_average(&mut self) -> Result<f64, Failed> {
+        if self.compared < self.answers.distances.len() {
+            return Err(Failure::Mismatch
+                .failed(format!("{}: more answers than pairs", self.path.display())));
+        }
+        Ok(self.answers.average)
+    }
+
+    fn check(&mut self, value: f64, reference: f64) {
+        if value != reference {
+            self.mismatches += 1;
+            self.largest_difference = self.largest_difference.max((value - reference).abs());
+        }
+    }
+}
+
+/// One worker thread's share of the pairs.
+struct Worker {
+    pairs: usize,
+    sum: f64,
+    seconds: f64,
+}
+
+/// What processing the input found.
+struct Summary {
+    input_size: usize,
+    count: usize,
+    sum: f64,
+    /// Each thread's work, when the pairs were divided between threads.
+    workers: Vec<Worker>,
+    /// The largest difference between a SIMD distance and the scalar one, with --simd.
+    simd_difference: Option<f64>,
+    /// Every distance, kept only with --dump.
+    distances: Vec<f64>,
+}
+
+/// The distance of each pair, computed by `threads` threads each taking an even share of them
+/// in turn, with SIMD if `simd` and otherwise with `math`, and what each thread did.
+fn distances(pairs: &[Pair], threads: usize, simd: bool, math: Math) -> (Vec<f64>, Vec<Worker>) {
+    let mut distances = vec![0.0; pairs.len()];
+    let share = pairs.len().div_ceil(threads).max(1);
+    let workers = thread::scope(|scope| {
+        let workers: Vec<_> = pairs
+            .chunks(share)
+            .zip(distances.chunks_mut(share))
+            .map(|(pairs, distances)| {
+                scope.spawn(move || {
+                    let start = read_os_timer();
+                    if simd {
+                        haversine_simd(pairs, EARTH_RADIUS, distances);
+                    } else {
+                        for (&pair, distance) in pairs.iter().zip(&mut *distances) {
+                            *distance = math.haversine()(pair, EARTH_RADIUS);
+                        }
+                    }
+                    let sum = distances.iter().sum();
+                    Worker {
+                        pairs: pairs.len(),
+                        sum,
+                        seconds: os_ticks_to_seconds(read_os_timer() - start),
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    (distances, workers)
+}
+
+fn process(args: &Args, validation: &mut Option<Validation>) -> Result<Summary, Failed> {
+    time_function!();
+    let failed = |failure: Failure, message: String| {
+        failure.failed(format!("{}: {message}", args.input.display()))
+    };
+    let io = |error: io::Error| failed(Failure::Io, error.to_string());
+    let json = |error: JsonError| failed(Failure::from(&error), error.to_string());
+    let invalid = |message: String| failed(Failure::Invalid, message);
+    let mut summary = Summary {
+        input_size: 0,
+        count: 0,
+        sum: 0.0,
+        workers: Vec::new(),
+        simd_difference: None,
+        distances: Vec::new(),
+    };
+
+    if args.stream {
+        time_bandwidth!(
+            "stream",
+            fs::metadata(&args.input).map_or(0, |file| file.len())
+        );
+        let file = File::open(&args.input).map_err(io)?;
+        let mut elements = Elements::new(BufReader::new(file), "pairs")
+            .map_err(json)?
+            .ok_or_else(|| invalid(NO_PAIRS.to_string()))?;
+        for (index, element) in elements.by_ref().enumerate() {
+            let element = element.map_err(json)?;
+            let distance = args.math.haversine()(
+                pair(index, |name| element.get(name)?.as_f64()).map_err(invalid)?,
+                EARTH_RADIUS,
+            );
+            if let Some(validation) = validation {
+                let reference = validation.expect()?;
+                validation.check(distance, reference);
+            }
+            if args.dump.is_some() {
+                summary.distances.push(distance);
+            }
+            summary.sum += distance;
+            summary.count += 1;
+        }
+        if let Some(validation) = validation {
+            validation.expect_count(summary.count)?;
+        }
+        summary.input_size = elements.offset();
+        return Ok(summary);
+    }
+
+    let text = {
+        time_bandwidth!(
+            "read",
+            fs::metadata(&args.input).map_or(0, |file| file.len())
+        );
+        fs::read(&args.input).map_err(io)?
+    };
+    let pairs = {
+        time_bandwidth!("parse", text.len());
+        match args.allocator {
+            Allocator::Heap => {
+                let value = json::parse(&text).map_err(json)?;
+                let pairs = value
+                    .get("pairs")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid(NO_PAIRS.to_string()))?;
+                pairs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, element)| pair(index, |name| element.get(name)?.as_f64()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid)?
+            }
+            Allocator::Arena => {
+                let document = json::parse_document(&text).map_err(json)?;
+                let pairs = document
+                    .root()
+                    .get("pairs")
+                    .and_then(|pairs| pairs.elements())
+                    .ok_or_else(|| invalid(NO_PAIRS.to_string()))?;
+                pairs
+                    .enumerate()
+                    .map(|(index, element)| pair(index, |name| element.get(name)?.as_f64()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid)?
+            }
+        }
+    };
+
+    if let Some(validation) = validation {
+        validation.expect_count(pairs.len())?;
+    }
+    let (distances, workers) = {
+        time_bandwidth!("haversine", std::mem::size_of_val(pairs.as_slice()));
+        distances(&pairs, args.threads.get(), args.simd, args.math)
+    };
+    if args.simd {
+        let largest = pairs
+            .iter()
+            .zip(&distances)
+            .map(|(&pair, distance)| (distance - reference_haversine(pair, EARTH_RADIUS)).abs())
+            .fold(0.0, f64::max);
+        summary.simd_difference = Some(largest);
+    }
+    if let Some(validation) = validation {
+        time_bandwidth!("validate", 2 * std::mem::size_of_val(distances.as_slice()));
+        for &distance in &distances {
+            let reference = validation.expect()?;
+            validation.check(distance, reference);
+        }
+    }
+    summary.input_size = text.len();
+    summary.count = pairs.len();
+    summary.sum = workers.iter().map(|worker| worker.sum).sum();
+    if args.threads.get() > 1 {
+        summary.workers = workers;
+    }
+    if args.dump.is_some() {
+        summary.distances = distances;
+    }
+    Ok(summary)
+}
+
+/// Times parsing `text` and averaging its pairs with each parser in turn, printing a line for
+/// each.
+#[cfg(feature = "serde_bench")]
+fn compare_serde(text: &[u8]) -> Result<(), String> {
+    fn average(pairs: impl Iterator<Item = Result<Pair, String>>) -> Result<f64, String> {
+        let (mut sum, mut count) = (0.0, 0);
+        for pair in pairs {
+            sum += reference_haversine(pair?, EARTH_RADIUS);
+            count += 1;
+        }
+        Ok(if count == 0 { 0.0 } else { sum / count as f64 })
+    }
+
+    let heap = || {
+        let json = json::parse(text).map_err(|error| error.to_string())?;
+        let pairs = json
+            .get("pairs")
+            .and_then(Value::as_array)
+            .ok_or(NO_PAIRS)?;
+        average(
+            pairs
+                .iter()
+                .enumerate()
+                .map(|(index, element)| pair(index, |name| element.get(name)?.as_f64())),
+        )
+    };
+    let arena = || {
+        let json = json::parse_document(text).map_err(|error| error.to_string())?;
+        let pairs = json
+            .root()
+            .get("pairs")
+            .and_then(|pairs| pairs.elements())
+            .ok_or(NO_PAIRS)?;
+        average(
+            pairs
+                .enumerate()
+                .map(|(index, element)| pair(index, |name| element.get(name)?.as_f64())),
+        )
+    };
+    let serde = || {
+        let json: serde_json::Value =
+            serde_json::from_slice(text).map_err(|error| error.to_string())?;
+        let pairs = json["pairs"].as_array().ok_or(NO_PAIRS)?;
+        average(
+            pairs
+                .iter()
+                .enumerate()
+                .map(|(index, element)| pair(index, |name| element.get(name)?.as_f64())),
+        )
+    };
+    let time = |name: &str, parse: &dyn Fn() -> Result<f64, String>| -> Result<(), String> {
+        let start = read_os_timer();
+        let average = parse()?;
+        let seconds = os_ticks_to_seconds(read_os_timer() - start);
+        let throughput = text.len() as f64 / seconds / 1e6;
+        println!("{name:<12}{seconds:>10.3}{throughput:>10.1}  {average:.16}");
+        Ok(())
+    };
+
+    println!("{:<12}{:>10}{:>10}  average", "parser", "seconds", "MB/s");
+    time("heap", &heap)?;
+    time("arena", &arena)?;
+    time("serde_json", &serde)?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.trace.is_some() || args.folded.is_some() {
+        profiler::begin_tracing();
+    } else {
+        profiler::begin();
+    }
+    #[cfg(feature = "serde_bench")]
+    if args.compare_serde {
+        let input = args.input.display();
+        return match fs::read(&args.input).map(|text| compare_serde(&text)) {
+            Ok(Ok(())) => ExitCode::SUCCESS,
+            Ok(Err(message)) => Failure::Invalid
+                .failed(format!("{input}: {message}"))
+                .report(),
+            Err(error) => Failure::Io.failed(format!("{input}: {error}")).report(),
+        };
+    }
+    let mut validation = match args.validate.as_deref().map(Validation::open).transpose() {
+        Ok(validation) => validation,
+        Err(failed) => return failed.report(),
+    };
+    let summary = match process(&args, &mut validation) {
+        Ok(summary) => summary,
+        Err(failed) => return failed.report(),
+    };
+
+    let average = if summary.count == 0 {
+        0.0
+    } else {
+        summary.sum / summary.count as f64
+    };
+    if let Some(path) = &args.dump {
+        time_bandwidth!("dump", std::mem::size_of_val(summary.distances.as_slice()));
+        let written = write_atomically(path, |out| {
+            let mut dump = AnswersWriter::new(out)?;
+            for &distance in &summary.distances {
+                dump.distance(distance)?;
+            }
+            dump.finish(average).map(drop)
+        });
+        if let Err(error) = written {
+            eprintln!("{}: {error}", path.display());
+            return Failure::Io.into();
+        }
+    }
+    // The course's harness prints exactly these lines, so the two can be compared line by line;
+    // anything more comes after them. Its "sum" is the average.
+    println!("Input size: {}", summary.input_size);
+    println!("Pair count: {}", summary.count);
+    println!("Haversine sum: {average:.16}");
+    let mut valid = true;
+    if let Some(validation) = &mut validation {
+        let reference = match validation.reference_average() {
+            Ok(reference) => reference,
+            Err(failed) => return failed.report(),
+        };
+        println!();
+        println!("Validation:");
+        println!("Reference sum: {reference:.16}");
+        println!("Difference: {:.16}", average - reference);
+        valid = validation.mismatches == 0 && average == reference;
+    }
+
+    if let Some(validation) = &validation {
+        println!();
+        println!(
+            "Differing pairs: {} of {} (largest difference {:.16})",
+            validation.mismatches, summary.count, validation.largest_difference
+        );
+    }
+    if !summary.workers.is_empty() {
+        println!();
+        println!("Threads:");
+        for (index, worker) in summary.workers.iter().enumerate() {
+            println!(
+                "{index:>4}: {} pairs in {:.6}s ({:.0} pairs/s)",
+                worker.pairs,
+                worker.seconds,
+                worker.pairs as f64 / worker.seconds
+            );
+        }
+    }
+    if let Some(difference) = summary.simd_difference {
+        println!();
+        println!(
+            "SIMD check: largest difference from scalar {difference:e} (tolerance {TOLERANCE:e})"
+        );
+        valid &= difference <= TOLERANCE;
+    }
+    if args.profile || args.trace.is_some() || args.folded.is_some() {
+        let report = profiler::end();
+        if args.profile {
+            println!();
+            print!("{report}");
+        }
+        let exports: [(_, fn(&_, &mut _) -> _); 2] = [
+            (&args.trace, profiler::Report::write_chrome_trace),
+            (&args.folded, profiler::Report::write_folded_stacks),
+        ];
+        for (path, write) in exports {
+            let Some(path) = path else { continue };
+            if let Err(error) = write_atomically(path, |out| write(&report, out)) {
+                eprintln!("{}: {error}", path.display());
+                return Failure::Io.into();
+            }
+        }
+    }
+
+    if valid {
+        ExitCode::SUCCESS
+    } else {
+        Failure::Mismatch.into()
+    }
+}
@@ -0,0 +1,118 @@
+use clap::Parser;
+use computer_enhance::math;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/// Compare the math module's functions with std's over the inputs haversine gives them, and
+/// print each one's largest and average error.
+#[derive(Parser)]
+#[command(name = "math_accuracy")]
+struct Args {
+    /// Inputs to try across each function's domain, evenly spaced from one end to the other.
+    #[arg(long, value_name = "N", default_value_t = 1_000_000)]
+    samples: u64,
+}
+
+/// A function to check, and the range of inputs to check it over.
+struct Sweep {
+    name: &'static str,
+    custom: fn(f64) -> f64,
+    reference: fn(f64) -> f64,
+    min: f64,
+    max: f64,
+}
+
+const SWEEPS: [Sweep; 4] = [
+    Sweep {
+        name: "sin",
+        custom: math::sin,
+        reference: f64::sin,
+        min: -PI,
+        max: PI,
+    },
+    Sweep {
+        name: "cos",
+        custom: math::cos,
+        reference: f64::cos,
+        min: -FRAC_PI_2,
+        max: FRAC_PI_2,
+    },
+    Sweep {
+        name: "asin",
+        custom: math::asin,
+        reference: f64::asin,
+        min: 0.0,
+        max: 1.0,
+    },
+    Sweep {
+        name: "sqrt",
+        custom: math::sqrt,
+        reference: f64::sqrt,
+        min: 0.0,
+        max: 1.0,
+    },
+];
+
+/// How far a function strayed from its reference over a sweep.
+#[derive(Default)]
+struct Error {
+    largest: f64,
+    /// The input the largest error was at.
+    at: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Error {
+    fn add(&mut self, input: f64, error: f64) {
+        if error > self.largest {
+            self.largest = error;
+            self.at = input;
+        }
+        self.sum += error;
+        self.count += 1;
+    }
+
+    fn average(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+}
+
+fn measure(sweep: &Sweep, samples: u64) -> Error {
+    let mut error = Error::default();
+    let step = match samples {
+        0 | 1 => 0.0,
+        _ => (sweep.max - sweep.min) / (samples - 1) as f64,
+    };
+    for index in 0..samples {
+        // The last input lands exactly on the end of the domain, where errors tend to be.
+        let input = if index + 1 == samples {
+            sweep.max
+        } else {
+            sweep.min + step * index as f64
+        };
+        let difference = (sweep.custom)(input) - (sweep.reference)(input);
+        error.add(input, difference.abs());
+    }
+    error
+}
+
+fn main() {
+    let args = Args::parse();
+    println!("Largest error (average error) at input [function domain]:");
+    for sweep in &SWEEPS {
+        let error = measure(sweep, args.samples);
+        println!(
+            "{:+.24} ({:+.24}) at {:+.24} [{} {:.6}..{:.6}]",
+            error.largest,
+            error.average(),
+            error.at,
+            sweep.name,
+            sweep.min,
+            sweep.max
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{buffer, parse_size};
+use computer_enhance::{failure::Failure, metrics::ticks_to_seconds, repetition_tester::BenchArgs};
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes between the lines read. A multiple of the bytes a cache's sets span puts every
+    /// line in the same set: 4K for a typical L1 of 64 sets of 64-byte lines.
+    #[arg(long, value_parser = parse_size, default_value = "4K")]
+    stride: usize,
+
+    /// Read from 1 up to this many lines, all in the same set.
+    #[arg(long, default_value_t = 32)]
+    max_ways: usize,
+
+    /// Bytes to read each trial, going over the lines again and again.
+    #[arg(long, value_parser = parse_size, default_value = "64M")]
+    total: usize,
+}
+
+const LINE: usize = 64;
+
+/// Reads the `ways` 64-byte lines `stride` bytes apart from `data`, `passes` times over.
+fn read_lines(data: &[u8], ways: usize, stride: usize, passes: usize) {
+    assert!(ways >= 1 && passes >= 1 && (ways - 1) * stride + LINE <= data.len());
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: the assertion keeps every line inside `data`, and SSE2 is part of x86-64.
+    unsafe {
+        std::arch::asm!(
+            "2:",
+            "mov {line}, {data}",
+            "mov {left}, {ways}",
+            "3:",
+            "movdqu xmm0, [{line}]",
+            "movdqu xmm1, [{line} + 16]",
+            "movdqu xmm2, [{line} + 32]",
+            "movdqu xmm3, [{line} + 48]",
+            "add {line}, {stride}",
+            "dec {left}",
+            "jnz 3b",
+            "dec {passes}",
+            "jnz 2b",
+            data = in(reg) data.as_ptr(),
+            ways = in(reg) ways,
+            stride = in(reg) stride,
+            passes = inout(reg) passes => _,
+            line = out(reg) _,
+            left = out(reg) _,
+            out("xmm0") _,
+            out("xmm1") _,
+            out("xmm2") _,
+            out("xmm3") _,
+            options(nostack, readonly),
+        );
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for _ in 0..passes {
+        for way in 0..ways {
+            let line = &data[way * stride..way * stride + LINE];
+            for word in line.chunks_exact(8) {
+                // SAFETY: `word` is 8 readable bytes; volatile so the reads aren't dropped.
+                std::hint::black_box(unsafe { std::ptr::read_volatile(word.as_ptr()) });
+            }
+        }
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let stride = args.stride.max(LINE);
+    let max_ways = args.max_ways.max(1);
+    let data = match buffer((max_ways - 1) * stride + LINE) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {max_ways} lines {stride} bytes apart: {error}");
+            return Failure::Io.into();
+        }
+    };
+
+    let mut bench = args.bench_args.bench();
+    let mut bandwidths = Vec::new();
+    for ways in 1..=max_ways {
+        let passes = (args.total / (ways * LINE)).max(1);
+        let bytes = (passes * ways * LINE) as u64;
+        let name = format!("{ways} ways");
+        if let Some(results) = bench.run(&name, bytes, || read_lines(&data, ways, stride, passes)) {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            bandwidths.push((ways, bytes as f64 / seconds / (1u64 << 30) as f64));
+        }
+    }
+
+    // A bar for each, to make the cliff easy to spot, and the biggest step down named.
+    println!();
+    let best = bandwidths
+        .iter()
+        .map(|&(_, bandwidth)| bandwidth)
+        .fold(0.0, f64::max);
+    for &(ways, bandwidth) in &bandwidths {
+        let bar = "#".repeat((40.0 * bandwidth / best.max(f64::MIN_POSITIVE)) as usize);
+        println!("{ways:>4} ways {bandwidth:>9.2}GB/s {bar}");
+    }
+    let cliff = bandwidths
+        .windows(2)
+        .map(|pair| (pair[1].0, pair[0].1 / pair[1].1))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((ways, ratio)) = cliff {
+        println!(
+            "Steepest drop: {ratio:.2}x slower going from {} to {ways} ways, so the cache \
+             holding {stride}-byte strides may be {}-way",
+            ways - 1,
+            ways - 1
+        );
+    }
+    bench.finish()
+}
@@ -0,0 +1,74 @@
+use crate::{parse_size, shuffle};
+use computer_enhance::{metrics::ticks_to_seconds, repetition_tester::BenchArgs};
+use std::{hint::black_box, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes of memory to chase pointers through; repeat for several. Takes K, M, and G
+    /// suffixes.
+    #[arg(long, value_parser = parse_size, default_values = ["16K", "256K", "4M", "64M", "256M"])]
+    size: Vec<usize>,
+
+    /// Loads to make each trial, each waiting for the last.
+    #[arg(long, default_value_t = 10_000_000)]
+    loads: usize,
+}
+
+/// One pointer per 64-byte line, so every load is to a different line.
+const WORDS_PER_LINE: usize = 64 / std::mem::size_of::<usize>();
+
+/// A chain through every line of `size` bytes in a random order, as the index of the next
+/// line's word in each line's first word. Random, so the prefetchers can't guess the next.
+fn chain(size: usize, seed: u64) -> Vec<usize> {
+    let lines = (size / 64).max(1);
+    let mut order: Vec<usize> = (0..lines).collect();
+    shuffle(&mut order, seed);
+    let mut words = vec![0; lines * WORDS_PER_LINE];
+    for (index, &line) in order.iter().enumerate() {
+        let next = order[(index + 1) % lines];
+        words[line * WORDS_PER_LINE] = next * WORDS_PER_LINE;
+    }
+    words
+}
+
+/// Follows `loads` links of `chain` from its start, so each load's address is the last's
+/// result.
+fn chase(chain: &[usize], loads: usize) -> usize {
+    let mut index = 0;
+    for _ in 0..loads {
+        index = chain[index];
+    }
+    index
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let loads = args.loads.max(1);
+    let mut bench = args.bench_args.bench();
+    let mut latencies = Vec::new();
+    for &size in &args.size {
+        let chain = chain(size, size as u64);
+        let name = format!("{size} bytes");
+        let bytes = (loads * std::mem::size_of::<usize>()) as u64;
+        if let Some(results) = bench.run(&name, bytes, || {
+            black_box(chase(black_box(&chain), loads));
+        }) {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            latencies.push((
+                size,
+                1e9 * seconds / loads as f64,
+                results.min.ticks as f64 / loads as f64,
+            ));
+        }
+    }
+
+    println!();
+    println!("{:>12}  {:>10}  {:>12}", "size", "ns/load", "ticks/load");
+    for (size, nanoseconds, ticks) in latencies {
+        println!("{size:>12}  {nanoseconds:>10.2}  {ticks:>12.2}");
+    }
+    println!("(Ticks are the CPU timer's, which counts at the base clock, not the current one.)");
+    bench.finish()
+}
@@ -0,0 +1,104 @@
+mod associativity;
+mod latency;
+mod prefetch;
+mod read;
+mod streaming;
+mod sweep;
+mod tlb;
+mod unaligned;
+mod write;
+
+use clap::{Parser, Subcommand};
+use computer_enhance::{environment::Environment, failure::EXIT_STATUSES, os_memory::Pages};
+use std::{io, process::ExitCode};
+
+/// Measure the memory system with loops written to do nothing but move bytes, each repeated
+/// until it stops getting faster.
+#[derive(Parser)]
+#[command(name = "memory_bench", after_help = EXIT_STATUSES)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Follow a chain of pointers in random order through different amounts of memory, timing
+    /// how long each load takes when it can't start until the last finishes.
+    Latency(latency::Args),
+    /// Read lines out of cache with and without prefetching them different distances ahead,
+    /// comparing the bandwidth of each.
+    Prefetch(prefetch::Args),
+    /// Read a buffer with loops of different load widths and compare their bandwidth.
+    Read(read::Args),
+    /// Read more and more lines that all fall in the same cache set, showing the bandwidth
+    /// drop once there are more than the cache has ways.
+    Associativity(associativity::Args),
+    /// Fill buffers of different sizes with ordinary and non-temporal stores, showing when
+    /// writing around the cache wins.
+    Streaming(streaming::Args),
+    /// Read regions from a few KB to hundreds of MB, writing each size's bandwidth to a CSV
+    /// file, where the drops show the sizes of the caches.
+    Sweep(sweep::Args),
+    /// Load one line from each of more and more pages, showing the slowdown once there are
+    /// more pages than the TLB holds entries for.
+    Tlb(tlb::Args),
+    /// Read and write starting at different offsets from a page boundary, and load across
+    /// pages, comparing each with aligned accesses.
+    Unaligned(unaligned::Args),
+    /// Write a buffer with loops of different store widths and compare their bandwidth.
+    Write(write::Args),
+}
+
+/// Parses a byte count, with an optional K, M, or G suffix for powers of 1024.
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let (digits, scale) = match text.as_bytes().last().map(u8::to_ascii_uppercase) {
+        Some(b'K') => (&text[..text.len() - 1], 1 << 10),
+        Some(b'M') => (&text[..text.len() - 1], 1 << 20),
+        Some(b'G') => (&text[..text.len() - 1], 1 << 30),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|count| count.checked_mul(scale))
+        .ok_or_else(|| format!("invalid size {text:?}"))
+}
+
+/// `size` bytes from the OS, already touched so that no test pays for faulting them in.
+pub fn buffer(size: usize) -> io::Result<Pages> {
+    let mut pages = Pages::new(size)?;
+    pages.fill(1);
+    Ok(pages)
+}
+
+/// Puts `items` in a random order, the same one for the same seed.
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    // xorshift64* for a Fisher-Yates shuffle; any decent mixing will do.
+    let mut state = seed | 1;
+    let mut random = || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    };
+    for index in (1..items.len()).rev() {
+        items.swap(index, (random() % (index as u64 + 1)) as usize);
+    }
+}
+
+fn main() -> ExitCode {
+    let command = Cli::parse().command;
+    println!("{}", Environment::detect());
+    match command {
+        Command::Associativity(args) => associativity::run(args),
+        Command::Latency(args) => latency::run(args),
+        Command::Prefetch(args) => prefetch::run(args),
+        Command::Read(args) => read::run(args),
+        Command::Streaming(args) => streaming::run(args),
+        Command::Sweep(args) => sweep::run(args),
+        Command::Tlb(args) => tlb::run(args),
+        Command::Unaligned(args) => unaligned::run(args),
+        Command::Write(args) => write::run(args),
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{buffer, parse_size, shuffle};
+use computer_enhance::{failure::Failure, metrics::ticks_to_seconds, repetition_tester::BenchArgs};
+use std::{hint::black_box, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes to read, ideally well past the last cache. Takes K, M, and G suffixes.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    size: usize,
+
+    /// Lines ahead to prefetch; repeat for several. 0 doesn't prefetch at all.
+    #[arg(long, default_values_t = [0, 1, 2, 4, 8, 16, 32, 64])]
+    distance: Vec<usize>,
+
+    /// The order to visit the lines in: front to back, which the hardware prefetchers see
+    /// coming anyway, or shuffled, which they can't.
+    #[arg(long, value_enum, default_value_t)]
+    order: Order,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Order {
+    Sequential,
+    #[default]
+    Shuffled,
+}
+
+const LINE: usize = 64;
+
+/// Reads each line of `data` in `order`, prefetching the line `distance` further on in the
+/// order as it goes, if that's not 0.
+fn read(data: &[u8], order: &[usize], distance: usize) -> u64 {
+    let mut sum = 0u64;
+    for (index, &line) in order.iter().enumerate() {
+        if distance != 0 {
+            if let Some(&ahead) = order.get(index + distance) {
+                prefetch(&data[ahead * LINE]);
+            }
+        }
+        for word in data[line * LINE..(line + 1) * LINE].chunks_exact(8) {
+            sum = sum.wrapping_add(u64::from_ne_bytes(word.try_into().unwrap()));
+        }
+    }
+    sum
+}
+
+/// Asks for the line holding `byte` to be brought into every level of cache.
+#[inline(always)]
+fn prefetch(byte: &u8) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: SSE is part of x86-64, and prefetching never faults.
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>((byte as *const u8).cast());
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = byte;
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let lines = (args.size / LINE).max(1);
+    let data = match buffer(lines * LINE) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {} bytes: {error}", lines * LINE);
+            return Failure::Io.into();
+        }
+    };
+    let mut order: Vec<usize> = (0..lines).collect();
+    if args.order == Order::Shuffled {
+        shuffle(&mut order, lines as u64);
+    }
+    if cfg!(not(target_arch = "x86_64")) {
+        println!("Prefetching is only done on x86-64; every distance will read as 0 does.");
+    }
+
+    let bytes = (lines * LINE) as u64;
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for &distance in &args.distance {
+        let name = match distance {
+            0 => "no prefetch".to_string(),
+            distance => format!("prefetch {distance} lines ahead"),
+        };
+        if let Some(results) = bench.run(&name, bytes, || {
+            black_box(read(&data, &order, distance));
+        }) {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            rows.push((name, bytes as f64 / seconds / (1u64 << 30) as f64));
+        }
+    }
+
+    // Compared with not prefetching, if that was one of the runs.
+    println!();
+    let baseline = args
+        .distance
+        .iter()
+        .position(|&distance| distance == 0)
+        .and_then(|index| rows.get(index))
+        .map(|row| row.1);
+    for (name, bandwidth) in &rows {
+        print!("{name:<28} {bandwidth:>8.2}GB/s");
+        if let Some(baseline) = baseline {
+            print!("  {:.2}x", bandwidth / baseline);
+        }
+        println!();
+    }
+    bench.finish()
+}
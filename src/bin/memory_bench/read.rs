@@ -0,0 +1,214 @@
+use crate::{buffer, parse_size};
+use computer_enhance::{failure::Failure, repetition_tester::BenchArgs};
+use std::{hint::black_box, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes to read each trial, rounded down to a multiple of 128. Takes K, M, and G
+    /// suffixes.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    size: usize,
+}
+
+/// Reads all of a buffer whose length is a non-zero multiple of 128.
+pub type Kernel = fn(&[u8]);
+
+/// The loops to compare, fastest-looking last. The assembly ones only load, so they show the
+/// most the core can pull in at each width; the Rust one also adds up what it reads. Narrow
+/// loads fall furthest behind when `--size` fits in cache, where bandwidth isn't the limit.
+fn kernels() -> Vec<(&'static str, Kernel)> {
+    let mut kernels: Vec<(&'static str, Kernel)> = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        kernels.push(("asm 1-byte x4", x86::read_1x4));
+        kernels.push(("asm 2-byte x4", x86::read_2x4));
+        kernels.push(("asm 4-byte x4", x86::read_4x4));
+    }
+    kernels.push(("rust u64 sum", read_sum));
+    #[cfg(target_arch = "x86_64")]
+    {
+        kernels.push(("asm 8-byte x4", x86::read_8x4));
+        kernels.push(("asm 16-byte x4", x86::read_16x4));
+        if is_x86_feature_detected!("avx") {
+            kernels.push(("asm 32-byte x4", x86::read_32x4));
+        }
+    }
+    kernels
+}
+
+/// The loop that looks fastest on this CPU, for benchmarks that just need to read quickly.
+pub fn fastest_kernel() -> (&'static str, Kernel) {
+    kernels().pop().unwrap_or(("rust u64 sum", read_sum))
+}
+
+fn read_sum(data: &[u8]) {
+    let sum = data.chunks_exact(8).fold(0u64, |sum, word| {
+        sum.wrapping_add(u64::from_ne_bytes(word.try_into().unwrap()))
+    });
+    black_box(sum);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::asm;
+
+    pub fn read_1x4(data: &[u8]) {
+        // SAFETY: reads 4 bytes a time up to `data.len()`, a multiple of 4.
+        unsafe {
+            asm!(
+                "2:",
+                "movzx {value:e}, byte ptr [{data} + {index}]",
+                "movzx {value:e}, byte ptr [{data} + {index} + 1]",
+                "movzx {value:e}, byte ptr [{data} + {index} + 2]",
+                "movzx {value:e}, byte ptr [{data} + {index} + 3]",
+                "add {index}, 4",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                value = out(reg) _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    pub fn read_2x4(data: &[u8]) {
+        // SAFETY: reads 8 bytes a time up to `data.len()`, a multiple of 8.
+        unsafe {
+            asm!(
+                "2:",
+                "movzx {value:e}, word ptr [{data} + {index}]",
+                "movzx {value:e}, word ptr [{data} + {index} + 2]",
+                "movzx {value:e}, word ptr [{data} + {index} + 4]",
+                "movzx {value:e}, word ptr [{data} + {index} + 6]",
+                "add {index}, 8",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                value = out(reg) _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    pub fn read_4x4(data: &[u8]) {
+        // SAFETY: reads 16 bytes a time up to `data.len()`, a multiple of 16.
+        unsafe {
+            asm!(
+                "2:",
+                "mov {value:e}, [{data} + {index}]",
+                "mov {value:e}, [{data} + {index} + 4]",
+                "mov {value:e}, [{data} + {index} + 8]",
+                "mov {value:e}, [{data} + {index} + 12]",
+                "add {index}, 16",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                value = out(reg) _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    pub fn read_8x4(data: &[u8]) {
+        // SAFETY: reads 32 bytes a time up to `data.len()`, a multiple of 32.
+        unsafe {
+            asm!(
+                "2:",
+                "mov {value}, [{data} + {index}]",
+                "mov {value}, [{data} + {index} + 8]",
+                "mov {value}, [{data} + {index} + 16]",
+                "mov {value}, [{data} + {index} + 24]",
+                "add {index}, 32",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                value = out(reg) _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    pub fn read_16x4(data: &[u8]) {
+        // SAFETY: reads 64 bytes a time up to `data.len()`, a multiple of 64. SSE2 is part of
+        // x86-64.
+        unsafe {
+            asm!(
+                "2:",
+                "movdqu xmm0, [{data} + {index}]",
+                "movdqu xmm1, [{data} + {index} + 16]",
+                "movdqu xmm2, [{data} + {index} + 32]",
+                "movdqu xmm3, [{data} + {index} + 48]",
+                "add {index}, 64",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                out("xmm0") _,
+                out("xmm1") _,
+                out("xmm2") _,
+                out("xmm3") _,
+                options(nostack, readonly),
+            );
+        }
+    }
+
+    /// Only listed when the CPU has AVX.
+    pub fn read_32x4(data: &[u8]) {
+        // SAFETY: the caller checked for AVX.
+        unsafe { read_32x4_avx(data) }
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn read_32x4_avx(data: &[u8]) {
+        // SAFETY: reads 128 bytes a time up to `data.len()`, a multiple of 128.
+        unsafe {
+            asm!(
+                "2:",
+                "vmovdqu ymm0, [{data} + {index}]",
+                "vmovdqu ymm1, [{data} + {index} + 32]",
+                "vmovdqu ymm2, [{data} + {index} + 64]",
+                "vmovdqu ymm3, [{data} + {index} + 96]",
+                "add {index}, 128",
+                "cmp {index}, {len}",
+                "jb 2b",
+                "vzeroupper",
+                data = in(reg) data.as_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                out("ymm0") _,
+                out("ymm1") _,
+                out("ymm2") _,
+                out("ymm3") _,
+                options(nostack, readonly),
+            );
+        }
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let size = (args.size / 128 * 128).max(128);
+    let data = match buffer(size) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {size} bytes: {error}");
+            return Failure::Io.into();
+        }
+    };
+    let mut bench = args.bench_args.bench();
+    for (name, kernel) in kernels() {
+        bench.run(name, size as u64, || kernel(&data));
+    }
+    bench.finish()
+}
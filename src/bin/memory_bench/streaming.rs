@@ -0,0 +1,117 @@
+use crate::{buffer, parse_size};
+use computer_enhance::{failure::Failure, metrics::ticks_to_seconds, repetition_tester::BenchArgs};
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes to fill; repeat for several. Takes K, M, and G suffixes.
+    #[arg(long, value_parser = parse_size, default_values = ["64K", "1M", "16M", "256M"])]
+    size: Vec<usize>,
+
+    /// Bytes to write each trial, filling smaller buffers again and again.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    total: usize,
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::{_mm_setzero_si128, _mm_sfence, _mm_store_si128, _mm_stream_si128};
+
+    /// Fills `data`, 16-byte aligned and a multiple of 64 bytes long, with ordinary stores,
+    /// which bring each line into the cache before writing over it.
+    pub fn store(data: &mut [u8]) {
+        assert!(data.as_ptr().align_offset(16) == 0 && data.len().is_multiple_of(64));
+        for line in data.chunks_exact_mut(64) {
+            let line = line.as_mut_ptr().cast();
+            // SAFETY: SSE2 is part of x86-64, and the line is 64 writable bytes, 16-aligned.
+            unsafe {
+                let zero = _mm_setzero_si128();
+                _mm_store_si128(line, zero);
+                _mm_store_si128(line.add(1), zero);
+                _mm_store_si128(line.add(2), zero);
+                _mm_store_si128(line.add(3), zero);
+            }
+        }
+    }
+
+    /// Fills `data` like `store`, but with MOVNTDQ, which writes around the cache, combining
+    /// each line's stores to send to memory whole without reading it first.
+    pub fn stream(data: &mut [u8]) {
+        assert!(data.as_ptr().align_offset(16) == 0 && data.len().is_multiple_of(64));
+        for line in data.chunks_exact_mut(64) {
+            let line = line.as_mut_ptr().cast();
+            // SAFETY: as for `store`.
+            unsafe {
+                let zero = _mm_setzero_si128();
+                _mm_stream_si128(line, zero);
+                _mm_stream_si128(line.add(1), zero);
+                _mm_stream_si128(line.add(2), zero);
+                _mm_stream_si128(line.add(3), zero);
+            }
+        }
+        // SAFETY: SSE is part of x86-64. The fence orders the streamed stores before anything
+        // after, as they're weakly ordered.
+        unsafe { _mm_sfence() };
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn run(args: Args) -> ExitCode {
+    let Some(&largest) = args.size.iter().max() else {
+        return ExitCode::SUCCESS;
+    };
+    let mut data = match buffer(largest / 64 * 64 + 64) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {largest} bytes: {error}");
+            return Failure::Io.into();
+        }
+    };
+
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for &size in &args.size {
+        let size = (size / 64 * 64).max(64);
+        let passes = (args.total / size).max(1);
+        let bytes = (passes * size) as u64;
+        let mut bandwidth = |name: &str, fill: fn(&mut [u8])| {
+            let results = bench.run(&format!("{name} {size} bytes"), bytes, || {
+                for _ in 0..passes {
+                    fill(&mut data[..size]);
+                }
+            })?;
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            Some(bytes as f64 / seconds / (1u64 << 30) as f64)
+        };
+        let stored = bandwidth("stores", x86::store);
+        let streamed = bandwidth("streaming stores", x86::stream);
+        rows.push((size, stored, streamed));
+    }
+
+    println!();
+    println!(
+        "{:>12}  {:>12}  {:>14}  faster",
+        "size", "stores GB/s", "streamed GB/s"
+    );
+    for (size, stored, streamed) in rows {
+        let (Some(stored), Some(streamed)) = (stored, streamed) else {
+            continue;
+        };
+        let faster = if streamed > stored {
+            format!("streamed, {:.2}x", streamed / stored)
+        } else {
+            format!("stores, {:.2}x", stored / streamed)
+        };
+        println!("{size:>12}  {stored:>12.2}  {streamed:>14.2}  {faster}");
+    }
+    bench.finish()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn run(_args: Args) -> ExitCode {
+    eprintln!("streaming stores are only measured on x86-64");
+    Failure::Usage.into()
+}
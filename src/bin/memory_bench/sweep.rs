@@ -0,0 +1,91 @@
+use crate::{buffer, parse_size, read};
+use computer_enhance::{
+    atomic_file::write_atomically, failure::Failure, metrics::ticks_to_seconds,
+    repetition_tester::BenchArgs,
+};
+use std::{io::Write, path::PathBuf, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// CSV file to write each size's best bandwidth to.
+    output: PathBuf,
+
+    /// The smallest region to read. Takes K, M, and G suffixes.
+    #[arg(long, value_parser = parse_size, default_value = "4K")]
+    min: usize,
+
+    /// The largest region to read.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    max: usize,
+
+    /// Sizes to try from each power of two up to the next, spaced evenly by ratio: 1 for
+    /// powers of two only, 2 to add the sizes halfway between, and so on.
+    #[arg(long, default_value_t = 2)]
+    steps: u32,
+
+    /// Bytes to read each trial, going over smaller regions again and again, so every size
+    /// takes about as long.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    total: usize,
+}
+
+/// The sizes from `min` to `max`, `steps` to each doubling, in multiples of 128.
+fn sizes(min: usize, max: usize, steps: u32) -> Vec<usize> {
+    let mut sizes: Vec<usize> = Vec::new();
+    let mut power = min.max(128).next_power_of_two() / 2;
+    while power <= max {
+        for step in 0..steps.max(1) {
+            let size = power as f64 * 2f64.powf(step as f64 / steps.max(1) as f64);
+            let size = size as usize / 128 * 128;
+            if (min..=max).contains(&size) && sizes.last() != Some(&size) {
+                sizes.push(size);
+            }
+        }
+        power *= 2;
+    }
+    sizes
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let sizes = sizes(args.min, args.max, args.steps);
+    let Some(&largest) = sizes.last() else {
+        eprintln!("no sizes between {} and {} bytes", args.min, args.max);
+        return Failure::Usage.into();
+    };
+    let data = match buffer(largest) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {largest} bytes: {error}");
+            return Failure::Io.into();
+        }
+    };
+    let (kernel_name, kernel) = read::fastest_kernel();
+    println!("Reading with {kernel_name}");
+
+    let mut bench = args.bench_args.bench();
+    let mut csv = String::from("size,seconds,gb_per_second\n");
+    for size in sizes {
+        let region = &data[..size];
+        let passes = (args.total / size).max(1);
+        let bytes = (passes * size) as u64;
+        let Some(results) = bench.run(&format!("{size} bytes"), bytes, || {
+            for _ in 0..passes {
+                kernel(region);
+            }
+        }) else {
+            continue;
+        };
+        let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+        let bandwidth = bytes as f64 / seconds / (1u64 << 30) as f64;
+        csv += &format!("{size},{seconds:e},{bandwidth}\n");
+    }
+
+    if let Err(error) = write_atomically(&args.output, |out| out.write_all(csv.as_bytes())) {
+        eprintln!("{}: {error}", args.output.display());
+        return Failure::Io.into();
+    }
+    bench.finish()
+}
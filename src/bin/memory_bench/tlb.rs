@@ -0,0 +1,89 @@
+use computer_enhance::{
+    failure::Failure, metrics::ticks_to_seconds, os_memory::Pages, repetition_tester::BenchArgs,
+};
+use std::{hint::black_box, process::ExitCode};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Touch from 1 page up to this many, doubling each time.
+    #[arg(long, default_value_t = 16384)]
+    max_pages: usize,
+
+    /// Loads to make each trial, going over the pages again and again.
+    #[arg(long, default_value_t = 10_000_000)]
+    loads: usize,
+
+    /// Also run every test in memory backed by large pages, so the same 4K-spaced addresses
+    /// need far fewer TLB entries. See `Pages::new_large` for what that takes.
+    #[arg(long)]
+    large_pages: bool,
+}
+
+const PAGE: usize = 4096;
+const LINE: usize = 64;
+
+/// Loads 8 bytes from each of the first `pages` 4K pages of `data`, `passes` times over. Each
+/// page's line is a different one of its 64, so the lines spread over the cache's sets instead
+/// of all landing in one.
+fn touch(data: &[u8], pages: usize, passes: usize) -> u64 {
+    let mut sum = 0u64;
+    for _ in 0..passes {
+        for page in 0..pages {
+            let at = page * PAGE + page % (PAGE / LINE) * LINE;
+            sum = sum.wrapping_add(u64::from_ne_bytes(data[at..at + 8].try_into().unwrap()));
+        }
+    }
+    sum
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let max_pages = args.max_pages.max(1);
+    let mut kinds = vec![("4K pages", Pages::new(max_pages * PAGE))];
+    if args.large_pages {
+        kinds.push(("large pages", Pages::new_large(max_pages * PAGE)));
+    }
+    let mut buffers = Vec::new();
+    for (kind, pages) in kinds {
+        match pages {
+            Ok(mut data) => {
+                data.fill(1);
+                buffers.push((kind, data));
+            }
+            Err(error) => {
+                eprintln!(
+                    "can't allocate {} bytes in {kind}: {error}",
+                    max_pages * PAGE
+                );
+                return Failure::Io.into();
+            }
+        }
+    }
+
+    let mut bench = args.bench_args.bench();
+    let mut rows = Vec::new();
+    for (kind, data) in &buffers {
+        let mut pages = 1;
+        while pages <= max_pages {
+            let passes = (args.loads / pages).max(1);
+            let loads = passes * pages;
+            let name = format!("{pages} pages ({kind})");
+            if let Some(results) = bench.run(&name, (loads * 8) as u64, || {
+                black_box(touch(data, pages, passes));
+            }) {
+                let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+                rows.push((pages, kind, 1e9 * seconds / loads as f64));
+            }
+            pages *= 2;
+        }
+    }
+
+    println!();
+    println!("{:>8}  {:>12}  {:>10}", "pages", "kind", "ns/load");
+    for (pages, kind, nanoseconds) in rows {
+        println!("{pages:>8}  {kind:>12}  {nanoseconds:>10.3}");
+    }
+    bench.finish()
+}
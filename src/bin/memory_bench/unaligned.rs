@@ -0,0 +1,123 @@
+use crate::{buffer, parse_size, read, write};
+use computer_enhance::{
+    failure::Failure,
+    metrics::ticks_to_seconds,
+    repetition_tester::{Bench, BenchArgs},
+};
+use std::{hint::black_box, process::ExitCode, ptr};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes to read and write at each offset, rounded down to a multiple of 128: small
+    /// enough to stay in L1 by default, so the penalty isn't hidden behind waiting on memory.
+    #[arg(long, value_parser = parse_size, default_value = "16K")]
+    size: usize,
+
+    /// Bytes past a page boundary to start at; repeat for several. 0 is aligned.
+    #[arg(long, default_values_t = [0, 1, 8, 16, 32, 48, 63])]
+    offset: Vec<usize>,
+
+    /// Bytes to process each trial, going over the region again and again.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    total: usize,
+
+    /// Pages to load 16 bytes from each pass of the page test, at the start of each page or
+    /// straddling its end.
+    #[arg(long, default_value_t = 32)]
+    pages: usize,
+}
+
+const PAGE: usize = 4096;
+
+/// Makes an unaligned 16-byte load `offset` bytes into each of `pages` pages of `data`, `passes`
+/// times over.
+fn load_pages(data: &[u8], pages: usize, offset: usize, passes: usize) {
+    assert!(pages >= 1 && (pages - 1) * PAGE + offset + 16 <= data.len());
+    for _ in 0..passes {
+        for page in 0..pages {
+            // SAFETY: the assertion keeps the 16 bytes inside `data`.
+            let value = unsafe {
+                ptr::read_unaligned(data.as_ptr().add(page * PAGE + offset).cast::<u128>())
+            };
+            black_box(value);
+        }
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let size = (args.size / 128 * 128).max(128);
+    let largest = args.offset.iter().copied().max().unwrap_or(0);
+    let pages = args.pages.max(1);
+    let mut data = match buffer((size + largest).max((pages + 1) * PAGE)) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {} bytes: {error}", size + largest);
+            return Failure::Io.into();
+        }
+    };
+    let passes = (args.total / size).max(1);
+    let bytes = (passes * size) as u64;
+    let (read_name, read) = read::fastest_kernel();
+    let (write_name, write) = write::fastest_kernel();
+    println!("Reading with {read_name} and writing with {write_name}");
+
+    let mut bench = args.bench_args.bench();
+    let bandwidth = |bench: &mut Bench, name: &str, test: &mut dyn FnMut()| {
+        let results = bench.run(name, bytes, test)?;
+        let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+        Some(bytes as f64 / seconds / (1u64 << 30) as f64)
+    };
+    let mut rows = Vec::new();
+    for &offset in &args.offset {
+        let reads = bandwidth(&mut bench, &format!("read at +{offset}"), &mut || {
+            for _ in 0..passes {
+                read(&data[offset..offset + size]);
+            }
+        });
+        let writes = bandwidth(&mut bench, &format!("write at +{offset}"), &mut || {
+            for _ in 0..passes {
+                write(&mut data[offset..offset + size]);
+            }
+        });
+        rows.push((offset, reads, writes));
+    }
+
+    let page_passes = (args.total / (pages * 16)).max(1);
+    let loads = (page_passes * pages) as u64;
+    let mut page_loads = Vec::new();
+    for (name, offset) in [("within pages", 0), ("across pages", PAGE - 8)] {
+        let results = bench.run(&format!("16-byte loads {name}"), loads * 16, || {
+            load_pages(&data, pages, offset, page_passes)
+        });
+        if let Some(results) = results {
+            let seconds = ticks_to_seconds(results.min.ticks, results.frequency);
+            page_loads.push((name, 1e9 * seconds / loads as f64));
+        }
+    }
+
+    // Penalties are relative to the first offset given, normally 0.
+    println!();
+    println!(
+        "{:>6}  {:>10}  {:>8}  {:>10}  {:>8}",
+        "offset", "read GB/s", "penalty", "write GB/s", "penalty"
+    );
+    let baseline = rows.first().map(|&(_, reads, writes)| (reads, writes));
+    let cell = |value: Option<f64>, baseline: Option<f64>| match (value, baseline) {
+        (Some(value), Some(baseline)) => format!("{value:>10.2}  {:>7.2}x", baseline / value),
+        _ => format!("{:>10}  {:>8}", "-", "-"),
+    };
+    for &(offset, reads, writes) in &rows {
+        println!(
+            "{offset:>6}  {}  {}",
+            cell(reads, baseline.and_then(|baseline| baseline.0)),
+            cell(writes, baseline.and_then(|baseline| baseline.1))
+        );
+    }
+    for (name, nanoseconds) in page_loads {
+        println!("16-byte loads {name}: {nanoseconds:.3}ns each");
+    }
+    bench.finish()
+}
@@ -0,0 +1,136 @@
+use crate::{buffer, parse_size};
+use computer_enhance::{failure::Failure, repetition_tester::BenchArgs};
+use std::process::ExitCode;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    bench_args: BenchArgs,
+
+    /// Bytes to write each trial, rounded down to a multiple of 128. Takes K, M, and G
+    /// suffixes.
+    #[arg(long, value_parser = parse_size, default_value = "256M")]
+    size: usize,
+}
+
+/// Writes all of a buffer whose length is a non-zero multiple of 128.
+pub type Kernel = fn(&mut [u8]);
+
+/// The loops to compare, as for reading. Their stores go through the cache as usual, so
+/// each line is read in before it's written over.
+fn kernels() -> Vec<(&'static str, Kernel)> {
+    let mut kernels: Vec<(&'static str, Kernel)> = vec![("rust fill", fill)];
+    #[cfg(target_arch = "x86_64")]
+    {
+        kernels.push(("asm 8-byte x4", x86::write_8x4));
+        kernels.push(("asm 16-byte x4", x86::write_16x4));
+        if is_x86_feature_detected!("avx") {
+            kernels.push(("asm 32-byte x4", x86::write_32x4));
+        }
+    }
+    kernels
+}
+
+/// The loop that looks fastest on this CPU, as for reading.
+pub fn fastest_kernel() -> (&'static str, Kernel) {
+    kernels().pop().unwrap_or(("rust fill", fill))
+}
+
+fn fill(data: &mut [u8]) {
+    data.fill(0);
+    std::hint::black_box(data);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::asm;
+
+    pub fn write_8x4(data: &mut [u8]) {
+        // SAFETY: writes 32 bytes a time up to `data.len()`, a multiple of 32.
+        unsafe {
+            asm!(
+                "2:",
+                "mov [{data} + {index}], {index}",
+                "mov [{data} + {index} + 8], {index}",
+                "mov [{data} + {index} + 16], {index}",
+                "mov [{data} + {index} + 24], {index}",
+                "add {index}, 32",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_mut_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                options(nostack),
+            );
+        }
+    }
+
+    pub fn write_16x4(data: &mut [u8]) {
+        // SAFETY: writes 64 bytes a time up to `data.len()`, a multiple of 64. SSE2 is part
+        // of x86-64.
+        unsafe {
+            asm!(
+                "pxor xmm0, xmm0",
+                "2:",
+                "movdqu [{data} + {index}], xmm0",
+                "movdqu [{data} + {index} + 16], xmm0",
+                "movdqu [{data} + {index} + 32], xmm0",
+                "movdqu [{data} + {index} + 48], xmm0",
+                "add {index}, 64",
+                "cmp {index}, {len}",
+                "jb 2b",
+                data = in(reg) data.as_mut_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                out("xmm0") _,
+                options(nostack),
+            );
+        }
+    }
+
+    /// Only listed when the CPU has AVX.
+    pub fn write_32x4(data: &mut [u8]) {
+        // SAFETY: the caller checked for AVX.
+        unsafe { write_32x4_avx(data) }
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn write_32x4_avx(data: &mut [u8]) {
+        // SAFETY: writes 128 bytes a time up to `data.len()`, a multiple of 128.
+        unsafe {
+            asm!(
+                "vxorps ymm0, ymm0, ymm0",
+                "2:",
+                "vmovdqu [{data} + {index}], ymm0",
+                "vmovdqu [{data} + {index} + 32], ymm0",
+                "vmovdqu [{data} + {index} + 64], ymm0",
+                "vmovdqu [{data} + {index} + 96], ymm0",
+                "add {index}, 128",
+                "cmp {index}, {len}",
+                "jb 2b",
+                "vzeroupper",
+                data = in(reg) data.as_mut_ptr(),
+                len = in(reg) data.len(),
+                index = inout(reg) 0usize => _,
+                out("ymm0") _,
+                options(nostack),
+            );
+        }
+    }
+}
+
+pub fn run(args: Args) -> ExitCode {
+    let size = (args.size / 128 * 128).max(128);
+    let mut data = match buffer(size) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("can't allocate {size} bytes: {error}");
+            return Failure::Io.into();
+        }
+    };
+    let mut bench = args.bench_args.bench();
+    for (name, kernel) in kernels() {
+        bench.run(name, size as u64, || kernel(&mut data));
+    }
+    bench.finish()
+}
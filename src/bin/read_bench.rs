@@ -0,0 +1,221 @@
+use clap::Parser;
+use computer_enhance::{
+    atomic_file::write_atomically,
+    environment::Environment,
+    failure::{Failure, EXIT_STATUSES},
+    os_memory::{FileView, Pages},
+    repetition_tester::{self, Allocation, BenchArgs, Buffers, RepetitionTester, TestSet},
+};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Time reading a whole file in different ways, repeating each until it stops getting faster,
+/// and compare their throughput.
+#[derive(Parser)]
+#[command(name = "read_bench", after_help = EXIT_STATUSES)]
+struct Args {
+    /// File to read: ideally large, and read once first so it's in the OS's cache.
+    input: PathBuf,
+
+    /// Buffers to read into: allocated once, fresh from the heap, or fresh from the OS for
+    /// every read, in ordinary or large pages. All of them by default; repeat for several.
+    #[arg(long, value_enum)]
+    allocation: Vec<AllocationArg>,
+
+    /// Read in chunks of this many bytes into one buffer of that size, as a program
+    /// processing the file piece by piece would. Repeat for several.
+    #[arg(long, value_name = "BYTES", default_values_t = [4096, 65536, 1 << 20])]
+    chunk: Vec<usize>,
+
+    /// Test every way of reading this many times over, in turn, comparing each way's best.
+    #[arg(long, default_value_t = 1)]
+    rounds: u32,
+
+    #[command(flatten)]
+    bench_args: BenchArgs,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum AllocationArg {
+    Reuse,
+    Fresh,
+    Mapped,
+    Large,
+}
+
+impl From<AllocationArg> for Allocation {
+    fn from(allocation: AllocationArg) -> Allocation {
+        match allocation {
+            AllocationArg::Reuse => Allocation::Reuse,
+            AllocationArg::Fresh => Allocation::Fresh,
+            AllocationArg::Mapped => Allocation::Mapped,
+            AllocationArg::Large => Allocation::Large,
+        }
+    }
+}
+
+/// One way of reading the file.
+#[derive(Clone, Copy)]
+enum Strategy {
+    /// `fs::read`, which opens the file and allocates its own buffer.
+    FsRead,
+    /// One `read_exact` of the whole file into a buffer its size.
+    ReadExact(Allocation),
+    /// `read` calls of a chunk's bytes at a time, into one buffer that size.
+    Chunks(usize, Allocation),
+    /// Mapping the file, and touching every cache line of it so the OS has to page it all in.
+    Map,
+}
+
+impl Strategy {
+    fn name(self) -> String {
+        match self {
+            Strategy::FsRead => "fs::read".to_string(),
+            Strategy::ReadExact(allocation) => format!("read_exact ({})", allocation.name()),
+            Strategy::Chunks(size, allocation) => {
+                format!("{size}-byte chunks ({})", allocation.name())
+            }
+            Strategy::Map => "mmap".to_string(),
+        }
+    }
+}
+
+/// Runs one wave of `strategy` over the file at `input`, `size` bytes long.
+fn test(tester: &mut RepetitionTester, input: &Path, size: usize, strategy: Strategy) {
+    let fail = |tester: &mut RepetitionTester, error: io::Error| tester.error(error.to_string());
+    let mut buffers = match strategy {
+        Strategy::ReadExact(allocation) => Some(Buffers::new(allocation, size)),
+        Strategy::Chunks(chunk, allocation) => Some(Buffers::new(allocation, chunk)),
+        Strategy::FsRead | Strategy::Map => None,
+    };
+    while tester.is_testing() {
+        if let Strategy::FsRead = strategy {
+            tester.begin_time();
+            let read = fs::read(input);
+            tester.end_time();
+            match read {
+                Ok(bytes) => tester.count_bytes(bytes.len() as u64),
+                Err(error) => fail(tester, error),
+            }
+            continue;
+        }
+
+        let mut file = match File::open(input) {
+            Ok(file) => file,
+            Err(error) => {
+                fail(tester, error);
+                continue;
+            }
+        };
+        let mut buffer = match buffers.as_mut().map(Buffers::get).transpose() {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                fail(tester, error);
+                continue;
+            }
+        };
+        tester.begin_time();
+        let read = match (strategy, &mut buffer) {
+            (Strategy::ReadExact(_), Some(buffer)) => file.read_exact(buffer).map(|()| size),
+            (Strategy::Chunks(..), Some(buffer)) => {
+                let mut total = 0;
+                loop {
+                    match file.read(buffer) {
+                        Ok(0) => break Ok(total),
+                        Ok(read) => total += read,
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                        Err(error) => break Err(error),
+                    }
+                }
+            }
+            _ => FileView::new(&file).map(|view| {
+                let touched: u64 = view.iter().step_by(64).map(|&byte| byte as u64).sum();
+                std::hint::black_box(touched);
+                view.len()
+            }),
+        };
+        tester.end_time();
+        match read {
+            Ok(read) => tester.count_bytes(read as u64),
+            Err(error) => fail(tester, error),
+        }
+        if let (Some(buffers), Some(buffer)) = (&mut buffers, buffer) {
+            buffers.put(buffer);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    println!("{}", Environment::detect());
+    let size = match fs::metadata(&args.input) {
+        Ok(metadata) => metadata.len() as usize,
+        Err(error) => {
+            eprintln!("{}: {error}", args.input.display());
+            return Failure::Io.into();
+        }
+    };
+    let allocations: Vec<Allocation> = if args.allocation.is_empty() {
+        Allocation::ALL.to_vec()
+    } else {
+        args.allocation
+            .iter()
+            .map(|&allocation| allocation.into())
+            .collect()
+    };
+    if allocations.contains(&Allocation::Large) {
+        if let Err(error) = Pages::new_large(size) {
+            eprintln!("no large pages ({error}), so the large tests use ordinary ones");
+        }
+    }
+    let mut strategies = vec![Strategy::FsRead];
+    for &allocation in &allocations {
+        strategies.push(Strategy::ReadExact(allocation));
+        for &chunk in &args.chunk {
+            strategies.push(Strategy::Chunks(chunk.max(1), allocation));
+        }
+    }
+    strategies.push(Strategy::Map);
+
+    let mut tester = args.bench_args.tester();
+    let mut set = TestSet::new(size as u64);
+    for strategy in strategies {
+        let input = &args.input;
+        set.add(strategy.name(), move |tester: &mut RepetitionTester| {
+            test(tester, input, size, strategy)
+        });
+    }
+    let mut csv = Vec::new();
+    let _ = writeln!(csv, "{}", repetition_tester::CSV_HEADER);
+    let comparison = set.run(
+        &mut tester,
+        args.bench_args.window(),
+        args.rounds.max(1),
+        |name, tester| {
+            let _ = tester.write_csv(&mut csv, name);
+        },
+    );
+    for (name, message) in set.errors() {
+        eprintln!("{name}: {message}");
+    }
+    let failed = !set.errors().is_empty();
+    println!();
+    print!("{comparison}");
+
+    if let Some(path) = &args.bench_args.csv {
+        if let Err(error) = write_atomically(path, |out| out.write_all(&csv)) {
+            eprintln!("{}: {error}", path.display());
+            return Failure::Io.into();
+        }
+    }
+    // The tests only fail when the input can't be read.
+    if failed {
+        Failure::Io.into()
+    } else {
+        ExitCode::SUCCESS
+    }
+}
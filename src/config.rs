@@ -0,0 +1,186 @@
+use std::{error::Error, fmt};
+
+/// One `key = value` line of a configuration file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Setting {
+    /// The `[section]` the line is under, if any.
+    pub section: Option<String>,
+    /// The key, with dashes turned to underscores so `size-keywords` and `size_keywords` match.
+    pub key: String,
+    /// The value, unquoted.
+    pub value: String,
+    /// 1-based line number, for messages.
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Line `line` isn't a comment, a `[section]`, or a `key = value` setting.
+    Malformed { line: usize },
+    /// The quoted value on line `line` doesn't end, or has an escape other than `\"` or `\\`.
+    InvalidString { line: usize },
+    /// `key` is set twice in the same section, the second time on line `line`.
+    Duplicate { line: usize, key: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Malformed { line } => {
+                write!(f, "line {line}: expected `key = value` or `[section]`")
+            }
+            ConfigError::InvalidString { line } => write!(f, "line {line}: invalid string"),
+            ConfigError::Duplicate { line, key } => write!(f, "line {line}: {key} is set twice"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')
+}
+
+/// A value after the `=`: a "basic" string with `\"` and `\\` escapes, a 'literal' string, or
+/// a bare word such as `true` or `0x100`, on line `line`. Returns the value and what follows it.
+fn value(text: &str, line: usize) -> Result<(String, &str), ConfigError> {
+    let invalid = ConfigError::InvalidString { line };
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Ok((value, &rest[index + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                    _ => return Err(invalid),
+                },
+                c => value.push(c),
+            }
+        }
+        Err(invalid)
+    } else if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or(invalid)?;
+        Ok((rest[..end].to_string(), &rest[end + 1..]))
+    } else {
+        let end = text.find(['#', ' ', '\t']).unwrap_or(text.len());
+        if end == 0 {
+            return Err(ConfigError::Malformed { line });
+        }
+        Ok((text[..end].to_string(), &text[end..]))
+    }
+}
+
+/// Parses the subset of TOML that holds flat settings: `key = value` lines, optionally under
+/// `[section]` headers, with `#` comments.
+pub fn parse_config(text: &str) -> Result<Vec<Setting>, ConfigError> {
+    let mut settings: Vec<Setting> = Vec::new();
+    let mut section = None;
+    for (index, text) in text.lines().enumerate() {
+        let line = index + 1;
+        let malformed = ConfigError::Malformed { line };
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = text.strip_prefix('[') {
+            let (name, rest) = header.split_once(']').ok_or(malformed)?;
+            let rest = rest.trim_start();
+            if !is_name(name.trim()) || !(rest.is_empty() || rest.starts_with('#')) {
+                return Err(ConfigError::Malformed { line });
+            }
+            section = Some(name.trim().to_string());
+            continue;
+        }
+
+        let (key, rest) = text.split_once('=').ok_or(malformed)?;
+        let key = key.trim();
+        if !is_name(key) {
+            return Err(ConfigError::Malformed { line });
+        }
+        let (value, rest) = value(rest.trim_start(), line)?;
+        let rest = rest.trim_start();
+        if !(rest.is_empty() || rest.starts_with('#')) {
+            return Err(ConfigError::Malformed { line });
+        }
+        let key = key.replace('-', "_");
+        if (settings.iter()).any(|setting| setting.section == section && setting.key == key) {
+            return Err(ConfigError::Duplicate { line, key });
+        }
+        settings.push(Setting {
+            section: section.clone(),
+            key,
+            value,
+            line,
+        });
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setting(section: Option<&str>, key: &str, value: &str, line: usize) -> Setting {
+        Setting {
+            section: section.map(str::to_string),
+            key: key.to_string(),
+            value: value.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn settings_are_read_under_their_sections() {
+        let text = "# defaults\n\
+                    syntax = \"masm\"\n\
+                    size-keywords = 'always'  # on every operand\n\
+                    \n\
+                    [disasm]\n\
+                    origin = 0x100\n\
+                    output_dir = \"out \\\"dir\\\"\"\n";
+        assert_eq!(
+            parse_config(text).unwrap(),
+            [
+                setting(None, "syntax", "masm", 2),
+                setting(None, "size_keywords", "always", 3),
+                setting(Some("disasm"), "origin", "0x100", 6),
+                setting(Some("disasm"), "output_dir", "out \"dir\"", 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        assert_eq!(
+            parse_config("case = upper\nradix\n"),
+            Err(ConfigError::Malformed { line: 2 })
+        );
+        assert_eq!(
+            parse_config("case = upper lower"),
+            Err(ConfigError::Malformed { line: 1 })
+        );
+        assert_eq!(
+            parse_config("case = # none"),
+            Err(ConfigError::Malformed { line: 1 })
+        );
+        assert_eq!(
+            parse_config("[disasm\n"),
+            Err(ConfigError::Malformed { line: 1 })
+        );
+        assert_eq!(
+            parse_config("case = \"upper"),
+            Err(ConfigError::InvalidString { line: 1 })
+        );
+        assert_eq!(
+            parse_config("case = upper\ncase = lower"),
+            Err(ConfigError::Duplicate {
+                line: 2,
+                key: "case".to_string()
+            })
+        );
+    }
+}
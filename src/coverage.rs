@@ -0,0 +1,95 @@
+use crate::listing::Listing;
+use std::{fmt, ops::Range};
+
+/// How far a program got with a byte of itself, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Use {
+    /// Neither decoded nor executed: most likely data.
+    Untouched,
+    /// Part of an instruction in the listing that never ran.
+    Decoded,
+    /// Part of an instruction that ran.
+    Executed,
+}
+
+impl fmt::Display for Use {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Use::Untouched => "untouched",
+            Use::Decoded => "decoded",
+            Use::Executed => "executed",
+        })
+    }
+}
+
+/// How each byte of a loaded program was used over a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    /// Physical address of the program's first byte.
+    start: usize,
+    uses: Vec<Use>,
+}
+
+impl Coverage {
+    /// Coverage of the program at the physical addresses `program`, all of it untouched.
+    pub fn new(program: Range<usize>) -> Self {
+        Coverage {
+            start: program.start,
+            uses: vec![Use::Untouched; program.len()],
+        }
+    }
+
+    // Raises the bytes at the physical addresses `range` to at least `to`, ignoring any
+    // outside the program.
+    fn mark(&mut self, range: Range<usize>, to: Use) {
+        let start = range.start.saturating_sub(self.start).min(self.uses.len());
+        let end = range.end.saturating_sub(self.start).min(self.uses.len());
+        for byte in &mut self.uses[start..end] {
+            *byte = (*byte).max(to);
+        }
+    }
+
+    /// Marks every instruction in `listing` as decoded.
+    pub fn decoded(&mut self, listing: &Listing) {
+        for instruction in &listing.instructions {
+            let start = listing.address(instruction);
+            self.mark(start..start + instruction.length, Use::Decoded);
+        }
+    }
+
+    /// Marks the `length` bytes of an instruction at physical `address` as executed.
+    pub fn executed(&mut self, address: usize, length: usize) {
+        self.mark(address..address + length, Use::Executed);
+    }
+
+    /// How the byte at physical `address` was used, or `None` if it isn't in the program.
+    pub fn use_at(&self, address: usize) -> Option<Use> {
+        self.uses.get(address.checked_sub(self.start)?).copied()
+    }
+
+    /// Runs of bytes used the same way, as physical addresses.
+    pub fn runs(&self) -> Vec<(Range<usize>, Use)> {
+        let mut runs: Vec<(Range<usize>, Use)> = Vec::new();
+        for (offset, &byte) in self.uses.iter().enumerate() {
+            let address = self.start + offset;
+            match runs.last_mut() {
+                Some((range, last)) if *last == byte => range.end = address + 1,
+                _ => runs.push((address..address + 1, byte)),
+            }
+        }
+        runs
+    }
+
+    /// Bytes used exactly as `kind`.
+    pub fn count(&self, kind: Use) -> usize {
+        self.uses.iter().filter(|&&byte| byte == kind).count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.uses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uses.is_empty()
+    }
+}
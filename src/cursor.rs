@@ -0,0 +1,100 @@
+/// Reads little-endian scalars from a slice of bytes, tracking how far it has read.
+///
+/// The 8086 stores every multi-byte value low byte first, and so do the binary files the rest
+/// of the course produces, so every reader of raw bytes should go through here rather than
+/// stitching `hi << 8 | lo` together by hand. Each read returns `None`, and doesn't move, when
+/// too few bytes are left; callers turn that into their own errors.
+#[derive(Clone, Copy, Debug)]
+pub struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Cursor { bytes, position: 0 }
+    }
+
+    /// Number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves to `position`, which may be anywhere up to the end of the bytes.
+    pub fn seek(&mut self, position: usize) {
+        assert!(position <= self.bytes.len(), "seek past the end");
+        self.position = position;
+    }
+
+    /// All of the bytes, read or not.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The bytes not yet read.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    /// The next `count` bytes.
+    pub fn read_bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.remaining().get(..count)?;
+        self.position += count;
+        Some(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        Some(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        Some(u8::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i8(&mut self) -> Option<i8> {
+        Some(i8::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u16_le(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i16_le(&mut self) -> Option<i16> {
+        Some(i16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32_le(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64_le(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_f64_le(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.read_array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalars_read_low_byte_first_and_stop_at_the_end() {
+        let mut cursor = Cursor::new(&[0xFE, 0x34, 0x12, 0x78, 0x56, 0x34, 0x12, 0xFF]);
+        assert_eq!(cursor.read_i8(), Some(-2));
+        assert_eq!(cursor.read_u16_le(), Some(0x1234));
+        assert_eq!(cursor.read_u32_le(), Some(0x1234_5678));
+        assert_eq!(cursor.position(), 7);
+        // Too few left: nothing is consumed.
+        assert_eq!(cursor.read_u16_le(), None);
+        assert_eq!(cursor.remaining(), [0xFF]);
+        assert_eq!(cursor.read_u8(), Some(0xFF));
+        assert_eq!(cursor.read_u8(), None);
+
+        cursor.seek(1);
+        assert_eq!(cursor.read_i16_le(), Some(0x1234));
+        assert_eq!(cursor.read_bytes(2), Some(&[0x78, 0x56][..]));
+    }
+}
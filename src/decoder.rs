@@ -0,0 +1,1017 @@
+use crate::{
+    cursor::Cursor,
+    instruction::{AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register, Rep},
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind, Read},
+    ops::ControlFlow,
+};
+
+//--------------------------------
+//            Byte #1            |
+//--------------------------------
+// 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 |
+//--------------------------------
+//         OPCODE        | D | W |
+//--------------------------------
+const D: u8 = 0b00000010;
+const W: u8 = 0b00000001;
+
+//--------------------------------
+//            Byte #2            |
+//--------------------------------
+// 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 |
+//--------------------------------
+//  MOD  |    REG    |    R/M    |
+//--------------------------------
+const MOD: u8 = 0b11000000;
+const REG: u8 = 0b00111000;
+const R_M: u8 = 0b00000111;
+
+const MOD_MM_NO_DISP: u8 = 0b00;
+const MOD_MM_8_BIT_DISP: u8 = 0b01;
+const MOD_MM_16_BIT_DISP: u8 = 0b10;
+const MOD_RM_NO_DISP: u8 = 0b11;
+
+// The eight ALU operations share one encoding family, selected by bits 5..3 of the opcode (or
+// by REG in the immediate group).
+pub(crate) const ALU: [Mnemonic; 8] = [
+    Mnemonic::Add,
+    Mnemonic::Or,
+    Mnemonic::Adc,
+    Mnemonic::Sbb,
+    Mnemonic::And,
+    Mnemonic::Sub,
+    Mnemonic::Xor,
+    Mnemonic::Cmp,
+];
+
+pub(crate) const SHIFT: [Option<Mnemonic>; 8] = [
+    Some(Mnemonic::Rol),
+    Some(Mnemonic::Ror),
+    Some(Mnemonic::Rcl),
+    Some(Mnemonic::Rcr),
+    Some(Mnemonic::Shl),
+    Some(Mnemonic::Shr),
+    None,
+    Some(Mnemonic::Sar),
+];
+
+// Indexed by the low nibble of 0x70..=0x7F.
+pub(crate) const JCC: [Mnemonic; 16] = [
+    Mnemonic::Jo,
+    Mnemonic::Jno,
+    Mnemonic::Jb,
+    Mnemonic::Jnb,
+    Mnemonic::Je,
+    Mnemonic::Jne,
+    Mnemonic::Jbe,
+    Mnemonic::Ja,
+    Mnemonic::Js,
+    Mnemonic::Jns,
+    Mnemonic::Jp,
+    Mnemonic::Jnp,
+    Mnemonic::Jl,
+    Mnemonic::Jnl,
+    Mnemonic::Jle,
+    Mnemonic::Jg,
+];
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Reading the input failed, when decoding it in chunks.
+    Io(io::Error),
+    /// The input ended part-way through the instruction starting at `offset`.
+    UnexpectedEof { offset: usize },
+    /// The instruction at `offset` uses an opcode (or opcode extension) the 8086 doesn't define.
+    UnsupportedOpcode { offset: usize, opcode: u8 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(error) => write!(f, "failed to read input: {error}"),
+            DecodeError::UnexpectedEof { offset } => {
+                write!(f, "input ends inside the instruction at offset {offset}")
+            }
+            DecodeError::UnsupportedOpcode { offset, opcode } => {
+                write!(f, "unsupported opcode {opcode:#04x} at offset {offset}")
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> Self {
+        DecodeError::Io(error)
+    }
+}
+
+/// Decodes 8086 machine code one instruction at a time from bytes already in memory.
+pub struct Decoder<'a> {
+    cursor: Cursor<'a>,
+    origin: usize,
+    start: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Decoder::with_origin(bytes, 0)
+    }
+
+    /// Decodes as if the first of `bytes` were loaded at address `origin` (e.g. `0x100` for a
+    /// .COM image), so that offsets and jump targets come out as addresses.
+    pub fn with_origin(bytes: &'a [u8], origin: usize) -> Self {
+        Decoder {
+            cursor: Cursor::new(bytes),
+            origin,
+            start: 0,
+        }
+    }
+
+    /// Address of the next byte to be decoded.
+    pub fn offset(&self) -> usize {
+        self.origin + self.cursor.position()
+    }
+
+    /// The bytes not yet decoded.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.cursor.remaining()
+    }
+
+    /// Decodes the next instruction, or returns `None` at a clean end of input.
+    pub fn next_instruction(&mut self) -> Result<Option<Instruction>, DecodeError> {
+        self.start = self.offset();
+
+        if self.remaining().is_empty() {
+            return Ok(None);
+        }
+        let mut byte_one = self.read_u8()?;
+
+        let mut lock = false;
+        let mut rep = None;
+        let mut segment_override = None;
+        loop {
+            match byte_one {
+                0xF0 => lock = true,
+                0xF2 => rep = Some(Rep::Repne),
+                0xF3 => rep = Some(Rep::Rep),
+                0x26 | 0x2E | 0x36 | 0x3E => {
+                    segment_override = Some(Register::from_segment(byte_one >> 3));
+                }
+                _ => break,
+            }
+            byte_one = self.read_u8()?;
+        }
+
+        let mut instruction = self.decode_opcode(byte_one)?;
+        instruction.length = self.offset() - self.start;
+        instruction.lock = lock;
+        instruction.rep = rep;
+        instruction.segment_override = segment_override;
+        let end = self.cursor.position();
+        tracing::debug!(
+            offset = format_args!("{:#06x}", self.start),
+            opcode = format_args!("{byte_one:#04x}"),
+            mnemonic = ?instruction.mnemonic,
+            lock,
+            rep = ?rep,
+            segment_override = ?segment_override,
+            bytes = format_args!("{:02x?}", &self.cursor.bytes()[end - instruction.length..end]),
+            "decoded"
+        );
+
+        Ok(Some(instruction))
+    }
+
+    // Running out of input mid-instruction is a property of the instruction, not of the input.
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        self.cursor
+            .read_array()
+            .ok_or(DecodeError::UnexpectedEof { offset: self.start })
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(u8::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_i8(&mut self) -> Result<i8, DecodeError> {
+        Ok(i8::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_i16(&mut self) -> Result<i16, DecodeError> {
+        Ok(i16::from_le_bytes(self.read_array()?))
+    }
+
+    /// Immediate data following the operands: one byte (sign-extended) or one word.
+    fn data(&mut self, is_word: bool) -> Result<Operand, DecodeError> {
+        let value = if is_word {
+            self.read_i16()? as i32
+        } else {
+            self.read_i8()? as i32
+        };
+        tracing::debug!(
+            offset = format_args!("{:#06x}", self.start),
+            value,
+            "immediate"
+        );
+        Ok(Operand::Immediate(value))
+    }
+
+    fn unsupported(&self, opcode: u8) -> DecodeError {
+        DecodeError::UnsupportedOpcode {
+            offset: self.start,
+            opcode,
+        }
+    }
+
+    // Addresses and far pointers come from memory; the 8086 doesn't define a register form.
+    fn memory_only(&self, opcode: u8, operand: Operand) -> Result<(), DecodeError> {
+        match operand {
+            Operand::Register(_) => Err(self.unsupported(opcode)),
+            _ => Ok(()),
+        }
+    }
+
+    //--------------------------------
+    //            Byte #2            |
+    //--------------------------------
+    //  MOD  |    REG    |    R/M    |
+    //--------------------------------
+    // Returns the raw REG field together with the operand selected by MOD and R/M, consuming
+    // any displacement bytes.
+    fn mod_reg_r_m(&mut self, is_word: bool) -> Result<(u8, Operand), DecodeError> {
+        let byte_two = self.read_u8()?;
+        let mode = (byte_two & MOD) >> 6;
+        let reg = (byte_two & REG) >> 3;
+        let r_m = byte_two & R_M;
+        tracing::debug!(
+            offset = format_args!("{:#06x}", self.start),
+            r#mod = format_args!("{mode:02b}"),
+            reg = format_args!("{reg:03b}"),
+            r_m = format_args!("{r_m:03b}"),
+            w = is_word as u8,
+            "ModRM"
+        );
+
+        let (displacement, displacement_bytes) = match mode {
+            MOD_MM_NO_DISP if r_m == 0b110 => (self.read_i16()?, 2),
+            MOD_MM_NO_DISP => (0, 0),
+            MOD_MM_8_BIT_DISP => (self.read_i8()? as i16, 1),
+            MOD_MM_16_BIT_DISP => (self.read_i16()?, 2),
+            MOD_RM_NO_DISP => {
+                return Ok((reg, Operand::Register(Register::from_reg(r_m, is_word))))
+            }
+            _ => unreachable!(),
+        };
+        tracing::debug!(
+            offset = format_args!("{:#06x}", self.start),
+            displacement,
+            "displacement"
+        );
+
+        let address = EffectiveAddress {
+            base: AddressBase::from_r_m(r_m, mode),
+            displacement,
+            displacement_bytes,
+        };
+        Ok((reg, Operand::Memory(address)))
+    }
+
+    /// MOD/REG/R/M form where the D bit selects whether REG is the destination.
+    fn reg_r_m(&mut self, mnemonic: Mnemonic, byte_one: u8) -> Result<Instruction, DecodeError> {
+        let is_word = (byte_one & W) == W;
+        let destination_in_reg = (byte_one & D) == D;
+
+        let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+        let reg = Operand::Register(Register::from_reg(reg, is_word));
+
+        Ok(if destination_in_reg {
+            instruction(mnemonic, is_word, [Some(reg), Some(r_m)])
+        } else {
+            instruction(mnemonic, is_word, [Some(r_m), Some(reg)])
+        })
+    }
+
+    fn relative_byte(&mut self) -> Result<Operand, DecodeError> {
+        Ok(Operand::Relative(self.read_i8()? as i16))
+    }
+
+    fn relative_word(&mut self) -> Result<Operand, DecodeError> {
+        Ok(Operand::Relative(self.read_i16()?))
+    }
+
+    fn direct_address(&mut self) -> Result<Operand, DecodeError> {
+        Ok(Operand::Memory(EffectiveAddress::new(
+            AddressBase::Direct,
+            self.read_i16()?,
+        )))
+    }
+
+    fn far_pointer(&mut self) -> Result<Operand, DecodeError> {
+        let offset = self.read_u16()?;
+        let segment = self.read_u16()?;
+        Ok(Operand::Far { segment, offset })
+    }
+
+    // TODO(jmarcil): Replace with a jump table?
+    fn decode_opcode(&mut self, byte_one: u8) -> Result<Instruction, DecodeError> {
+        let is_word = (byte_one & W) == W;
+        let accumulator = Operand::Register(if is_word { Register::Ax } else { Register::Al });
+
+        let instruction = match byte_one {
+            //--------------------------------
+            //  ALU - Reg/Mem with Reg
+            //--------------------------------
+            0x00..=0x3F if byte_one & 0b100 == 0 => {
+                self.reg_r_m(ALU[(byte_one >> 3) as usize & 0b111], byte_one)?
+            }
+            //--------------------------------
+            //  ALU - Imm to Acc
+            //--------------------------------
+            0x00..=0x3F if byte_one & 0b110 == 0b100 => {
+                let data = self.data(is_word)?;
+                let mnemonic = ALU[(byte_one >> 3) as usize & 0b111];
+                instruction(mnemonic, is_word, [Some(accumulator), Some(data)])
+            }
+            0x06 | 0x0E | 0x16 | 0x1E => {
+                let segment = Operand::Register(Register::from_segment(byte_one >> 3));
+                instruction(Mnemonic::Push, true, [Some(segment), None])
+            }
+            0x07 | 0x0F | 0x17 | 0x1F => {
+                let segment = Operand::Register(Register::from_segment(byte_one >> 3));
+                instruction(Mnemonic::Pop, true, [Some(segment), None])
+            }
+            0x27 => instruction(Mnemonic::Daa, false, [None, None]),
+            0x2F => instruction(Mnemonic::Das, false, [None, None]),
+            0x37 => instruction(Mnemonic::Aaa, false, [None, None]),
+            0x3F => instruction(Mnemonic::Aas, false, [None, None]),
+            //--------------------------------
+            //  INC/DEC/PUSH/POP - Reg
+            //--------------------------------
+            0x40..=0x5F => {
+                let mnemonic = [Mnemonic::Inc, Mnemonic::Dec, Mnemonic::Push, Mnemonic::Pop]
+                    [(byte_one >> 3) as usize & 0b11];
+                let reg = Operand::Register(Register::from_reg(byte_one, true));
+                instruction(mnemonic, true, [Some(reg), None])
+            }
+            //--------------------------------
+            //  Jcc - Short
+            //--------------------------------
+            0x70..=0x7F => {
+                let target = self.relative_byte()?;
+                instruction(JCC[(byte_one & 0xF) as usize], false, [Some(target), None])
+            }
+            //--------------------------------
+            //  ALU - Imm to Reg/Mem
+            //--------------------------------
+            0x80..=0x83 => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                let data = self.data(byte_one == 0x81)?;
+                instruction(ALU[reg as usize], is_word, [Some(r_m), Some(data)])
+            }
+            0x84 | 0x85 => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                let reg = Operand::Register(Register::from_reg(reg, is_word));
+                instruction(Mnemonic::Test, is_word, [Some(r_m), Some(reg)])
+            }
+            0x86 | 0x87 => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                let reg = Operand::Register(Register::from_reg(reg, is_word));
+                instruction(Mnemonic::Xchg, is_word, [Some(reg), Some(r_m)])
+            }
+            //--------------------------------
+            //  MOV - Reg/Mem to/from Reg
+            //--------------------------------
+            0x88..=0x8B => self.reg_r_m(Mnemonic::Mov, byte_one)?,
+            //--------------------------------
+            //  MOV - Seg to/from Reg/Mem
+            //--------------------------------
+            0x8C | 0x8E => {
+                let (reg, r_m) = self.mod_reg_r_m(true)?;
+                let segment = Operand::Register(Register::from_segment(reg));
+                if byte_one == 0x8C {
+                    instruction(Mnemonic::Mov, true, [Some(r_m), Some(segment)])
+                } else {
+                    instruction(Mnemonic::Mov, true, [Some(segment), Some(r_m)])
+                }
+            }
+            0x8D => {
+                let (reg, r_m) = self.mod_reg_r_m(true)?;
+                self.memory_only(byte_one, r_m)?;
+                let reg = Operand::Register(Register::from_reg(reg, true));
+                instruction(Mnemonic::Lea, true, [Some(reg), Some(r_m)])
+            }
+            0x8F => match self.mod_reg_r_m(true)? {
+                (0b000, r_m) => instruction(Mnemonic::Pop, true, [Some(r_m), None]),
+                _ => return Err(self.unsupported(byte_one)),
+            },
+            0x90..=0x97 => {
+                let reg = Operand::Register(Register::from_reg(byte_one, true));
+                instruction(
+                    Mnemonic::Xchg,
+                    true,
+                    [Some(Operand::Register(Register::Ax)), Some(reg)],
+                )
+            }
+            0x98 => instruction(Mnemonic::Cbw, false, [None, None]),
+            0x99 => instruction(Mnemonic::Cwd, true, [None, None]),
+            0x9A => {
+                let target = self.far_pointer()?;
+                instruction(Mnemonic::Call, true, [Some(target), None])
+            }
+            0x9B => instruction(Mnemonic::Wait, false, [None, None]),
+            0x9C => instruction(Mnemonic::Pushf, true, [None, None]),
+            0x9D => instruction(Mnemonic::Popf, true, [None, None]),
+            0x9E => instruction(Mnemonic::Sahf, false, [None, None]),
+            0x9F => instruction(Mnemonic::Lahf, false, [None, None]),
+            //--------------------------------
+            //  MOV - Mem to/from Acc
+            //--------------------------------
+            0xA0 | 0xA1 => {
+                let address = self.direct_address()?;
+                instruction(Mnemonic::Mov, is_word, [Some(accumulator), Some(address)])
+            }
+            0xA2 | 0xA3 => {
+                let address = self.direct_address()?;
+                instruction(Mnemonic::Mov, is_word, [Some(address), Some(accumulator)])
+            }
+            0xA4 | 0xA5 => instruction(Mnemonic::Movs, is_word, [None, None]),
+            0xA6 | 0xA7 => instruction(Mnemonic::Cmps, is_word, [None, None]),
+            0xA8 | 0xA9 => {
+                let data = self.data(is_word)?;
+                instruction(Mnemonic::Test, is_word, [Some(accumulator), Some(data)])
+            }
+            0xAA | 0xAB => instruction(Mnemonic::Stos, is_word, [None, None]),
+            0xAC | 0xAD => instruction(Mnemonic::Lods, is_word, [None, None]),
+            0xAE | 0xAF => instruction(Mnemonic::Scas, is_word, [None, None]),
+            //------------------------------------------------------------------------------------------------
+            //                                       MOV - Imm to Reg                                        |
+            //------------------------------------------------------------------------------------------------
+            //          BYTE #1              |            BYTE #2            |            BYTE #3            |
+            //------------------------------------------------------------------------------------------------
+            // 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 | 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 | 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 |
+            //------------------------------------------------------------------------------------------------
+            // 1 | 0 | 1 | 1 | W |    REG    |             DATA              |         DATA (W == 1)         |
+            //------------------------------------------------------------------------------------------------
+            0xB0..=0xBF => {
+                let is_word = (byte_one & 0b1000) == 0b1000;
+                let reg = Operand::Register(Register::from_reg(byte_one, is_word));
+                let data = self.data(is_word)?;
+                instruction(Mnemonic::Mov, is_word, [Some(reg), Some(data)])
+            }
+            0xC2 | 0xCA => {
+                let mnemonic = if byte_one == 0xC2 {
+                    Mnemonic::Ret
+                } else {
+                    Mnemonic::Retf
+                };
+                let data = self.data(true)?;
+                instruction(mnemonic, true, [Some(data), None])
+            }
+            0xC3 => instruction(Mnemonic::Ret, true, [None, None]),
+            0xCB => instruction(Mnemonic::Retf, true, [None, None]),
+            0xC4 | 0xC5 => {
+                let mnemonic = if byte_one == 0xC4 {
+                    Mnemonic::Les
+                } else {
+                    Mnemonic::Lds
+                };
+                let (reg, r_m) = self.mod_reg_r_m(true)?;
+                self.memory_only(byte_one, r_m)?;
+                let reg = Operand::Register(Register::from_reg(reg, true));
+                instruction(mnemonic, true, [Some(reg), Some(r_m)])
+            }
+            //--------------------------------
+            //  MOV - Imm to Reg/Mem
+            //--------------------------------
+            0xC6 | 0xC7 => match self.mod_reg_r_m(is_word)? {
+                (0b000, r_m) => {
+                    let data = self.data(is_word)?;
+                    instruction(Mnemonic::Mov, is_word, [Some(r_m), Some(data)])
+                }
+                _ => return Err(self.unsupported(byte_one)),
+            },
+            0xCC => instruction(Mnemonic::Int3, false, [None, None]),
+            0xCD => {
+                let vector = Operand::Immediate(self.read_u8()? as i32);
+                instruction(Mnemonic::Int, false, [Some(vector), None])
+            }
+            0xCE => instruction(Mnemonic::Into, false, [None, None]),
+            0xCF => instruction(Mnemonic::Iret, true, [None, None]),
+            //--------------------------------
+            //  Shift/Rotate - By 1 or CL
+            //--------------------------------
+            0xD0..=0xD3 => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                let Some(mnemonic) = SHIFT[reg as usize] else {
+                    return Err(self.unsupported(byte_one));
+                };
+                let count = if byte_one & D == D {
+                    Operand::Register(Register::Cl)
+                } else {
+                    Operand::Immediate(1)
+                };
+                instruction(mnemonic, is_word, [Some(r_m), Some(count)])
+            }
+            0xD4 | 0xD5 => {
+                let mnemonic = if byte_one == 0xD4 {
+                    Mnemonic::Aam
+                } else {
+                    Mnemonic::Aad
+                };
+                // The second byte is the base (0x0A); NASM's plain AAM/AAD always encode 10.
+                match self.read_u8()? {
+                    0x0A => instruction(mnemonic, false, [None, None]),
+                    _ => return Err(self.unsupported(byte_one)),
+                }
+            }
+            0xD7 => instruction(Mnemonic::Xlat, false, [None, None]),
+            0xD8..=0xDF => {
+                let (reg, r_m) = self.mod_reg_r_m(true)?;
+                let opcode = Operand::Immediate((((byte_one & 0b111) << 3) | reg) as i32);
+                instruction(Mnemonic::Esc, true, [Some(opcode), Some(r_m)])
+            }
+            //--------------------------------
+            //  LOOP/JCXZ - Short
+            //--------------------------------
+            0xE0..=0xE3 => {
+                let mnemonic = [
+                    Mnemonic::Loopnz,
+                    Mnemonic::Loopz,
+                    Mnemonic::Loop,
+                    Mnemonic::Jcxz,
+                ][(byte_one & 0b11) as usize];
+                let target = self.relative_byte()?;
+                instruction(mnemonic, false, [Some(target), None])
+            }
+            //--------------------------------
+            //  IN/OUT - Fixed and Variable Port
+            //--------------------------------
+            0xE4 | 0xE5 => {
+                let port = Operand::Immediate(self.read_u8()? as i32);
+                instruction(Mnemonic::In, is_word, [Some(accumulator), Some(port)])
+            }
+            0xE6 | 0xE7 => {
+                let port = Operand::Immediate(self.read_u8()? as i32);
+                instruction(Mnemonic::Out, is_word, [Some(port), Some(accumulator)])
+            }
+            0xEC | 0xED => {
+                let port = Operand::Register(Register::Dx);
+                instruction(Mnemonic::In, is_word, [Some(accumulator), Some(port)])
+            }
+            0xEE | 0xEF => {
+                let port = Operand::Register(Register::Dx);
+                instruction(Mnemonic::Out, is_word, [Some(port), Some(accumulator)])
+            }
+            //--------------------------------
+            //  CALL/JMP - Direct
+            //--------------------------------
+            0xE8 => {
+                let target = self.relative_word()?;
+                instruction(Mnemonic::Call, true, [Some(target), None])
+            }
+            0xE9 => {
+                let target = self.relative_word()?;
+                instruction(Mnemonic::Jmp, true, [Some(target), None])
+            }
+            0xEA => {
+                let target = self.far_pointer()?;
+                instruction(Mnemonic::Jmp, true, [Some(target), None])
+            }
+            0xEB => {
+                let target = self.relative_byte()?;
+                instruction(Mnemonic::Jmp, false, [Some(target), None])
+            }
+            0xF4 => instruction(Mnemonic::Hlt, false, [None, None]),
+            0xF5 => instruction(Mnemonic::Cmc, false, [None, None]),
+            //--------------------------------
+            //  TEST/NOT/NEG/MUL/DIV - Reg/Mem
+            //--------------------------------
+            0xF6 | 0xF7 => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                match reg {
+                    0b000 => {
+                        let data = self.data(is_word)?;
+                        instruction(Mnemonic::Test, is_word, [Some(r_m), Some(data)])
+                    }
+                    0b001 => return Err(self.unsupported(byte_one)),
+                    _ => {
+                        let mnemonic = [
+                            Mnemonic::Not,
+                            Mnemonic::Neg,
+                            Mnemonic::Mul,
+                            Mnemonic::Imul,
+                            Mnemonic::Div,
+                            Mnemonic::Idiv,
+                        ][reg as usize - 2];
+                        instruction(mnemonic, is_word, [Some(r_m), None])
+                    }
+                }
+            }
+            0xF8 => instruction(Mnemonic::Clc, false, [None, None]),
+            0xF9 => instruction(Mnemonic::Stc, false, [None, None]),
+            0xFA => instruction(Mnemonic::Cli, false, [None, None]),
+            0xFB => instruction(Mnemonic::Sti, false, [None, None]),
+            0xFC => instruction(Mnemonic::Cld, false, [None, None]),
+            0xFD => instruction(Mnemonic::Std, false, [None, None]),
+            //--------------------------------
+            //  INC/DEC/CALL/JMP/PUSH - Reg/Mem
+            //--------------------------------
+            0xFE | 0xFF => {
+                let (reg, r_m) = self.mod_reg_r_m(is_word)?;
+                let (mnemonic, far) = match (reg, is_word) {
+                    (0b000, _) => (Mnemonic::Inc, false),
+                    (0b001, _) => (Mnemonic::Dec, false),
+                    (0b010, true) => (Mnemonic::Call, false),
+                    (0b011, true) => (Mnemonic::Call, true),
+                    (0b100, true) => (Mnemonic::Jmp, false),
+                    (0b101, true) => (Mnemonic::Jmp, true),
+                    (0b110, true) => (Mnemonic::Push, false),
+                    _ => return Err(self.unsupported(byte_one)),
+                };
+                if far {
+                    self.memory_only(byte_one, r_m)?;
+                }
+                let mut instruction = instruction(mnemonic, is_word, [Some(r_m), None]);
+                instruction.far = far;
+                instruction
+            }
+            _ => return Err(self.unsupported(byte_one)),
+        };
+
+        Ok(Instruction {
+            offset: self.start,
+            ..instruction
+        })
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<Instruction, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_instruction().transpose()
+    }
+}
+
+fn instruction(mnemonic: Mnemonic, wide: bool, operands: [Option<Operand>; 2]) -> Instruction {
+    Instruction {
+        offset: 0,
+        length: 0,
+        mnemonic,
+        operands,
+        wide,
+        far: false,
+        lock: false,
+        rep: None,
+        segment_override: None,
+    }
+}
+
+/// Decodes all of `bytes` into a `Vec`.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Instruction>, DecodeError> {
+    Decoder::new(bytes).collect()
+}
+
+/// Like `decode`, with addresses starting at `origin` rather than zero.
+pub fn decode_at(bytes: &[u8], origin: usize) -> Result<Vec<Instruction>, DecodeError> {
+    Decoder::with_origin(bytes, origin).collect()
+}
+
+/// Decodes `bytes` without collecting them, handing each instruction and its offset to `visit`.
+///
+/// Returns `ControlFlow::Break` with the visitor's value if it stopped early, or
+/// `ControlFlow::Continue` once all of `bytes` has been decoded.
+pub fn decode_with<B, F>(bytes: &[u8], mut visit: F) -> Result<ControlFlow<B>, DecodeError>
+where
+    F: FnMut(usize, &Instruction) -> ControlFlow<B>,
+{
+    let mut decoder = Decoder::new(bytes);
+    while let Some(instruction) = decoder.next_instruction()? {
+        if let ControlFlow::Break(value) = visit(instruction.offset, &instruction) {
+            return Ok(ControlFlow::Break(value));
+        }
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Decodes everything `reader` holds, as if loaded at `origin`, reading `chunk` bytes at a
+/// time so memory stays the same however long the input is. Each instruction is handed to
+/// `visit` with its bytes. One that straddles two chunks is carried over and decoded whole
+/// once the next is read.
+///
+/// Returns as `decode_with` does.
+pub fn decode_chunked<R, B, F>(
+    mut reader: R,
+    chunk: usize,
+    origin: usize,
+    mut visit: F,
+) -> Result<ControlFlow<B>, DecodeError>
+where
+    R: Read,
+    F: FnMut(&Instruction, &[u8]) -> ControlFlow<B>,
+{
+    let mut buffer = vec![0; chunk.max(1)];
+    // Bytes of `buffer` holding input, and the address of the first of them.
+    let mut filled = 0;
+    let mut base = origin;
+    loop {
+        let mut end_of_input = false;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => {
+                    end_of_input = true;
+                    break;
+                }
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let bytes = &buffer[..filled];
+        let mut decoder = Decoder::with_origin(bytes, base);
+        let decoded = loop {
+            match decoder.next_instruction() {
+                Ok(Some(instruction)) => {
+                    let start = instruction.offset - base;
+                    let encoding = &bytes[start..start + instruction.length];
+                    if let ControlFlow::Break(value) = visit(&instruction, encoding) {
+                        return Ok(ControlFlow::Break(value));
+                    }
+                }
+                Ok(None) => break filled,
+                Err(DecodeError::UnexpectedEof { offset }) if !end_of_input => break offset - base,
+                Err(error) => return Err(error),
+            }
+        };
+        if end_of_input {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        // An instruction as long as the whole chunk (which only endless prefixes make) needs
+        // more room rather than another read into a full buffer.
+        if decoded == 0 {
+            buffer.resize(buffer.len() * 2, 0);
+        }
+        buffer.copy_within(decoded..filled, 0);
+        filled -= decoded;
+        base += decoded;
+    }
+}
+
+/// A boundary between the pieces `decode_parallel` split its input into that fell part-way
+/// through an instruction, so the thread decoding from it started out of step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Disagreement {
+    /// Address the piece started at.
+    pub boundary: usize,
+    /// Address where the piece's instructions came back into step with the ones before, or
+    /// `None` if they never did and the piece was decoded again in order.
+    pub resynchronized: Option<usize>,
+}
+
+/// What `decode_parallel` decoded.
+#[derive(Debug, Default)]
+pub struct ParallelDecode {
+    /// The same instructions `decode_at` would give.
+    pub instructions: Vec<Instruction>,
+    pub disagreements: Vec<Disagreement>,
+}
+
+/// What one thread of `decode_parallel` decoded of its piece.
+#[derive(Default)]
+struct Piece {
+    instructions: Vec<Instruction>,
+    /// Where decoding failed: errors if the piece was in step there, and noise if not.
+    errors: Vec<DecodeError>,
+}
+
+/// Decodes `bytes` as `decode_at` does, split into `threads` pieces decoded at once.
+///
+/// Each thread starts at its piece's first byte, which may be part-way through an instruction.
+/// Variable-length code tends to fall back into step within a few instructions, so a piece is
+/// joined to the one before at the first address both decoded an instruction at; only the
+/// instructions before that are thrown away, and are reported as a `Disagreement`.
+pub fn decode_parallel(
+    bytes: &[u8],
+    origin: usize,
+    threads: usize,
+) -> Result<ParallelDecode, DecodeError> {
+    let threads = threads.clamp(1, bytes.len().max(1));
+    let bounds: Vec<usize> = (0..=threads)
+        .map(|piece| piece * bytes.len() / threads)
+        .collect();
+    let pieces: Vec<Piece> = std::thread::scope(|scope| {
+        let handles: Vec<_> = bounds
+            .windows(2)
+            .map(|range| scope.spawn(move || decode_piece(bytes, origin, range[0], range[1])))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let mut decoded = ParallelDecode::default();
+    for (index, mut piece) in pieces.into_iter().enumerate() {
+        let (boundary, end) = (origin + bounds[index], origin + bounds[index + 1]);
+        let next = decoded
+            .instructions
+            .last()
+            .map_or(origin, |instruction| instruction.end());
+        // Decode in order from where the pieces before ended until in step with this one.
+        let mut decoder = Decoder::with_origin(&bytes[next - origin..], next);
+        loop {
+            let offset = decoder.offset();
+            if offset >= end {
+                if index != 0 && next < end {
+                    decoded.disagreements.push(Disagreement {
+                        boundary,
+                        resynchronized: None,
+                    });
+                }
+                break;
+            }
+            let starts = &piece.instructions;
+            if let Ok(start) =
+                starts.binary_search_by_key(&offset, |instruction| instruction.offset)
+            {
+                if let Some(error) = piece.errors.into_iter().find(|error| match error {
+                    DecodeError::UnexpectedEof { offset: at }
+                    | DecodeError::UnsupportedOpcode { offset: at, .. } => *at >= offset,
+                    DecodeError::Io(_) => true,
+                }) {
+                    return Err(error);
+                }
+                if offset != boundary {
+                    decoded.disagreements.push(Disagreement {
+                        boundary,
+                        resynchronized: Some(offset),
+                    });
+                }
+                decoded
+                    .instructions
+                    .extend(piece.instructions.drain(start..));
+                break;
+            }
+            match decoder.next_instruction()? {
+                Some(instruction) => decoded.instructions.push(instruction),
+                None => break,
+            }
+        }
+    }
+    Ok(decoded)
+}
+
+/// Decodes `bytes` as `decode_at` does, but steps over any byte no instruction can start at,
+/// such as data amongst the code, rather than failing there. Returns the instructions and the
+/// errors where bytes were stepped over.
+pub fn decode_skipping_data(bytes: &[u8], origin: usize) -> (Vec<Instruction>, Vec<DecodeError>) {
+    let piece = decode_piece(bytes, origin, 0, bytes.len());
+    (piece.instructions, piece.errors)
+}
+
+/// Decodes the instructions starting from `start` up to `end` in `bytes`, carrying on past
+/// failures a byte later, since they may only mean the piece started out of step.
+fn decode_piece(bytes: &[u8], origin: usize, start: usize, end: usize) -> Piece {
+    let mut piece = Piece::default();
+    let mut decoder = Decoder::with_origin(&bytes[start..], origin + start);
+    while decoder.offset() < origin + end {
+        match decoder.next_instruction() {
+            Ok(Some(instruction)) => piece.instructions.push(instruction),
+            Ok(None) => break,
+            Err(error) => {
+                piece.errors.push(error);
+                decoder.cursor.seek(decoder.start + 1 - (origin + start));
+            }
+        }
+    }
+    piece
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_decoding_carries_instructions_across_chunks() {
+        // mov cx, 0x1234; add word [bx + si + 0x5678], 0x9abc; rep movsb; jnz -2
+        let code = [
+            0xB9, 0x34, 0x12, 0x81, 0x80, 0x78, 0x56, 0xBC, 0x9A, 0xF3, 0xA4, 0x75, 0xFE,
+        ];
+        let whole = decode_at(&code, 0x100).unwrap();
+        for chunk in 1..=code.len() + 1 {
+            let mut decoded = Vec::new();
+            let result = decode_chunked(&code[..], chunk, 0x100, |instruction, bytes| {
+                let start = instruction.offset - 0x100;
+                assert_eq!(bytes, &code[start..start + instruction.length]);
+                decoded.push(*instruction);
+                ControlFlow::<()>::Continue(())
+            });
+            assert!(matches!(result, Ok(ControlFlow::Continue(()))));
+            assert_eq!(decoded, whole, "{chunk}-byte chunks");
+        }
+
+        let truncated = decode_chunked(&code[..8], 3, 0, |_, _| ControlFlow::<()>::Continue(()));
+        assert!(matches!(
+            truncated,
+            Err(DecodeError::UnexpectedEof { offset: 3 })
+        ));
+    }
+
+    #[test]
+    fn parallel_decoding_matches_decoding_in_order() {
+        // mov cx, 0x1234; add word [bx + si + 0x5678], 0x9abc; rep movsb; jnz -2
+        let code = [
+            0xB9, 0x34, 0x12, 0x81, 0x80, 0x78, 0x56, 0xBC, 0x9A, 0xF3, 0xA4, 0x75, 0xFE,
+        ];
+        let bytes = code.repeat(20);
+        let whole = decode_at(&bytes, 0x100).unwrap();
+        for threads in [1, 2, 3, 5, 8, 13, 64, bytes.len() + 1] {
+            let decoded = decode_parallel(&bytes, 0x100, threads).unwrap();
+            assert_eq!(decoded.instructions, whole, "{threads} threads");
+            for disagreement in decoded.disagreements {
+                assert!(!whole.iter().any(|i| i.offset == disagreement.boundary));
+            }
+        }
+
+        // An unsupported opcode is still an error when a piece starts in step just before it.
+        let mut broken = bytes.clone();
+        broken[130] = 0x60;
+        assert!(matches!(
+            decode_parallel(&broken, 0, 4),
+            Err(DecodeError::UnsupportedOpcode { offset: 130, .. })
+        ));
+    }
+
+    #[test]
+    fn short_encodings_decode_within_their_bytes() {
+        // Every first byte with every MOD/REG/RM byte, and every byte after that, cut short at
+        // each length: each must decode to an instruction no longer than it, or fail cleanly.
+        let mut bytes = [0u8; 3];
+        for prefix in 0..1 << 24 {
+            bytes.copy_from_slice(&(prefix as u32).to_le_bytes()[..3]);
+            for length in 1..=3 {
+                if length < 3 && prefix >> (8 * length) != 0 {
+                    continue;
+                }
+                let code = &bytes[..length];
+                match Decoder::new(code).next_instruction() {
+                    Ok(Some(instruction)) => assert!(
+                        instruction.length <= length,
+                        "{code:02x?} decodes as {} bytes",
+                        instruction.length
+                    ),
+                    Ok(None) => unreachable!(),
+                    Err(DecodeError::UnexpectedEof { offset: 0 })
+                    | Err(DecodeError::UnsupportedOpcode { offset: 0, .. }) => {}
+                    Err(error) => panic!("{code:02x?}: {error}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn addresses_and_far_pointers_only_come_from_memory() {
+        // lea/les/lds ax, [bx] and call/jmp far [bx], each beside its form with BX for [bx].
+        for (memory, register) in [
+            ([0x8D, 0x07], [0x8D, 0xC3]),
+            ([0xC4, 0x07], [0xC4, 0xC3]),
+            ([0xC5, 0x07], [0xC5, 0xC3]),
+            ([0xFF, 0x1F], [0xFF, 0xDB]),
+            ([0xFF, 0x2F], [0xFF, 0xEB]),
+        ] {
+            let instruction = Decoder::new(&memory).next_instruction().unwrap().unwrap();
+            assert_eq!(instruction.length, 2, "{memory:02x?}");
+            assert!(matches!(
+                Decoder::new(&register).next_instruction(),
+                Err(DecodeError::UnsupportedOpcode { offset: 0, opcode }) if opcode == register[0]
+            ));
+        }
+        // A near CALL/JMP through a register is fine.
+        for near in [[0xFF, 0xD3], [0xFF, 0xE3]] {
+            let instruction = Decoder::new(&near).next_instruction().unwrap().unwrap();
+            assert_eq!(
+                instruction.operands[0],
+                Some(Operand::Register(Register::Bx))
+            );
+        }
+    }
+}
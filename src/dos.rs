@@ -0,0 +1,54 @@
+use crate::{
+    instruction::Register,
+    simulator::{physical_address, Cpu},
+};
+use std::io::Write;
+
+/// INT 21h, the DOS function dispatcher.
+pub const DOS_INTERRUPT: u8 = 0x21;
+/// INT 20h, the old way for a .COM program to terminate.
+pub const TERMINATE_INTERRUPT: u8 = 0x20;
+
+/// Hooks just enough of DOS for tiny programs to print and exit, with their output going to
+/// `out`. INT 21h handles:
+///
+/// - AH=00h: terminate.
+/// - AH=02h: print the character in DL.
+/// - AH=09h: print the `$`-terminated string at DS:DX.
+/// - AH=4Ch: terminate with exit code AL.
+///
+/// Other functions are left to the guest's vector. Terminating sets `Cpu::exit_code`.
+pub fn install(cpu: &mut Cpu, mut out: impl Write + 'static) {
+    cpu.hook_interrupt(TERMINATE_INTERRUPT, |cpu| {
+        cpu.exit_code = Some(0);
+        true
+    });
+    cpu.hook_interrupt(DOS_INTERRUPT, move |cpu| {
+        match cpu.registers.get(Register::Ah) {
+            0x00 => cpu.exit_code = Some(0),
+            0x02 => {
+                let character = cpu.registers.get(Register::Dl);
+                let _ = out.write_all(&[character as u8]);
+                cpu.registers.set(Register::Al, character);
+            }
+            0x09 => {
+                let segment = cpu.registers.get(Register::Ds);
+                let start = cpu.registers.get(Register::Dx);
+                // Give up at the end of the segment if there's no `$` before it.
+                let text: Vec<u8> = (0..=u16::MAX)
+                    .map(|index| {
+                        let offset = start.wrapping_add(index);
+                        cpu.memory.read_u8(physical_address(segment, offset))
+                    })
+                    .take_while(|&byte| byte != b'$')
+                    .collect();
+                let _ = out.write_all(&text);
+                cpu.registers.set(Register::Al, b'$' as u16);
+            }
+            0x4C => cpu.exit_code = Some(cpu.registers.get(Register::Al) as u8),
+            _ => return false,
+        }
+        let _ = out.flush();
+        true
+    });
+}
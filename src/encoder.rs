@@ -0,0 +1,781 @@
+use crate::{
+    decoder::{ALU, JCC, SHIFT},
+    instruction::{AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register, Rep},
+};
+use std::{error::Error, fmt};
+
+/// Why an instruction couldn't be encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// No encoding of the mnemonic takes these operands.
+    InvalidOperands,
+    /// A register operand is a byte where `wide` says words, or the other way round.
+    SizeMismatch,
+    /// Immediate data, or a short jump's displacement, doesn't fit the field it's encoded in.
+    OutOfRange,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EncodeError::InvalidOperands => "invalid combination of opcode and operands",
+            EncodeError::SizeMismatch => "mismatch in operand sizes",
+            EncodeError::OutOfRange => "value out of range",
+        })
+    }
+}
+
+impl Error for EncodeError {}
+
+/// The number in a REG field, or R/M field with MOD 11, that selects `register`, or in an SR
+/// field for a segment register.
+pub(crate) fn register_code(register: Register) -> u8 {
+    use Register::*;
+    match register {
+        Al | Ax | Es => 0,
+        Cl | Cx | Cs => 1,
+        Dl | Dx | Ss => 2,
+        Bl | Bx | Ds => 3,
+        Ah | Sp => 4,
+        Ch | Bp => 5,
+        Dh | Si => 6,
+        Bh | Di => 7,
+    }
+}
+
+/// Appends a ModRM byte with `reg` in its REG field, and `r_m`'s MOD and R/M fields and
+/// displacement, the shortest that gives the address.
+fn mod_r_m(bytes: &mut Vec<u8>, reg: u8, r_m: Operand) -> Result<(), EncodeError> {
+    let address = match r_m {
+        Operand::Register(register) if !register.is_segment() => {
+            bytes.push(0b11 << 6 | reg << 3 | register_code(register));
+            return Ok(());
+        }
+        Operand::Memory(address) => address,
+        _ => return Err(EncodeError::InvalidOperands),
+    };
+    let displacement = address.displacement;
+    let r_m = match address.base {
+        AddressBase::Direct => {
+            bytes.push(reg << 3 | 0b110);
+            bytes.extend(displacement.to_le_bytes());
+            return Ok(());
+        }
+        AddressBase::BxSi => 0,
+        AddressBase::BxDi => 1,
+        AddressBase::BpSi => 2,
+        AddressBase::BpDi => 3,
+        AddressBase::Si => 4,
+        AddressBase::Di => 5,
+        AddressBase::Bp => 6,
+        AddressBase::Bx => 7,
+    };
+    match EffectiveAddress::new(address.base, displacement).displacement_bytes {
+        0 => bytes.push(reg << 3 | r_m),
+        1 => bytes.extend([0b01 << 6 | reg << 3 | r_m, displacement as u8]),
+        _ => {
+            bytes.push(0b10 << 6 | reg << 3 | r_m);
+            bytes.extend(displacement.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Appends immediate data, a word if `wide`.
+pub(crate) fn data(bytes: &mut Vec<u8>, value: i32, wide: bool) -> Result<(), EncodeError> {
+    match wide {
+        true if (-0x8000..=0xFFFF).contains(&value) => bytes.extend((value as u16).to_le_bytes()),
+        false if (-0x80..=0xFF).contains(&value) => bytes.push(value as u8),
+        _ => return Err(EncodeError::OutOfRange),
+    }
+    Ok(())
+}
+
+fn is_r_m(operand: Operand) -> bool {
+    match operand {
+        Operand::Register(register) => !register.is_segment(),
+        Operand::Memory(_) => true,
+        _ => false,
+    }
+}
+
+fn is_accumulator(operand: Operand) -> bool {
+    matches!(operand, Operand::Register(Register::Al | Register::Ax))
+}
+
+/// Whether the operand at `index` of `mnemonic` is a register that doesn't give the operation's
+/// size: a shift count in CL, or a port number in DX.
+pub(crate) fn is_count_or_port(mnemonic: Mnemonic, index: usize) -> bool {
+    use Mnemonic::*;
+    matches!(
+        (mnemonic, index),
+        (Shl | Shr | Sar | Rol | Ror | Rcl | Rcr, 1) | (In, 1) | (Out, 0)
+    )
+}
+
+/// Encodes `instruction` in its shortest form, the one NASM picks: `decode` reads the bytes
+/// back as the same instruction, but for its length, the size of any displacement, and the
+/// order of XCHG's operands.
+///
+/// The instruction's `offset` and `length` are ignored; a `Relative` operand is a displacement
+/// from the end of the encoding, which is as long for any displacement that fits.
+pub fn encode(instruction: &Instruction) -> Result<Vec<u8>, EncodeError> {
+    encode_sized(instruction, None)
+}
+
+/// Encodes `instruction` as `encode` does, except that if `sign_extended` is given it chooses
+/// between a word ALU instruction's sign-extended byte of data and a full word of it, as
+/// NASM's BYTE and WORD keywords on the data do.
+pub(crate) fn encode_sized(
+    instruction: &Instruction,
+    sign_extended: Option<bool>,
+) -> Result<Vec<u8>, EncodeError> {
+    use Mnemonic::*;
+    use Operand::{Far, Immediate, Memory, Register as Reg, Relative};
+
+    let wide = instruction.wide;
+    let w = wide as u8;
+    let mnemonic = instruction.mnemonic;
+    for (index, operand) in instruction.operands.iter().enumerate() {
+        if let Some(Reg(register)) = operand {
+            if !register.is_segment()
+                && register.is_word() != wide
+                && !is_count_or_port(mnemonic, index)
+            {
+                return Err(EncodeError::SizeMismatch);
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    if instruction.lock {
+        bytes.push(0xF0);
+    }
+    match instruction.rep {
+        Some(Rep::Rep) => bytes.push(0xF3),
+        Some(Rep::Repne) => bytes.push(0xF2),
+        None => {}
+    }
+    match instruction.segment_override {
+        Some(segment) if segment.is_segment() => bytes.push(0x26 | register_code(segment) << 3),
+        Some(_) => return Err(EncodeError::InvalidOperands),
+        None => {}
+    }
+
+    let operands = (instruction.operands[0], instruction.operands[1]);
+    let alu = ALU.iter().position(|&alu| alu == mnemonic);
+    let shift = SHIFT.iter().position(|&shift| shift == Some(mnemonic));
+    let jcc = JCC.iter().position(|&jcc| jcc == mnemonic);
+    let group = |mnemonic| match mnemonic {
+        Not => Some(2),
+        Neg => Some(3),
+        Mul => Some(4),
+        Imul => Some(5),
+        Div => Some(6),
+        Idiv => Some(7),
+        _ => None,
+    };
+    let string = |mnemonic| match mnemonic {
+        Movs => Some(0xA4),
+        Cmps => Some(0xA6),
+        Stos => Some(0xAA),
+        Lods => Some(0xAC),
+        Scas => Some(0xAE),
+        _ => None,
+    };
+    let fixed = |mnemonic| match mnemonic {
+        Daa => Some(0x27),
+        Das => Some(0x2F),
+        Aaa => Some(0x37),
+        Aas => Some(0x3F),
+        Cbw => Some(0x98),
+        Cwd => Some(0x99),
+        Wait => Some(0x9B),
+        Pushf => Some(0x9C),
+        Popf => Some(0x9D),
+        Sahf => Some(0x9E),
+        Lahf => Some(0x9F),
+        Ret => Some(0xC3),
+        Retf => Some(0xCB),
+        Int3 => Some(0xCC),
+        Into => Some(0xCE),
+        Iret => Some(0xCF),
+        Xlat => Some(0xD7),
+        Hlt => Some(0xF4),
+        Cmc => Some(0xF5),
+        Clc => Some(0xF8),
+        Stc => Some(0xF9),
+        Cli => Some(0xFA),
+        Sti => Some(0xFB),
+        Cld => Some(0xFC),
+        Std => Some(0xFD),
+        _ => None,
+    };
+    let short = |displacement: i16| i8::try_from(displacement).map_err(|_| EncodeError::OutOfRange);
+
+    match (mnemonic, operands) {
+        (_, (Some(dst), Some(Reg(src)))) if alu.is_some() && is_r_m(dst) && !src.is_segment() => {
+            bytes.push((alu.unwrap() as u8) << 3 | w);
+            mod_r_m(&mut bytes, register_code(src), dst)?;
+        }
+        (_, (Some(Reg(dst)), Some(src @ Memory(_)))) if alu.is_some() => {
+            bytes.push((alu.unwrap() as u8) << 3 | 0b10 | w);
+            mod_r_m(&mut bytes, register_code(dst), src)?;
+        }
+        (_, (Some(dst), Some(Immediate(value)))) if alu.is_some() && is_r_m(dst) => {
+            let n = alu.unwrap() as u8;
+            let fits = i8::try_from(value as u16 as i16).is_ok();
+            let sign_extended = wide
+                && match sign_extended {
+                    Some(true) if !fits => return Err(EncodeError::OutOfRange),
+                    Some(sign_extended) => sign_extended,
+                    None => fits,
+                };
+            if sign_extended {
+                bytes.push(0x83);
+                mod_r_m(&mut bytes, n, dst)?;
+                bytes.push(value as u8);
+            } else if is_accumulator(dst) {
+                bytes.push(n << 3 | 0b100 | w);
+                data(&mut bytes, value, wide)?;
+            } else {
+                bytes.push(0x80 | w);
+                mod_r_m(&mut bytes, n, dst)?;
+                data(&mut bytes, value, wide)?;
+            }
+        }
+
+        (Mov, (Some(Reg(sr)), Some(src))) if sr.is_segment() && is_r_m(src) => {
+            bytes.push(0x8E);
+            mod_r_m(&mut bytes, register_code(sr), src)?;
+        }
+        (Mov, (Some(dst), Some(Reg(sr)))) if sr.is_segment() && is_r_m(dst) => {
+            bytes.push(0x8C);
+            mod_r_m(&mut bytes, register_code(sr), dst)?;
+        }
+        (Mov, (Some(acc), Some(Memory(address))))
+            if is_accumulator(acc) && address.base == AddressBase::Direct =>
+        {
+            bytes.push(0xA0 | w);
+            bytes.extend(address.displacement.to_le_bytes());
+        }
+        (Mov, (Some(Memory(address)), Some(acc)))
+            if is_accumulator(acc) && address.base == AddressBase::Direct =>
+        {
+            bytes.push(0xA2 | w);
+            bytes.extend(address.displacement.to_le_bytes());
+        }
+        (Mov, (Some(Reg(dst)), Some(Immediate(value)))) if !dst.is_segment() => {
+            bytes.push(0xB0 | w << 3 | register_code(dst));
+            data(&mut bytes, value, wide)?;
+        }
+        (Mov, (Some(dst @ Memory(_)), Some(Immediate(value)))) => {
+            bytes.push(0xC6 | w);
+            mod_r_m(&mut bytes, 0, dst)?;
+            data(&mut bytes, value, wide)?;
+        }
+        (Mov, (Some(dst), Some(Reg(src)))) if is_r_m(dst) => {
+            bytes.push(0x88 | w);
+            mod_r_m(&mut bytes, register_code(src), dst)?;
+        }
+        (Mov, (Some(Reg(dst)), Some(src @ Memory(_)))) => {
+            bytes.push(0x8A | w);
+            mod_r_m(&mut bytes, register_code(dst), src)?;
+        }
+
+        (Push, (Some(Reg(sr)), None)) if sr.is_segment() => {
+            bytes.push(0x06 | register_code(sr) << 3)
+        }
+        (Pop, (Some(Reg(sr)), None)) if sr.is_segment() => {
+            bytes.push(0x07 | register_code(sr) << 3)
+        }
+        (Push, (Some(Reg(register)), None)) if wide => bytes.push(0x50 | register_code(register)),
+        (Pop, (Some(Reg(register)), None)) if wide => bytes.push(0x58 | register_code(register)),
+        (Push, (Some(src @ Memory(_)), None)) if wide => {
+            bytes.push(0xFF);
+            mod_r_m(&mut bytes, 6, src)?;
+        }
+        (Pop, (Some(dst @ Memory(_)), None)) if wide => {
+            bytes.push(0x8F);
+            mod_r_m(&mut bytes, 0, dst)?;
+        }
+
+        (Xchg, (Some(Reg(Register::Ax)), Some(Reg(register))))
+        | (Xchg, (Some(Reg(register)), Some(Reg(Register::Ax)))) => {
+            bytes.push(0x90 | register_code(register));
+        }
+        (Xchg, (Some(Reg(reg)), Some(r_m))) | (Xchg, (Some(r_m @ Memory(_)), Some(Reg(reg)))) => {
+            bytes.push(0x86 | w);
+            mod_r_m(&mut bytes, register_code(reg), r_m)?;
+        }
+
+        (In, (Some(acc), Some(Immediate(port)))) if is_accumulator(acc) => {
+            bytes.push(0xE4 | w);
+            data(&mut bytes, port, false)?;
+        }
+        (In, (Some(acc), Some(Reg(Register::Dx)))) if is_accumulator(acc) => bytes.push(0xEC | w),
+        (Out, (Some(Immediate(port)), Some(acc))) if is_accumulator(acc) => {
+            bytes.push(0xE6 | w);
+            data(&mut bytes, port, false)?;
+        }
+        (Out, (Some(Reg(Register::Dx)), Some(acc))) if is_accumulator(acc) => bytes.push(0xEE | w),
+
+        (Lea | Lds | Les, (Some(Reg(reg)), Some(src @ Memory(_)))) if wide => {
+            bytes.push(match mnemonic {
+                Lea => 0x8D,
+                Lds => 0xC5,
+                _ => 0xC4,
+            });
+            mod_r_m(&mut bytes, register_code(reg), src)?;
+        }
+
+        (Inc, (Some(Reg(register)), None)) if wide => bytes.push(0x40 | register_code(register)),
+        (Dec, (Some(Reg(register)), None)) if wide => bytes.push(0x48 | register_code(register)),
+        (Inc | Dec, (Some(dst), None)) if is_r_m(dst) => {
+            bytes.push(0xFE | w);
+            mod_r_m(&mut bytes, (mnemonic == Dec) as u8, dst)?;
+        }
+        (_, (Some(dst), None)) if group(mnemonic).is_some() && is_r_m(dst) => {
+            bytes.push(0xF6 | w);
+            mod_r_m(&mut bytes, group(mnemonic).unwrap(), dst)?;
+        }
+        (Aam, (None, None)) => bytes.extend([0xD4, 0x0A]),
+        (Aad, (None, None)) => bytes.extend([0xD5, 0x0A]),
+
+        (Test, (Some(acc), Some(Immediate(value)))) if is_accumulator(acc) => {
+            bytes.push(0xA8 | w);
+            data(&mut bytes, value, wide)?;
+        }
+        (Test, (Some(dst), Some(Immediate(value)))) if is_r_m(dst) => {
+            bytes.push(0xF6 | w);
+            mod_r_m(&mut bytes, 0, dst)?;
+            data(&mut bytes, value, wide)?;
+        }
+        (Test, (Some(r_m), Some(Reg(reg)))) | (Test, (Some(Reg(reg)), Some(r_m @ Memory(_))))
+            if is_r_m(r_m) && !reg.is_segment() =>
+        {
+            bytes.push(0x84 | w);
+            mod_r_m(&mut bytes, register_code(reg), r_m)?;
+        }
+
+        (_, (Some(dst), Some(Immediate(1)))) if shift.is_some() => {
+            bytes.push(0xD0 | w);
+            mod_r_m(&mut bytes, shift.unwrap() as u8, dst)?;
+        }
+        (_, (Some(dst), Some(Reg(Register::Cl)))) if shift.is_some() => {
+            bytes.push(0xD2 | w);
+            mod_r_m(&mut bytes, shift.unwrap() as u8, dst)?;
+        }
+
+        (_, (None, None)) if string(mnemonic).is_some() => {
+            bytes.push(string(mnemonic).unwrap() | w)
+        }
+
+        (Call, (Some(Relative(displacement)), None)) => {
+            bytes.push(0xE8);
+            bytes.extend(displacement.to_le_bytes());
+        }
+        (Jmp, (Some(Relative(displacement)), None)) if wide => {
+            bytes.push(0xE9);
+            bytes.extend(displacement.to_le_bytes());
+        }
+        (Jmp, (Some(Relative(displacement)), None)) => {
+            bytes.extend([0xEB, short(displacement)? as u8]);
+        }
+        (Call | Jmp, (Some(Far { segment, offset }), None)) => {
+            bytes.push(if mnemonic == Call { 0x9A } else { 0xEA });
+            bytes.extend(offset.to_le_bytes());
+            bytes.extend(segment.to_le_bytes());
+        }
+        (Call | Jmp, (Some(target), None))
+            if is_r_m(target) && (!instruction.far || matches!(target, Memory(_))) =>
+        {
+            let reg = match (mnemonic, instruction.far) {
+                (Call, false) => 2,
+                (Call, true) => 3,
+                (_, false) => 4,
+                (_, true) => 5,
+            };
+            bytes.push(0xFF);
+            mod_r_m(&mut bytes, reg, target)?;
+        }
+        (Ret | Retf, (Some(Immediate(value)), None)) => {
+            bytes.push(if mnemonic == Ret { 0xC2 } else { 0xCA });
+            data(&mut bytes, value, true)?;
+        }
+        (_, (Some(Relative(displacement)), None)) if jcc.is_some() => {
+            bytes.extend([0x70 | jcc.unwrap() as u8, short(displacement)? as u8]);
+        }
+        (Loopnz | Loopz | Loop | Jcxz, (Some(Relative(displacement)), None)) => {
+            let opcode = match mnemonic {
+                Loopnz => 0xE0,
+                Loopz => 0xE1,
+                Loop => 0xE2,
+                _ => 0xE3,
+            };
+            bytes.extend([opcode, short(displacement)? as u8]);
+        }
+        (Int, (Some(Immediate(vector)), None)) => {
+            bytes.push(0xCD);
+            data(&mut bytes, vector, false)?;
+        }
+        (Esc, (Some(Immediate(opcode @ 0..=0x3F)), Some(src))) if is_r_m(src) => {
+            bytes.push(0xD8 | (opcode >> 3) as u8);
+            mod_r_m(&mut bytes, opcode as u8 & 0b111, src)?;
+        }
+        (_, (None, None)) if fixed(mnemonic).is_some() => bytes.push(fixed(mnemonic).unwrap()),
+        _ => return Err(EncodeError::InvalidOperands),
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+
+    /// SplitMix64, as `ProgramGenerator` uses, seeded the same each run.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+    }
+
+    fn instruction(mnemonic: Mnemonic, wide: bool, operands: [Option<Operand>; 2]) -> Instruction {
+        Instruction {
+            offset: 0,
+            length: 0,
+            mnemonic,
+            operands,
+            wide,
+            far: false,
+            lock: false,
+            rep: None,
+            segment_override: None,
+        }
+    }
+
+    /// Decodes `encoding`, checking it's exactly one instruction.
+    fn decode(encoding: &[u8]) -> Instruction {
+        let decoded = Decoder::new(encoding).next_instruction().unwrap().unwrap();
+        assert_eq!(decoded.length, encoding.len(), "{encoding:02X?}");
+        decoded
+    }
+
+    /// `instruction` with any displacement at its shortest.
+    fn shortest(instruction: &Instruction) -> Instruction {
+        let mut shortest = *instruction;
+        for operand in shortest.operands.iter_mut().flatten() {
+            if let Operand::Memory(address) = operand {
+                *address = EffectiveAddress::new(address.base, address.displacement);
+            }
+        }
+        shortest
+    }
+
+    /// Whether `actual` is `expected` but for its length, the size of any displacement, and the
+    /// order of XCHG's operands.
+    fn same(expected: &Instruction, actual: &Instruction) -> bool {
+        let expected = shortest(expected);
+        let mut actual = Instruction {
+            length: expected.length,
+            ..shortest(actual)
+        };
+        if actual.mnemonic == Mnemonic::Xchg && actual != expected {
+            actual.operands.reverse();
+        }
+        actual == expected
+    }
+
+    #[test]
+    fn decoded_instructions_encode_to_no_more_bytes_that_decode_the_same() {
+        let mut random = Random(0x8086);
+        for _ in 0..100_000 {
+            let bytes = random.next().to_le_bytes();
+            let Ok(Some(instruction)) = Decoder::new(&bytes).next_instruction() else {
+                continue;
+            };
+            let encoding = encode(&instruction)
+                .unwrap_or_else(|error| panic!("{bytes:02X?}: {error} for {instruction:?}"));
+            assert!(encoding.len() <= instruction.length, "{bytes:02X?}");
+            let decoded = decode(&encoding);
+            assert!(
+                same(&instruction, &decoded),
+                "{bytes:02X?} encoded as {encoding:02X?}, which is {decoded:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn addresses_take_the_shortest_displacement() {
+        let bases = [
+            AddressBase::BxSi,
+            AddressBase::BxDi,
+            AddressBase::BpSi,
+            AddressBase::BpDi,
+            AddressBase::Si,
+            AddressBase::Di,
+            AddressBase::Bp,
+            AddressBase::Bx,
+            AddressBase::Direct,
+        ];
+        for base in bases {
+            for displacement in [0, 1, -1, 127, -128, 128, -129, i16::MAX, i16::MIN] {
+                // mov [base + displacement], dx
+                let address = EffectiveAddress::new(base, displacement);
+                let mov = instruction(
+                    Mnemonic::Mov,
+                    true,
+                    [
+                        Some(Operand::Memory(address)),
+                        Some(Operand::Register(Register::Dx)),
+                    ],
+                );
+                let encoding = encode(&mov).unwrap();
+                let length = match (base, displacement) {
+                    (AddressBase::Direct, _) => 4,
+                    (AddressBase::Bp, 0) => 3,
+                    (_, 0) => 2,
+                    (_, -128..=127) => 3,
+                    _ => 4,
+                };
+                assert_eq!(encoding.len(), length, "{address:?}");
+                assert!(same(&mov, &decode(&encoding)), "{address:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn word_data_is_sign_extended_from_a_byte_where_it_fits() {
+        let bx = Operand::Memory(EffectiveAddress::new(AddressBase::Bx, 0));
+        for (value, opcode) in [(-129, 0x81), (-128, 0x83), (127, 0x83), (128, 0x81)] {
+            // add word [bx], value
+            let add = instruction(
+                Mnemonic::Add,
+                true,
+                [Some(bx), Some(Operand::Immediate(value))],
+            );
+            let encoding = encode(&add).unwrap();
+            assert_eq!(encoding[0], opcode, "{value}");
+            assert!(same(&add, &decode(&encoding)), "{value}");
+        }
+    }
+
+    #[test]
+    fn invalid_instructions_are_refused() {
+        let register = |register| Some(Operand::Register(register));
+        let mov = instruction(
+            Mnemonic::Mov,
+            false,
+            [register(Register::Al), register(Register::Bx)],
+        );
+        assert_eq!(encode(&mov), Err(EncodeError::SizeMismatch));
+
+        let mov = instruction(
+            Mnemonic::Mov,
+            true,
+            [Some(Operand::Immediate(1)), register(Register::Ax)],
+        );
+        assert_eq!(encode(&mov), Err(EncodeError::InvalidOperands));
+
+        let int = instruction(Mnemonic::Int, false, [Some(Operand::Immediate(256)), None]);
+        assert_eq!(encode(&int), Err(EncodeError::OutOfRange));
+
+        let jmp = instruction(Mnemonic::Jmp, false, [Some(Operand::Relative(200)), None]);
+        assert_eq!(encode(&jmp), Err(EncodeError::OutOfRange));
+    }
+
+    /// Strategies for instructions the decoder could have produced, built up from operands.
+    mod generate {
+        use super::*;
+        use proptest::{prelude::*, sample::select};
+
+        const BASES: [AddressBase; 9] = [
+            AddressBase::BxSi,
+            AddressBase::BxDi,
+            AddressBase::BpSi,
+            AddressBase::BpDi,
+            AddressBase::Si,
+            AddressBase::Di,
+            AddressBase::Bp,
+            AddressBase::Bx,
+            AddressBase::Direct,
+        ];
+
+        /// Where a value stops fitting in a sign-extended byte, or a word.
+        const EDGES: [i16; 9] = [0, 1, -1, 127, -128, 128, -129, i16::MAX, i16::MIN];
+
+        /// Where a displacement changes size, as well as anywhere at all.
+        fn displacement() -> impl Strategy<Value = i16> {
+            prop_oneof![
+                select(EDGES.to_vec()),
+                any::<i8>().prop_map(i16::from),
+                any::<i16>(),
+            ]
+        }
+
+        /// Every addressing mode, `[BP]` and direct addresses included, with the displacement
+        /// size the decoder records for it.
+        pub fn address() -> impl Strategy<Value = EffectiveAddress> {
+            (select(BASES.to_vec()), displacement())
+                .prop_map(|(base, displacement)| EffectiveAddress::new(base, displacement))
+        }
+
+        pub fn register(wide: bool) -> impl Strategy<Value = Register> {
+            (0..8u8).prop_map(move |code| Register::from_reg(code, wide))
+        }
+
+        pub fn r_m(wide: bool) -> impl Strategy<Value = Operand> {
+            prop_oneof![
+                register(wide).prop_map(Operand::Register),
+                address().prop_map(Operand::Memory),
+            ]
+        }
+
+        /// Data as the decoder sign-extends it, weighted towards the edges of a byte, since
+        /// word data that fits one can be encoded in one.
+        pub fn immediate(wide: bool) -> BoxedStrategy<Operand> {
+            let value = if wide {
+                prop_oneof![
+                    select(EDGES.to_vec()).prop_map(i32::from),
+                    any::<i8>().prop_map(i32::from),
+                    any::<i16>().prop_map(i32::from)
+                ]
+                .boxed()
+            } else {
+                any::<i8>().prop_map(i32::from).boxed()
+            };
+            value.prop_map(Operand::Immediate).boxed()
+        }
+
+        fn build(
+            mnemonic: Mnemonic,
+            wide: bool,
+            first: Operand,
+            second: Option<Operand>,
+        ) -> Instruction {
+            super::instruction(mnemonic, wide, [Some(first), second])
+        }
+
+        /// ALU operations and MOV, register or memory with register or data.
+        fn binary() -> impl Strategy<Value = Instruction> {
+            let mnemonics: Vec<Mnemonic> = ALU.iter().copied().chain([Mnemonic::Mov]).collect();
+            (select(mnemonics), any::<bool>()).prop_flat_map(|(mnemonic, wide)| {
+                prop_oneof![
+                    (register(wide), r_m(wide)).prop_map(move |(register, r_m)| build(
+                        mnemonic,
+                        wide,
+                        Operand::Register(register),
+                        Some(r_m)
+                    )),
+                    (address(), register(wide)).prop_map(move |(address, register)| build(
+                        mnemonic,
+                        wide,
+                        Operand::Memory(address),
+                        Some(Operand::Register(register))
+                    )),
+                    (r_m(wide), immediate(wide)).prop_map(move |(r_m, data)| build(
+                        mnemonic,
+                        wide,
+                        r_m,
+                        Some(data)
+                    )),
+                ]
+            })
+        }
+
+        /// The group 3 and INC/DEC operations on one register or memory operand.
+        fn unary() -> impl Strategy<Value = Instruction> {
+            use Mnemonic::*;
+            let mnemonics = vec![Inc, Dec, Neg, Not, Mul, Imul, Div, Idiv];
+            (select(mnemonics), any::<bool>()).prop_flat_map(|(mnemonic, wide)| {
+                r_m(wide).prop_map(move |r_m| build(mnemonic, wide, r_m, None))
+            })
+        }
+
+        /// Shifts and rotates by one or by CL.
+        fn shift() -> impl Strategy<Value = Instruction> {
+            let mnemonics: Vec<Mnemonic> = SHIFT.iter().flatten().copied().collect();
+            let counts = vec![Operand::Immediate(1), Operand::Register(Register::Cl)];
+            (select(mnemonics), any::<bool>(), select(counts)).prop_flat_map(
+                |(mnemonic, wide, count)| {
+                    r_m(wide).prop_map(move |r_m| build(mnemonic, wide, r_m, Some(count)))
+                },
+            )
+        }
+
+        /// Jumps by a displacement: a byte of it for the short forms, a word for the near ones.
+        fn relative() -> impl Strategy<Value = Instruction> {
+            let short: Vec<Mnemonic> = JCC
+                .iter()
+                .copied()
+                .chain([Mnemonic::Jmp, Mnemonic::Loop, Mnemonic::Jcxz])
+                .collect();
+            prop_oneof![
+                (select(short), any::<i8>()).prop_map(|(mnemonic, displacement)| build(
+                    mnemonic,
+                    false,
+                    Operand::Relative(displacement.into()),
+                    None
+                )),
+                (select(vec![Mnemonic::Jmp, Mnemonic::Call]), any::<i16>()).prop_map(
+                    |(mnemonic, displacement)| build(
+                        mnemonic,
+                        true,
+                        Operand::Relative(displacement),
+                        None
+                    )
+                ),
+            ]
+        }
+
+        pub fn any_instruction() -> impl Strategy<Value = Instruction> {
+            prop_oneof![
+                4 => binary(),
+                1 => unary(),
+                1 => shift(),
+                1 => relative(),
+                1 => (register(true), address()).prop_map(|(register, address)| build(
+                    Mnemonic::Lea,
+                    true,
+                    Operand::Register(register),
+                    Some(Operand::Memory(address))
+                )),
+            ]
+        }
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(4096))]
+
+        #[test]
+        fn generated_instructions_decode_back_the_same(instruction in generate::any_instruction()) {
+            let encoding = encode(&instruction)
+                .unwrap_or_else(|error| panic!("{error} for {instruction:?}"));
+            let decoded = decode(&encoding);
+            proptest::prop_assert!(
+                same(&instruction, &decoded),
+                "encoded as {encoding:02X?}, which is {decoded:?}"
+            );
+        }
+
+        /// Every address comes back with the displacement it had, at the size it was given.
+        #[test]
+        fn generated_addresses_decode_back_exactly(address in generate::address()) {
+            // mov [address], ax
+            let mov = instruction(
+                Mnemonic::Mov,
+                true,
+                [Some(Operand::Memory(address)), Some(Operand::Register(Register::Ax))],
+            );
+            let decoded = decode(&encode(&mov).unwrap());
+            proptest::prop_assert_eq!(decoded.operands[0], Some(Operand::Memory(address)));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{metrics::estimate_cpu_timer_frequency, os_threads::physical_cores};
+use std::{fmt, time::Duration};
+
+/// What a benchmark ran on, and anything about it likely to make results vary from run to run,
+/// to print before them.
+#[derive(Clone, Debug, Default)]
+pub struct Environment {
+    /// The CPU's brand string, if it has one.
+    pub cpu: Option<String>,
+    /// `read_cpu_timer` ticks a second.
+    pub timer_frequency: u64,
+    pub physical_cores: Option<usize>,
+    pub logical_cpus: Option<usize>,
+    pub warnings: Vec<String>,
+}
+
+impl Environment {
+    /// Looks at this machine, taking a tenth of a second to estimate the timer's frequency.
+    pub fn detect() -> Environment {
+        let mut warnings = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::x86_64::__cpuid;
+            // Bit 31 of ECX in leaf 1 says there's a hypervisor, and bit 8 of EDX in leaf
+            // 0x80000007 that the timestamp counter keeps one rate whatever the clock does.
+            if __cpuid(1).ecx & (1 << 31) != 0 {
+                warnings.push("running in a VM, which may share the CPU and hide its counters");
+            }
+            if __cpuid(0x8000_0000).eax < 0x8000_0007 || __cpuid(0x8000_0007).edx & (1 << 8) == 0 {
+                warnings.push("the CPU timer isn't invariant, so it speeds up and slows down");
+            }
+        }
+        let mut warnings: Vec<String> = warnings.into_iter().map(String::from).collect();
+        warnings.extend(platform::warnings());
+        Environment {
+            cpu: cpu_brand(),
+            timer_frequency: estimate_cpu_timer_frequency(Duration::from_millis(100)),
+            physical_cores: physical_cores().ok().map(|cores| cores.len()),
+            logical_cpus: std::thread::available_parallelism()
+                .ok()
+                .map(|cpus| cpus.get()),
+            warnings,
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown =
+            |count: Option<usize>| count.map_or("?".to_string(), |count| count.to_string());
+        writeln!(f, "CPU: {}", self.cpu.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "CPU timer: {:.3}GHz", self.timer_frequency as f64 / 1e9)?;
+        writeln!(
+            f,
+            "Cores: {} physical, {} logical",
+            unknown(self.physical_cores),
+            unknown(self.logical_cpus)
+        )?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The brand string from CPUID on x86-64, or /proc/cpuinfo's model name on Linux.
+fn cpu_brand() -> Option<String> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::x86_64::__cpuid;
+        if __cpuid(0x8000_0000).eax >= 0x8000_0004 {
+            let bytes: Vec<u8> = (0x8000_0002..=0x8000_0004)
+                .flat_map(|leaf| {
+                    let registers = __cpuid(leaf);
+                    [registers.eax, registers.ebx, registers.ecx, registers.edx]
+                })
+                .flat_map(u32::to_le_bytes)
+                .take_while(|&byte| byte != 0)
+                .collect();
+            let brand = String::from_utf8_lossy(&bytes).trim().to_string();
+            if !brand.is_empty() {
+                return Some(brand);
+            }
+        }
+    }
+    platform::model_name()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use std::fs;
+
+    pub fn model_name() -> Option<String> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == "model name").then(|| value.trim().to_string())
+        })
+    }
+
+    /// What the cpufreq governor and turbo settings in sysfs say about the clock.
+    pub fn warnings() -> Vec<String> {
+        let read = |path: &str| {
+            fs::read_to_string(path)
+                .ok()
+                .map(|text| text.trim().to_string())
+        };
+        let mut warnings = Vec::new();
+        if let Some(governor) = read("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor") {
+            if governor != "performance" {
+                warnings.push(format!(
+                    "the cpufreq governor is {governor}, not performance, so the clock may \
+                     change mid-test"
+                ));
+            }
+        }
+        let turbo = match read("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+            Some(no_turbo) => no_turbo == "0",
+            None => read("/sys/devices/system/cpu/cpufreq/boost").is_some_and(|boost| boost == "1"),
+        };
+        if turbo {
+            warnings.push("turbo boost is on, so the clock depends on temperature and load".into());
+        }
+        warnings
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{ffi::c_void, ptr};
+
+    /// A GUID, as its 16 bytes.
+    type Guid = [u8; 16];
+
+    /// The High Performance and Ultimate Performance power schemes.
+    const FAST_SCHEMES: [Guid; 2] = [
+        guid(
+            0x8c5e7fda,
+            0xe8bf,
+            0x4a96,
+            [0x9a, 0x85, 0xa6, 0xe2, 0x3a, 0x8c, 0x63, 0x5c],
+        ),
+        guid(
+            0xe9a42b02,
+            0xd5df,
+            0x448d,
+            [0xaa, 0x00, 0x03, 0xf1, 0x47, 0x49, 0xeb, 0x61],
+        ),
+    ];
+
+    const fn guid(first: u32, second: u16, third: u16, rest: [u8; 8]) -> Guid {
+        let [a, b, c, d] = first.to_le_bytes();
+        let [e, f] = second.to_le_bytes();
+        let [g, h] = third.to_le_bytes();
+        let [i, j, k, l, m, n, o, p] = rest;
+        [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p]
+    }
+
+    #[link(name = "powrprof")]
+    extern "system" {
+        fn PowerGetActiveScheme(root: *mut c_void, scheme: *mut *mut Guid) -> u32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LocalFree(memory: *mut c_void) -> *mut c_void;
+    }
+
+    pub fn model_name() -> Option<String> {
+        None
+    }
+
+    /// Whether the active power plan is one that keeps the clock up.
+    pub fn warnings() -> Vec<String> {
+        let mut scheme = ptr::null_mut();
+        // SAFETY: it only writes `scheme`, with memory for us to free with LocalFree.
+        if unsafe { PowerGetActiveScheme(ptr::null_mut(), &mut scheme) } != 0 {
+            return Vec::new();
+        }
+        // SAFETY: it succeeded, so `scheme` points to a GUID, which only we free.
+        let active = unsafe {
+            let active = *scheme;
+            LocalFree(scheme.cast());
+            active
+        };
+        if FAST_SCHEMES.contains(&active) {
+            Vec::new()
+        } else {
+            vec![
+                "the power plan isn't High or Ultimate Performance, so the clock may change \
+                  mid-test"
+                    .to_string(),
+            ]
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+mod platform {
+    pub fn model_name() -> Option<String> {
+        None
+    }
+
+    pub fn warnings() -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_environment_report_names_the_timer() {
+        let environment = Environment::detect();
+        assert!(environment.timer_frequency > 0);
+        let report = environment.to_string();
+        assert!(report.starts_with("CPU: "), "{report}");
+        assert!(report.contains("\nCPU timer: "), "{report}");
+    }
+}
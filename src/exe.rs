@@ -0,0 +1,141 @@
+use crate::memory::Memory;
+use std::{error::Error, fmt};
+
+/// Size of the fixed part of an MZ header.
+const HEADER_SIZE: usize = 0x1C;
+
+/// Whether `file` starts with an MZ executable's signature. DOS accepts it either way round.
+pub fn is_exe(file: &[u8]) -> bool {
+    file.starts_with(b"MZ") || file.starts_with(b"ZM")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExeError {
+    /// The file ends inside the header or its relocation table.
+    Truncated,
+    /// The header claims to be larger than the image it's part of.
+    HeaderTooLarge,
+}
+
+impl fmt::Display for ExeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExeError::Truncated => write!(f, "the file ends inside the MZ header"),
+            ExeError::HeaderTooLarge => write!(f, "the MZ header is larger than the image"),
+        }
+    }
+}
+
+impl Error for ExeError {}
+
+/// The parts of an MZ header a loader needs. Segments are relative to where the load module
+/// is put.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MzHeader {
+    /// Bytes of the file that are header or load module; anything after is overlay data.
+    pub image_size: usize,
+    pub header_paragraphs: u16,
+    /// Paragraphs the program needs after its load module, such as for uninitialized data.
+    pub min_extra_paragraphs: u16,
+    pub max_extra_paragraphs: u16,
+    pub ss: u16,
+    pub sp: u16,
+    pub ip: u16,
+    pub cs: u16,
+}
+
+/// A segment word in the load module to add the load segment to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub segment: u16,
+    pub offset: u16,
+}
+
+impl Relocation {
+    /// Offset of the word from the start of the load module.
+    pub fn address(&self) -> usize {
+        ((self.segment as usize) << 4) + self.offset as usize
+    }
+}
+
+/// A parsed MZ executable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exe<'a> {
+    pub header: MzHeader,
+    pub relocations: Vec<Relocation>,
+    /// The code and data loaded into memory, which follow the header.
+    pub module: &'a [u8],
+}
+
+impl<'a> Exe<'a> {
+    /// Parses `file`, which `is_exe`. A file shorter than its header says is loaded as far as
+    /// it goes, as DOS does.
+    pub fn parse(file: &'a [u8]) -> Result<Exe<'a>, ExeError> {
+        let word = |offset: usize| -> Result<u16, ExeError> {
+            match file.get(offset..offset + 2) {
+                Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+                None => Err(ExeError::Truncated),
+            }
+        };
+        if file.len() < HEADER_SIZE {
+            return Err(ExeError::Truncated);
+        }
+
+        // The size is in 512-byte pages, the last of them only partly used unless its count
+        // of bytes is 0.
+        let (last_page, pages) = (word(0x02)? as usize, word(0x04)? as usize);
+        let image_size = match last_page {
+            0 => pages * 512,
+            _ => pages.saturating_sub(1) * 512 + last_page,
+        };
+        let header = MzHeader {
+            image_size,
+            header_paragraphs: word(0x08)?,
+            min_extra_paragraphs: word(0x0A)?,
+            max_extra_paragraphs: word(0x0C)?,
+            ss: word(0x0E)?,
+            sp: word(0x10)?,
+            ip: word(0x14)?,
+            cs: word(0x16)?,
+        };
+
+        let (count, table) = (word(0x06)? as usize, word(0x18)? as usize);
+        let relocations = (0..count)
+            .map(|index| {
+                let entry = table + 4 * index;
+                Ok(Relocation {
+                    offset: word(entry)?,
+                    segment: word(entry + 2)?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let start = header.header_paragraphs as usize * 16;
+        if start > image_size {
+            return Err(ExeError::HeaderTooLarge);
+        }
+        let end = image_size.min(file.len());
+        Ok(Exe {
+            header,
+            relocations,
+            module: file.get(start..end).unwrap_or_default(),
+        })
+    }
+
+    /// Offset of the entry point from the start of the load module.
+    pub fn entry(&self) -> usize {
+        ((self.header.cs as usize) << 4) + self.header.ip as usize
+    }
+
+    /// Copies the load module into `memory` at `segment`:0 and adds `segment` to every
+    /// relocated word.
+    pub fn load(&self, memory: &mut Memory, segment: u16) {
+        let base = (segment as usize) << 4;
+        memory.load(base, self.module);
+        for relocation in &self.relocations {
+            let address = base + relocation.address();
+            let value = memory.read_u16(address).wrapping_add(segment);
+            memory.write_u16(address, value);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::instruction::{AddressBase, Instruction, Operand};
+use std::fmt::{self, Write};
+
+/// What a run of an instruction's bytes encodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartKind {
+    Prefix,
+    /// The opcode byte, split into the named bit fields given (widths add up to 8).
+    Opcode(&'static [(&'static str, u8)]),
+    /// The MOD/REG/R/M byte.
+    ModRegRm,
+    Displacement,
+    Data,
+    /// A direct memory address: MOD=00 R/M=110, or the accumulator forms of MOV.
+    Address,
+    /// The signed offset of a relative jump or call from the next instruction.
+    IpIncrement,
+    /// The offset half of a far pointer.
+    Offset,
+    /// The segment half of a far pointer.
+    Segment,
+}
+
+/// A run of an instruction's bytes and what they encode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Part<'a> {
+    pub kind: PartKind,
+    pub bytes: &'a [u8],
+}
+
+//--------------------------------
+//            Byte #1            |
+//--------------------------------
+// 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 |
+//--------------------------------
+//         OPCODE        | D | W |
+//--------------------------------
+const OPCODE: &[(&str, u8)] = &[("OPCODE", 8)];
+const OPCODE_D_W: &[(&str, u8)] = &[("OPCODE", 6), ("D", 1), ("W", 1)];
+const OPCODE_S_W: &[(&str, u8)] = &[("OPCODE", 6), ("S", 1), ("W", 1)];
+const OPCODE_V_W: &[(&str, u8)] = &[("OPCODE", 6), ("V", 1), ("W", 1)];
+const OPCODE_W: &[(&str, u8)] = &[("OPCODE", 7), ("W", 1)];
+const OPCODE_REG: &[(&str, u8)] = &[("OPCODE", 5), ("REG", 3)];
+const OPCODE_W_REG: &[(&str, u8)] = &[("OPCODE", 4), ("W", 1), ("REG", 3)];
+const OPCODE_SR: &[(&str, u8)] = &[("OPCODE", 3), ("SR", 2), ("OPCODE", 3)];
+
+fn opcode_fields(opcode: u8) -> &'static [(&'static str, u8)] {
+    match opcode {
+        0x00..=0x3F if opcode & 0b100 == 0 => OPCODE_D_W,
+        0x00..=0x3F if opcode & 0b110 == 0b100 => OPCODE_W,
+        0x06 | 0x07 | 0x0E | 0x0F | 0x16 | 0x17 | 0x1E | 0x1F => OPCODE_SR,
+        0x40..=0x5F | 0x90..=0x97 => OPCODE_REG,
+        0x80..=0x83 => OPCODE_S_W,
+        0x84..=0x87 | 0xA0..=0xAF | 0xC6 | 0xC7 => OPCODE_W,
+        0x88..=0x8B => OPCODE_D_W,
+        0xB0..=0xBF => OPCODE_W_REG,
+        0xD0..=0xD3 => OPCODE_V_W,
+        0xE4..=0xE7 | 0xEC..=0xEF | 0xF6 | 0xF7 | 0xFE | 0xFF => OPCODE_W,
+        _ => OPCODE,
+    }
+}
+
+fn has_mod_reg_r_m(opcode: u8) -> bool {
+    match opcode {
+        0x00..=0x3F => opcode & 0b100 == 0,
+        0x80..=0x8F | 0xC4..=0xC7 | 0xD0..=0xD3 | 0xD8..=0xDF | 0xF6 | 0xF7 | 0xFE | 0xFF => true,
+        _ => false,
+    }
+}
+
+fn is_prefix(byte: u8) -> bool {
+    matches!(byte, 0xF0 | 0xF2 | 0xF3 | 0x26 | 0x2E | 0x36 | 0x3E)
+}
+
+/// Splits `bytes`, the encoding of `instruction`, into the parts an 8086 manual would label.
+pub fn parts<'a>(bytes: &'a [u8], instruction: &Instruction) -> Vec<Part<'a>> {
+    let mut parts = Vec::new();
+    let mut rest = bytes;
+    let mut take = |rest: &mut &'a [u8], kind: PartKind, count: usize| {
+        let (taken, remaining) = rest.split_at(count.min(rest.len()));
+        if !taken.is_empty() {
+            parts.push(Part { kind, bytes: taken });
+        }
+        *rest = remaining;
+    };
+
+    while rest.first().is_some_and(|&byte| is_prefix(byte)) {
+        take(&mut rest, PartKind::Prefix, 1);
+    }
+    let Some(&opcode) = rest.first() else {
+        return parts;
+    };
+    take(&mut rest, PartKind::Opcode(opcode_fields(opcode)), 1);
+
+    if has_mod_reg_r_m(opcode) {
+        if let Some(&byte_two) = rest.first() {
+            let (kind, count) = match (byte_two >> 6, byte_two & 0b111) {
+                (0b00, 0b110) => (PartKind::Address, 2),
+                (0b10, _) => (PartKind::Displacement, 2),
+                (0b01, _) => (PartKind::Displacement, 1),
+                _ => (PartKind::Displacement, 0),
+            };
+            take(&mut rest, PartKind::ModRegRm, 1);
+            take(&mut rest, kind, count);
+        }
+    }
+
+    let operands = || instruction.operands.iter().flatten();
+    if let Some(Operand::Far { .. }) = operands().next() {
+        take(&mut rest, PartKind::Offset, 2);
+        take(&mut rest, PartKind::Segment, 2);
+    } else if operands().any(|operand| matches!(operand, Operand::Relative(_))) {
+        let count = rest.len();
+        take(&mut rest, PartKind::IpIncrement, count);
+    } else if !has_mod_reg_r_m(opcode)
+        && operands().any(|operand| {
+            matches!(operand, Operand::Memory(address) if address.base == AddressBase::Direct)
+        })
+    {
+        take(&mut rest, PartKind::Address, 2);
+    }
+    let count = rest.len();
+    take(&mut rest, PartKind::Data, count);
+    parts
+}
+
+fn little_endian(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| (value << 8) | byte as u16)
+}
+
+fn signed(bytes: &[u8]) -> i16 {
+    match bytes {
+        [byte] => *byte as i8 as i16,
+        _ => little_endian(bytes) as i16,
+    }
+}
+
+/// Writes one line per part of `instruction`'s encoding: the bytes in hex, then each field's
+/// name and value, e.g. `89    10001001  OPCODE=100010 D=0 W=1`.
+pub fn write_explanation(
+    out: &mut impl Write,
+    bytes: &[u8],
+    instruction: &Instruction,
+) -> fmt::Result {
+    for part in parts(bytes, instruction) {
+        let hex: Vec<String> = part
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        write!(out, "{:<6}", hex.join(" "))?;
+
+        // Hex, padded to the field's size: `0x0c` or `0x0f6c`.
+        let value = format!(
+            "{:#0width$x}",
+            little_endian(part.bytes),
+            width = 2 + 2 * part.bytes.len()
+        );
+        match part.kind {
+            PartKind::Prefix => write!(out, "{:08b}  PREFIX", part.bytes[0])?,
+            PartKind::Opcode(fields) => {
+                let byte = part.bytes[0];
+                write!(out, "{byte:08b} ")?;
+                let mut shift = 8;
+                for (name, width) in fields {
+                    shift -= width;
+                    let field = (byte as u16 >> shift) & ((1 << width) - 1);
+                    write!(out, " {name}={field:0width$b}", width = *width as usize)?;
+                }
+            }
+            PartKind::ModRegRm => {
+                let byte = part.bytes[0];
+                write!(
+                    out,
+                    "{byte:08b}  MOD={:02b} REG={:03b} RM={:03b}",
+                    byte >> 6,
+                    (byte >> 3) & 0b111,
+                    byte & 0b111
+                )?;
+            }
+            PartKind::Displacement => write!(out, "DISP={}", signed(part.bytes))?,
+            PartKind::IpIncrement => write!(out, "IP-INC={}", signed(part.bytes))?,
+            PartKind::Data => write!(out, "DATA={value}")?,
+            PartKind::Address => write!(out, "ADDR={value}")?,
+            PartKind::Offset => write!(out, "OFFSET={value}")?,
+            PartKind::Segment => write!(out, "SEGMENT={value}")?,
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use crate::{
+    formatter::InstructionFormatter,
+    instruction::{AddressBase, Instruction, Operand, Rep},
+    metadata::timing,
+};
+use std::io::{self, Write};
+
+/// Writes `text` as a JSON string literal.
+pub fn write_json_string(out: &mut (impl Write + ?Sized), text: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\t' => out.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+fn write_json_operand(
+    out: &mut (impl Write + ?Sized),
+    instruction: &Instruction,
+    operand: &Operand,
+) -> io::Result<()> {
+    match operand {
+        Operand::Register(register) => {
+            write!(
+                out,
+                "{{\"kind\": \"register\", \"register\": \"{}\"}}",
+                register.name()
+            )
+        }
+        Operand::Memory(address) => {
+            write!(out, "{{\"kind\": \"memory\", \"registers\": [")?;
+            for (index, register) in address.base.registers().iter().enumerate() {
+                if index > 0 {
+                    write!(out, ", ")?;
+                }
+                write!(out, "\"{}\"", register.name())?;
+            }
+            write!(out, "], ")?;
+            if address.base == AddressBase::Direct {
+                write!(out, "\"address\": {}, ", address.displacement as u16)?;
+            } else {
+                write!(out, "\"displacement\": {}, ", address.displacement)?;
+            }
+            match instruction.segment_override {
+                Some(segment) => write!(out, "\"segment\": \"{}\", ", segment.name())?,
+                None => write!(out, "\"segment\": null, ")?,
+            }
+            let size = if instruction.wide { "word" } else { "byte" };
+            write!(out, "\"size\": \"{size}\"}}")
+        }
+        Operand::Immediate(value) => write!(out, "{{\"kind\": \"immediate\", \"value\": {value}}}"),
+        Operand::Relative(displacement) => {
+            let target = instruction.end() as i64 + *displacement as i64;
+            write!(
+                out,
+                "{{\"kind\": \"relative\", \"displacement\": {displacement}, \"target\": {target}}}"
+            )
+        }
+        Operand::Far { segment, offset } => write!(
+            out,
+            "{{\"kind\": \"far\", \"segment\": {segment}, \"offset\": {offset}}}"
+        ),
+    }
+}
+
+/// Writes `instructions` as a JSON array of objects, one per line.
+///
+/// `image` is the decoded input and `origin` the address its first byte was decoded at, so
+/// each instruction's raw bytes can be included.
+pub fn write_json(
+    out: &mut (impl Write + ?Sized),
+    instructions: &[Instruction],
+    image: &[u8],
+    origin: usize,
+    formatter: &InstructionFormatter,
+) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (index, instruction) in instructions.iter().enumerate() {
+        let start = instruction.offset - origin;
+        let bytes = &image[start..start + instruction.length];
+
+        write!(
+            out,
+            "  {{\"offset\": {}, \"length\": {}, \"bytes\": [",
+            instruction.offset, instruction.length
+        )?;
+        for (index, byte) in bytes.iter().enumerate() {
+            if index > 0 {
+                write!(out, ", ")?;
+            }
+            write!(out, "{byte}")?;
+        }
+        write!(
+            out,
+            "], \"mnemonic\": \"{}\", \"wide\": {}, ",
+            instruction.mnemonic.name(),
+            instruction.wide
+        )?;
+        write!(
+            out,
+            "\"far\": {}, \"lock\": {}, ",
+            instruction.far, instruction.lock
+        )?;
+        match instruction.rep {
+            Some(Rep::Rep) => write!(out, "\"rep\": \"rep\", ")?,
+            Some(Rep::Repne) => write!(out, "\"rep\": \"repne\", ")?,
+            None => write!(out, "\"rep\": null, ")?,
+        }
+
+        write!(out, "\"operands\": [")?;
+        for (index, operand) in instruction.operands.iter().flatten().enumerate() {
+            if index > 0 {
+                write!(out, ", ")?;
+            }
+            write_json_operand(out, instruction, operand)?;
+        }
+        write!(out, "], \"text\": ")?;
+        write_json_string(out, &formatter.display(instruction).to_string())?;
+
+        let separator = if index + 1 < instructions.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(out, "}}{separator}")?;
+    }
+    writeln!(out, "]")
+}
+
+/// Writes `text` as a CSV field, quoting it if it contains a separator, quote, or line break.
+pub fn write_csv_field(out: &mut (impl Write + ?Sized), text: &str) -> io::Result<()> {
+    if !text.contains([',', '"', '\n', '\r']) {
+        return out.write_all(text.as_bytes());
+    }
+    write!(out, "\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Writes `instructions` as CSV with a header row: offset, length, bytes (hex), mnemonic,
+/// destination and source operand text, and estimated clocks (`base + ea`).
+///
+/// `image` and `origin` are as for [`write_json`].
+pub fn write_csv(
+    out: &mut (impl Write + ?Sized),
+    instructions: &[Instruction],
+    image: &[u8],
+    origin: usize,
+    formatter: &InstructionFormatter,
+) -> io::Result<()> {
+    writeln!(out, "offset,length,bytes,mnemonic,dst,src,clocks")?;
+    let mut text = String::new();
+    for instruction in instructions {
+        let start = instruction.offset - origin;
+        let bytes = &image[start..start + instruction.length];
+
+        write!(out, "{},{},", instruction.offset, instruction.length)?;
+        for (index, byte) in bytes.iter().enumerate() {
+            if index > 0 {
+                write!(out, " ")?;
+            }
+            write!(out, "{byte:02x}")?;
+        }
+
+        // Writing into a String can't fail.
+        text.clear();
+        let _ = formatter.write_mnemonic(&mut text, instruction);
+        write!(out, ",")?;
+        write_csv_field(out, &text)?;
+        for index in 0..instruction.operands.len() {
+            text.clear();
+            let _ = formatter.write_operand_at(&mut text, instruction, index);
+            write!(out, ",")?;
+            write_csv_field(out, &text)?;
+        }
+        writeln!(out, ",{}", timing(instruction).total())?;
+    }
+    Ok(())
+}
+
+/// Bytes of an instruction shown on each line of NDISASM output; longer ones continue below.
+const NDISASM_BYTES_PER_LINE: usize = 8;
+
+/// Writes `instructions` laid out as `ndisasm -b 16` does: the offset in eight hex digits, the
+/// bytes in hex padded to a fixed width, and the instruction, as `formatter` writes it. Use
+/// `InstructionFormatter::ndisasm()` for output that diffs cleanly against NDISASM's.
+///
+/// `image` and `origin` are as for [`write_json`].
+pub fn write_ndisasm(
+    out: &mut (impl Write + ?Sized),
+    instructions: &[Instruction],
+    image: &[u8],
+    origin: usize,
+    formatter: &InstructionFormatter,
+) -> io::Result<()> {
+    let mut text = String::new();
+    for instruction in instructions {
+        let start = instruction.offset - origin;
+        let bytes = &image[start..start + instruction.length];
+        let (first, rest) = bytes.split_at(bytes.len().min(NDISASM_BYTES_PER_LINE));
+
+        write!(out, "{:08X}  ", instruction.offset)?;
+        for byte in first {
+            write!(out, "{byte:02X}")?;
+        }
+        // Writing into a String can't fail.
+        text.clear();
+        let _ = formatter.write_encoded(&mut text, instruction, bytes);
+        let padding = (NDISASM_BYTES_PER_LINE + 1 - first.len()) * 2;
+        writeln!(out, "{:padding$}{text}", "")?;
+
+        for line in rest.chunks(NDISASM_BYTES_PER_LINE) {
+            write!(out, "         -")?;
+            for byte in line {
+                write!(out, "{byte:02X}")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use crate::{decoder::DecodeError, haversine::AnswersError, json::JsonError};
+use std::{error::Error, fmt, process::ExitCode};
+
+/// The kinds of failure the binaries tell apart by exit status, so scripts can react to one
+/// without reading its message. Success is 0, and 2 is a command line clap rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// 1: what was compared or verified differs from what was expected.
+    Mismatch = 1,
+    /// 2: options that can't be used together, or an argument clap couldn't check.
+    Usage = 2,
+    /// 3: a file couldn't be read or written, or the system refused memory or a CPU.
+    Io = 3,
+    /// 4: machine code couldn't be decoded.
+    Decode = 4,
+    /// 5: the simulated program faulted, or ran past a limit.
+    Simulation = 5,
+    /// 6: an input is malformed or fails a check, such as a configuration, symbols, or JSON
+    /// file, an executable's header, or a measurement outside its bounds.
+    Invalid = 6,
+}
+
+/// The statuses below, for a binary's --help.
+pub const EXIT_STATUSES: &str = "\
+Exit status:
+  0  success
+  1  what was compared or verified differs
+  2  invalid command line
+  3  a file couldn't be read or written, or the system refused memory or a CPU
+  4  machine code couldn't be decoded
+  5  the simulated program faulted or ran past a limit
+  6  an input is malformed or fails a check";
+
+impl Failure {
+    /// A failure of this kind, described by `message`.
+    pub fn failed(self, message: impl Into<String>) -> Failed {
+        Failed {
+            failure: self,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> ExitCode {
+        ExitCode::from(failure as u8)
+    }
+}
+
+impl From<&DecodeError> for Failure {
+    fn from(error: &DecodeError) -> Failure {
+        match error {
+            DecodeError::Io(_) => Failure::Io,
+            _ => Failure::Decode,
+        }
+    }
+}
+
+impl From<&AnswersError> for Failure {
+    fn from(error: &AnswersError) -> Failure {
+        match error {
+            AnswersError::Io(_) => Failure::Io,
+            _ => Failure::Invalid,
+        }
+    }
+}
+
+impl From<&JsonError> for Failure {
+    fn from(error: &JsonError) -> Failure {
+        match error {
+            JsonError::Io(_) => Failure::Io,
+            _ => Failure::Invalid,
+        }
+    }
+}
+
+/// A failure and its message, as the binaries report them.
+#[derive(Debug)]
+pub struct Failed {
+    pub failure: Failure,
+    pub message: String,
+}
+
+impl Failed {
+    /// Prints the message to stderr, and returns the status to exit with.
+    pub fn report(&self) -> ExitCode {
+        eprintln!("{}", self.message);
+        self.failure.into()
+    }
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Failed {}
@@ -0,0 +1,104 @@
+use std::{
+    fmt::{self, Display},
+    ops::{BitAnd, BitOr, BitOrAssign, Not},
+};
+
+//----------------------------------------------------------------
+//                             FLAGS                             |
+//----------------------------------------------------------------
+// F | E | D | C | B | A | 9 | 8 | 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0 |
+//----------------------------------------------------------------
+//   |   |   |   | O | D | I | T | S | Z |   | A |   | P |   | C |
+//----------------------------------------------------------------
+/// A set of 8086 status/control flags, laid out as in the FLAGS register.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flags(pub u16);
+
+impl Flags {
+    pub const NONE: Flags = Flags(0);
+    pub const CARRY: Flags = Flags(1 << 0);
+    pub const PARITY: Flags = Flags(1 << 2);
+    pub const AUX_CARRY: Flags = Flags(1 << 4);
+    pub const ZERO: Flags = Flags(1 << 6);
+    pub const SIGN: Flags = Flags(1 << 7);
+    pub const TRAP: Flags = Flags(1 << 8);
+    pub const INTERRUPT: Flags = Flags(1 << 9);
+    pub const DIRECTION: Flags = Flags(1 << 10);
+    pub const OVERFLOW: Flags = Flags(1 << 11);
+
+    /// Every flag that exists on the 8086.
+    pub const ALL: Flags = Flags(0b1111_1101_0101);
+    /// The six flags set by arithmetic results.
+    pub const STATUS: Flags = Flags(0b1000_1101_0101);
+
+    // Single-letter names in the order the reference sim86 prints them.
+    const LETTERS: [(Flags, char); 9] = [
+        (Flags::CARRY, 'C'),
+        (Flags::PARITY, 'P'),
+        (Flags::AUX_CARRY, 'A'),
+        (Flags::ZERO, 'Z'),
+        (Flags::SIGN, 'S'),
+        (Flags::OVERFLOW, 'O'),
+        (Flags::INTERRUPT, 'I'),
+        (Flags::DIRECTION, 'D'),
+        (Flags::TRAP, 'T'),
+    ];
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 & Flags::ALL.0 == 0
+    }
+
+    pub fn set(&mut self, flags: Flags, value: bool) {
+        if value {
+            self.0 |= flags.0;
+        } else {
+            self.0 &= !flags.0;
+        }
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Flags) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Flags {
+    type Output = Flags;
+
+    fn bitand(self, rhs: Flags) -> Flags {
+        Flags(self.0 & rhs.0)
+    }
+}
+
+impl Not for Flags {
+    type Output = Flags;
+
+    fn not(self) -> Flags {
+        Flags(!self.0 & Flags::ALL.0)
+    }
+}
+
+/// Writes the set flags as letters, e.g. `CZ`.
+impl Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (flag, letter) in Flags::LETTERS {
+            if self.contains(flag) {
+                write!(f, "{letter}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,705 @@
+use crate::instruction::{AddressBase, Instruction, Mnemonic, Operand, Register, Rep};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Write},
+    str::FromStr,
+};
+
+/// Letter case of mnemonics, registers, and keywords.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Case {
+    #[default]
+    Upper,
+    Lower,
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "upper" => Ok(Case::Upper),
+            "lower" => Ok(Case::Lower),
+            _ => Err(format!("unknown case {text:?} (expected upper or lower)")),
+        }
+    }
+}
+
+/// Assembler dialect to target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+    #[default]
+    Nasm,
+    /// MASM/TASM: `word ptr` size keywords, `ds:` on direct addresses, and `1234h` hex.
+    Masm,
+    /// What `ndisasm -b 16` writes: `jz` for `je`, `[es:bx+0x4]`, no space after commas, and
+    /// `byte +0x4` for a byte immediate sign-extended to a word.
+    Ndisasm,
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "nasm" => Ok(Syntax::Nasm),
+            "masm" => Ok(Syntax::Masm),
+            "ndisasm" => Ok(Syntax::Ndisasm),
+            _ => Err(format!(
+                "unknown syntax {text:?} (expected nasm, masm, or ndisasm)"
+            )),
+        }
+    }
+}
+
+/// How immediates and displacements are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    /// `0x1234`, or `1234h` under MASM syntax, which has no `0x` form.
+    Hex,
+    /// `1234h`, and `0abcdh` when the first digit is a letter.
+    HexSuffix,
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "decimal" => Ok(Radix::Decimal),
+            "hex" => Ok(Radix::Hex),
+            "suffix" => Ok(Radix::HexSuffix),
+            _ => Err(format!(
+                "unknown radix {text:?} (expected decimal, hex, or suffix)"
+            )),
+        }
+    }
+}
+
+/// When to write `BYTE`/`WORD` in front of a memory operand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizeKeywords {
+    /// Only when no register operand gives away the operand width.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for SizeKeywords {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "auto" => Ok(SizeKeywords::Auto),
+            "always" => Ok(SizeKeywords::Always),
+            "never" => Ok(SizeKeywords::Never),
+            _ => Err(format!(
+                "unknown size keyword policy {text:?} (expected auto, always, or never)"
+            )),
+        }
+    }
+}
+
+/// How the targets of relative jumps are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelStyle {
+    /// `$+N`, relative to the start of the jump.
+    #[default]
+    Relative,
+    /// The absolute target offset.
+    Absolute,
+    /// The name assigned by `Labels`, falling back to `Relative` for unnamed targets.
+    Named,
+}
+
+/// Kinds of token that get their own color.
+#[derive(Clone, Copy)]
+enum Style {
+    Mnemonic,
+    Register,
+    Number,
+    Label,
+    Comment,
+}
+
+impl Style {
+    fn escape(self) -> &'static str {
+        match self {
+            Style::Mnemonic => "\x1b[1;36m",
+            Style::Register => "\x1b[32m",
+            Style::Number => "\x1b[33m",
+            Style::Label => "\x1b[35m",
+            Style::Comment => "\x1b[90m",
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// What NDISASM shows of an instruction's encoding that decoding doesn't keep.
+#[derive(Clone, Copy, Default)]
+struct Encoding {
+    /// The immediate is a byte sign-extended to a word (opcode 83).
+    sign_extended: bool,
+    /// Bytes of displacement on a memory operand with registers, if the encoding is known.
+    displacement: Option<u8>,
+}
+
+impl Encoding {
+    fn new(bytes: &[u8]) -> Encoding {
+        let is_prefix = |byte: &u8| matches!(byte, 0x26 | 0x2E | 0x36 | 0x3E | 0xF0 | 0xF2 | 0xF3);
+        let mut rest = bytes.iter().skip_while(|byte| is_prefix(byte));
+        let opcode = rest.next();
+        // Every memory operand but a direct address comes from a MOD R/M byte after the opcode.
+        let mode = rest.next().map(|modrm| modrm >> 6);
+        Encoding {
+            sign_extended: opcode == Some(&0x83),
+            displacement: mode.map(|mode| match mode {
+                0b01 => 1,
+                0b10 => 2,
+                _ => 0,
+            }),
+        }
+    }
+}
+
+/// Names for every jump target in a program, `label_0` onwards in offset order.
+#[derive(Clone, Debug, Default)]
+pub struct Labels {
+    names: BTreeMap<usize, String>,
+}
+
+impl Labels {
+    pub fn generate(instructions: &[Instruction]) -> Labels {
+        Labels::with_symbols(instructions, &BTreeMap::new())
+    }
+
+    /// Like `generate`, but jump targets named in `symbols` keep that name, and every symbol that
+    /// lands on the start of an instruction becomes a label too. Only the remaining targets are
+    /// numbered.
+    pub fn with_symbols(instructions: &[Instruction], symbols: &BTreeMap<usize, String>) -> Labels {
+        let mut names = BTreeMap::new();
+        for target in instructions.iter().filter_map(Instruction::branch_target) {
+            names.insert(target, String::new());
+        }
+        for instruction in instructions {
+            if let Some(name) = symbols.get(&instruction.offset) {
+                names.insert(instruction.offset, name.clone());
+            }
+        }
+        for (target, name) in names.iter_mut() {
+            if let Some(symbol) = symbols.get(target) {
+                name.clone_from(symbol);
+            }
+        }
+
+        let unnamed = names.values_mut().filter(|name| name.is_empty());
+        for (index, name) in unnamed.enumerate() {
+            *name = format!("label_{index}");
+        }
+        Labels { names }
+    }
+
+    pub fn name(&self, offset: usize) -> Option<&str> {
+        self.names.get(&offset).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.names
+            .iter()
+            .map(|(offset, name)| (*offset, name.as_str()))
+    }
+}
+
+/// Renders decoded instructions as assembly text, NASM-compatible by default.
+///
+/// Decoding never prints anything itself, so the same `Instruction` can be rendered any number
+/// of ways by differently configured formatters. `Instruction`'s `Display` uses the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct InstructionFormatter {
+    pub syntax: Syntax,
+    pub case: Case,
+    pub radix: Radix,
+    pub size_keywords: SizeKeywords,
+    pub label_style: LabelStyle,
+    pub labels: Labels,
+    /// Names written in place of direct memory addresses, e.g. `[count]` for `[1234]`.
+    pub symbols: BTreeMap<usize, String>,
+    /// Write `short`/`near` on JMP, CALL, and Jcc targets so they reassemble to the same encoding.
+    pub distance_keywords: bool,
+    /// Wrap tokens in ANSI color escapes. Only meant for terminals.
+    pub color: bool,
+}
+
+impl InstructionFormatter {
+    /// The settings `ndisasm -b 16` output is matched with: its syntax, lower case, hex, and
+    /// absolute jump targets.
+    pub fn ndisasm() -> InstructionFormatter {
+        InstructionFormatter {
+            syntax: Syntax::Ndisasm,
+            case: Case::Lower,
+            radix: Radix::Hex,
+            label_style: LabelStyle::Absolute,
+            ..InstructionFormatter::default()
+        }
+    }
+
+    /// Wraps `instruction` in something that can be used with `format!` and friends.
+    pub fn display<'a>(&'a self, instruction: &'a Instruction) -> Formatted<'a> {
+        Formatted {
+            formatter: self,
+            instruction,
+        }
+    }
+
+    /// Writes a label definition line (e.g. `label_0:`), if any label targets `offset`.
+    pub fn write_label(&self, out: &mut impl Write, offset: usize) -> fmt::Result {
+        match self.labels.name(offset) {
+            Some(name) if self.label_style == LabelStyle::Named => {
+                self.styled(out, Style::Label, |out| write!(out, "{name}:"))?;
+                writeln!(out)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes ` ; comment`, to follow an instruction on the same line.
+    pub fn write_comment(&self, out: &mut impl Write, comment: &str) -> fmt::Result {
+        out.write_char(' ')?;
+        self.styled(out, Style::Comment, |out| write!(out, "; {comment}"))
+    }
+
+    pub fn write(&self, out: &mut impl Write, instruction: &Instruction) -> fmt::Result {
+        self.write_instruction(out, instruction, Encoding::default())
+    }
+
+    /// Writes `instruction` as `write` does, given `bytes`, its encoding, from which NDISASM
+    /// syntax takes details decoding doesn't keep: the size of a displacement, and whether an
+    /// immediate was sign-extended.
+    pub fn write_encoded(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        bytes: &[u8],
+    ) -> fmt::Result {
+        self.write_instruction(out, instruction, Encoding::new(bytes))
+    }
+
+    fn write_instruction(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        encoding: Encoding,
+    ) -> fmt::Result {
+        self.styled(out, Style::Mnemonic, |out| {
+            self.write_mnemonic(out, instruction)
+        })?;
+
+        let explicit_size = self.explicit_size(instruction);
+        let separator = match self.syntax {
+            Syntax::Ndisasm => ",",
+            _ => ", ",
+        };
+        let operands = match self.syntax {
+            Syntax::Ndisasm if is_nop(instruction) => &[],
+            _ => &instruction.operands[..],
+        };
+        for (index, operand) in operands.iter().flatten().enumerate() {
+            out.write_str(if index == 0 { " " } else { separator })?;
+            self.write_operand(out, instruction, operand, explicit_size, encoding)?;
+        }
+        Ok(())
+    }
+
+    /// Writes operand `index` (0 is the destination) alone, or nothing if there isn't one.
+    pub fn write_operand_at(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        index: usize,
+    ) -> fmt::Result {
+        match &instruction.operands[index] {
+            Some(operand) => self.write_operand(
+                out,
+                instruction,
+                operand,
+                self.explicit_size(instruction),
+                Encoding::default(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the prefixes, mnemonic, width suffix, and `far`: everything before the operands.
+    pub fn write_mnemonic(&self, out: &mut impl Write, instruction: &Instruction) -> fmt::Result {
+        if instruction.lock {
+            self.write_keyword(out, "lock ")?;
+        }
+        let ndisasm = self.syntax == Syntax::Ndisasm;
+        match instruction.rep {
+            // NDISASM names F3 after what it means for the instruction it's on.
+            Some(Rep::Rep)
+                if ndisasm && matches!(instruction.mnemonic, Mnemonic::Cmps | Mnemonic::Scas) =>
+            {
+                self.write_keyword(out, "repe ")?
+            }
+            Some(Rep::Rep) => self.write_keyword(out, "rep ")?,
+            Some(Rep::Repne) => self.write_keyword(out, "repne ")?,
+            None => {}
+        }
+        // With a memory operand the segment is written on it. String instructions and XLAT
+        // have none to write it on, so it goes before the mnemonic as a prefix.
+        let has_memory = (instruction.operands.iter())
+            .any(|operand| matches!(operand, Some(Operand::Memory(_))));
+        if let Some(segment) = instruction.segment_override.filter(|_| !has_memory) {
+            self.write_keyword(out, segment.name())?;
+            out.write_char(' ')?;
+        }
+
+        let masm = self.syntax == Syntax::Masm;
+        match instruction.mnemonic {
+            Mnemonic::Int3 if masm => self.write_keyword(out, "int 3")?,
+            Mnemonic::Xlat if masm || ndisasm => self.write_keyword(out, "xlatb")?,
+            mnemonic if ndisasm => self.write_keyword(out, ndisasm_name(instruction, mnemonic))?,
+            mnemonic => self.write_keyword(out, mnemonic.name())?,
+        }
+        if instruction.mnemonic.is_string() {
+            self.write_keyword(out, if instruction.wide { "w" } else { "b" })?;
+        }
+        // MASM spells a far memory operand as `dword ptr` on the operand instead.
+        if instruction.far && !masm {
+            self.write_keyword(out, " far")?;
+        }
+        Ok(())
+    }
+
+    fn explicit_size(&self, instruction: &Instruction) -> bool {
+        match self.size_keywords {
+            _ if instruction.far => self.syntax == Syntax::Masm,
+            SizeKeywords::Auto => !sized_by_register(instruction),
+            SizeKeywords::Always => true,
+            SizeKeywords::Never => false,
+        }
+    }
+
+    fn styled<W: Write>(
+        &self,
+        out: &mut W,
+        style: Style,
+        write: impl FnOnce(&mut W) -> fmt::Result,
+    ) -> fmt::Result {
+        if !self.color {
+            return write(out);
+        }
+        out.write_str(style.escape())?;
+        write(out)?;
+        out.write_str(RESET)
+    }
+
+    fn write_keyword(&self, out: &mut impl Write, keyword: &str) -> fmt::Result {
+        match self.case {
+            Case::Upper => out.write_str(&keyword.to_uppercase()),
+            Case::Lower => out.write_str(keyword),
+        }
+    }
+
+    fn write_register(&self, out: &mut impl Write, register: Register) -> fmt::Result {
+        self.styled(out, Style::Register, |out| {
+            self.write_keyword(out, register.name())
+        })
+    }
+
+    fn write_number(&self, out: &mut impl Write, value: i32) -> fmt::Result {
+        let magnitude = (value as i64).unsigned_abs();
+        let sign = if value < 0 { "-" } else { "" };
+        self.styled(out, Style::Number, |out| match (self.radix, self.syntax) {
+            (Radix::Decimal, _) => write!(out, "{value}"),
+            (Radix::Hex, Syntax::Nasm | Syntax::Ndisasm) => write!(out, "{sign}{magnitude:#x}"),
+            (Radix::Hex, Syntax::Masm) | (Radix::HexSuffix, _) => {
+                let digits = format!("{magnitude:x}");
+                // A leading zero keeps values like `0ffh` from being read as identifiers.
+                let zero = if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    "0"
+                } else {
+                    ""
+                };
+                write!(out, "{sign}{zero}{digits}h")
+            }
+        })
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        operand: &Operand,
+        explicit_size: bool,
+        encoding: Encoding,
+    ) -> fmt::Result {
+        let ndisasm = self.syntax == Syntax::Ndisasm;
+        match operand {
+            Operand::Register(register) => self.write_register(out, *register),
+            Operand::Memory(address) => {
+                let masm = self.syntax == Syntax::Masm;
+                if explicit_size {
+                    let size = match (instruction.far, instruction.wide) {
+                        (true, _) => "dword",
+                        (false, true) => "word",
+                        (false, false) => "byte",
+                    };
+                    self.write_keyword(out, size)?;
+                    self.write_keyword(out, if masm { " ptr " } else { " " })?;
+                }
+                // MASM reads a bare `[1234]` as an immediate, so direct addresses need a segment.
+                let segment = match instruction.segment_override {
+                    None if masm && address.base == AddressBase::Direct => Some(Register::Ds),
+                    segment => segment,
+                };
+                if let Some(segment) = segment.filter(|_| !ndisasm) {
+                    self.write_register(out, segment)?;
+                    out.write_char(':')?;
+                }
+
+                out.write_char('[')?;
+                if let Some(segment) = segment.filter(|_| ndisasm) {
+                    self.write_register(out, segment)?;
+                    out.write_char(':')?;
+                }
+                let plus = if ndisasm { "+" } else { " + " };
+                if address.base == AddressBase::Direct {
+                    let target = address.displacement as u16;
+                    match self.symbols.get(&(target as usize)) {
+                        Some(name) => self.styled(out, Style::Label, |out| out.write_str(name))?,
+                        None => self.write_number(out, target as i32)?,
+                    }
+                } else {
+                    for (index, register) in address.base.registers().iter().enumerate() {
+                        if index > 0 {
+                            out.write_str(plus)?;
+                        }
+                        self.write_register(out, *register)?;
+                    }
+
+                    let displacement = address.displacement as i32;
+                    if ndisasm {
+                        self.write_ndisasm_displacement(out, displacement, encoding)?;
+                    } else if displacement > 0 {
+                        out.write_str(" + ")?;
+                        self.write_number(out, displacement)?;
+                    } else if displacement < 0 {
+                        out.write_str(" - ")?;
+                        self.write_number(out, -displacement)?;
+                    }
+                }
+                out.write_char(']')
+            }
+            Operand::Immediate(value) if ndisasm => {
+                self.write_ndisasm_immediate(out, instruction, *value, encoding)
+            }
+            Operand::Immediate(value) => self.write_number(out, *value),
+            Operand::Relative(displacement) if ndisasm => {
+                if instruction.mnemonic == Mnemonic::Jmp && !instruction.wide {
+                    self.write_keyword(out, "short ")?;
+                }
+                // Targets wrap around the segment, as IP does.
+                let target = instruction.end() as i64 + *displacement as i64;
+                self.write_number(out, target as u16 as i32)
+            }
+            Operand::Relative(displacement) => {
+                // LOOP and JCXZ only come in one size, and `wide` records which size the others
+                // were.
+                let sized = !matches!(
+                    instruction.mnemonic,
+                    Mnemonic::Loop | Mnemonic::Loopz | Mnemonic::Loopnz | Mnemonic::Jcxz
+                );
+                if self.distance_keywords && sized {
+                    let distance = match (instruction.wide, self.syntax) {
+                        (false, _) => "short ",
+                        (true, Syntax::Nasm | Syntax::Ndisasm) => "near ",
+                        (true, Syntax::Masm) => "near ptr ",
+                    };
+                    self.write_keyword(out, distance)?;
+                }
+
+                let target = instruction.branch_target();
+                match (self.label_style, target) {
+                    (LabelStyle::Named, Some(target)) if self.labels.name(target).is_some() => {
+                        let name = self.labels.name(target).unwrap_or_default();
+                        self.styled(out, Style::Label, |out| out.write_str(name))
+                    }
+                    (LabelStyle::Absolute, Some(target)) => self.write_number(out, target as i32),
+                    _ => {
+                        let delta = *displacement as i32 + instruction.length as i32;
+                        out.write_char('$')?;
+                        if delta >= 0 {
+                            out.write_char('+')?;
+                        }
+                        self.write_number(out, delta)
+                    }
+                }
+            }
+            Operand::Far { segment, offset } => {
+                self.write_number(out, *segment as i32)?;
+                out.write_char(':')?;
+                self.write_number(out, *offset as i32)
+            }
+        }
+    }
+}
+
+impl InstructionFormatter {
+    /// Writes a displacement as NDISASM does: a byte signed, as `+0x4` or `-0x4`, even if it's
+    /// zero, and a word unsigned, as `+0xfffc`. Without the encoding, its size is guessed.
+    fn write_ndisasm_displacement(
+        &self,
+        out: &mut impl Write,
+        displacement: i32,
+        encoding: Encoding,
+    ) -> fmt::Result {
+        let size = encoding.displacement.unwrap_or(match displacement {
+            0 => 0,
+            -128..=127 => 1,
+            _ => 2,
+        });
+        match size {
+            0 => Ok(()),
+            1 if displacement < 0 => {
+                out.write_char('-')?;
+                self.write_number(out, -displacement)
+            }
+            _ => {
+                out.write_char('+')?;
+                let mask = if size == 1 { 0xFF } else { 0xFFFF };
+                self.write_number(out, displacement & mask)
+            }
+        }
+    }
+
+    /// Writes an immediate as NDISASM does: unsigned, except for a byte sign-extended to a
+    /// word, which it writes as `byte +0x4` or `byte -0x4`, and a shift count of 1.
+    fn write_ndisasm_immediate(
+        &self,
+        out: &mut impl Write,
+        instruction: &Instruction,
+        value: i32,
+        encoding: Encoding,
+    ) -> fmt::Result {
+        if is_shift(instruction.mnemonic) {
+            return write!(out, "{value}");
+        }
+        if encoding.sign_extended {
+            self.write_keyword(out, if value < 0 { "byte -" } else { "byte +" })?;
+            return self.write_number(out, value.abs());
+        }
+        let mask = match instruction.mnemonic {
+            Mnemonic::Ret | Mnemonic::Retf => 0xFFFF,
+            _ if instruction.wide => 0xFFFF,
+            _ => 0xFF,
+        };
+        self.write_number(out, value & mask)
+    }
+}
+
+/// What NDISASM calls `mnemonic`, where that differs: the names it uses for conditions, and
+/// NOP for the one-byte XCHG AX, AX.
+fn ndisasm_name(instruction: &Instruction, mnemonic: Mnemonic) -> &'static str {
+    match mnemonic {
+        Mnemonic::Je => "jz",
+        Mnemonic::Jne => "jnz",
+        Mnemonic::Jb => "jc",
+        Mnemonic::Jnb => "jnc",
+        Mnemonic::Jbe => "jna",
+        Mnemonic::Jp => "jpe",
+        Mnemonic::Jnp => "jpo",
+        Mnemonic::Jle => "jng",
+        Mnemonic::Loopz => "loope",
+        Mnemonic::Loopnz => "loopne",
+        Mnemonic::Xchg if is_nop(instruction) => "nop",
+        mnemonic => mnemonic.name(),
+    }
+}
+
+/// Whether `instruction` is 90, XCHG AX, AX, which NDISASM writes as NOP.
+fn is_nop(instruction: &Instruction) -> bool {
+    instruction.mnemonic == Mnemonic::Xchg
+        && instruction.length == 1
+        && instruction.operands == [Some(Operand::Register(Register::Ax)); 2]
+}
+
+fn is_shift(mnemonic: Mnemonic) -> bool {
+    matches!(
+        mnemonic,
+        Mnemonic::Shl
+            | Mnemonic::Shr
+            | Mnemonic::Sar
+            | Mnemonic::Rol
+            | Mnemonic::Ror
+            | Mnemonic::Rcl
+            | Mnemonic::Rcr
+    )
+}
+
+// A register operand fixes the width of a memory operand, except for the CL count of a shift.
+fn sized_by_register(instruction: &Instruction) -> bool {
+    let operands = if is_shift(instruction.mnemonic) {
+        &instruction.operands[..1]
+    } else {
+        &instruction.operands[..]
+    };
+    operands
+        .iter()
+        .any(|operand| matches!(operand, Some(Operand::Register(_))))
+}
+
+pub struct Formatted<'a> {
+    formatter: &'a InstructionFormatter,
+    instruction: &'a Instruction,
+}
+
+impl Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.formatter.write(f, self.instruction)
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        InstructionFormatter::default().write(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+
+    fn decode(bytes: &[u8]) -> Instruction {
+        Decoder::new(bytes).next_instruction().unwrap().unwrap()
+    }
+
+    #[test]
+    fn segment_overrides_without_a_memory_operand_are_prefixes() {
+        // cs movsb; es xlat; mov al, [es:bx]
+        let movs = decode(&[0x2E, 0xA4]);
+        let xlat = decode(&[0x26, 0xD7]);
+        let mov = decode(&[0x26, 0x8A, 0x07]);
+        let nasm = InstructionFormatter::default();
+        let masm = InstructionFormatter {
+            syntax: Syntax::Masm,
+            ..InstructionFormatter::default()
+        };
+        let ndisasm = InstructionFormatter::ndisasm();
+
+        assert_eq!(nasm.display(&movs).to_string(), "CS MOVSB");
+        assert_eq!(masm.display(&movs).to_string(), "CS MOVSB");
+        assert_eq!(ndisasm.display(&movs).to_string(), "cs movsb");
+        assert_eq!(nasm.display(&xlat).to_string(), "ES XLAT");
+        assert_eq!(masm.display(&xlat).to_string(), "ES XLATB");
+        assert_eq!(ndisasm.display(&xlat).to_string(), "es xlatb");
+        assert_eq!(nasm.display(&mov).to_string(), "MOV AL, ES:[BX]");
+        assert_eq!(ndisasm.display(&mov).to_string(), "mov al,[es:bx]");
+    }
+}
@@ -0,0 +1,290 @@
+use std::io;
+
+/// Something the CPU's performance monitoring unit can count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Cycles,
+    Instructions,
+    BranchMisses,
+    CacheMisses,
+}
+
+impl Event {
+    pub const COUNT: usize = 4;
+    pub const ALL: [Event; Event::COUNT] = [
+        Event::Cycles,
+        Event::Instructions,
+        Event::BranchMisses,
+        Event::CacheMisses,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Cycles => "cycles",
+            Event::Instructions => "instructions",
+            Event::BranchMisses => "branch misses",
+            Event::CacheMisses => "cache misses",
+        }
+    }
+}
+
+/// A count for each `Event`, in the order of `Event::ALL`.
+pub type EventCounts = [u64; Event::COUNT];
+
+/// Counters of this thread's events, running from when they're opened. Read them before and
+/// after some work for what it took.
+pub trait HardwareCounters {
+    /// Whether `event` is being counted. Its count is always 0 if not.
+    fn counts(&self, event: Event) -> bool;
+
+    /// Each event's count so far.
+    fn read(&mut self) -> EventCounts;
+}
+
+/// Opens as many of the events as the OS and CPU will count together, in user mode only:
+/// with perf_event_open on Linux. Fails if it can't count any of them, as in VMs without a
+/// virtual PMU, or where perf_event_paranoid forbids it. On Windows, which only gives out the
+/// other events through ETW to administrators, it counts just cycles, with
+/// QueryThreadCycleTime: at the timestamp counter's rate, not the core's, and only while the
+/// thread runs.
+pub fn open_hardware_counters() -> io::Result<Box<dyn HardwareCounters>> {
+    platform::open()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use super::{Event, EventCounts, HardwareCounters};
+    use std::{
+        ffi::{c_int, c_long, c_ulong},
+        fs::File,
+        io::{self, Read},
+        os::unix::io::{AsRawFd, FromRawFd},
+    };
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_FORMAT_GROUP: u64 = 1 << 3;
+    const EXCLUDE_KERNEL: u64 = 1 << 5;
+    const EXCLUDE_HV: u64 = 1 << 6;
+    const PERF_FLAG_FD_CLOEXEC: c_ulong = 8;
+    const ENOENT: i32 = 2;
+    const EACCES: i32 = 13;
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_PERF_EVENT_OPEN: Option<c_long> = Some(298);
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    const SYS_PERF_EVENT_OPEN: Option<c_long> = Some(241);
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )))]
+    const SYS_PERF_EVENT_OPEN: Option<c_long> = None;
+
+    /// `struct perf_event_attr` as of PERF_ATTR_SIZE_VER5, with everything after the flags
+    /// left zero.
+    #[repr(C)]
+    struct PerfEventAttr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        rest: [u64; 8],
+    }
+
+    extern "C" {
+        fn syscall(number: c_long, ...) -> c_long;
+    }
+
+    /// The counters, in a group so they all run at once, led by the first to open.
+    struct PerfEvents {
+        leader: File,
+        _members: Vec<File>,
+        /// The events counted, in the order the group reads them.
+        events: Vec<Event>,
+    }
+
+    fn config(event: Event) -> u64 {
+        match event {
+            Event::Cycles => 0,
+            Event::Instructions => 1,
+            Event::CacheMisses => 3,
+            Event::BranchMisses => 5,
+        }
+    }
+
+    fn open_event(number: c_long, event: Event, group: c_int) -> io::Result<File> {
+        let attr = PerfEventAttr {
+            kind: PERF_TYPE_HARDWARE,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config: config(event),
+            sample_period: 0,
+            sample_type: 0,
+            read_format: PERF_FORMAT_GROUP,
+            flags: EXCLUDE_KERNEL | EXCLUDE_HV,
+            rest: [0; 8],
+        };
+        // SAFETY: it only reads `attr`, which is as big as it says. Process 0 on CPU -1 is
+        // this thread on any CPU.
+        let fd = unsafe {
+            syscall(
+                number,
+                &attr as *const PerfEventAttr,
+                0 as c_int,
+                -1 as c_int,
+                group,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptor is new, and no one else's.
+        Ok(unsafe { File::from_raw_fd(fd as c_int) })
+    }
+
+    pub fn open() -> io::Result<Box<dyn HardwareCounters>> {
+        let Some(number) = SYS_PERF_EVENT_OPEN else {
+            return Err(io::ErrorKind::Unsupported.into());
+        };
+        let mut files: Vec<File> = Vec::new();
+        let mut events = Vec::new();
+        let mut first_error = None;
+        for event in Event::ALL {
+            let group = files.first().map_or(-1, |leader| leader.as_raw_fd());
+            match open_event(number, event, group) {
+                Ok(file) => {
+                    files.push(file);
+                    events.push(event);
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        if files.is_empty() {
+            let error = first_error.unwrap_or_else(|| io::ErrorKind::Unsupported.into());
+            return Err(match error.raw_os_error() {
+                Some(ENOENT) => io::Error::new(error.kind(), "the CPU has no counters to open"),
+                Some(EACCES) => io::Error::new(
+                    error.kind(),
+                    "kernel.perf_event_paranoid doesn't let this user count events",
+                ),
+                _ => error,
+            });
+        }
+        let leader = files.remove(0);
+        Ok(Box::new(PerfEvents {
+            leader,
+            _members: files,
+            events,
+        }))
+    }
+
+    impl HardwareCounters for PerfEvents {
+        fn counts(&self, event: Event) -> bool {
+            self.events.contains(&event)
+        }
+
+        fn read(&mut self) -> EventCounts {
+            // How many values there are, then each of them.
+            let mut bytes = [0u8; 8 * (1 + Event::COUNT)];
+            let mut counts = EventCounts::default();
+            // A group's counts come in one read, or not at all if it's failed, when they're
+            // left at zero.
+            if let Ok(read) = self.leader.read(&mut bytes) {
+                let values = bytes[..read]
+                    .chunks_exact(8)
+                    .skip(1)
+                    .map(|value| u64::from_ne_bytes(value.try_into().unwrap()));
+                for (&event, value) in self.events.iter().zip(values) {
+                    counts[event as usize] = value;
+                }
+            }
+            counts
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Event, EventCounts, HardwareCounters};
+    use std::{ffi::c_void, io};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn QueryThreadCycleTime(thread: *mut c_void, cycles: *mut u64) -> i32;
+    }
+
+    /// The cycles Windows has charged to the calling thread.
+    struct ThreadCycles;
+
+    fn thread_cycles() -> io::Result<u64> {
+        let mut cycles = 0;
+        // SAFETY: it only writes `cycles`. The current thread's handle is a constant that
+        // needn't be closed.
+        if unsafe { QueryThreadCycleTime(GetCurrentThread(), &mut cycles) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(cycles)
+    }
+
+    pub fn open() -> io::Result<Box<dyn HardwareCounters>> {
+        thread_cycles()?;
+        Ok(Box::new(ThreadCycles))
+    }
+
+    impl HardwareCounters for ThreadCycles {
+        fn counts(&self, event: Event) -> bool {
+            event == Event::Cycles
+        }
+
+        fn read(&mut self) -> EventCounts {
+            let mut counts = EventCounts::default();
+            // It worked once in `open`, and only fails for bad handles.
+            counts[Event::Cycles as usize] = thread_cycles().unwrap_or_default();
+            counts
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+mod platform {
+    use super::HardwareCounters;
+    use std::io;
+
+    pub fn open() -> io::Result<Box<dyn HardwareCounters>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_count_work_between_reads() {
+        // Not every machine has counters to open, or lets us open them.
+        let Ok(mut counters) = open_hardware_counters() else {
+            return;
+        };
+        let before = counters.read();
+        std::hint::black_box((0..1_000_000u64).map(std::hint::black_box).sum::<u64>());
+        let after = counters.read();
+        for event in Event::ALL {
+            let (before, after) = (before[event as usize], after[event as usize]);
+            if counters.counts(event) {
+                assert!(after >= before, "{}", event.name());
+            } else {
+                assert_eq!((before, after), (0, 0));
+            }
+        }
+        if counters.counts(Event::Instructions) {
+            let instructions = Event::Instructions as usize;
+            assert!(after[instructions] - before[instructions] >= 1_000_000);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use crate::{
+    cursor::Cursor,
+    math,
+    trace_hash::{FNV_OFFSET_BASIS, FNV_PRIME},
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+/// The Earth's radius in kilometres that the course's reference figures are computed with.
+pub const EARTH_RADIUS: f64 = 6372.8;
+
+/// A pair of points as longitude `x` and latitude `y`, in degrees.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pair {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+}
+
+/// The functions a haversine is computed with.
+#[derive(Clone, Copy)]
+struct Functions {
+    sin: fn(f64) -> f64,
+    cos: fn(f64) -> f64,
+    asin: fn(f64) -> f64,
+    sqrt: fn(f64) -> f64,
+}
+
+fn haversine(pair: Pair, radius: f64, functions: Functions) -> f64 {
+    let Functions {
+        sin,
+        cos,
+        asin,
+        sqrt,
+    } = functions;
+    let (lat0, lat1) = (pair.y0, pair.y1);
+    let d_lat = (lat1 - lat0).to_radians();
+    let d_lon = (pair.x1 - pair.x0).to_radians();
+    let (lat0, lat1) = (lat0.to_radians(), lat1.to_radians());
+
+    let square = |x: f64| x * x;
+    let a = square(sin(d_lat / 2.0)) + cos(lat0) * cos(lat1) * square(sin(d_lon / 2.0));
+    let c = 2.0 * asin(sqrt(a));
+    radius * c
+}
+
+/// Great-circle distance between the two points of `pair` on a sphere of `radius`, computed as
+/// the course's reference does, so results can be compared exactly.
+pub fn reference_haversine(pair: Pair, radius: f64) -> f64 {
+    let functions = Functions {
+        sin: f64::sin,
+        cos: f64::cos,
+        asin: f64::asin,
+        sqrt: f64::sqrt,
+    };
+    haversine(pair, radius, functions)
+}
+
+/// `reference_haversine` with the `math` module's functions in place of std's.
+pub fn custom_haversine(pair: Pair, radius: f64) -> f64 {
+    let functions = Functions {
+        sin: math::sin,
+        cos: math::cos,
+        asin: math::asin,
+        sqrt: math::sqrt,
+    };
+    haversine(pair, radius, functions)
+}
+
+/// The bytes an answer file starts with.
+pub const ANSWERS_MAGIC: [u8; 4] = *b"HVSA";
+/// The answer file format `AnswersHeader` describes; older or newer ones are refused.
+pub const ANSWERS_VERSION: u32 = 1;
+
+/// The start of an answer file from `haversine_gen --answers`: `ANSWERS_MAGIC`, then the
+/// version, pair count, and checksum, each little-endian. After it come `count` distances and
+/// then their average, each a little-endian f64, and `checksum` is an `AnswersChecksum` of
+/// them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnswersHeader {
+    pub count: u64,
+    pub checksum: u64,
+}
+
+#[derive(Debug)]
+pub enum AnswersError {
+    Io(io::Error),
+    /// The file doesn't start with `ANSWERS_MAGIC`.
+    NotAnswers,
+    /// The file is in a version of the format other than `ANSWERS_VERSION`.
+    Version(u32),
+    /// The file is `size` bytes, which isn't what `count` answers take.
+    Size {
+        size: u64,
+        count: u64,
+    },
+    /// The answers don't add up to the header's checksum.
+    Checksum,
+}
+
+impl fmt::Display for AnswersError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnswersError::Io(error) => write!(f, "{error}"),
+            AnswersError::NotAnswers => write!(f, "not an answer file"),
+            AnswersError::Version(version) => write!(
+                f,
+                "answer file version {version}, but only version {ANSWERS_VERSION} is supported"
+            ),
+            AnswersError::Size { size, count } => {
+                let header = AnswersHeader {
+                    count: *count,
+                    checksum: 0,
+                };
+                write!(
+                    f,
+                    "{size} bytes, but answers for {count} pairs take {}; the file is truncated \
+                     or damaged",
+                    header.file_size()
+                )
+            }
+            AnswersError::Checksum => write!(f, "checksum mismatch; the file is damaged"),
+        }
+    }
+}
+
+impl Error for AnswersError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AnswersError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AnswersError {
+    fn from(error: io::Error) -> Self {
+        AnswersError::Io(error)
+    }
+}
+
+impl AnswersHeader {
+    pub const SIZE: usize = 24;
+
+    /// The size of the whole file this header starts.
+    pub fn file_size(&self) -> u64 {
+        Self::SIZE as u64 + 8 * (self.count + 1)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&ANSWERS_MAGIC);
+        bytes[4..8].copy_from_slice(&ANSWERS_VERSION.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.count.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<AnswersHeader, AnswersError> {
+        let mut cursor = Cursor::new(bytes);
+        // Every read is within the array, so none of them can come up short.
+        if cursor.read_array() != Some(ANSWERS_MAGIC) {
+            return Err(AnswersError::NotAnswers);
+        }
+        let version = cursor.read_u32_le().unwrap();
+        if version != ANSWERS_VERSION {
+            return Err(AnswersError::Version(version));
+        }
+        Ok(AnswersHeader {
+            count: cursor.read_u64_le().unwrap(),
+            checksum: cursor.read_u64_le().unwrap(),
+        })
+    }
+}
+
+/// The distances in an answer file, and their average.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Answers {
+    pub distances: Vec<f64>,
+    pub average: f64,
+}
+
+/// Reads a whole answer file from `input` and checks it: the header, the file's size, and the
+/// checksum of everything after the header, so a truncated or damaged file is refused before
+/// any of its answers is used.
+pub fn read_answers(input: &mut (impl Read + ?Sized)) -> Result<Answers, AnswersError> {
+    let mut file = Vec::new();
+    input.read_to_end(&mut file)?;
+    let mut cursor = Cursor::new(&file);
+    let header = cursor
+        .read_array()
+        .ok_or(AnswersError::NotAnswers)
+        .and_then(|header| AnswersHeader::from_bytes(&header))?;
+    let size = file.len() as u64;
+    if size != header.file_size() {
+        return Err(AnswersError::Size {
+            size,
+            count: header.count,
+        });
+    }
+
+    let mut checksum = AnswersChecksum::new();
+    checksum.write(cursor.remaining());
+    if checksum.digest() != header.checksum {
+        return Err(AnswersError::Checksum);
+    }
+    // The size matched, so there's an f64 for every distance and the average.
+    let distances = (0..header.count)
+        .map(|_| cursor.read_f64_le().unwrap())
+        .collect();
+    let average = cursor.read_f64_le().unwrap();
+    Ok(Answers { distances, average })
+}
+
+/// Writes an answer file: a distance at a time, then their average, then the header once the
+/// checksum is known, which is why it has to be able to seek back to the start.
+pub struct AnswersWriter<W> {
+    out: W,
+    count: u64,
+    checksum: AnswersChecksum,
+}
+
+impl<W: Write + Seek> AnswersWriter<W> {
+    pub fn new(mut out: W) -> io::Result<AnswersWriter<W>> {
+        // A placeholder for the header.
+        out.write_all(&[0; AnswersHeader::SIZE])?;
+        Ok(AnswersWriter {
+            out,
+            count: 0,
+            checksum: AnswersChecksum::new(),
+        })
+    }
+
+    fn write(&mut self, value: f64) -> io::Result<()> {
+        let bytes = value.to_le_bytes();
+        self.checksum.write(&bytes);
+        self.out.write_all(&bytes)
+    }
+
+    pub fn distance(&mut self, distance: f64) -> io::Result<()> {
+        self.count += 1;
+        self.write(distance)
+    }
+
+    /// Writes `average` after the distances and fills in the header.
+    pub fn finish(mut self, average: f64) -> io::Result<W> {
+        self.write(average)?;
+        let header = AnswersHeader {
+            count: self.count,
+            checksum: self.checksum.digest(),
+        };
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header.to_bytes())?;
+        Ok(self.out)
+    }
+}
+
+/// 64-bit FNV-1a over the answers after the header, added a piece at a time.
+#[derive(Clone, Copy, Debug)]
+pub struct AnswersChecksum(u64);
+
+impl Default for AnswersChecksum {
+    fn default() -> Self {
+        AnswersChecksum(FNV_OFFSET_BASIS)
+    }
+}
+
+impl AnswersChecksum {
+    pub fn new() -> Self {
+        AnswersChecksum::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn digest(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn distance(x0: f64, y0: f64, x1: f64, y1: f64) -> f64 {
+        reference_haversine(Pair { x0, y0, x1, y1 }, EARTH_RADIUS)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn known_distances_come_out() {
+        // Nashville to Los Angeles, the usual worked example on this radius.
+        assert_close(distance(-86.67, 36.12, -118.40, 33.94), 2887.2599506071106);
+        // One degree of the equator, whichever way round.
+        let degree = EARTH_RADIUS * PI / 180.0;
+        assert_close(distance(10.0, 0.0, 11.0, 0.0), degree);
+        assert_close(distance(11.0, 0.0, 10.0, 0.0), degree);
+    }
+
+    #[test]
+    fn answer_files_read_back_and_refuse_damaged_ones() {
+        let header = AnswersHeader {
+            count: 1000,
+            checksum: 0x0123_4567_89AB_CDEF,
+        };
+        let mut bytes = header.to_bytes();
+        assert_eq!(AnswersHeader::from_bytes(&bytes).unwrap(), header);
+        assert_eq!(header.file_size(), 24 + 8 * 1001);
+
+        bytes[4] = 2;
+        assert!(matches!(
+            AnswersHeader::from_bytes(&bytes),
+            Err(AnswersError::Version(2))
+        ));
+        bytes[0] = b'{';
+        assert!(matches!(
+            AnswersHeader::from_bytes(&bytes),
+            Err(AnswersError::NotAnswers)
+        ));
+
+        let mut writer = AnswersWriter::new(io::Cursor::new(Vec::new())).unwrap();
+        writer.distance(1.5).unwrap();
+        writer.distance(2.5).unwrap();
+        let mut file = writer.finish(2.0).unwrap().into_inner();
+        assert_eq!(
+            read_answers(&mut &file[..]).unwrap(),
+            Answers {
+                distances: vec![1.5, 2.5],
+                average: 2.0
+            }
+        );
+        file[30] ^= 1;
+        assert!(matches!(
+            read_answers(&mut &file[..]),
+            Err(AnswersError::Checksum)
+        ));
+        file.pop();
+        assert!(matches!(
+            read_answers(&mut &file[..]),
+            Err(AnswersError::Size { size: 47, count: 2 })
+        ));
+        assert!(matches!(
+            read_answers(&mut &file[..10]),
+            Err(AnswersError::NotAnswers)
+        ));
+    }
+
+    #[test]
+    fn custom_math_agrees_with_the_reference() {
+        for (x0, y0, x1, y1) in [(-86.67, 36.12, -118.40, 33.94), (0.0, 90.0, 0.0, -90.0)] {
+            let pair = Pair { x0, y0, x1, y1 };
+            assert_close(
+                custom_haversine(pair, EARTH_RADIUS),
+                reference_haversine(pair, EARTH_RADIUS),
+            );
+        }
+    }
+
+    #[test]
+    fn edge_cases_come_out() {
+        // Identical points, however they're written.
+        assert_eq!(distance(12.5, -45.0, 12.5, -45.0), 0.0);
+        assert_close(distance(-180.0, 30.0, 180.0, 30.0), 0.0);
+        // Pole to pole is half way round, and every longitude at a pole is the same point.
+        assert_close(distance(0.0, 90.0, 0.0, -90.0), EARTH_RADIUS * PI);
+        assert_close(distance(-120.0, 90.0, 45.0, 90.0), 0.0);
+        // Across the antimeridian is the short way.
+        assert_close(distance(179.5, 0.0, -179.5, 0.0), EARTH_RADIUS * PI / 180.0);
+        // Antipodes anywhere are half way round too.
+        assert_close(distance(-30.0, 20.0, 150.0, -20.0), EARTH_RADIUS * PI);
+    }
+}
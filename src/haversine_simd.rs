@@ -0,0 +1,295 @@
+use crate::{
+    haversine::Pair,
+    math::{ASIN, COS, SIN},
+};
+use std::{
+    f64::consts::{FRAC_PI_2, FRAC_PI_4, PI},
+    ops::{Add, Mul, Sub},
+};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Two f64 lanes: an SSE2 register on x86-64, which always has SSE2, and a plain array
+/// elsewhere, so the same code runs everywhere and gives the same results.
+#[derive(Clone, Copy)]
+struct F64x2(
+    #[cfg(target_arch = "x86_64")] __m128d,
+    #[cfg(not(target_arch = "x86_64"))] [f64; 2],
+);
+
+/// A comparison's result in each lane, for `select`.
+#[derive(Clone, Copy)]
+struct Mask(F64x2);
+
+#[cfg(target_arch = "x86_64")]
+impl F64x2 {
+    fn new(a: f64, b: f64) -> F64x2 {
+        // SAFETY: SSE2 is part of the x86-64 baseline.
+        unsafe { F64x2(_mm_set_pd(b, a)) }
+    }
+
+    fn splat(value: f64) -> F64x2 {
+        unsafe { F64x2(_mm_set1_pd(value)) }
+    }
+
+    fn to_array(self) -> [f64; 2] {
+        let mut lanes = [0.0; 2];
+        unsafe { _mm_storeu_pd(lanes.as_mut_ptr(), self.0) };
+        lanes
+    }
+
+    fn sqrt(self) -> F64x2 {
+        unsafe { F64x2(_mm_sqrt_pd(self.0)) }
+    }
+
+    fn abs(self) -> F64x2 {
+        unsafe { F64x2(_mm_andnot_pd(_mm_set1_pd(-0.0), self.0)) }
+    }
+
+    fn gt(self, other: F64x2) -> Mask {
+        unsafe { Mask(F64x2(_mm_cmpgt_pd(self.0, other.0))) }
+    }
+
+    fn lt(self, other: F64x2) -> Mask {
+        unsafe { Mask(F64x2(_mm_cmplt_pd(self.0, other.0))) }
+    }
+
+    /// `if_true` in the lanes where `mask` is set, `if_false` in the others.
+    fn select(mask: Mask, if_true: F64x2, if_false: F64x2) -> F64x2 {
+        let mask = mask.0 .0;
+        unsafe {
+            F64x2(_mm_or_pd(
+                _mm_and_pd(mask, if_true.0),
+                _mm_andnot_pd(mask, if_false.0),
+            ))
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+macro_rules! lanewise {
+    ($trait:ident, $method:ident, $intrinsic:ident) => {
+        impl $trait for F64x2 {
+            type Output = F64x2;
+
+            fn $method(self, other: F64x2) -> F64x2 {
+                unsafe { F64x2($intrinsic(self.0, other.0)) }
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+lanewise!(Add, add, _mm_add_pd);
+#[cfg(target_arch = "x86_64")]
+lanewise!(Sub, sub, _mm_sub_pd);
+#[cfg(target_arch = "x86_64")]
+lanewise!(Mul, mul, _mm_mul_pd);
+
+#[cfg(not(target_arch = "x86_64"))]
+impl F64x2 {
+    fn new(a: f64, b: f64) -> F64x2 {
+        F64x2([a, b])
+    }
+
+    fn splat(value: f64) -> F64x2 {
+        F64x2([value; 2])
+    }
+
+    fn to_array(self) -> [f64; 2] {
+        self.0
+    }
+
+    fn map(self, f: impl Fn(f64) -> f64) -> F64x2 {
+        F64x2(self.0.map(f))
+    }
+
+    fn sqrt(self) -> F64x2 {
+        self.map(f64::sqrt)
+    }
+
+    fn abs(self) -> F64x2 {
+        self.map(f64::abs)
+    }
+
+    fn gt(self, other: F64x2) -> Mask {
+        let [a, b] = self.0;
+        let [c, d] = other.0;
+        Mask(F64x2([(a > c) as u8 as f64, (b > d) as u8 as f64]))
+    }
+
+    fn lt(self, other: F64x2) -> Mask {
+        other.gt(self)
+    }
+
+    fn select(mask: Mask, if_true: F64x2, if_false: F64x2) -> F64x2 {
+        let lane = |index: usize| {
+            if mask.0 .0[index] != 0.0 {
+                if_true.0[index]
+            } else {
+                if_false.0[index]
+            }
+        };
+        F64x2([lane(0), lane(1)])
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+macro_rules! lanewise {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for F64x2 {
+            type Output = F64x2;
+
+            fn $method(self, other: F64x2) -> F64x2 {
+                F64x2([self.0[0] $op other.0[0], self.0[1] $op other.0[1]])
+            }
+        }
+    };
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+lanewise!(Add, add, +);
+#[cfg(not(target_arch = "x86_64"))]
+lanewise!(Sub, sub, -);
+#[cfg(not(target_arch = "x86_64"))]
+lanewise!(Mul, mul, *);
+
+/// The polynomial with `coefficients` in `x²`, Horner's way.
+fn even_polynomial(coefficients: &[f64], x: F64x2) -> F64x2 {
+    let x2 = x * x;
+    coefficients
+        .iter()
+        .rev()
+        .fold(F64x2::splat(0.0), |sum, &coefficient| {
+            sum * x2 + F64x2::splat(coefficient)
+        })
+}
+
+/// Sine of `x` in [0, π/2], from whichever series is more accurate there: sin(x) = cos(π/2 - x)
+/// keeps the argument within π/4, so it's exactly 1 at π/2.
+fn sin_quadrant(x: F64x2) -> F64x2 {
+    let quarter = F64x2::splat(FRAC_PI_4);
+    let complement = F64x2::splat(FRAC_PI_2) - x;
+    F64x2::select(
+        x.gt(quarter),
+        even_polynomial(&COS, complement),
+        x * even_polynomial(&SIN, x),
+    )
+}
+
+/// Sine of `x` in [-π, π].
+fn sin(x: F64x2) -> F64x2 {
+    // sin(π - x) = sin(x) brings the magnitude into [0, π/2]; the sign comes back after.
+    let magnitude = x.abs();
+    let reduced = F64x2::select(
+        magnitude.gt(F64x2::splat(FRAC_PI_2)),
+        F64x2::splat(PI) - magnitude,
+        magnitude,
+    );
+    let sine = sin_quadrant(reduced);
+    let zero = F64x2::splat(0.0);
+    F64x2::select(x.lt(zero), zero - sine, sine)
+}
+
+/// Cosine of `x` in [-π/2, π/2].
+fn cos(x: F64x2) -> F64x2 {
+    sin_quadrant(F64x2::splat(FRAC_PI_2) - x.abs())
+}
+
+/// Arcsine of `x` in [0, 1].
+fn asin(x: F64x2) -> F64x2 {
+    // Near 1 the series converges too slowly; asin(x) = π/2 - 2 asin(√((1 - x) / 2)) moves
+    // anything above 0.5 below it.
+    let large = x.gt(F64x2::splat(0.5));
+    let reduced = F64x2::select(
+        large,
+        ((F64x2::splat(1.0) - x) * F64x2::splat(0.5)).sqrt(),
+        x,
+    );
+    let arcsine = reduced * even_polynomial(&ASIN, reduced);
+    F64x2::select(
+        large,
+        F64x2::splat(FRAC_PI_2) - F64x2::splat(2.0) * arcsine,
+        arcsine,
+    )
+}
+
+/// `reference_haversine` for two pairs at once.
+fn haversine(pairs: [Pair; 2], radius: f64) -> [f64; 2] {
+    let [a, b] = pairs;
+    let radians = F64x2::splat(PI / 180.0);
+    let half = F64x2::splat(0.5);
+    let (x0, x1) = (F64x2::new(a.x0, b.x0), F64x2::new(a.x1, b.x1));
+    let (lat0, lat1) = (F64x2::new(a.y0, b.y0), F64x2::new(a.y1, b.y1));
+
+    let d_lat = (lat1 - lat0) * radians;
+    let d_lon = (x1 - x0) * radians;
+    let (lat0, lat1) = (lat0 * radians, lat1 * radians);
+
+    let (sin_lat, sin_lon) = (sin(d_lat * half), sin(d_lon * half));
+    let a = sin_lat * sin_lat + cos(lat0) * cos(lat1) * (sin_lon * sin_lon);
+    let c = F64x2::splat(2.0) * asin(a.sqrt());
+    (F64x2::splat(radius) * c).to_array()
+}
+
+/// How far `haversine_simd`'s distances may be from `reference_haversine`'s, in kilometres
+/// on the Earth. Both usually agree to within a few units in the last place, but arcsine is so
+/// steep near 1 that for nearly antipodal points a last-place difference in its argument
+/// moves the distance by micrometres.
+pub const TOLERANCE: f64 = 1e-6;
+
+/// The distance of each pair in `pairs` into `distances`, two pairs at a time with
+/// polynomial sine, cosine, and arcsine. They're within `TOLERANCE` of `reference_haversine`
+/// for longitudes in [-180, 180] and latitudes in [-90, 90], and wrong outside them.
+pub fn haversine_simd(pairs: &[Pair], radius: f64, distances: &mut [f64]) {
+    let mut pairs = pairs.chunks_exact(2);
+    let mut outputs = distances.chunks_exact_mut(2);
+    for (pair, output) in pairs.by_ref().zip(outputs.by_ref()) {
+        output.copy_from_slice(&haversine([pair[0], pair[1]], radius));
+    }
+    // An odd one out fills both lanes.
+    if let (Some(&last), Some(output)) = (
+        pairs.remainder().first(),
+        outputs.into_remainder().first_mut(),
+    ) {
+        *output = haversine([last; 2], radius)[0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::haversine::{reference_haversine, EARTH_RADIUS};
+
+    #[test]
+    fn simd_distances_match_the_scalar_reference() {
+        // A grid over the whole valid range, poles and antimeridian included.
+        let mut pairs = Vec::new();
+        for step in 0..=40 {
+            let t = step as f64 / 40.0;
+            pairs.push(Pair {
+                x0: -180.0 + 360.0 * t,
+                y0: -90.0 + 180.0 * t,
+                x1: 180.0 - 360.0 * t * t,
+                y1: 90.0 - 180.0 * t * t * t,
+            });
+        }
+        pairs.push(Pair {
+            x0: -180.0,
+            y0: 0.0,
+            x1: 180.0,
+            y1: 0.0,
+        });
+        let mut distances = vec![0.0; pairs.len()];
+        haversine_simd(&pairs, EARTH_RADIUS, &mut distances);
+
+        for (pair, distance) in pairs.iter().zip(distances) {
+            let reference = reference_haversine(*pair, EARTH_RADIUS);
+            assert!(
+                (distance - reference).abs() <= TOLERANCE,
+                "{pair:?}: {distance} vs {reference}"
+            );
+        }
+    }
+}
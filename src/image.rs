@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+
+/// File formats `write_image` can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary PPM (P6). Alpha is dropped.
+    Ppm,
+    /// 32-bit uncompressed BMP.
+    Bmp,
+    /// RGBA PNG with uncompressed (stored) deflate blocks.
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks a format from a file extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<ImageFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "bmp" => Some(ImageFormat::Bmp),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `pixels`, `width * height` RGBA quadruples in rows from the top, as an image file.
+pub fn write_image(
+    out: &mut (impl Write + ?Sized),
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    assert_eq!(pixels.len(), width * height * 4, "pixels must be RGBA");
+    match format {
+        ImageFormat::Ppm => write_ppm(out, width, height, pixels),
+        ImageFormat::Bmp => write_bmp(out, width, height, pixels),
+        ImageFormat::Png => write_png(out, width, height, pixels),
+    }
+}
+
+fn write_ppm(
+    out: &mut (impl Write + ?Sized),
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    for pixel in pixels.chunks_exact(4) {
+        out.write_all(&pixel[..3])?;
+    }
+    Ok(())
+}
+
+fn write_bmp(
+    out: &mut (impl Write + ?Sized),
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    const HEADERS: u32 = 14 + 40;
+    let size = (width * height * 4) as u32;
+
+    // BITMAPFILEHEADER
+    out.write_all(b"BM")?;
+    out.write_all(&(HEADERS + size).to_le_bytes())?;
+    out.write_all(&[0; 4])?;
+    out.write_all(&HEADERS.to_le_bytes())?;
+    // BITMAPINFOHEADER; a negative height stores rows top-down.
+    out.write_all(&40u32.to_le_bytes())?;
+    out.write_all(&(width as i32).to_le_bytes())?;
+    out.write_all(&(-(height as i32)).to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&32u16.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&size.to_le_bytes())?;
+    out.write_all(&[0; 16])?;
+
+    for pixel in pixels.chunks_exact(4) {
+        out.write_all(&[pixel[2], pixel[1], pixel[0], pixel[3]])?;
+    }
+    Ok(())
+}
+
+fn crc32(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn write_png_chunk(out: &mut (impl Write + ?Sized), kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data).copied());
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn write_png(
+    out: &mut (impl Write + ?Sized),
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlace.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_png_chunk(out, b"IHDR", &header)?;
+
+    // Each row is preceded by its filter type, 0 for none.
+    let mut raw = Vec::with_capacity(height * (width * 4 + 1));
+    for row in pixels.chunks_exact(width * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // A zlib stream of stored blocks: no compression, so no deflate implementation needed.
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in &raw {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    zlib.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    write_png_chunk(out, b"IDAT", &zlib)?;
+
+    write_png_chunk(out, b"IEND", &[])
+}
@@ -0,0 +1,726 @@
+use std::{ops::BitOr, str::FromStr};
+
+/// Operation performed by an instruction.
+///
+/// String instructions (`MOVS`, `CMPS`, ...) are width-agnostic here; the `wide` flag on the
+/// owning `Instruction` selects between the byte and word forms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mnemonic {
+    Mov,
+    Push,
+    Pop,
+    Xchg,
+    In,
+    Out,
+    Xlat,
+    Lea,
+    Lds,
+    Les,
+    Lahf,
+    Sahf,
+    Pushf,
+    Popf,
+    Add,
+    Adc,
+    Inc,
+    Aaa,
+    Daa,
+    Sub,
+    Sbb,
+    Dec,
+    Neg,
+    Cmp,
+    Aas,
+    Das,
+    Mul,
+    Imul,
+    Aam,
+    Div,
+    Idiv,
+    Aad,
+    Cbw,
+    Cwd,
+    Not,
+    Shl,
+    Shr,
+    Sar,
+    Rol,
+    Ror,
+    Rcl,
+    Rcr,
+    And,
+    Test,
+    Or,
+    Xor,
+    Movs,
+    Cmps,
+    Scas,
+    Lods,
+    Stos,
+    Call,
+    Jmp,
+    Ret,
+    Retf,
+    Je,
+    Jl,
+    Jle,
+    Jb,
+    Jbe,
+    Jp,
+    Jo,
+    Js,
+    Jne,
+    Jnl,
+    Jg,
+    Jnb,
+    Ja,
+    Jnp,
+    Jno,
+    Jns,
+    Loop,
+    Loopz,
+    Loopnz,
+    Jcxz,
+    Int,
+    Int3,
+    Into,
+    Iret,
+    Clc,
+    Cmc,
+    Stc,
+    Cld,
+    Std,
+    Cli,
+    Sti,
+    Hlt,
+    Wait,
+    Esc,
+}
+
+impl Mnemonic {
+    /// Lowercase NASM spelling of the mnemonic.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mnemonic::Mov => "mov",
+            Mnemonic::Push => "push",
+            Mnemonic::Pop => "pop",
+            Mnemonic::Xchg => "xchg",
+            Mnemonic::In => "in",
+            Mnemonic::Out => "out",
+            Mnemonic::Xlat => "xlat",
+            Mnemonic::Lea => "lea",
+            Mnemonic::Lds => "lds",
+            Mnemonic::Les => "les",
+            Mnemonic::Lahf => "lahf",
+            Mnemonic::Sahf => "sahf",
+            Mnemonic::Pushf => "pushf",
+            Mnemonic::Popf => "popf",
+            Mnemonic::Add => "add",
+            Mnemonic::Adc => "adc",
+            Mnemonic::Inc => "inc",
+            Mnemonic::Aaa => "aaa",
+            Mnemonic::Daa => "daa",
+            Mnemonic::Sub => "sub",
+            Mnemonic::Sbb => "sbb",
+            Mnemonic::Dec => "dec",
+            Mnemonic::Neg => "neg",
+            Mnemonic::Cmp => "cmp",
+            Mnemonic::Aas => "aas",
+            Mnemonic::Das => "das",
+            Mnemonic::Mul => "mul",
+            Mnemonic::Imul => "imul",
+            Mnemonic::Aam => "aam",
+            Mnemonic::Div => "div",
+            Mnemonic::Idiv => "idiv",
+            Mnemonic::Aad => "aad",
+            Mnemonic::Cbw => "cbw",
+            Mnemonic::Cwd => "cwd",
+            Mnemonic::Not => "not",
+            Mnemonic::Shl => "shl",
+            Mnemonic::Shr => "shr",
+            Mnemonic::Sar => "sar",
+            Mnemonic::Rol => "rol",
+            Mnemonic::Ror => "ror",
+            Mnemonic::Rcl => "rcl",
+            Mnemonic::Rcr => "rcr",
+            Mnemonic::And => "and",
+            Mnemonic::Test => "test",
+            Mnemonic::Or => "or",
+            Mnemonic::Xor => "xor",
+            Mnemonic::Movs => "movs",
+            Mnemonic::Cmps => "cmps",
+            Mnemonic::Scas => "scas",
+            Mnemonic::Lods => "lods",
+            Mnemonic::Stos => "stos",
+            Mnemonic::Call => "call",
+            Mnemonic::Jmp => "jmp",
+            Mnemonic::Ret => "ret",
+            Mnemonic::Retf => "retf",
+            Mnemonic::Je => "je",
+            Mnemonic::Jl => "jl",
+            Mnemonic::Jle => "jle",
+            Mnemonic::Jb => "jb",
+            Mnemonic::Jbe => "jbe",
+            Mnemonic::Jp => "jp",
+            Mnemonic::Jo => "jo",
+            Mnemonic::Js => "js",
+            Mnemonic::Jne => "jne",
+            Mnemonic::Jnl => "jnl",
+            Mnemonic::Jg => "jg",
+            Mnemonic::Jnb => "jnb",
+            Mnemonic::Ja => "ja",
+            Mnemonic::Jnp => "jnp",
+            Mnemonic::Jno => "jno",
+            Mnemonic::Jns => "jns",
+            Mnemonic::Loop => "loop",
+            Mnemonic::Loopz => "loopz",
+            Mnemonic::Loopnz => "loopnz",
+            Mnemonic::Jcxz => "jcxz",
+            Mnemonic::Int => "int",
+            Mnemonic::Int3 => "int3",
+            Mnemonic::Into => "into",
+            Mnemonic::Iret => "iret",
+            Mnemonic::Clc => "clc",
+            Mnemonic::Cmc => "cmc",
+            Mnemonic::Stc => "stc",
+            Mnemonic::Cld => "cld",
+            Mnemonic::Std => "std",
+            Mnemonic::Cli => "cli",
+            Mnemonic::Sti => "sti",
+            Mnemonic::Hlt => "hlt",
+            Mnemonic::Wait => "wait",
+            Mnemonic::Esc => "esc",
+        }
+    }
+
+    /// True for the string instructions, which take a `B`/`W` suffix instead of operands.
+    pub fn is_string(self) -> bool {
+        self.kind() == Kind::String
+    }
+
+    /// Which of the manual's groups the mnemonic is listed under.
+    pub fn kind(self) -> Kind {
+        use Mnemonic::*;
+        match self {
+            Mov | Push | Pop | Xchg | In | Out | Xlat | Lea | Lds | Les | Lahf | Sahf | Pushf
+            | Popf => Kind::DataTransfer,
+            Add | Adc | Inc | Aaa | Daa | Sub | Sbb | Dec | Neg | Cmp | Aas | Das | Mul | Imul
+            | Aam | Div | Idiv | Aad | Cbw | Cwd => Kind::Arithmetic,
+            Not | Shl | Shr | Sar | Rol | Ror | Rcl | Rcr | And | Test | Or | Xor => Kind::Logic,
+            Movs | Cmps | Scas | Lods | Stos => Kind::String,
+            Call | Jmp | Ret | Retf | Je | Jl | Jle | Jb | Jbe | Jp | Jo | Js | Jne | Jnl | Jg
+            | Jnb | Ja | Jnp | Jno | Jns | Loop | Loopz | Loopnz | Jcxz | Int | Int3 | Into
+            | Iret => Kind::ControlTransfer,
+            Clc | Cmc | Stc | Cld | Std | Cli | Sti | Hlt | Wait | Esc => Kind::ProcessorControl,
+        }
+    }
+
+    /// True for the jumps taken depending on the flags, such as JE, and on CX, the LOOPs and
+    /// JCXZ.
+    pub fn is_conditional_jump(self) -> bool {
+        use Mnemonic::*;
+        matches!(
+            self,
+            Je | Jl
+                | Jle
+                | Jb
+                | Jbe
+                | Jp
+                | Jo
+                | Js
+                | Jne
+                | Jnl
+                | Jg
+                | Jnb
+                | Ja
+                | Jnp
+                | Jno
+                | Jns
+                | Loop
+                | Loopz
+                | Loopnz
+                | Jcxz
+        )
+    }
+}
+
+/// The six groups the 8086 manual sorts the instruction set into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    DataTransfer,
+    Arithmetic,
+    /// Bitwise operations, shifts, and rotates.
+    Logic,
+    String,
+    /// Jumps, calls, returns, and interrupts.
+    ControlTransfer,
+    /// Flag operations and the instructions that synchronize with the outside: HLT, WAIT, ESC.
+    ProcessorControl,
+}
+
+/// What an instruction does, in finer and overlapping groups than its `Kind`, for analyses to
+/// test with `contains` instead of matching on mnemonics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Groups(pub u16);
+
+impl Groups {
+    pub const NONE: Groups = Groups(0);
+    /// Can go somewhere other than the next instruction: jumps, calls, returns, and interrupts.
+    pub const BRANCH: Groups = Groups(1 << 0);
+    /// A branch taken only sometimes, depending on the flags or CX.
+    pub const CONDITIONAL: Groups = Groups(1 << 1);
+    /// Saves where to return to: CALL and the software interrupts.
+    pub const CALL: Groups = Groups(1 << 2);
+    /// RET, RETF, and IRET.
+    pub const RETURN: Groups = Groups(1 << 3);
+    /// INT, INT3, and INTO.
+    pub const INTERRUPT: Groups = Groups(1 << 4);
+    /// A CALL or JMP whose target is in a register or memory rather than the instruction.
+    pub const INDIRECT: Groups = Groups(1 << 5);
+    /// A branch that loads CS as well as IP.
+    pub const FAR: Groups = Groups(1 << 6);
+    /// Pushes or pops.
+    pub const STACK: Groups = Groups(1 << 7);
+    /// IN and OUT.
+    pub const IO: Groups = Groups(1 << 8);
+    /// Reads or writes memory other than the stack: through a memory operand, or as the string
+    /// instructions and XLAT do. LEA only computes an address, so it isn't included.
+    pub const MEMORY: Groups = Groups(1 << 9);
+    /// Repeated by a REP prefix.
+    pub const REPEATED: Groups = Groups(1 << 10);
+
+    pub fn contains(self, other: Groups) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Groups {
+    type Output = Groups;
+
+    fn bitor(self, rhs: Groups) -> Groups {
+        Groups(self.0 | rhs.0)
+    }
+}
+
+//----------------------------------------------
+//    REG    | W == 0 | W == 1 |   SR   | SEG  |
+//----------------------------------------------
+// 0 | 0 | 0 |   AL   |   AX   | 0 | 0 |  ES  |
+//----------------------------------------------
+// 0 | 0 | 1 |   CL   |   CX   | 0 | 1 |  CS  |
+//----------------------------------------------
+// 0 | 1 | 0 |   DL   |   DX   | 1 | 0 |  SS  |
+//----------------------------------------------
+// 0 | 1 | 1 |   BL   |   BX   | 1 | 1 |  DS  |
+//----------------------------------------------
+// 1 | 0 | 0 |   AH   |   SP   |
+//-----------------------------
+// 1 | 0 | 1 |   CH   |   BP   |
+//-----------------------------
+// 1 | 1 | 0 |   DH   |   SI   |
+//-----------------------------
+// 1 | 1 | 1 |   BH   |   DI   |
+//-----------------------------
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Register {
+    Al,
+    Cl,
+    Dl,
+    Bl,
+    Ah,
+    Ch,
+    Dh,
+    Bh,
+    Ax,
+    Cx,
+    Dx,
+    Bx,
+    Sp,
+    Bp,
+    Si,
+    Di,
+    Es,
+    Cs,
+    Ss,
+    Ds,
+}
+
+impl Register {
+    /// Decodes a 3-bit REG (or R/M when MOD == 11) field.
+    pub fn from_reg(reg: u8, is_word: bool) -> Register {
+        const BYTE: [Register; 8] = [
+            Register::Al,
+            Register::Cl,
+            Register::Dl,
+            Register::Bl,
+            Register::Ah,
+            Register::Ch,
+            Register::Dh,
+            Register::Bh,
+        ];
+        const WORD: [Register; 8] = [
+            Register::Ax,
+            Register::Cx,
+            Register::Dx,
+            Register::Bx,
+            Register::Sp,
+            Register::Bp,
+            Register::Si,
+            Register::Di,
+        ];
+
+        if is_word {
+            WORD[(reg & 0b111) as usize]
+        } else {
+            BYTE[(reg & 0b111) as usize]
+        }
+    }
+
+    /// Decodes a 2-bit SR field.
+    pub fn from_segment(sr: u8) -> Register {
+        match sr & 0b11 {
+            0b00 => Register::Es,
+            0b01 => Register::Cs,
+            0b10 => Register::Ss,
+            0b11 => Register::Ds,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Lowercase NASM spelling of the register.
+    pub fn name(self) -> &'static str {
+        match self {
+            Register::Al => "al",
+            Register::Cl => "cl",
+            Register::Dl => "dl",
+            Register::Bl => "bl",
+            Register::Ah => "ah",
+            Register::Ch => "ch",
+            Register::Dh => "dh",
+            Register::Bh => "bh",
+            Register::Ax => "ax",
+            Register::Cx => "cx",
+            Register::Dx => "dx",
+            Register::Bx => "bx",
+            Register::Sp => "sp",
+            Register::Bp => "bp",
+            Register::Si => "si",
+            Register::Di => "di",
+            Register::Es => "es",
+            Register::Cs => "cs",
+            Register::Ss => "ss",
+            Register::Ds => "ds",
+        }
+    }
+
+    /// True for 16-bit registers, including the segment registers.
+    pub fn is_word(self) -> bool {
+        !matches!(
+            self,
+            Register::Al
+                | Register::Cl
+                | Register::Dl
+                | Register::Bl
+                | Register::Ah
+                | Register::Ch
+                | Register::Dh
+                | Register::Bh
+        )
+    }
+
+    pub fn is_segment(self) -> bool {
+        matches!(
+            self,
+            Register::Es | Register::Cs | Register::Ss | Register::Ds
+        )
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    /// Parses a register name in either case.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let name = text.to_ascii_lowercase();
+        (0..8)
+            .flat_map(|reg| {
+                [
+                    Register::from_reg(reg, false),
+                    Register::from_reg(reg, true),
+                ]
+            })
+            .chain((0..4).map(Register::from_segment))
+            .find(|register| register.name() == name)
+            .ok_or_else(|| format!("unknown register {text:?}"))
+    }
+}
+
+//-----------------------------------------------
+//    R/M    | MOD == 00 | MOD == 01 | MOD == 10 |
+//-----------------------------------------------
+// 0 | 0 | 0 |  BX + SI  |  BX + SI + D8/D16     |
+//-----------------------------------------------
+// 0 | 0 | 1 |  BX + DI  |  BX + DI + D8/D16     |
+//-----------------------------------------------
+// 0 | 1 | 0 |  BP + SI  |  BP + SI + D8/D16     |
+//-----------------------------------------------
+// 0 | 1 | 1 |  BP + DI  |  BP + DI + D8/D16     |
+//-----------------------------------------------
+// 1 | 0 | 0 |    SI     |  SI + D8/D16          |
+//-----------------------------------------------
+// 1 | 0 | 1 |    DI     |  DI + D8/D16          |
+//-----------------------------------------------
+// 1 | 1 | 0 |  DIRECT   |  BP + D8/D16          |
+//-----------------------------------------------
+// 1 | 1 | 1 |    BX     |  BX + D8/D16          |
+//-----------------------------------------------
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressBase {
+    BxSi,
+    BxDi,
+    BpSi,
+    BpDi,
+    Si,
+    Di,
+    Bp,
+    Bx,
+    /// No registers; the displacement is the 16-bit address itself.
+    Direct,
+}
+
+impl AddressBase {
+    /// Decodes a 3-bit R/M field for a memory operand (MOD != 11).
+    pub fn from_r_m(r_m: u8, mode: u8) -> AddressBase {
+        match r_m & 0b111 {
+            0b000 => AddressBase::BxSi,
+            0b001 => AddressBase::BxDi,
+            0b010 => AddressBase::BpSi,
+            0b011 => AddressBase::BpDi,
+            0b100 => AddressBase::Si,
+            0b101 => AddressBase::Di,
+            0b110 if mode == 0b00 => AddressBase::Direct,
+            0b110 => AddressBase::Bp,
+            0b111 => AddressBase::Bx,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The registers summed to form the address, in NASM order.
+    pub fn registers(self) -> &'static [Register] {
+        match self {
+            AddressBase::BxSi => &[Register::Bx, Register::Si],
+            AddressBase::BxDi => &[Register::Bx, Register::Di],
+            AddressBase::BpSi => &[Register::Bp, Register::Si],
+            AddressBase::BpDi => &[Register::Bp, Register::Di],
+            AddressBase::Si => &[Register::Si],
+            AddressBase::Di => &[Register::Di],
+            AddressBase::Bp => &[Register::Bp],
+            AddressBase::Bx => &[Register::Bx],
+            AddressBase::Direct => &[],
+        }
+    }
+}
+
+/// A memory operand: `[base + displacement]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EffectiveAddress {
+    pub base: AddressBase,
+    pub displacement: i16,
+    /// Bytes of displacement in the encoding: 0, 1 or 2. `[BX + 0]` may carry a byte of zero,
+    /// which costs clocks to add, and a direct address always carries 2.
+    pub displacement_bytes: u8,
+}
+
+impl EffectiveAddress {
+    /// `[base + displacement]` with the shortest displacement that encodes it, as NASM picks.
+    pub fn new(base: AddressBase, displacement: i16) -> EffectiveAddress {
+        let displacement_bytes = match base {
+            AddressBase::Direct => 2,
+            // [BP] alone has no MOD 00 form, which is taken by direct addresses.
+            AddressBase::Bp if displacement == 0 => 1,
+            _ if displacement == 0 => 0,
+            _ if i8::try_from(displacement).is_ok() => 1,
+            _ => 2,
+        };
+        EffectiveAddress {
+            base,
+            displacement,
+            displacement_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Register(Register),
+    Memory(EffectiveAddress),
+    /// Immediate data, sign-extended from its encoded width except for port numbers, interrupt
+    /// vectors, and shift counts, which are zero-extended.
+    Immediate(i32),
+    /// Jump displacement relative to the end of the instruction.
+    Relative(i16),
+    /// Absolute `segment:offset` target of a direct intersegment CALL/JMP.
+    Far {
+        segment: u16,
+        offset: u16,
+    },
+}
+
+/// Repeat prefix applied to a string instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rep {
+    /// `F3`: REP / REPE / REPZ.
+    Rep,
+    /// `F2`: REPNE / REPNZ.
+    Repne,
+}
+
+/// A single decoded instruction, including any prefixes that preceded it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    /// Address of the first prefix (or opcode) byte: its offset in the input plus the decoder's
+    /// origin.
+    pub offset: usize,
+    /// Number of bytes consumed, prefixes included.
+    pub length: usize,
+    pub mnemonic: Mnemonic,
+    /// Destination first, as written in NASM syntax.
+    pub operands: [Option<Operand>; 2],
+    /// Operates on words rather than bytes.
+    pub wide: bool,
+    /// Intersegment CALL/JMP through memory.
+    pub far: bool,
+    pub lock: bool,
+    pub rep: Option<Rep>,
+    pub segment_override: Option<Register>,
+}
+
+impl Instruction {
+    /// Address of the byte following this instruction.
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.mnemonic.kind()
+    }
+
+    pub fn groups(&self) -> Groups {
+        use Mnemonic::*;
+        let group = |set: bool, group: Groups| if set { group } else { Groups::NONE };
+        let operands = || self.operands.iter().flatten();
+        let mnemonic = self.mnemonic;
+        let interrupt = matches!(mnemonic, Int | Int3 | Into);
+        let returns = matches!(mnemonic, Ret | Retf | Iret);
+        let far_operand = operands().any(|operand| matches!(operand, Operand::Far { .. }));
+        let indirect = matches!(mnemonic, Call | Jmp)
+            && operands()
+                .any(|operand| matches!(operand, Operand::Register(_) | Operand::Memory(_)));
+
+        group(self.kind() == Kind::ControlTransfer, Groups::BRANCH)
+            | group(
+                mnemonic.is_conditional_jump() || mnemonic == Into,
+                Groups::CONDITIONAL,
+            )
+            | group(mnemonic == Call || interrupt, Groups::CALL)
+            | group(returns, Groups::RETURN)
+            | group(interrupt, Groups::INTERRUPT)
+            | group(indirect, Groups::INDIRECT)
+            | group(
+                self.far || far_operand || interrupt || matches!(mnemonic, Retf | Iret),
+                Groups::FAR,
+            )
+            | group(
+                matches!(mnemonic, Push | Pop | Pushf | Popf | Call) || interrupt || returns,
+                Groups::STACK,
+            )
+            | group(matches!(mnemonic, In | Out), Groups::IO)
+            | group(
+                (mnemonic != Lea
+                    && operands().any(|operand| matches!(operand, Operand::Memory(_))))
+                    || mnemonic.is_string()
+                    || mnemonic == Xlat,
+                Groups::MEMORY,
+            )
+            | group(self.rep.is_some(), Groups::REPEATED)
+    }
+
+    /// Whether this can go somewhere other than the next instruction.
+    pub fn is_branch(&self) -> bool {
+        self.groups().contains(Groups::BRANCH)
+    }
+
+    /// Whether this is a branch taken only sometimes.
+    pub fn is_conditional(&self) -> bool {
+        self.groups().contains(Groups::CONDITIONAL)
+    }
+
+    pub fn is_call(&self) -> bool {
+        self.groups().contains(Groups::CALL)
+    }
+
+    pub fn is_return(&self) -> bool {
+        self.groups().contains(Groups::RETURN)
+    }
+
+    /// Where a branch to a relative target goes, as an address like `offset`. `None` for
+    /// anything else, including branches whose target is in a register or memory or another
+    /// segment, and for a target before address 0.
+    pub fn branch_target(&self) -> Option<usize> {
+        match self.operands[0] {
+            Some(Operand::Relative(displacement)) => {
+                usize::try_from(self.end() as isize + displacement as isize).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+
+    fn decode(bytes: &[u8]) -> Instruction {
+        Decoder::with_origin(bytes, 0x100)
+            .next_instruction()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn instructions_are_grouped() {
+        // mov ax, [bx + si]
+        let mov = decode(&[0x8B, 0x00]);
+        assert_eq!(mov.kind(), Kind::DataTransfer);
+        assert_eq!(mov.groups(), Groups::MEMORY);
+        assert!(!mov.is_branch());
+
+        // jne $-2
+        let jne = decode(&[0x75, 0xFE]);
+        assert_eq!(jne.kind(), Kind::ControlTransfer);
+        assert!(jne.is_branch() && jne.is_conditional() && !jne.is_call());
+        assert_eq!(jne.branch_target(), Some(0x100));
+
+        // call [bx]
+        let call = decode(&[0xFF, 0x17]);
+        assert!(call.is_call());
+        assert!(call
+            .groups()
+            .contains(Groups::INDIRECT | Groups::STACK | Groups::MEMORY));
+        assert_eq!(call.branch_target(), None);
+
+        // retf
+        assert!(decode(&[0xCB])
+            .groups()
+            .contains(Groups::RETURN | Groups::FAR));
+
+        // rep stosw
+        let stos = decode(&[0xF3, 0xAB]);
+        assert_eq!(stos.kind(), Kind::String);
+        assert_eq!(stos.groups(), Groups::MEMORY | Groups::REPEATED);
+
+        // lea ax, [bx + si]
+        assert_eq!(decode(&[0x8D, 0x00]).groups(), Groups::NONE);
+    }
+}
@@ -0,0 +1,1121 @@
+use crate::time_block;
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead},
+    mem,
+};
+
+/// Arrays and objects nested deeper than this are refused rather than risk the stack.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they were written. Duplicate keys are kept; `get` finds the first.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member `key` of an object, or `None` if this isn't an object or has no such member.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+}
+
+/// A place in JSON text: a byte offset from the start, and the line and column it's at, both
+/// counted from 1, with columns in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (offset {})",
+            self.line, self.column, self.offset
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Io(io::Error),
+    /// The text ended before the value did.
+    UnexpectedEnd {
+        at: Location,
+    },
+    /// `found`, a token or a byte within one, can't appear `at` where `expected` should.
+    Unexpected {
+        at: Location,
+        found: String,
+        expected: &'static str,
+    },
+    /// The number starting `at` is malformed.
+    InvalidNumber {
+        at: Location,
+    },
+    /// The string starting `at` has a bad escape, a raw control character, or isn't UTF-8.
+    InvalidString {
+        at: Location,
+    },
+    /// Arrays and objects nest more than `MAX_DEPTH` deep `at` this one.
+    TooDeep {
+        at: Location,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Io(error) => write!(f, "failed to read input: {error}"),
+            JsonError::UnexpectedEnd { at } => {
+                write!(f, "the JSON ends part-way through a value at {at}")
+            }
+            JsonError::Unexpected {
+                at,
+                found,
+                expected,
+            } => write!(f, "unexpected {found} at {at}; expected {expected}"),
+            JsonError::InvalidNumber { at } => write!(f, "invalid number at {at}"),
+            JsonError::InvalidString { at } => write!(f, "invalid string at {at}"),
+            JsonError::TooDeep { at } => {
+                write!(f, "values nest more than {MAX_DEPTH} deep at {at}")
+            }
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `text` as a single JSON value, with nothing but whitespace after it.
+pub fn parse(text: &[u8]) -> Result<Value, JsonError> {
+    let mut parser = Parser::new(text);
+    let value = parser.value()?;
+    parser.end()?;
+    Ok(value)
+}
+
+/// One lexical element of JSON. A string's text is `S`: owned by `Lexer::next_token`, or left
+/// in the lexer by `Lexer::read_token`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token<S = String> {
+    BeginObject,
+    EndObject,
+    BeginArray,
+    EndArray,
+    Colon,
+    Comma,
+    /// A string with its escapes decoded.
+    String(S),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+/// Splits JSON text into tokens, skipping the whitespace between them. It only checks that
+/// each token is well formed; putting them together is the parser's job.
+pub struct Lexer<R> {
+    input: R,
+    /// Bytes consumed so far.
+    offset: usize,
+    /// Lines started so far, and the offset the current one starts at. Newlines can only come
+    /// between tokens, so whitespace is the only place they're counted.
+    line: usize,
+    line_start: usize,
+    /// Where the last token started, for reporting it as out of place.
+    start: usize,
+    /// The text of the last string read, reused from one string to the next.
+    string: String,
+    failed: bool,
+}
+
+impl<R: BufRead> Lexer<R> {
+    pub fn new(input: R) -> Self {
+        Lexer {
+            input,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+            start: 0,
+            string: String::new(),
+            failed: false,
+        }
+    }
+
+    /// Bytes read so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Where the last token read started.
+    pub fn token_start(&self) -> usize {
+        self.start
+    }
+
+    /// Where `offset`, on the line being read, is.
+    pub fn location(&self, offset: usize) -> Location {
+        Location {
+            offset,
+            line: self.line,
+            column: offset - self.line_start + 1,
+        }
+    }
+
+    /// The error for `token`, the last one read, appearing where `expected` should.
+    pub fn unexpected(&self, token: Token<()>, expected: &'static str) -> JsonError {
+        let found = match token {
+            Token::BeginObject => String::from("'{'"),
+            Token::EndObject => String::from("'}'"),
+            Token::BeginArray => String::from("'['"),
+            Token::EndArray => String::from("']'"),
+            Token::Colon => String::from("':'"),
+            Token::Comma => String::from("','"),
+            Token::String(()) => format!("string {:?}", self.string),
+            Token::Number(number) => format!("number {number}"),
+            Token::Bool(bool) => bool.to_string(),
+            Token::Null => String::from("null"),
+        };
+        JsonError::Unexpected {
+            at: self.location(self.start),
+            found,
+            expected,
+        }
+    }
+
+    /// The text of the last string token read by `read_token`.
+    pub fn string(&self) -> &str {
+        &self.string
+    }
+
+    /// The next token, or `None` once only whitespace is left.
+    pub fn next_token(&mut self) -> Result<Option<Token>, JsonError> {
+        let token = self.read_token()?;
+        Ok(token.map(|token| match token {
+            Token::BeginObject => Token::BeginObject,
+            Token::EndObject => Token::EndObject,
+            Token::BeginArray => Token::BeginArray,
+            Token::EndArray => Token::EndArray,
+            Token::Colon => Token::Colon,
+            Token::Comma => Token::Comma,
+            Token::String(()) => Token::String(self.string.clone()),
+            Token::Number(number) => Token::Number(number),
+            Token::Bool(bool) => Token::Bool(bool),
+            Token::Null => Token::Null,
+        }))
+    }
+
+    /// The next token as `next_token` gives it, but without copying out a string's text,
+    /// which stays in `string()` until the next token is read.
+    pub fn read_token(&mut self) -> Result<Option<Token<()>>, JsonError> {
+        self.skip_whitespace()?;
+        let Some(byte) = self.peek()? else {
+            return Ok(None);
+        };
+        self.start = self.offset;
+        let token = match byte {
+            b'{' => self.single(Token::BeginObject),
+            b'}' => self.single(Token::EndObject),
+            b'[' => self.single(Token::BeginArray),
+            b']' => self.single(Token::EndArray),
+            b':' => self.single(Token::Colon),
+            b',' => self.single(Token::Comma),
+            b'"' => {
+                self.read_string()?;
+                Token::String(())
+            }
+            b'-' | b'0'..=b'9' => Token::Number(self.number()?),
+            b't' => self.literal("true", Token::Bool(true))?,
+            b'f' => self.literal("false", Token::Bool(false))?,
+            b'n' => self.literal("null", Token::Null)?,
+            _ => return Err(self.unexpected_byte(byte, "a value or punctuation")),
+        };
+        Ok(Some(token))
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, JsonError> {
+        let buffer = self.input.fill_buf().map_err(JsonError::Io)?;
+        Ok(buffer.first().copied())
+    }
+
+    // Consumes the byte `peek` returned.
+    fn advance(&mut self) {
+        self.input.consume(1);
+        self.offset += 1;
+    }
+
+    fn next_byte(&mut self) -> Result<u8, JsonError> {
+        let byte = self.peek()?.ok_or_else(|| self.unexpected_end())?;
+        self.advance();
+        Ok(byte)
+    }
+
+    /// The error for the input ending here.
+    fn unexpected_end(&self) -> JsonError {
+        JsonError::UnexpectedEnd {
+            at: self.location(self.offset),
+        }
+    }
+
+    /// The error for `byte`, the next one, appearing where `expected` should.
+    fn unexpected_byte(&self, byte: u8, expected: &'static str) -> JsonError {
+        JsonError::Unexpected {
+            at: self.location(self.offset),
+            found: format!("{:?}", byte as char),
+            expected,
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), JsonError> {
+        while let Some(byte @ (b' ' | b'\t' | b'\n' | b'\r')) = self.peek()? {
+            self.advance();
+            if byte == b'\n' {
+                self.line += 1;
+                self.line_start = self.offset;
+            }
+        }
+        Ok(())
+    }
+
+    fn single(&mut self, token: Token<()>) -> Token<()> {
+        self.advance();
+        token
+    }
+
+    fn literal(&mut self, word: &'static str, token: Token<()>) -> Result<Token<()>, JsonError> {
+        for &expected in word.as_bytes() {
+            let byte = self.peek()?.ok_or_else(|| self.unexpected_end())?;
+            if byte != expected {
+                return Err(self.unexpected_byte(byte, word));
+            }
+            self.advance();
+        }
+        Ok(token)
+    }
+
+    fn hex_escape(&mut self, start: Location) -> Result<u16, JsonError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = (self.next_byte()? as char)
+                .to_digit(16)
+                .ok_or(JsonError::InvalidString { at: start })?;
+            value = value << 4 | digit as u16;
+        }
+        Ok(value)
+    }
+
+    // Decodes a string into `self.string`.
+    fn read_string(&mut self) -> Result<(), JsonError> {
+        let start = self.location(self.offset);
+        let invalid = || JsonError::InvalidString { at: start };
+        self.advance();
+        let mut bytes = mem::take(&mut self.string).into_bytes();
+        bytes.clear();
+        loop {
+            match self.next_byte()? {
+                b'"' => break,
+                b'\\' => {
+                    let character = match self.next_byte()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let unit = self.hex_escape(start)?;
+                            // A character outside the BMP is escaped as a surrogate pair.
+                            let code = if (0xD800..0xDC00).contains(&unit) {
+                                if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+                                    return Err(invalid());
+                                }
+                                let low = self.hex_escape(start)?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(invalid());
+                                }
+                                0x10000 + ((unit as u32 - 0xD800) << 10 | (low as u32 - 0xDC00))
+                            } else {
+                                unit as u32
+                            };
+                            char::from_u32(code).ok_or_else(invalid)?
+                        }
+                        _ => return Err(invalid()),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                0..=0x1F => return Err(invalid()),
+                byte => bytes.push(byte),
+            }
+        }
+        self.string = String::from_utf8(bytes).map_err(|_| invalid())?;
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<f64, JsonError> {
+        let start = self.location(self.offset);
+        let invalid = || JsonError::InvalidNumber { at: start };
+        let mut text = String::new();
+        // Appends a run of digits to `text`, returning how many there were.
+        let digits = |lexer: &mut Self, text: &mut String| -> Result<usize, JsonError> {
+            let mut count = 0;
+            while let Some(digit @ b'0'..=b'9') = lexer.peek()? {
+                text.push(digit as char);
+                lexer.advance();
+                count += 1;
+            }
+            Ok(count)
+        };
+        let optional = |lexer: &mut Self, text: &mut String, accepted: &[u8]| match lexer.peek()? {
+            Some(byte) if accepted.contains(&byte) => {
+                text.push(byte as char);
+                lexer.advance();
+                Ok(true)
+            }
+            _ => Ok::<_, JsonError>(false),
+        };
+
+        optional(self, &mut text, b"-")?;
+        // No leading zeros: a lone 0 is the whole integer part.
+        if !optional(self, &mut text, b"0")? && digits(self, &mut text)? == 0 {
+            return Err(invalid());
+        }
+        if optional(self, &mut text, b".")? && digits(self, &mut text)? == 0 {
+            return Err(invalid());
+        }
+        if optional(self, &mut text, b"eE")? {
+            optional(self, &mut text, b"+-")?;
+            if digits(self, &mut text)? == 0 {
+                return Err(invalid());
+            }
+        }
+
+        // Rust's conversion rounds correctly, so the nearest f64 to the text comes back.
+        text.parse().map_err(|_| invalid())
+    }
+}
+
+/// The tokens up to the end of the input, stopping after the first error.
+impl<R: BufRead> Iterator for Lexer<R> {
+    type Item = Result<Token, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let token = self.next_token();
+        self.failed = token.is_err();
+        token.transpose()
+    }
+}
+
+/// Where a parsed value's nodes and strings are allocated: each on the heap as a `Value`, or
+/// all together in a `Document`. Parsing is the same either way, so switching between them
+/// shows what the allocations cost.
+trait Allocator {
+    type Node;
+    type Key;
+    /// An array or object being parsed, collecting its elements or members.
+    type Array;
+    type Object;
+
+    fn string(&mut self, text: &str) -> Self::Node;
+    fn key(&mut self, text: &str) -> Self::Key;
+    fn scalar(&mut self, token: Token<()>) -> Self::Node;
+    fn array(&mut self) -> Self::Array;
+    fn push(&mut self, array: &mut Self::Array, element: Self::Node);
+    fn end_array(&mut self, array: Self::Array) -> Self::Node;
+    fn object(&mut self) -> Self::Object;
+    fn insert(&mut self, object: &mut Self::Object, key: Self::Key, value: Self::Node);
+    fn end_object(&mut self, object: Self::Object) -> Self::Node;
+}
+
+struct Heap;
+
+impl Allocator for Heap {
+    type Node = Value;
+    type Key = String;
+    type Array = Vec<Value>;
+    type Object = Vec<(String, Value)>;
+
+    fn string(&mut self, text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    fn key(&mut self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn scalar(&mut self, token: Token<()>) -> Value {
+        match token {
+            Token::Number(number) => Value::Number(number),
+            Token::Bool(bool) => Value::Bool(bool),
+            _ => Value::Null,
+        }
+    }
+
+    fn array(&mut self) -> Vec<Value> {
+        Vec::new()
+    }
+
+    fn push(&mut self, array: &mut Vec<Value>, element: Value) {
+        array.push(element);
+    }
+
+    fn end_array(&mut self, array: Vec<Value>) -> Value {
+        Value::Array(array)
+    }
+
+    fn object(&mut self) -> Vec<(String, Value)> {
+        Vec::new()
+    }
+
+    fn insert(&mut self, object: &mut Vec<(String, Value)>, key: String, value: Value) {
+        object.push((key, value));
+    }
+
+    fn end_object(&mut self, object: Vec<(String, Value)>) -> Value {
+        Value::Object(object)
+    }
+}
+
+/// Builds values from the tokens of a `Lexer`.
+pub struct Parser<R> {
+    lexer: Lexer<R>,
+}
+
+impl<R: BufRead> Parser<R> {
+    pub fn new(input: R) -> Self {
+        Parser {
+            lexer: Lexer::new(input),
+        }
+    }
+
+    pub fn lexer(&self) -> &Lexer<R> {
+        &self.lexer
+    }
+
+    /// Reads the next value.
+    pub fn value(&mut self) -> Result<Value, JsonError> {
+        let token = self.token()?;
+        self.value_in(&mut Heap, token, 0)
+    }
+
+    /// Reads the next value into a `Document`.
+    pub fn document(&mut self) -> Result<Document, JsonError> {
+        let mut document = Document::default();
+        let token = self.token()?;
+        let root = self.value_in(&mut document, token, 0)?;
+        document.nodes.push(root);
+        // Only needed while parsing.
+        document.pending = Vec::new();
+        Ok(document)
+    }
+
+    /// Checks that nothing but whitespace is left.
+    pub fn end(&mut self) -> Result<(), JsonError> {
+        match self.lexer.read_token()? {
+            None => Ok(()),
+            Some(token) => Err(self.lexer.unexpected(token, "the end of the input")),
+        }
+    }
+
+    // The next token, which the input must have.
+    fn token(&mut self) -> Result<Token<()>, JsonError> {
+        self.lexer
+            .read_token()?
+            .ok_or_else(|| self.lexer.unexpected_end())
+    }
+
+    /// The next token, which must be the punctuation `expected`, described as `description`.
+    fn expect(&mut self, expected: Token<()>, description: &'static str) -> Result<(), JsonError> {
+        let token = self.token()?;
+        if token != expected {
+            return Err(self.lexer.unexpected(token, description));
+        }
+        Ok(())
+    }
+
+    /// The value that starts with `token`, inside `depth` arrays and objects, allocated by
+    /// `allocator`.
+    fn value_in<A: Allocator>(
+        &mut self,
+        allocator: &mut A,
+        token: Token<()>,
+        depth: usize,
+    ) -> Result<A::Node, JsonError> {
+        match token {
+            Token::BeginObject | Token::BeginArray if depth == MAX_DEPTH => {
+                Err(JsonError::TooDeep {
+                    at: self.lexer.location(self.lexer.token_start()),
+                })
+            }
+            Token::BeginObject => {
+                time_block!("parse object");
+                let mut object = allocator.object();
+                self.elements(Token::EndObject, |parser, token| {
+                    let key = parser.key(token, |text| allocator.key(text))?;
+                    let token = parser.token()?;
+                    let value = parser.value_in(allocator, token, depth + 1)?;
+                    allocator.insert(&mut object, key, value);
+                    Ok(())
+                })?;
+                Ok(allocator.end_object(object))
+            }
+            Token::BeginArray => {
+                time_block!("parse array");
+                let mut array = allocator.array();
+                self.elements(Token::EndArray, |parser, token| {
+                    let element = parser.value_in(allocator, token, depth + 1)?;
+                    allocator.push(&mut array, element);
+                    Ok(())
+                })?;
+                Ok(allocator.end_array(array))
+            }
+            Token::String(()) => Ok(allocator.string(self.lexer.string())),
+            Token::Number(_) | Token::Bool(_) | Token::Null => Ok(allocator.scalar(token)),
+            Token::EndObject | Token::EndArray | Token::Colon | Token::Comma => {
+                Err(self.lexer.unexpected(token, "a value"))
+            }
+        }
+    }
+
+    // Elements separated by commas up to `close`, the opening bracket already read. Each
+    // element is given its first token.
+    fn elements(
+        &mut self,
+        close: Token<()>,
+        mut element: impl FnMut(&mut Self, Token<()>) -> Result<(), JsonError>,
+    ) -> Result<(), JsonError> {
+        let token = self.token()?;
+        if token == close {
+            return Ok(());
+        }
+        element(self, token)?;
+        self.rest(close, element)
+    }
+
+    // The elements after the first, each introduced by a comma, up to `close`.
+    fn rest(
+        &mut self,
+        close: Token<()>,
+        mut element: impl FnMut(&mut Self, Token<()>) -> Result<(), JsonError>,
+    ) -> Result<(), JsonError> {
+        loop {
+            match self.token()? {
+                Token::Comma => {
+                    let token = self.token()?;
+                    element(self, token)?;
+                }
+                token if token == close => return Ok(()),
+                token => return Err(self.lexer.unexpected(token, separator(close))),
+            }
+        }
+    }
+
+    /// A member's name, from its first token, handed to `keep` before the colon after it is
+    /// read.
+    fn key<T>(&mut self, token: Token<()>, keep: impl FnOnce(&str) -> T) -> Result<T, JsonError> {
+        if token != Token::String(()) {
+            return Err(self.lexer.unexpected(token, "a member name"));
+        }
+        let key = keep(self.lexer.string());
+        self.expect(Token::Colon, "':'")?;
+        Ok(key)
+    }
+}
+
+/// What may come after an element of the array or object that `close` ends.
+fn separator(close: Token<()>) -> &'static str {
+    match close {
+        Token::EndObject => "',' or '}'",
+        _ => "',' or ']'",
+    }
+}
+
+/// A JSON value whose nodes share one allocation and whose strings share another, however
+/// many there are, rather than each having its own as in a `Value`. `parse_document` builds
+/// one; `root` reads it.
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    /// Each array's elements, and each object's keys and values in turn, are contiguous; the
+    /// root is last.
+    nodes: Vec<Node>,
+    text: String,
+    /// Elements of the arrays and objects being parsed, waiting for their siblings.
+    pending: Vec<Node>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Node {
+    Null,
+    Bool(bool),
+    Number(f64),
+    /// A range of `Document::text`.
+    String {
+        start: usize,
+        end: usize,
+    },
+    Array {
+        first: usize,
+        len: usize,
+    },
+    /// `len` members, each a string node then its value.
+    Object {
+        first: usize,
+        len: usize,
+    },
+}
+
+// An array or object being parsed is where its elements start in `pending`; they move to
+// `nodes` together once it's complete, so that they're contiguous.
+impl Allocator for Document {
+    type Node = Node;
+    type Key = Node;
+    type Array = usize;
+    type Object = usize;
+
+    fn string(&mut self, text: &str) -> Node {
+        let start = self.text.len();
+        self.text.push_str(text);
+        Node::String {
+            start,
+            end: self.text.len(),
+        }
+    }
+
+    fn key(&mut self, text: &str) -> Node {
+        self.string(text)
+    }
+
+    fn scalar(&mut self, token: Token<()>) -> Node {
+        match token {
+            Token::Number(number) => Node::Number(number),
+            Token::Bool(bool) => Node::Bool(bool),
+            _ => Node::Null,
+        }
+    }
+
+    fn array(&mut self) -> usize {
+        self.pending.len()
+    }
+
+    fn push(&mut self, _: &mut usize, element: Node) {
+        self.pending.push(element);
+    }
+
+    fn end_array(&mut self, start: usize) -> Node {
+        let first = self.nodes.len();
+        self.nodes.extend(self.pending.drain(start..));
+        Node::Array {
+            first,
+            len: self.nodes.len() - first,
+        }
+    }
+
+    fn object(&mut self) -> usize {
+        self.pending.len()
+    }
+
+    fn insert(&mut self, _: &mut usize, key: Node, value: Node) {
+        self.pending.extend([key, value]);
+    }
+
+    fn end_object(&mut self, start: usize) -> Node {
+        let first = self.nodes.len();
+        self.nodes.extend(self.pending.drain(start..));
+        Node::Object {
+            first,
+            len: (self.nodes.len() - first) / 2,
+        }
+    }
+}
+
+/// Parses `text` as `parse` does, into a `Document`.
+pub fn parse_document(text: &[u8]) -> Result<Document, JsonError> {
+    let mut parser = Parser::new(text);
+    let document = parser.document()?;
+    parser.end()?;
+    Ok(document)
+}
+
+impl Document {
+    pub fn root(&self) -> NodeRef<'_> {
+        NodeRef {
+            document: self,
+            index: self.nodes.len() - 1,
+        }
+    }
+}
+
+/// A value in a `Document`.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeRef<'a> {
+    document: &'a Document,
+    index: usize,
+}
+
+impl<'a> NodeRef<'a> {
+    fn node(&self) -> Node {
+        self.document.nodes[self.index]
+    }
+
+    fn at(&self, index: usize) -> NodeRef<'a> {
+        NodeRef {
+            document: self.document,
+            index,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.node(), Node::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            Node::Bool(bool) => Some(bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.node() {
+            Node::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.node() {
+            Node::String { start, end } => Some(&self.document.text[start..end]),
+            _ => None,
+        }
+    }
+
+    /// The elements of an array, or `None` if this isn't one.
+    pub fn elements(&self) -> Option<impl Iterator<Item = NodeRef<'a>>> {
+        match self.node() {
+            Node::Array { first, len } => {
+                let this = *self;
+                Some((first..first + len).map(move |index| this.at(index)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The members of an object in the order they were written, or `None` if this isn't one.
+    pub fn members(&self) -> Option<impl Iterator<Item = (&'a str, NodeRef<'a>)>> {
+        match self.node() {
+            Node::Object { first, len } => {
+                let this = *self;
+                Some((0..len).map(move |member| {
+                    let key = this.at(first + 2 * member);
+                    (
+                        key.as_str().unwrap_or_default(),
+                        this.at(first + 2 * member + 1),
+                    )
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// The member `key` of an object, as `Value::get` finds it.
+    pub fn get(&self, key: &str) -> Option<NodeRef<'a>> {
+        self.members()?
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Where `Elements` is in the array it reads.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Start,
+    AfterElement,
+    Done,
+}
+
+/// The elements of one array member of a JSON object, each parsed as it's read rather than
+/// all at once, so memory stays bounded by the largest element however long the array is.
+/// Once the array ends the rest of the object is checked, and any error there is the last
+/// item.
+pub struct Elements<R> {
+    parser: Parser<R>,
+    position: Position,
+}
+
+impl<R: BufRead> Elements<R> {
+    /// Reads `input` up to the start of the array that is the object's member `key`. Returns
+    /// `None` if the object has no such member, or its first one isn't an array.
+    pub fn new(input: R, key: &str) -> Result<Option<Elements<R>>, JsonError> {
+        let mut parser = Parser::new(input);
+        parser.expect(Token::BeginObject, "'{'")?;
+        let mut token = parser.token()?;
+        if token == Token::EndObject {
+            parser.end()?;
+            return Ok(None);
+        }
+        loop {
+            let found = parser.key(token, |name| name == key)?;
+            let value = parser.token()?;
+            if found {
+                if value != Token::BeginArray {
+                    return Ok(None);
+                }
+                return Ok(Some(Elements {
+                    parser,
+                    position: Position::Start,
+                }));
+            }
+            parser.value_in(&mut Heap, value, 1)?;
+            match parser.token()? {
+                Token::Comma => token = parser.token()?,
+                Token::EndObject => {
+                    parser.end()?;
+                    return Ok(None);
+                }
+                token => return Err(parser.lexer.unexpected(token, separator(Token::EndObject))),
+            }
+        }
+    }
+
+    /// Bytes read so far.
+    pub fn offset(&self) -> usize {
+        self.parser.lexer.offset()
+    }
+
+    fn element(&mut self) -> Result<Option<Value>, JsonError> {
+        let parser = &mut self.parser;
+        let token = match (self.position, parser.token()?) {
+            (_, Token::EndArray) => {
+                // After the array: any further members, the object's close, and the end of
+                // the input.
+                parser.rest(Token::EndObject, |parser, token| {
+                    parser.key(token, |_| ())?;
+                    let token = parser.token()?;
+                    parser.value_in(&mut Heap, token, 1).map(drop)
+                })?;
+                parser.end()?;
+                return Ok(None);
+            }
+            (Position::AfterElement, Token::Comma) => parser.token()?,
+            (Position::Start, token) => token,
+            (_, token) => return Err(parser.lexer.unexpected(token, separator(Token::EndArray))),
+        };
+        let value = parser.value_in(&mut Heap, token, 2)?;
+        self.position = Position::AfterElement;
+        Ok(Some(value))
+    }
+}
+
+impl<R: BufRead> Iterator for Elements<R> {
+    type Item = Result<Value, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == Position::Done {
+            return None;
+        }
+        let element = self.element();
+        if !matches!(element, Ok(Some(_))) {
+            self.position = Position::Done;
+        }
+        element.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_of_every_kind_parse() {
+        let json = parse(br#" {"a": [1, -2.5e1, true, null], "b": "x\n\u00e9\ud83d\ude00"} "#);
+        assert_eq!(
+            json.unwrap(),
+            Value::Object(vec![
+                (
+                    String::from("a"),
+                    Value::Array(vec![
+                        Value::Number(1.0),
+                        Value::Number(-25.0),
+                        Value::Bool(true),
+                        Value::Null,
+                    ])
+                ),
+                (String::from("b"), Value::String(String::from("x\né😀"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn numbers_round_trip_exactly() {
+        for number in [0.1, -179.99999999999997, 1e-300, 86.96335635785641] {
+            let text = number.to_string();
+            assert_eq!(parse(text.as_bytes()).unwrap(), Value::Number(number));
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_rejected_where_it_goes_wrong() {
+        let error = |text: &[u8]| parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error(b"[1, 2,]"),
+            "unexpected ']' at line 1, column 7 (offset 6); expected a value"
+        );
+        assert_eq!(
+            error(b"01"),
+            "unexpected number 1 at line 1, column 2 (offset 1); expected the end of the input"
+        );
+        assert_eq!(
+            error(b"{\n  \"a\": 1\n  \"b\": 2}"),
+            "unexpected string \"b\" at line 3, column 3 (offset 13); expected ',' or '}'"
+        );
+        assert_eq!(
+            error(b"[tru]"),
+            "unexpected ']' at line 1, column 5 (offset 4); expected true"
+        );
+        assert_eq!(
+            error(b"-.5"),
+            "invalid number at line 1, column 1 (offset 0)"
+        );
+        assert_eq!(
+            error(b"{\"a\": 1"),
+            "the JSON ends part-way through a value at line 1, column 8 (offset 7)"
+        );
+        assert_eq!(
+            error(b"\n \"\\q\""),
+            "invalid string at line 2, column 2 (offset 2)"
+        );
+        let deep = "[".repeat(MAX_DEPTH + 1);
+        assert!(matches!(
+            parse(deep.as_bytes()),
+            Err(JsonError::TooDeep {
+                at: Location {
+                    offset: MAX_DEPTH,
+                    line: 1,
+                    ..
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn elements_stream_out_of_an_object() {
+        let text = br#"{"skip": {"x": [1]}, "pairs": [{"x0": 1}, 2] , "after": null}"#;
+        let elements = Elements::new(&text[..], "pairs").unwrap().unwrap();
+        let elements: Vec<Value> = elements.map(Result::unwrap).collect();
+        assert_eq!(
+            elements,
+            [
+                Value::Object(vec![(String::from("x0"), Value::Number(1.0))]),
+                Value::Number(2.0),
+            ]
+        );
+
+        assert!(Elements::new(&b"{\"other\": []}"[..], "pairs")
+            .unwrap()
+            .is_none());
+        let mut truncated = Elements::new(&b"{\"pairs\": [1, 2"[..], "pairs")
+            .unwrap()
+            .unwrap();
+        assert!(matches!(truncated.next(), Some(Ok(Value::Number(_)))));
+        assert!(matches!(truncated.next(), Some(Ok(Value::Number(_)))));
+        assert!(matches!(
+            truncated.next(),
+            Some(Err(JsonError::UnexpectedEnd { .. }))
+        ));
+        assert!(truncated.next().is_none());
+    }
+
+    #[test]
+    fn a_document_reads_back_as_its_value_does() {
+        let text = br#"{"pairs": [{"x0": 1.5, "y0": -2}, [true, null, "s"]], "n": {}}"#;
+        let document = parse_document(text).unwrap();
+        let root = document.root();
+        let pairs: Vec<_> = root.get("pairs").unwrap().elements().unwrap().collect();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].get("x0").unwrap().as_f64(), Some(1.5));
+        assert_eq!(pairs[0].get("y0").unwrap().as_f64(), Some(-2.0));
+        let inner: Vec<_> = pairs[1].elements().unwrap().collect();
+        assert_eq!(inner[0].as_bool(), Some(true));
+        assert!(inner[1].is_null());
+        assert_eq!(inner[2].as_str(), Some("s"));
+        assert_eq!(root.get("n").unwrap().members().unwrap().count(), 0);
+        assert!(root.get("missing").is_none());
+    }
+
+    #[test]
+    fn the_lexer_yields_tokens_with_their_positions() {
+        let mut lexer = Lexer::new(&b" {\"a\":[-1.5e2, false]}"[..]);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token().unwrap() {
+            tokens.push((lexer.token_start(), token));
+        }
+        assert_eq!(
+            tokens,
+            [
+                (1, Token::BeginObject),
+                (2, Token::String(String::from("a"))),
+                (5, Token::Colon),
+                (6, Token::BeginArray),
+                (7, Token::Number(-150.0)),
+                (13, Token::Comma),
+                (15, Token::Bool(false)),
+                (20, Token::EndArray),
+                (21, Token::EndObject),
+            ]
+        );
+        assert_eq!(lexer.offset(), 22);
+
+        let tokens: Vec<_> = Lexer::new(&b"[1 @ 2]"[..]).collect();
+        assert!(matches!(
+            tokens[..],
+            [
+                Ok(Token::BeginArray),
+                Ok(Token::Number(_)),
+                Err(JsonError::Unexpected {
+                    at: Location { offset: 3, .. },
+                    ..
+                })
+            ]
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+pub mod assembler;
+pub mod atomic_file;
+pub mod config;
+pub mod coverage;
+pub mod cursor;
+pub mod decoder;
+pub mod dos;
+pub mod encoder;
+pub mod environment;
+pub mod exe;
+pub mod explain;
+pub mod export;
+pub mod failure;
+pub mod flags;
+pub mod formatter;
+pub mod hardware_counters;
+pub mod haversine;
+pub mod haversine_simd;
+pub mod image;
+pub mod instruction;
+pub mod json;
+pub mod listing;
+pub mod log;
+pub mod math;
+pub mod memory;
+pub mod metadata;
+pub mod metrics;
+pub mod os_memory;
+pub mod os_metrics;
+pub mod os_threads;
+pub mod profiler;
+pub mod program_generator;
+pub mod repetition_tester;
+pub mod simulator;
+pub mod snapshot;
+pub mod text_mode;
+pub mod trace_hash;
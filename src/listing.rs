@@ -0,0 +1,112 @@
+use crate::{
+    decoder::decode_skipping_data,
+    instruction::Instruction,
+    memory::Memory,
+    simulator::{Access, Cpu},
+};
+use std::ops::Range;
+
+/// Instructions decoded from simulated memory, kept in step with it: when a program writes
+/// over its own code, the listing is decoded again.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Listing {
+    /// Physical address the listing starts at.
+    pub start: usize,
+    /// Offset the byte at `start` was decoded at, which instruction offsets count from.
+    pub origin: usize,
+    /// Number of bytes the listing covers, including any that don't decode.
+    pub length: usize,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Listing {
+    /// `instructions`, the first of which is at physical address `start`, covering the bytes
+    /// from it to the end of the last.
+    pub fn new(start: usize, instructions: Vec<Instruction>) -> Self {
+        let (origin, length) = match (instructions.first(), instructions.last()) {
+            (Some(first), Some(last)) => (first.offset, last.end() - first.offset),
+            _ => (0, 0),
+        };
+        Listing {
+            start,
+            origin,
+            length,
+            instructions,
+        }
+    }
+
+    /// Decodes the `length` bytes of `memory` from physical address `start` at offset `origin`.
+    /// Bytes no instruction starts at, such as data amongst the code, are stepped over and left
+    /// out rather than failing the whole listing; the CPU decodes whatever it really runs.
+    pub fn decode(memory: &Memory, start: usize, origin: usize, length: usize) -> Self {
+        let (instructions, _) = decode_skipping_data(memory.slice(start, length), origin);
+        Listing {
+            start,
+            origin,
+            length,
+            instructions,
+        }
+    }
+
+    /// Physical addresses the listing covers.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.length
+    }
+
+    /// Physical address of `instruction`, one of the listing's.
+    pub fn address(&self, instruction: &Instruction) -> usize {
+        self.start + instruction.offset - self.origin
+    }
+
+    /// Decodes the listing again from `memory`, keeping its range and origin. Bytes that no
+    /// longer decode, perhaps as the code there is only partly patched or runs into data, are
+    /// stepped over as `decode` does.
+    pub fn redecode(&mut self, memory: &Memory) {
+        *self = Listing::decode(memory, self.start, self.origin, self.length);
+    }
+
+    /// If the instruction `cpu` last executed wrote into the listing, decodes it again and
+    /// returns the first such write.
+    pub fn refresh(&mut self, cpu: &Cpu) -> Option<Access> {
+        let range = self.range();
+        let write = *cpu
+            .accesses
+            .iter()
+            .find(|access| access.write && access.overlaps(&range))?;
+        self.redecode(&cpu.memory);
+        Some(write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{Operand, Register};
+
+    #[test]
+    fn a_patched_immediate_is_run_and_listed() {
+        // mov byte [6], 2; mov ax, 1, whose immediate starts at 6; db "hi"
+        let code = [0xC6, 0x06, 0x06, 0x00, 0x02, 0xB8, 0x01, 0x00, b'h', b'i'];
+        let mut cpu = Cpu::with_memory(0x1000);
+        cpu.memory.load(0, &code);
+        let mut listing = Listing::decode(&cpu.memory, 0, 0, code.len());
+        assert_eq!(listing.instructions.len(), 2);
+        assert_eq!(listing.range(), 0..code.len());
+
+        let patch = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&patch).unwrap();
+        let write = listing.refresh(&cpu).unwrap();
+        assert_eq!(write.address, 6);
+        assert_eq!(
+            listing.instructions[1].operands[1],
+            Some(Operand::Immediate(2))
+        );
+
+        let patched = cpu.fetch().unwrap().unwrap();
+        assert_eq!(patched, listing.instructions[1]);
+        cpu.execute(&patched).unwrap();
+        assert_eq!(cpu.registers.get(Register::Ax), 2);
+        // It wrote nothing, so there's nothing to decode again.
+        assert_eq!(listing.refresh(&cpu), None);
+    }
+}
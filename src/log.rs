@@ -0,0 +1,40 @@
+use std::io;
+use tracing::Level;
+
+/// The level for `-q`, nothing, or one or more `-v`s. At `Debug` the decoder logs the fields it
+/// extracts from each instruction and the bytes it consumes; at `Trace` the simulator logs each
+/// instruction it runs.
+pub fn level_from_verbosity(verbose: u8, quiet: bool) -> Level {
+    match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Logs events at `level` and below to stderr from now on, a line each, apart from whatever
+/// goes to stdout.
+pub fn init(level: Level) {
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_raises_the_level() {
+        assert_eq!(level_from_verbosity(0, true), Level::ERROR);
+        assert_eq!(level_from_verbosity(0, false), Level::WARN);
+        assert_eq!(level_from_verbosity(2, false), Level::DEBUG);
+        assert_eq!(level_from_verbosity(5, false), Level::TRACE);
+        assert!(Level::WARN < Level::DEBUG);
+    }
+}
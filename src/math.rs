@@ -0,0 +1,169 @@
+use std::f64::consts::FRAC_PI_2;
+
+/// Taylor series coefficients, each the last divided by the two next factors of the
+/// factorial, alternating in sign: `first` is 1 for sine's and 0 for cosine's.
+const fn alternating_factorials<const N: usize>(first: usize) -> [f64; N] {
+    let mut coefficients = [0.0; N];
+    let mut term = 1.0;
+    let mut index = 0;
+    while index < N {
+        coefficients[index] = term;
+        let next = 2 * index + first;
+        term = -term / ((next + 1) * (next + 2)) as f64;
+        index += 1;
+    }
+    coefficients
+}
+
+// Ten terms of sine and eleven of cosine are exact to within rounding over [-π/2, π/2].
+pub(crate) const SIN: [f64; 10] = alternating_factorials(1);
+pub(crate) const COS: [f64; 11] = alternating_factorials(0);
+
+/// Series coefficients of arcsine: `(2n)! / (4^n (n!)^2 (2n + 1))` for x^(2n + 1). 25 terms
+/// are exact to within rounding up to 0.5.
+pub(crate) const ASIN: [f64; 25] = {
+    let mut coefficients = [0.0; 25];
+    let mut central = 1.0;
+    let mut n = 0;
+    while n < 25 {
+        coefficients[n] = central / (2 * n + 1) as f64;
+        central = central * ((2 * n + 1) as f64) / ((2 * n + 2) as f64);
+        n += 1;
+    }
+    coefficients
+};
+
+/// The polynomial with `coefficients` in `x²`, Horner's way.
+fn even_polynomial(coefficients: &[f64], x: f64) -> f64 {
+    let x2 = x * x;
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, &coefficient| sum * x2 + coefficient)
+}
+
+// π/2 split in two so that a multiple of the first part is exact for any quadrant count below
+// 2^20 or so, and the second part carries the rest.
+const FRAC_PI_2_HIGH: f64 = 1.570_796_326_734_125_6;
+const FRAC_PI_2_LOW: f64 = 6.077_100_506_506_192e-11;
+
+/// `x` as a number of quarter turns and what's left over, in [-π/4, π/4].
+fn quadrant(x: f64) -> (i64, f64) {
+    let turns = (x / FRAC_PI_2).round();
+    let rest = (x - turns * FRAC_PI_2_HIGH) - turns * FRAC_PI_2_LOW;
+    (turns as i64, rest)
+}
+
+/// Sine and cosine of `x` in [-π/4, π/4].
+fn sin_cos_reduced(x: f64) -> (f64, f64) {
+    (x * even_polynomial(&SIN, x), even_polynomial(&COS, x))
+}
+
+/// Sine of `x`. Precision falls off for angles of more than a million or so radians.
+pub fn sin(x: f64) -> f64 {
+    let (turns, rest) = quadrant(x);
+    let (sine, cosine) = sin_cos_reduced(rest);
+    match turns.rem_euclid(4) {
+        0 => sine,
+        1 => cosine,
+        2 => -sine,
+        _ => -cosine,
+    }
+}
+
+/// Cosine of `x`. Precision falls off for angles of more than a million or so radians.
+pub fn cos(x: f64) -> f64 {
+    let (turns, rest) = quadrant(x);
+    let (sine, cosine) = sin_cos_reduced(rest);
+    match turns.rem_euclid(4) {
+        0 => cosine,
+        1 => -sine,
+        2 => -cosine,
+        _ => sine,
+    }
+}
+
+/// Arcsine of `x` in [-1, 1], and NaN outside it.
+pub fn asin(x: f64) -> f64 {
+    let magnitude = x.abs();
+    if magnitude.is_nan() || magnitude > 1.0 {
+        return f64::NAN;
+    }
+    // Near 1 the series converges too slowly; asin(x) = π/2 - 2 asin(√((1 - x) / 2)) moves
+    // anything above 0.5 below it.
+    let arcsine = if magnitude > 0.5 {
+        let reduced = sqrt((1.0 - magnitude) * 0.5);
+        FRAC_PI_2 - 2.0 * reduced * even_polynomial(&ASIN, reduced)
+    } else {
+        magnitude * even_polynomial(&ASIN, magnitude)
+    };
+    arcsine.copysign(x)
+}
+
+/// Square root of `x` by Newton's method, from a first guess that halves its exponent.
+pub fn sqrt(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 || x.is_infinite() {
+        return x;
+    }
+    // Subnormals don't have the exponent the guess needs, so scale them up by 2^104 first.
+    if x < f64::MIN_POSITIVE {
+        return sqrt(x * 2f64.powi(104)) * 2f64.powi(-52);
+    }
+    let mut root = f64::from_bits((x.to_bits() >> 1) + (1023 << 51));
+    // The guess is within 6%, and each step doubles the correct digits.
+    for _ in 0..5 {
+        root = 0.5 * (root + x / root);
+    }
+    // Newton's method can settle a unit above the correctly rounded root; step down if that's
+    // closer.
+    let below = f64::from_bits(root.to_bits() - 1);
+    if (below * below - x).abs() < (root * root - x).abs() {
+        below
+    } else {
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Units in the last place between `a` and `b`.
+    fn ulps(a: f64, b: f64) -> u64 {
+        let ordered = |x: f64| {
+            let bits = x.to_bits() as i64;
+            if bits < 0 {
+                i64::MIN - bits
+            } else {
+                bits
+            }
+        };
+        ordered(a).abs_diff(ordered(b))
+    }
+
+    #[test]
+    fn functions_agree_with_std() {
+        for step in -2000..=2000 {
+            let x = step as f64 * 0.01;
+            assert!((sin(x) - x.sin()).abs() <= 1e-15, "sin {x}");
+            assert!((cos(x) - x.cos()).abs() <= 1e-15, "cos {x}");
+            let unit = step as f64 / 2000.0;
+            assert!((asin(unit) - unit.asin()).abs() <= 1e-15, "asin {unit}");
+            let positive = x.abs() * 1e3;
+            assert!(
+                ulps(sqrt(positive), positive.sqrt()) <= 1,
+                "sqrt {positive}"
+            );
+        }
+        assert_eq!(sin(FRAC_PI_2), 1.0);
+        assert_eq!(asin(1.0), FRAC_PI_2);
+        assert!(asin(1.5).is_nan() && sqrt(-1.0).is_nan());
+        assert_eq!(
+            sqrt(f64::MIN_POSITIVE / 4.0),
+            f64::MIN_POSITIVE.sqrt() / 2.0
+        );
+    }
+}
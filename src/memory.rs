@@ -0,0 +1,76 @@
+/// The 8086's 1MB physical address space.
+pub const ADDRESS_SPACE: usize = 1 << 20;
+
+/// Byte-addressable simulated memory. Addresses wrap around at the end, as they do on the
+/// 8086 when a segment:offset pair overflows 20 bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory {
+    bytes: Vec<u8>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new(ADDRESS_SPACE)
+    }
+}
+
+impl Memory {
+    /// Zeroed memory of `size` bytes, which must not be zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "memory needs at least one byte");
+        Memory {
+            bytes: vec![0; size],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn wrap(&self, address: usize) -> usize {
+        address % self.bytes.len()
+    }
+
+    pub fn read_u8(&self, address: usize) -> u8 {
+        self.bytes[self.wrap(address)]
+    }
+
+    pub fn write_u8(&mut self, address: usize, value: u8) {
+        let address = self.wrap(address);
+        self.bytes[address] = value;
+    }
+
+    /// Reads a little-endian word, low byte first.
+    pub fn read_u16(&self, address: usize) -> u16 {
+        u16::from_le_bytes([self.read_u8(address), self.read_u8(address + 1)])
+    }
+
+    /// Writes a little-endian word, low byte first.
+    pub fn write_u16(&mut self, address: usize, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write_u8(address, low);
+        self.write_u8(address + 1, high);
+    }
+
+    /// Copies `bytes` in starting at `address`, wrapping past the end.
+    pub fn load(&mut self, address: usize, bytes: &[u8]) {
+        for (index, &byte) in bytes.iter().enumerate() {
+            self.write_u8(address + index, byte);
+        }
+    }
+
+    /// Up to `length` bytes from `address`, stopping at the end of memory rather than wrapping.
+    pub fn slice(&self, address: usize, length: usize) -> &[u8] {
+        let start = self.wrap(address);
+        let end = start.saturating_add(length).min(self.bytes.len());
+        &self.bytes[start..end]
+    }
+}
@@ -0,0 +1,565 @@
+use crate::{
+    flags::Flags,
+    instruction::{AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register},
+};
+
+/// Documented 8086 timing for one instruction, from the 8086 Family User's Manual, Table 2-21.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Clocks excluding effective address calculation. For conditional transfers, when taken.
+    pub base: u32,
+    /// Upper bound of `base` for the data-dependent instructions (MUL/DIV); equal to `base`
+    /// everywhere else.
+    pub base_max: u32,
+    /// Clocks for a conditional transfer that isn't taken.
+    pub not_taken: Option<u32>,
+    /// Clocks added per bit shifted by a CL count, or per iteration of a REP string instruction.
+    pub per_repeat: u32,
+    /// Effective address calculation, including any segment override.
+    pub ea: u32,
+    /// Memory transfers. Word transfers at odd addresses cost 4 extra clocks each on the 8086.
+    pub transfers: u32,
+}
+
+impl Timing {
+    /// `base + ea`, the figure estimation starts from.
+    pub fn total(&self) -> u32 {
+        self.base + self.ea
+    }
+}
+
+/// Static facts about an instruction's behavior.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub timing: Timing,
+    /// Flags given a defined value.
+    pub flags_written: Flags,
+    /// Flags left undefined by the operation.
+    pub flags_undefined: Flags,
+}
+
+impl Metadata {
+    /// Every flag the instruction may change.
+    pub fn flags_modified(&self) -> Flags {
+        self.flags_written | self.flags_undefined
+    }
+}
+
+//----------------------------------------------
+//           EA Components          |  Clocks  |
+//----------------------------------------------
+// Displacement Only                |    6     |
+//----------------------------------------------
+// Base or Index Only               |    5     |
+//----------------------------------------------
+// Displacement + Base or Index     |    9     |
+//----------------------------------------------
+// Base + Index    | BP + DI, BX + SI |  7     |
+//                 | BP + SI, BX + DI |  8     |
+//----------------------------------------------
+// Disp + Base +   | BP + DI, BX + SI |  11    |
+//   Index         | BP + SI, BX + DI |  12    |
+//----------------------------------------------
+// Segment Override                 |   +2     |
+//----------------------------------------------
+/// Clocks to compute `address`, not counting a segment override.
+///
+/// The displacement is charged for whenever one is encoded, even a zero: `[BX + 0]` with a byte
+/// of displacement costs what `[BP + 0]` does, which has no other encoding.
+pub fn ea_clocks(address: &EffectiveAddress) -> u32 {
+    let has_displacement = address.displacement_bytes != 0;
+    match address.base {
+        AddressBase::Direct => 6,
+        AddressBase::Si | AddressBase::Di | AddressBase::Bp | AddressBase::Bx => {
+            if has_displacement {
+                9
+            } else {
+                5
+            }
+        }
+        AddressBase::BpDi | AddressBase::BxSi => {
+            if has_displacement {
+                11
+            } else {
+                7
+            }
+        }
+        AddressBase::BpSi | AddressBase::BxDi => {
+            if has_displacement {
+                12
+            } else {
+                8
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    None,
+    Reg,
+    Mem,
+    Imm,
+    Acc,
+    Seg,
+}
+
+fn shape(instruction: &Instruction, index: usize) -> Shape {
+    match instruction.operands[index] {
+        None => Shape::None,
+        Some(Operand::Register(register)) if register.is_segment() => Shape::Seg,
+        Some(Operand::Register(Register::Al | Register::Ax)) => Shape::Acc,
+        Some(Operand::Register(_)) => Shape::Reg,
+        Some(Operand::Memory(_)) => Shape::Mem,
+        Some(Operand::Immediate(_) | Operand::Relative(_) | Operand::Far { .. }) => Shape::Imm,
+    }
+}
+
+fn fixed(base: u32) -> Timing {
+    Timing {
+        base,
+        base_max: base,
+        ..Timing::default()
+    }
+}
+
+fn memory(base: u32, transfers: u32) -> Timing {
+    Timing {
+        transfers,
+        ..fixed(base)
+    }
+}
+
+fn varying(base: u32, base_max: u32) -> Timing {
+    Timing {
+        base,
+        base_max,
+        ..Timing::default()
+    }
+}
+
+fn conditional(taken: u32, not_taken: u32) -> Timing {
+    Timing {
+        not_taken: Some(not_taken),
+        ..fixed(taken)
+    }
+}
+
+// Accumulator operands are only distinguished where the 8086 has a dedicated encoding.
+fn operand_shapes(instruction: &Instruction) -> (Shape, Shape) {
+    let generalize = |shape| match shape {
+        Shape::Acc => Shape::Reg,
+        shape => shape,
+    };
+    (
+        generalize(shape(instruction, 0)),
+        generalize(shape(instruction, 1)),
+    )
+}
+
+// `MOV AX, [1234]` has its own encoding whose base clocks already include the address.
+fn is_direct_accumulator_mov(instruction: &Instruction) -> bool {
+    let accumulator_and_memory = matches!(
+        (shape(instruction, 0), shape(instruction, 1)),
+        (Shape::Acc, Shape::Mem) | (Shape::Mem, Shape::Acc)
+    );
+    let direct = instruction.operands.iter().flatten().any(|operand| {
+        matches!(operand, Operand::Memory(address) if address.base == AddressBase::Direct)
+    });
+    instruction.mnemonic == Mnemonic::Mov && accumulator_and_memory && direct
+}
+
+fn base_timing(instruction: &Instruction) -> Timing {
+    use Shape::*;
+
+    let shapes = operand_shapes(instruction);
+    let wide = instruction.wide;
+
+    match instruction.mnemonic {
+        Mnemonic::Mov => match shapes {
+            _ if is_direct_accumulator_mov(instruction) => memory(10, 1),
+            (Reg, Reg) | (Seg, Reg) | (Reg, Seg) => fixed(2),
+            (Reg, Mem) | (Seg, Mem) => memory(8, 1),
+            (Mem, Reg) | (Mem, Seg) => memory(9, 1),
+            (Reg, Imm) => fixed(4),
+            (Mem, Imm) => memory(10, 1),
+            _ => Timing::default(),
+        },
+        Mnemonic::Add
+        | Mnemonic::Adc
+        | Mnemonic::Sub
+        | Mnemonic::Sbb
+        | Mnemonic::And
+        | Mnemonic::Or
+        | Mnemonic::Xor => match shapes {
+            (Reg, Reg) => fixed(3),
+            (Reg, Mem) => memory(9, 1),
+            (Mem, Reg) => memory(16, 2),
+            (Reg, Imm) => fixed(4),
+            (Mem, Imm) => memory(17, 2),
+            _ => Timing::default(),
+        },
+        Mnemonic::Cmp => match shapes {
+            (Reg, Reg) => fixed(3),
+            (Reg, Mem) | (Mem, Reg) => memory(9, 1),
+            (Reg, Imm) => fixed(4),
+            (Mem, Imm) => memory(10, 1),
+            _ => Timing::default(),
+        },
+        Mnemonic::Test => match (shape(instruction, 0), shapes) {
+            (Acc, (_, Imm)) => fixed(4),
+            (_, (Reg, Reg)) => fixed(3),
+            (_, (Reg, Mem) | (Mem, Reg)) => memory(9, 1),
+            (_, (Reg, Imm)) => fixed(5),
+            (_, (Mem, Imm)) => memory(11, 1),
+            _ => Timing::default(),
+        },
+        Mnemonic::Inc | Mnemonic::Dec => match shapes {
+            (Reg, _) if wide => fixed(2),
+            (Reg, _) => fixed(3),
+            _ => memory(15, 2),
+        },
+        Mnemonic::Neg | Mnemonic::Not => match shapes {
+            (Reg, _) => fixed(3),
+            _ => memory(16, 2),
+        },
+        Mnemonic::Mul | Mnemonic::Imul | Mnemonic::Div | Mnemonic::Idiv => {
+            let in_memory = shapes.0 == Mem;
+            let (min, max) = match (instruction.mnemonic, wide) {
+                (Mnemonic::Mul, false) => (70, 77),
+                (Mnemonic::Mul, true) => (118, 133),
+                (Mnemonic::Imul, false) => (80, 98),
+                (Mnemonic::Imul, true) => (128, 154),
+                (Mnemonic::Div, false) => (80, 90),
+                (Mnemonic::Div, true) => (144, 162),
+                (Mnemonic::Idiv, false) => (101, 112),
+                (_, _) => (165, 184),
+            };
+            if in_memory {
+                Timing {
+                    transfers: 1,
+                    ..varying(min + 6, max + 6)
+                }
+            } else {
+                varying(min, max)
+            }
+        }
+        Mnemonic::Shl
+        | Mnemonic::Shr
+        | Mnemonic::Sar
+        | Mnemonic::Rol
+        | Mnemonic::Ror
+        | Mnemonic::Rcl
+        | Mnemonic::Rcr => match shapes {
+            (Reg, Imm) => fixed(2),
+            (Reg, Reg) => Timing {
+                per_repeat: 4,
+                ..fixed(8)
+            },
+            (Mem, Imm) => memory(15, 2),
+            _ => Timing {
+                per_repeat: 4,
+                ..memory(20, 2)
+            },
+        },
+        Mnemonic::Push => match shapes {
+            (Seg, _) => memory(10, 1),
+            (Reg, _) => memory(11, 1),
+            _ => memory(16, 2),
+        },
+        Mnemonic::Pop => match shapes {
+            (Reg, _) | (Seg, _) => memory(8, 1),
+            _ => memory(17, 2),
+        },
+        Mnemonic::Pushf => memory(10, 1),
+        Mnemonic::Popf => memory(8, 1),
+        Mnemonic::Xchg => match shapes {
+            (Reg, Mem) | (Mem, Reg) => memory(17, 2),
+            _ if shape(instruction, 0) == Acc && wide => fixed(3),
+            _ => fixed(4),
+        },
+        Mnemonic::In | Mnemonic::Out => {
+            let variable_port = instruction
+                .operands
+                .iter()
+                .flatten()
+                .any(|operand| *operand == Operand::Register(Register::Dx));
+            if variable_port {
+                memory(8, 1)
+            } else {
+                memory(10, 1)
+            }
+        }
+        Mnemonic::Xlat => memory(11, 1),
+        Mnemonic::Lea => fixed(2),
+        Mnemonic::Lds | Mnemonic::Les => memory(16, 2),
+        Mnemonic::Lahf | Mnemonic::Sahf => fixed(4),
+        Mnemonic::Aaa | Mnemonic::Aas | Mnemonic::Daa | Mnemonic::Das => fixed(4),
+        Mnemonic::Aam => fixed(83),
+        Mnemonic::Aad => fixed(60),
+        Mnemonic::Cbw => fixed(2),
+        Mnemonic::Cwd => fixed(5),
+        Mnemonic::Movs | Mnemonic::Cmps | Mnemonic::Scas | Mnemonic::Lods | Mnemonic::Stos => {
+            let (single, per_repeat, transfers) = match instruction.mnemonic {
+                Mnemonic::Movs => (18, 17, 2),
+                Mnemonic::Cmps => (22, 22, 2),
+                Mnemonic::Scas => (15, 15, 1),
+                Mnemonic::Lods => (12, 13, 1),
+                _ => (11, 10, 1),
+            };
+            if instruction.rep.is_some() {
+                Timing {
+                    per_repeat,
+                    ..memory(9, transfers)
+                }
+            } else {
+                memory(single, transfers)
+            }
+        }
+        Mnemonic::Call => match shapes {
+            _ if instruction.far => memory(37, 4),
+            (Imm, _) if matches!(instruction.operands[0], Some(Operand::Far { .. })) => {
+                memory(28, 2)
+            }
+            (Imm, _) => memory(19, 1),
+            (Reg, _) => memory(16, 1),
+            _ => memory(21, 2),
+        },
+        Mnemonic::Jmp => match shapes {
+            _ if instruction.far => memory(24, 2),
+            (Imm, _) => fixed(15),
+            (Reg, _) => fixed(11),
+            _ => memory(18, 1),
+        },
+        Mnemonic::Ret => match shapes {
+            (Imm, _) => memory(12, 1),
+            _ => memory(8, 1),
+        },
+        Mnemonic::Retf => match shapes {
+            (Imm, _) => memory(17, 2),
+            _ => memory(18, 2),
+        },
+        Mnemonic::Je
+        | Mnemonic::Jl
+        | Mnemonic::Jle
+        | Mnemonic::Jb
+        | Mnemonic::Jbe
+        | Mnemonic::Jp
+        | Mnemonic::Jo
+        | Mnemonic::Js
+        | Mnemonic::Jne
+        | Mnemonic::Jnl
+        | Mnemonic::Jg
+        | Mnemonic::Jnb
+        | Mnemonic::Ja
+        | Mnemonic::Jnp
+        | Mnemonic::Jno
+        | Mnemonic::Jns => conditional(16, 4),
+        Mnemonic::Loop => conditional(17, 5),
+        Mnemonic::Loopz => conditional(18, 6),
+        Mnemonic::Loopnz => conditional(19, 5),
+        Mnemonic::Jcxz => conditional(18, 6),
+        Mnemonic::Int => memory(51, 5),
+        Mnemonic::Int3 => memory(52, 5),
+        Mnemonic::Into => Timing {
+            transfers: 5,
+            ..conditional(53, 4)
+        },
+        Mnemonic::Iret => memory(24, 3),
+        Mnemonic::Clc
+        | Mnemonic::Cmc
+        | Mnemonic::Stc
+        | Mnemonic::Cld
+        | Mnemonic::Std
+        | Mnemonic::Cli
+        | Mnemonic::Sti
+        | Mnemonic::Hlt => fixed(2),
+        Mnemonic::Wait => fixed(3),
+        Mnemonic::Esc => match shapes {
+            (_, Mem) => memory(8, 1),
+            _ => fixed(2),
+        },
+    }
+}
+
+/// Documented timing, with the EA calculation filled in for memory operands.
+pub fn timing(instruction: &Instruction) -> Timing {
+    let mut timing = base_timing(instruction);
+
+    let address = instruction
+        .operands
+        .iter()
+        .flatten()
+        .find_map(|operand| match operand {
+            Operand::Memory(address) => Some(address),
+            _ => None,
+        });
+    if let Some(address) = address {
+        if !is_direct_accumulator_mov(instruction) {
+            timing.ea = ea_clocks(address);
+        }
+        if instruction.segment_override.is_some() {
+            timing.ea += 2;
+        }
+    }
+    if instruction.lock {
+        timing.base += 2;
+        timing.base_max += 2;
+    }
+    timing
+}
+
+/// Flags an instruction writes and leaves undefined.
+pub fn flags(mnemonic: Mnemonic) -> (Flags, Flags) {
+    let status = Flags::STATUS;
+    let shift = Flags::CARRY | Flags::OVERFLOW;
+    match mnemonic {
+        Mnemonic::Add
+        | Mnemonic::Adc
+        | Mnemonic::Sub
+        | Mnemonic::Sbb
+        | Mnemonic::Cmp
+        | Mnemonic::Neg
+        | Mnemonic::Cmps
+        | Mnemonic::Scas => (status, Flags::NONE),
+        Mnemonic::Inc | Mnemonic::Dec => (status & !Flags::CARRY, Flags::NONE),
+        Mnemonic::And | Mnemonic::Or | Mnemonic::Xor | Mnemonic::Test => {
+            (status & !Flags::AUX_CARRY, Flags::AUX_CARRY)
+        }
+        Mnemonic::Mul | Mnemonic::Imul => (shift, status & !shift),
+        Mnemonic::Div | Mnemonic::Idiv => (Flags::NONE, status),
+        Mnemonic::Shl | Mnemonic::Shr | Mnemonic::Sar => {
+            (status & !Flags::AUX_CARRY, Flags::AUX_CARRY)
+        }
+        Mnemonic::Rol | Mnemonic::Ror | Mnemonic::Rcl | Mnemonic::Rcr => (shift, Flags::NONE),
+        Mnemonic::Aaa | Mnemonic::Aas => (
+            Flags::AUX_CARRY | Flags::CARRY,
+            status & !(Flags::AUX_CARRY | Flags::CARRY),
+        ),
+        Mnemonic::Daa | Mnemonic::Das => (status & !Flags::OVERFLOW, Flags::OVERFLOW),
+        Mnemonic::Aam | Mnemonic::Aad => (
+            Flags::SIGN | Flags::ZERO | Flags::PARITY,
+            Flags::OVERFLOW | Flags::AUX_CARRY | Flags::CARRY,
+        ),
+        Mnemonic::Sahf => (status & !Flags::OVERFLOW, Flags::NONE),
+        Mnemonic::Popf | Mnemonic::Iret => (Flags::ALL, Flags::NONE),
+        Mnemonic::Clc | Mnemonic::Stc | Mnemonic::Cmc => (Flags::CARRY, Flags::NONE),
+        Mnemonic::Cld | Mnemonic::Std => (Flags::DIRECTION, Flags::NONE),
+        Mnemonic::Cli | Mnemonic::Sti => (Flags::INTERRUPT, Flags::NONE),
+        Mnemonic::Int | Mnemonic::Int3 | Mnemonic::Into => {
+            (Flags::TRAP | Flags::INTERRUPT, Flags::NONE)
+        }
+        _ => (Flags::NONE, Flags::NONE),
+    }
+}
+
+impl Instruction {
+    pub fn metadata(&self) -> Metadata {
+        let (flags_written, flags_undefined) = flags(self.mnemonic);
+        Metadata {
+            timing: timing(self),
+            flags_written,
+            flags_undefined,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+
+    fn decode(bytes: &[u8]) -> Instruction {
+        let instruction = Decoder::new(bytes).next_instruction().unwrap().unwrap();
+        assert_eq!(instruction.length, bytes.len(), "{bytes:02X?}");
+        instruction
+    }
+
+    #[test]
+    fn each_effective_address_form_costs_what_the_manual_says() {
+        // mov cx, [...]: 8 clocks plus the EA.
+        let forms: [(&[u8], u32); 17] = [
+            (&[0x8B, 0x0E, 0x34, 0x12], 6),  // [0x1234]
+            (&[0x8B, 0x0C], 5),              // [si]
+            (&[0x8B, 0x0D], 5),              // [di]
+            (&[0x8B, 0x0F], 5),              // [bx]
+            (&[0x8B, 0x4E, 0x00], 9),        // [bp + 0]
+            (&[0x8B, 0x4F, 0x00], 9),        // [bx + 0], with a byte of displacement
+            (&[0x8B, 0x4C, 0x10], 9),        // [si + 0x10]
+            (&[0x8B, 0x8D, 0x00, 0x10], 9),  // [di + 0x1000]
+            (&[0x8B, 0x0B], 7),              // [bp + di]
+            (&[0x8B, 0x08], 7),              // [bx + si]
+            (&[0x8B, 0x0A], 8),              // [bp + si]
+            (&[0x8B, 0x09], 8),              // [bx + di]
+            (&[0x8B, 0x4B, 0x04], 11),       // [bp + di + 4]
+            (&[0x8B, 0x48, 0x00], 11),       // [bx + si + 0]
+            (&[0x8B, 0x4A, 0xFC], 12),       // [bp + si - 4]
+            (&[0x8B, 0x89, 0x00, 0x01], 12), // [bx + di + 0x100]
+            (&[0x26, 0x8B, 0x0F], 5 + 2),    // es:[bx]
+        ];
+        for (bytes, ea) in forms {
+            let timing = timing(&decode(bytes));
+            assert_eq!(timing.ea, ea, "{bytes:02X?}");
+            assert_eq!(timing.total(), 8 + ea, "{bytes:02X?}");
+            assert_eq!(timing.transfers, 1, "{bytes:02X?}");
+        }
+    }
+
+    #[test]
+    fn zero_displacements_cost_only_when_encoded() {
+        let bx = EffectiveAddress::new(AddressBase::Bx, 0);
+        let bx_byte = EffectiveAddress {
+            displacement_bytes: 1,
+            ..bx
+        };
+        assert_eq!(ea_clocks(&bx), 5);
+        assert_eq!(ea_clocks(&bx_byte), 9);
+        assert_eq!(ea_clocks(&EffectiveAddress::new(AddressBase::Bp, 0)), 9);
+        assert_eq!(ea_clocks(&EffectiveAddress::new(AddressBase::Direct, 0)), 6);
+    }
+
+    #[test]
+    fn instructions_cost_what_the_manual_says() {
+        let table: [(&[u8], u32, u32, u32); 14] = [
+            // (encoding, base, EA, transfers)
+            (&[0x89, 0xD9], 2, 0, 0),              // mov cx, bx
+            (&[0x8B, 0x0F], 8, 5, 1),              // mov cx, [bx]
+            (&[0x89, 0x0F], 9, 5, 1),              // mov [bx], cx
+            (&[0xA1, 0x34, 0x12], 10, 0, 1),       // mov ax, [0x1234]
+            (&[0xB9, 0x05, 0x00], 4, 0, 0),        // mov cx, 5
+            (&[0xC7, 0x07, 0x05, 0x00], 10, 5, 1), // mov word [bx], 5
+            (&[0x01, 0xD9], 3, 0, 0),              // add cx, bx
+            (&[0x03, 0x0F], 9, 5, 1),              // add cx, [bx]
+            (&[0x01, 0x0F], 16, 5, 2),             // add [bx], cx
+            (&[0x83, 0xC1, 0x05], 4, 0, 0),        // add cx, 5
+            (&[0x83, 0x47, 0x02, 0x05], 17, 9, 2), // add word [bx + 2], 5
+            (&[0x83, 0x3F, 0x05], 10, 5, 1),       // cmp word [bx], 5
+            (&[0xF0, 0x01, 0x0F], 16 + 2, 5, 2),   // lock add [bx], cx
+            (&[0xD1, 0xE0], 2, 0, 0),              // shl ax, 1
+        ];
+        for (bytes, base, ea, transfers) in table {
+            let timing = timing(&decode(bytes));
+            assert_eq!(
+                (timing.base, timing.ea, timing.transfers),
+                (base, ea, transfers),
+                "{bytes:02X?}"
+            );
+        }
+
+        // mul word [bx]
+        let mul = timing(&decode(&[0xF7, 0x27]));
+        assert_eq!((mul.base, mul.base_max, mul.ea), (124, 139, 5));
+
+        // shl ax, cl
+        let shl = timing(&decode(&[0xD3, 0xE0]));
+        assert_eq!((shl.base, shl.per_repeat), (8, 4));
+
+        // rep movsw
+        let movs = timing(&decode(&[0xF3, 0xA5]));
+        assert_eq!((movs.base, movs.per_repeat, movs.transfers), (9, 17, 2));
+
+        // je $+2
+        let je = timing(&decode(&[0x74, 0x00]));
+        assert_eq!((je.base, je.not_taken), (16, Some(4)));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::os_metrics::{os_timer_frequency, read_os_timer};
+use std::{fmt, time::Duration};
+
+/// The CPU's timestamp counter: RDTSC on x86-64 and the virtual counter on AArch64. Elsewhere
+/// it falls back to the OS timer, so code timed with it still runs. Ticks only mean something
+/// relative to each other; `estimate_cpu_timer_frequency` says how many there are to a second.
+#[inline(always)]
+pub fn read_cpu_timer() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: RDTSC is available on every x86-64 CPU.
+        unsafe { std::arch::x86_64::_rdtsc() }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ticks: u64;
+        // SAFETY: CNTVCT_EL0 is readable from user space on every AArch64 OS Rust targets.
+        unsafe { std::arch::asm!("mrs {}, cntvct_el0", out(reg) ticks) };
+        ticks
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        read_os_timer()
+    }
+}
+
+/// How `read_cpu_timer_serialized` keeps its read in order with the instructions around it.
+/// Out of order, a plain read can happen before earlier instructions finish or after later ones
+/// start, so the time between two reads needn't be the time of what's between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Serialization {
+    /// The plain read `read_cpu_timer` does.
+    None,
+    /// RDTSCP, which waits for earlier instructions, then LFENCE, which holds later ones back.
+    Rdtscp,
+    /// LFENCE, RDTSC, LFENCE: the read waits for earlier instructions, and later ones for it.
+    Lfence,
+    /// MFENCE first as well, so earlier stores are visible to other cores before the read too.
+    Mfence,
+}
+
+impl Serialization {
+    pub const ALL: [Serialization; 4] = [
+        Serialization::None,
+        Serialization::Rdtscp,
+        Serialization::Lfence,
+        Serialization::Mfence,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Serialization::None => "none",
+            Serialization::Rdtscp => "rdtscp",
+            Serialization::Lfence => "lfence",
+            Serialization::Mfence => "mfence",
+        }
+    }
+
+    /// Whether this CPU can read the timer this way. Only RDTSCP is ever missing, on the
+    /// oldest x86-64 CPUs. Elsewhere the fenced reads are an ISB before the read on AArch64,
+    /// and just the plain read on other CPUs.
+    pub fn is_available(self) -> bool {
+        #[cfg(target_arch = "x86_64")]
+        if self == Serialization::Rdtscp {
+            // Bit 27 of EDX in CPUID leaf 0x80000001 is RDTSCP.
+            return std::arch::x86_64::__cpuid(0x8000_0001).edx & (1 << 27) != 0;
+        }
+        true
+    }
+}
+
+/// `read_cpu_timer`, kept in order with the instructions around it as `serialization` says,
+/// which must be available.
+#[inline(always)]
+pub fn read_cpu_timer_serialized(serialization: Serialization) -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::x86_64::{__rdtscp, _mm_lfence, _mm_mfence, _rdtsc};
+        // SAFETY: RDTSC and the fences are on every x86-64 CPU, and callers only ask for
+        // RDTSCP where it's available.
+        unsafe {
+            match serialization {
+                Serialization::None => _rdtsc(),
+                Serialization::Rdtscp => {
+                    let mut processor = 0;
+                    let ticks = __rdtscp(&mut processor);
+                    _mm_lfence();
+                    ticks
+                }
+                Serialization::Lfence | Serialization::Mfence => {
+                    if serialization == Serialization::Mfence {
+                        _mm_mfence();
+                    }
+                    _mm_lfence();
+                    let ticks = _rdtsc();
+                    _mm_lfence();
+                    ticks
+                }
+            }
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if serialization != Serialization::None {
+            // SAFETY: ISB only waits for earlier instructions to finish.
+            unsafe { std::arch::asm!("isb", options(nostack, preserves_flags)) };
+        }
+        read_cpu_timer()
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        let _ = serialization;
+        read_cpu_timer()
+    }
+}
+
+/// How many ticks of `read_cpu_timer` there are to a second, measured by counting them while
+/// the OS timer runs for `interval`. Longer intervals give closer estimates.
+pub fn estimate_cpu_timer_frequency(interval: Duration) -> u64 {
+    let os_frequency = os_timer_frequency();
+    let wait = (interval.as_secs_f64() * os_frequency as f64) as u64;
+    let cpu_start = read_cpu_timer();
+    let os_start = read_os_timer();
+    let mut elapsed = 0;
+    while elapsed < wait {
+        elapsed = read_os_timer() - os_start;
+    }
+    let ticks = read_cpu_timer().wrapping_sub(cpu_start);
+    match elapsed {
+        0 => 0,
+        elapsed => (ticks as u128 * os_frequency as u128 / elapsed as u128) as u64,
+    }
+}
+
+/// `ticks` of the CPU timer in seconds, at `frequency` ticks a second.
+pub fn ticks_to_seconds(ticks: u64, frequency: u64) -> f64 {
+    if frequency == 0 {
+        0.0
+    } else {
+        ticks as f64 / frequency as f64
+    }
+}
+
+pub(crate) const MEGABYTE: f64 = 1024.0 * 1024.0;
+pub(crate) const GIGABYTE: f64 = 1024.0 * MEGABYTE;
+
+/// Bytes a second, in MB/s, or GB/s once there are enough of them.
+pub(crate) struct Bandwidth(pub f64);
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 >= GIGABYTE {
+            write!(f, "{:.2}GB/s", self.0 / GIGABYTE)
+        } else {
+            write!(f, "{:.2}MB/s", self.0 / MEGABYTE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cpu_timer_advances_at_its_estimated_frequency() {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(10));
+        assert!(frequency > 0);
+        let start = read_cpu_timer();
+        assert!(read_cpu_timer() >= start);
+        for serialization in Serialization::ALL {
+            if serialization.is_available() {
+                assert!(read_cpu_timer_serialized(serialization) >= start);
+            }
+        }
+        assert_eq!(ticks_to_seconds(frequency * 3, frequency), 3.0);
+        assert_eq!(ticks_to_seconds(1, 0), 0.0);
+        assert_eq!(Bandwidth(1.5 * MEGABYTE).to_string(), "1.50MB/s");
+        assert_eq!(Bandwidth(2.5 * GIGABYTE).to_string(), "2.50GB/s");
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    fs::File,
+    io,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice,
+};
+
+/// Zeroed memory straight from the OS, a page at a time: mmap on Unix and VirtualAlloc on
+/// Windows. Each page faults the first time it's touched, however recently the last was freed,
+/// unlike the heap, which hands back memory it already has.
+pub struct Pages {
+    pointer: NonNull<u8>,
+    len: usize,
+    /// How many bytes were mapped, which large pages round `len` up to.
+    mapped: usize,
+    large: bool,
+}
+
+// SAFETY: `Pages` owns its memory outright, like a `Vec<u8>`.
+unsafe impl Send for Pages {}
+unsafe impl Sync for Pages {}
+
+impl Pages {
+    pub fn new(len: usize) -> io::Result<Pages> {
+        let pointer = if len == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(platform::allocate(len)?).ok_or_else(io::Error::last_os_error)?
+        };
+        Ok(Pages {
+            pointer,
+            len,
+            mapped: len,
+            large: false,
+        })
+    }
+
+    /// Like `new`, but in large pages, each covering what many small ones would with a single
+    /// TLB entry and taking a single fault: 2MB hugetlb pages on Linux, or MEM_LARGE_PAGES on
+    /// Windows. Linux only has them once they're reserved, in /proc/sys/vm/nr_hugepages, and
+    /// Windows only for users with the "Lock pages in memory" privilege enabled.
+    pub fn new_large(len: usize) -> io::Result<Pages> {
+        let (pointer, mapped) = platform::allocate_large(len.max(1))?;
+        let pointer = NonNull::new(pointer).ok_or_else(io::Error::last_os_error)?;
+        Ok(Pages {
+            pointer,
+            len,
+            mapped,
+            large: true,
+        })
+    }
+
+    /// Large pages if the OS will give us them, as `new_large`, and ordinary ones if not.
+    pub fn new_preferring_large(len: usize) -> io::Result<Pages> {
+        Pages::new_large(len).or_else(|_| Pages::new(len))
+    }
+
+    /// Whether these are large pages.
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+}
+
+impl Deref for Pages {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the pages are `len` bytes, all initialized to zero, and live as long as self.
+        unsafe { slice::from_raw_parts(self.pointer.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for Pages {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: as for `deref`, and borrowed mutably through self.
+        unsafe { slice::from_raw_parts_mut(self.pointer.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Pages {
+    fn drop(&mut self) {
+        if self.mapped != 0 {
+            // SAFETY: the pages came from `platform::allocate` or `allocate_large` with this
+            // length.
+            unsafe { platform::free(self.pointer.as_ptr(), self.mapped) };
+        }
+    }
+}
+
+/// A whole file mapped read-only into memory, so reading it is just reading memory, with the
+/// OS paging it in as each part is first touched.
+pub struct FileView {
+    pointer: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: the view is read-only and owned, like a `Box<[u8]>`.
+unsafe impl Send for FileView {}
+unsafe impl Sync for FileView {}
+
+impl FileView {
+    /// Maps all of `file`, which must be open for reading. It's undefined what the view holds if
+    /// the file changes meanwhile.
+    pub fn new(file: &File) -> io::Result<FileView> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|error| io::Error::new(io::ErrorKind::OutOfMemory, error))?;
+        let pointer = if len == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(platform::map(file, len)?).ok_or_else(io::Error::last_os_error)?
+        };
+        Ok(FileView { pointer, len })
+    }
+}
+
+impl Deref for FileView {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the view is `len` bytes and lives as long as self.
+        unsafe { slice::from_raw_parts(self.pointer.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for FileView {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: the view came from `platform::map` with this length.
+            unsafe { platform::unmap(self.pointer.as_ptr(), self.len) };
+        }
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{
+        ffi::{c_int, c_void},
+        fs::File,
+        io,
+        os::unix::io::AsRawFd,
+        ptr,
+    };
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_PRIVATE: c_int = 2;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const MAP_ANONYMOUS: c_int = 0x20;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const MAP_ANONYMOUS: c_int = 0x1000;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const MAP_HUGETLB: c_int = 0x40000;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const HUGE_PAGE: usize = 2 << 20;
+
+    extern "C" {
+        fn mmap(
+            address: *mut c_void,
+            len: usize,
+            protection: c_int,
+            flags: c_int,
+            file: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(address: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub fn allocate(len: usize) -> io::Result<*mut u8> {
+        // SAFETY: an anonymous mapping touches no existing memory.
+        let pointer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if pointer == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pointer.cast())
+    }
+
+    /// Large pages, and how many bytes of them there are.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn allocate_large(len: usize) -> io::Result<(*mut u8, usize)> {
+        let len = len.div_ceil(HUGE_PAGE) * HUGE_PAGE;
+        // SAFETY: as for `allocate`.
+        let pointer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB,
+                -1,
+                0,
+            )
+        };
+        if pointer == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((pointer.cast(), len))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn allocate_large(_len: usize) -> io::Result<(*mut u8, usize)> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// # Safety
+    ///
+    /// `pointer` and `len` must be from `allocate` or `allocate_large`, and the memory unused
+    /// from now on.
+    pub unsafe fn free(pointer: *mut u8, len: usize) {
+        munmap(pointer.cast(), len);
+    }
+
+    pub fn map(file: &File, len: usize) -> io::Result<*mut u8> {
+        // SAFETY: a new read-only mapping touches no existing memory, and the descriptor is
+        // open for as long as the call.
+        let pointer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if pointer == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pointer.cast())
+    }
+
+    /// # Safety
+    ///
+    /// As for `free`, from `map`.
+    pub unsafe fn unmap(pointer: *mut u8, len: usize) {
+        munmap(pointer.cast(), len);
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{ffi::c_void, fs::File, io, os::windows::io::AsRawHandle, ptr};
+
+    const FILE_MAP_READ: u32 = 4;
+    const PAGE_READONLY: u32 = 2;
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const MEM_LARGE_PAGES: u32 = 0x2000_0000;
+    const PAGE_READWRITE: u32 = 4;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn VirtualAlloc(
+            address: *mut c_void,
+            size: usize,
+            allocation_type: u32,
+            protection: u32,
+        ) -> *mut c_void;
+        fn VirtualFree(address: *mut c_void, size: usize, free_type: u32) -> i32;
+        fn GetLargePageMinimum() -> usize;
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *mut c_void,
+            protection: u32,
+            size_high: u32,
+            size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+        fn UnmapViewOfFile(address: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub fn allocate(len: usize) -> io::Result<*mut u8> {
+        // SAFETY: a fresh allocation touches no existing memory.
+        let pointer = unsafe {
+            VirtualAlloc(
+                ptr::null_mut(),
+                len,
+                MEM_RESERVE | MEM_COMMIT,
+                PAGE_READWRITE,
+            )
+        };
+        if pointer.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pointer.cast())
+    }
+
+    /// Large pages, and how many bytes of them there are.
+    pub fn allocate_large(len: usize) -> io::Result<(*mut u8, usize)> {
+        // SAFETY: it takes nothing and always succeeds.
+        let page = unsafe { GetLargePageMinimum() };
+        if page == 0 {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let len = len.div_ceil(page) * page;
+        // SAFETY: as for `allocate`.
+        let pointer = unsafe {
+            VirtualAlloc(
+                ptr::null_mut(),
+                len,
+                MEM_RESERVE | MEM_COMMIT | MEM_LARGE_PAGES,
+                PAGE_READWRITE,
+            )
+        };
+        if pointer.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((pointer.cast(), len))
+    }
+
+    /// # Safety
+    ///
+    /// `pointer` must be from `allocate` or `allocate_large`, and the memory unused from now
+    /// on.
+    pub unsafe fn free(pointer: *mut u8, _len: usize) {
+        VirtualFree(pointer.cast(), 0, MEM_RELEASE);
+    }
+
+    pub fn map(file: &File, _len: usize) -> io::Result<*mut u8> {
+        // SAFETY: the file's handle is open for as long as the call, and a new read-only view
+        // touches no existing memory. The view keeps the mapping alive once its handle closes.
+        unsafe {
+            let mapping = CreateFileMappingW(
+                file.as_raw_handle(),
+                ptr::null_mut(),
+                PAGE_READONLY,
+                0,
+                0,
+                ptr::null(),
+            );
+            if mapping.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let pointer = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+            let error = io::Error::last_os_error();
+            CloseHandle(mapping);
+            if pointer.is_null() {
+                return Err(error);
+            }
+            Ok(pointer.cast())
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `pointer` must be from `map`, and the view unused from now on.
+    pub unsafe fn unmap(pointer: *mut u8, _len: usize) {
+        UnmapViewOfFile(pointer.cast());
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use std::{alloc, fs::File, io};
+
+    const PAGE: usize = 4096;
+
+    pub fn allocate(len: usize) -> io::Result<*mut u8> {
+        let layout = alloc::Layout::from_size_align(len, PAGE)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        // SAFETY: `len` isn't zero.
+        Ok(unsafe { alloc::alloc_zeroed(layout) })
+    }
+
+    pub fn allocate_large(_len: usize) -> io::Result<(*mut u8, usize)> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// # Safety
+    ///
+    /// `pointer` and `len` must be from `allocate`, and the memory unused from now on.
+    pub unsafe fn free(pointer: *mut u8, len: usize) {
+        alloc::dealloc(pointer, alloc::Layout::from_size_align_unchecked(len, PAGE));
+    }
+
+    pub fn map(_file: &File, _len: usize) -> io::Result<*mut u8> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// # Safety
+    ///
+    /// Never called, as nothing can be mapped.
+    pub unsafe fn unmap(_pointer: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_start_zeroed_and_can_be_written() {
+        let mut pages = Pages::new(3 * 4096 + 1).unwrap();
+        assert_eq!(pages.len(), 3 * 4096 + 1);
+        assert!(pages.iter().all(|&byte| byte == 0));
+        pages.fill(7);
+        assert_eq!(pages[3 * 4096], 7);
+        assert!(Pages::new(0).unwrap().is_empty());
+
+        let mut pages = Pages::new_preferring_large(3 << 20).unwrap();
+        assert_eq!(pages.len(), 3 << 20);
+        pages.fill(7);
+        assert_eq!(pages[(3 << 20) - 1], 7);
+    }
+
+    #[test]
+    fn file_views_hold_the_file() {
+        let path = std::env::temp_dir().join(format!("file_view_{}", std::process::id()));
+        let contents: Vec<u8> = (0..10_000).map(|index| index as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+        let view = File::open(&path).map(|file| FileView::new(&file));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&view.unwrap().unwrap()[..], &contents[..]);
+    }
+}
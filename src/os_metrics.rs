@@ -0,0 +1,232 @@
+/// The OS's high-resolution monotonic timer, in ticks of `os_timer_frequency`:
+/// QueryPerformanceCounter on Windows, mach_absolute_time on macOS, and
+/// clock_gettime(CLOCK_MONOTONIC) in nanoseconds on Linux. Elsewhere it's nanoseconds since the
+/// first call, from `Instant`.
+pub fn read_os_timer() -> u64 {
+    platform::read()
+}
+
+/// How many ticks of `read_os_timer` there are to a second.
+pub fn os_timer_frequency() -> u64 {
+    platform::frequency()
+}
+
+/// `ticks` of the OS timer in seconds.
+pub fn os_ticks_to_seconds(ticks: u64) -> f64 {
+    ticks as f64 / os_timer_frequency() as f64
+}
+
+/// Page faults this process has taken so far, soft and hard: from GetProcessMemoryInfo on
+/// Windows and getrusage elsewhere. Always 0 where neither exists.
+pub fn read_os_page_fault_count() -> u64 {
+    faults::count()
+}
+
+#[cfg(windows)]
+mod platform {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn QueryPerformanceCounter(count: *mut i64) -> i32;
+        fn QueryPerformanceFrequency(frequency: *mut i64) -> i32;
+    }
+
+    pub fn read() -> u64 {
+        let mut count = 0;
+        // SAFETY: it only writes the count, and can't fail on Windows XP or later.
+        unsafe { QueryPerformanceCounter(&mut count) };
+        count as u64
+    }
+
+    pub fn frequency() -> u64 {
+        let mut frequency = 0;
+        // SAFETY: as for QueryPerformanceCounter.
+        unsafe { QueryPerformanceFrequency(&mut frequency) };
+        frequency as u64
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    #[repr(C)]
+    #[derive(Default)]
+    struct TimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    extern "C" {
+        fn mach_absolute_time() -> u64;
+        fn mach_timebase_info(info: *mut TimebaseInfo) -> i32;
+    }
+
+    pub fn read() -> u64 {
+        // SAFETY: it takes nothing and always succeeds.
+        unsafe { mach_absolute_time() }
+    }
+
+    /// A tick is `numer / denom` nanoseconds.
+    pub fn frequency() -> u64 {
+        let mut info = TimebaseInfo::default();
+        // SAFETY: it only writes `info`.
+        unsafe { mach_timebase_info(&mut info) };
+        1_000_000_000 * info.denom as u64 / info.numer.max(1) as u64
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use std::ffi::{c_int, c_long};
+
+    const CLOCK_MONOTONIC: c_int = 1;
+
+    #[repr(C)]
+    struct Timespec {
+        seconds: c_long,
+        nanoseconds: c_long,
+    }
+
+    extern "C" {
+        fn clock_gettime(clock: c_int, time: *mut Timespec) -> c_int;
+    }
+
+    pub fn read() -> u64 {
+        let mut time = Timespec {
+            seconds: 0,
+            nanoseconds: 0,
+        };
+        // SAFETY: it only writes `time`, and CLOCK_MONOTONIC always exists.
+        unsafe { clock_gettime(CLOCK_MONOTONIC, &mut time) };
+        time.seconds as u64 * 1_000_000_000 + time.nanoseconds as u64
+    }
+
+    pub fn frequency() -> u64 {
+        1_000_000_000
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "android"
+)))]
+mod platform {
+    use std::{sync::OnceLock, time::Instant};
+
+    pub fn read() -> u64 {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+
+    pub fn frequency() -> u64 {
+        1_000_000_000
+    }
+}
+
+#[cfg(windows)]
+mod faults {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProcessMemoryCounters {
+        size: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn K32GetProcessMemoryInfo(
+            process: *mut c_void,
+            counters: *mut ProcessMemoryCounters,
+            size: u32,
+        ) -> i32;
+    }
+
+    pub fn count() -> u64 {
+        let mut counters = ProcessMemoryCounters {
+            size: std::mem::size_of::<ProcessMemoryCounters>() as u32,
+            ..ProcessMemoryCounters::default()
+        };
+        // SAFETY: it only writes `counters`, which is as big as it's told. The current
+        // process's handle is a constant that needn't be closed.
+        unsafe {
+            K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.size);
+        }
+        counters.page_fault_count as u64
+    }
+}
+
+#[cfg(unix)]
+mod faults {
+    use std::ffi::{c_int, c_long};
+
+    const RUSAGE_SELF: c_int = 0;
+
+    /// `struct rusage`. Only the fault counts are read, so the times needn't be exact: a
+    /// `timeval` takes 16 bytes on every 64-bit Unix either way.
+    #[repr(C)]
+    struct Rusage {
+        user_time: [c_long; 2],
+        system_time: [c_long; 2],
+        max_resident_set_size: c_long,
+        integral_shared_size: c_long,
+        integral_unshared_data_size: c_long,
+        integral_unshared_stack_size: c_long,
+        minor_faults: c_long,
+        major_faults: c_long,
+        rest: [c_long; 8],
+    }
+
+    extern "C" {
+        fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+    }
+
+    pub fn count() -> u64 {
+        // SAFETY: all zeroes is a valid `Rusage`, and getrusage only writes it.
+        let mut usage: Rusage = unsafe { std::mem::zeroed() };
+        // SAFETY: as above; RUSAGE_SELF always exists.
+        unsafe { getrusage(RUSAGE_SELF, &mut usage) };
+        (usage.minor_faults + usage.major_faults) as u64
+    }
+}
+
+#[cfg(not(any(windows, unix)))]
+mod faults {
+    pub fn count() -> u64 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_os_timer_counts_up_at_its_frequency() {
+        let start = read_os_timer();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let seconds = os_ticks_to_seconds(read_os_timer() - start);
+        assert!((0.02..1.0).contains(&seconds), "{seconds}");
+    }
+
+    #[test]
+    fn touching_fresh_pages_faults() {
+        let before = read_os_page_fault_count();
+        let mut pages = vec![0u8; 64 << 20];
+        for page in pages.chunks_mut(4096) {
+            page[0] = 1;
+        }
+        std::hint::black_box(&pages);
+        assert!(read_os_page_fault_count() > before);
+    }
+}
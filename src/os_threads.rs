@@ -0,0 +1,171 @@
+use std::io;
+
+/// Keeps the calling thread on logical CPU `cpu` from now on, numbered as the OS numbers
+/// them: sched_setaffinity on Linux and SetThreadAffinityMask on Windows, where only the first
+/// 64 CPUs can be named. Unsupported elsewhere.
+pub fn pin_thread_to_cpu(cpu: usize) -> io::Result<()> {
+    platform::pin(cpu)
+}
+
+/// The logical CPUs of each physical core, which share its execution units as hyperthreads.
+/// Both the cores and each core's CPUs are in order of the CPUs' numbers.
+pub fn physical_cores() -> io::Result<Vec<Vec<usize>>> {
+    let mut cores = platform::cores()?;
+    for cpus in &mut cores {
+        cpus.sort_unstable();
+    }
+    cores.sort_unstable();
+    Ok(cores)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    use std::{collections::BTreeMap, ffi::c_int, fs, io};
+
+    /// `cpu_set_t`: a bit for each of up to 1024 CPUs.
+    #[repr(C)]
+    struct CpuSet([u64; 16]);
+
+    extern "C" {
+        fn sched_setaffinity(pid: c_int, size: usize, set: *const CpuSet) -> c_int;
+    }
+
+    pub fn pin(cpu: usize) -> io::Result<()> {
+        let mut set = CpuSet([0; 16]);
+        let Some(word) = set.0.get_mut(cpu / 64) else {
+            return Err(io::ErrorKind::InvalidInput.into());
+        };
+        *word |= 1 << (cpu % 64);
+        // SAFETY: it only reads `set`, which is as big as it's told. Thread 0 is the caller.
+        if unsafe { sched_setaffinity(0, std::mem::size_of::<CpuSet>(), &set) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Groups the online CPUs in sysfs by their package and core IDs.
+    pub fn cores() -> io::Result<Vec<Vec<usize>>> {
+        let mut cores: BTreeMap<(u32, u32), Vec<usize>> = BTreeMap::new();
+        for entry in fs::read_dir("/sys/devices/system/cpu")? {
+            let path = entry?.path();
+            let Some(cpu) = path
+                .file_name()
+                .and_then(|name| name.to_str()?.strip_prefix("cpu")?.parse().ok())
+            else {
+                continue;
+            };
+            let id = |name: &str| -> Option<u32> {
+                fs::read_to_string(path.join("topology").join(name))
+                    .ok()?
+                    .trim()
+                    .parse()
+                    .ok()
+            };
+            // Offline CPUs have no topology.
+            if let (Some(package), Some(core)) = (id("physical_package_id"), id("core_id")) {
+                cores.entry((package, core)).or_default().push(cpu);
+            }
+        }
+        if cores.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(cores.into_values().collect())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{ffi::c_void, io, ptr};
+
+    const RELATION_PROCESSOR_CORE: u32 = 0;
+
+    /// `SYSTEM_LOGICAL_PROCESSOR_INFORMATION`, with the union it ends in left as bytes.
+    #[repr(C)]
+    struct LogicalProcessorInformation {
+        processor_mask: usize,
+        relationship: u32,
+        rest: [u64; 2],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+        fn GetLogicalProcessorInformation(
+            buffer: *mut LogicalProcessorInformation,
+            length: *mut u32,
+        ) -> i32;
+    }
+
+    pub fn pin(cpu: usize) -> io::Result<()> {
+        if cpu >= usize::BITS as usize {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        // SAFETY: the current thread's handle is a constant that needn't be closed.
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << cpu) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The processor cores' masks, from the first call with a buffer big enough.
+    pub fn cores() -> io::Result<Vec<Vec<usize>>> {
+        let mut length = 0;
+        // SAFETY: with no buffer it only writes the length one needs to be.
+        unsafe { GetLogicalProcessorInformation(ptr::null_mut(), &mut length) };
+        let size = std::mem::size_of::<LogicalProcessorInformation>();
+        let mut records = Vec::with_capacity(length as usize / size + 1);
+        length = (records.capacity() * size) as u32;
+        // SAFETY: it writes at most `length` bytes, the capacity of `records`.
+        if unsafe { GetLogicalProcessorInformation(records.as_mut_ptr(), &mut length) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: it wrote `length` bytes of whole records.
+        unsafe { records.set_len(length as usize / size) };
+        Ok(records
+            .iter()
+            .filter(|record| record.relationship == RELATION_PROCESSOR_CORE)
+            .map(|record| {
+                (0..usize::BITS as usize)
+                    .filter(|cpu| record.processor_mask & (1 << cpu) != 0)
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+mod platform {
+    use std::io;
+
+    pub fn pin(_cpu: usize) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn cores() -> io::Result<Vec<Vec<usize>>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_pin_to_a_cores_cpu() {
+        let cores = match physical_cores() {
+            Err(error) if error.kind() == io::ErrorKind::Unsupported => return,
+            cores => cores.unwrap(),
+        };
+        assert!(cores.iter().all(|cpus| !cpus.is_empty()));
+        let cpu = cores[0][0];
+        std::thread::spawn(move || pin_thread_to_cpu(cpu))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert!(std::thread::spawn(|| pin_thread_to_cpu(1 << 20))
+            .join()
+            .unwrap()
+            .is_err());
+    }
+}
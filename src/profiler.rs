@@ -0,0 +1,577 @@
+use crate::metrics::{
+    estimate_cpu_timer_frequency, read_cpu_timer, ticks_to_seconds, Bandwidth, MEGABYTE,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Times the rest of the enclosing block as a zone called `$label`, a `&'static str`, nested
+/// within whichever zone is open around it. Without the `profiler` feature it's nothing at all.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! time_block {
+    ($label:expr) => {
+        let _zone = {
+            fn label() -> &'static str {
+                $label
+            }
+            static SITE: $crate::profiler::Site = $crate::profiler::Site::new(label);
+            $crate::profiler::Zone::open(&SITE, 0)
+        };
+    };
+}
+
+/// Times the rest of the enclosing block like `time_block!`, counting `$bytes` as processed in
+/// it, so the report gives the zone's bandwidth. Without the `profiler` feature `$bytes` isn't
+/// even evaluated.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! time_bandwidth {
+    ($label:expr, $bytes:expr) => {
+        let _zone = {
+            fn label() -> &'static str {
+                $label
+            }
+            static SITE: $crate::profiler::Site = $crate::profiler::Site::new(label);
+            $crate::profiler::Zone::open(&SITE, $bytes as u64)
+        };
+    };
+}
+
+/// Times the rest of the enclosing function as a zone named after it. Without the `profiler`
+/// feature it's nothing at all.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! time_function {
+    () => {
+        let _zone = {
+            fn here() {}
+            fn label() -> &'static str {
+                $crate::profiler::function_name(::std::any::type_name_of_val(&here))
+            }
+            static SITE: $crate::profiler::Site = $crate::profiler::Site::new(label);
+            $crate::profiler::Zone::open(&SITE, 0)
+        };
+    };
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! time_block {
+    ($label:expr) => {};
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! time_bandwidth {
+    ($label:expr, $bytes:expr) => {};
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! time_function {
+    () => {};
+}
+
+/// The function `here`, a function nested in it, is in, from `here`'s full type name.
+pub fn function_name(here: &'static str) -> &'static str {
+    let function = here.strip_suffix("::here").unwrap_or(here);
+    function.rsplit("::").next().unwrap_or(function)
+}
+
+/// Sites are numbered from 1 as they're first reached; 0 stands for being outside every zone.
+static SITES: AtomicUsize = AtomicUsize::new(1);
+
+/// One place in the code that `time_block!` or `time_function!` times. Each has a static of its
+/// own, so every time through it adds to the same zone.
+pub struct Site {
+    label: fn() -> &'static str,
+    index: AtomicUsize,
+}
+
+impl Site {
+    pub const fn new(label: fn() -> &'static str) -> Site {
+        Site {
+            label,
+            index: AtomicUsize::new(0),
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self.index.load(Ordering::Relaxed) {
+            0 => {
+                let next = SITES.fetch_add(1, Ordering::Relaxed);
+                // Another thread may have got here first; its number stands.
+                match self
+                    .index
+                    .compare_exchange(0, next, Ordering::Relaxed, Ordering::Relaxed)
+                {
+                    Ok(_) => next,
+                    Err(index) => index,
+                }
+            }
+            index => index,
+        }
+    }
+}
+
+/// Everything timed under one site.
+#[derive(Clone, Copy, Default)]
+struct Anchor {
+    label: Option<fn() -> &'static str>,
+    hits: u64,
+    /// Ticks inside the zone, including the zones nested in it. Time in a zone re-entered
+    /// within itself counts once, for the outermost entry.
+    inclusive: u64,
+    /// Ticks inside the zone but outside any nested in it.
+    exclusive: u64,
+    /// Bytes processed inside the zone, by `time_bandwidth!`.
+    bytes: u64,
+}
+
+/// One time through a zone, recorded by `begin_tracing`.
+#[derive(Clone, Copy)]
+struct Entry {
+    index: usize,
+    start: u64,
+    elapsed: u64,
+    depth: u32,
+}
+
+/// The zones a thread has timed since `begin`, by site.
+#[derive(Default)]
+struct Profile {
+    anchors: Vec<Anchor>,
+    /// The zone open now.
+    parent: usize,
+    /// How many zones are open.
+    depth: u32,
+    start: u64,
+    /// Every time through every zone, in the order they closed, when tracing.
+    entries: Option<Vec<Entry>>,
+}
+
+impl Profile {
+    fn anchor(&mut self, index: usize) -> &mut Anchor {
+        if index >= self.anchors.len() {
+            self.anchors.resize(index + 1, Anchor::default());
+        }
+        &mut self.anchors[index]
+    }
+}
+
+thread_local! {
+    static PROFILE: RefCell<Profile> = RefCell::new(Profile::default());
+}
+
+/// A zone being timed, until it's dropped.
+pub struct Zone {
+    index: usize,
+    parent: usize,
+    /// The site's inclusive ticks when the zone opened, which its own elapsed time replaces any
+    /// that a recursive entry adds meanwhile.
+    inclusive: u64,
+    depth: u32,
+    start: u64,
+}
+
+impl Zone {
+    /// Opens a zone at `site` that processes `bytes`.
+    pub fn open(site: &'static Site, bytes: u64) -> Zone {
+        let index = site.index();
+        let (parent, inclusive, depth) = PROFILE.with(|profile| {
+            let mut profile = profile.borrow_mut();
+            let anchor = profile.anchor(index);
+            anchor.label = Some(site.label);
+            anchor.bytes += bytes;
+            let inclusive = anchor.inclusive;
+            let depth = profile.depth;
+            profile.depth += 1;
+            (
+                std::mem::replace(&mut profile.parent, index),
+                inclusive,
+                depth,
+            )
+        });
+        Zone {
+            index,
+            parent,
+            inclusive,
+            depth,
+            start: read_cpu_timer(),
+        }
+    }
+}
+
+impl Drop for Zone {
+    fn drop(&mut self) {
+        let elapsed = read_cpu_timer().wrapping_sub(self.start);
+        PROFILE.with(|profile| {
+            let mut profile = profile.borrow_mut();
+            profile.parent = self.parent;
+            profile.depth = self.depth;
+            if let Some(entries) = &mut profile.entries {
+                entries.push(Entry {
+                    index: self.index,
+                    start: self.start,
+                    elapsed,
+                    depth: self.depth,
+                });
+            }
+            // The parent's own time is what's left once its children's is taken away.
+            let parent = profile.anchor(self.parent);
+            parent.exclusive = parent.exclusive.wrapping_sub(elapsed);
+            let anchor = profile.anchor(self.index);
+            anchor.hits += 1;
+            anchor.inclusive = self.inclusive + elapsed;
+            anchor.exclusive = anchor.exclusive.wrapping_add(elapsed);
+        });
+    }
+}
+
+/// Starts profiling this thread afresh, forgetting any zones timed before.
+pub fn begin() {
+    start(None);
+}
+
+/// Starts profiling like `begin`, but also records every time through every zone, for
+/// `Report::write_chrome_trace`. That takes memory for each one, and a little more time.
+pub fn begin_tracing() {
+    start(Some(Vec::new()));
+}
+
+fn start(entries: Option<Vec<Entry>>) {
+    PROFILE.with(|profile| {
+        *profile.borrow_mut() = Profile {
+            start: read_cpu_timer(),
+            entries,
+            ..Profile::default()
+        }
+    });
+}
+
+/// What this thread's zones took since `begin`. Estimating the CPU timer's frequency for it
+/// takes a tenth of a second.
+pub fn end() -> Report {
+    let end = read_cpu_timer();
+    let (start, anchors, mut entries) = PROFILE.with(|profile| {
+        let mut profile = profile.borrow_mut();
+        let entries = profile.entries.take().unwrap_or_default();
+        (profile.start, profile.anchors.clone(), entries)
+    });
+    let zones = anchors
+        .iter()
+        .skip(1)
+        .filter_map(|anchor| {
+            Some(ZoneReport {
+                label: anchor.label?(),
+                hits: anchor.hits,
+                inclusive: anchor.inclusive,
+                exclusive: anchor.exclusive,
+                bytes: anchor.bytes,
+            })
+        })
+        .filter(|zone| zone.hits != 0)
+        .collect();
+    // Zones close before those around them, but a trace lists them as they opened.
+    entries.sort_by_key(|entry| (entry.start, entry.depth));
+    let spans = entries
+        .iter()
+        .filter_map(|entry| {
+            Some(Span {
+                label: anchors[entry.index].label?(),
+                start: entry.start.wrapping_sub(start),
+                duration: entry.elapsed,
+                depth: entry.depth,
+            })
+        })
+        .collect();
+    Report {
+        total: end.wrapping_sub(start),
+        frequency: estimate_cpu_timer_frequency(Duration::from_millis(100)),
+        zones,
+        spans,
+    }
+}
+
+/// The time spent in one zone, in CPU timer ticks.
+#[derive(Clone, Debug)]
+pub struct ZoneReport {
+    pub label: &'static str,
+    pub hits: u64,
+    /// Ticks with the zones nested in it.
+    pub inclusive: u64,
+    /// Ticks without them: its own.
+    pub exclusive: u64,
+    pub bytes: u64,
+}
+
+impl ZoneReport {
+    /// Inclusive ticks each time through the zone, on average.
+    pub fn ticks_per_hit(&self) -> u64 {
+        self.inclusive / self.hits.max(1)
+    }
+}
+
+/// The zones timed between `begin` and `end`, in the order they were first reached.
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// Ticks from `begin` to `end`.
+    pub total: u64,
+    /// CPU timer ticks a second.
+    pub frequency: u64,
+    pub zones: Vec<ZoneReport>,
+    /// Every time through every zone, in the order they opened, if `begin_tracing` started the
+    /// profile.
+    pub spans: Vec<Span>,
+}
+
+/// One time through a zone, in CPU timer ticks.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub label: &'static str,
+    /// Ticks after `begin_tracing` that it opened.
+    pub start: u64,
+    pub duration: u64,
+    /// How many zones it was nested in.
+    pub depth: u32,
+}
+
+impl Report {
+    /// Writes the spans as Chrome's trace event JSON, for chrome://tracing or Perfetto.
+    pub fn write_chrome_trace(&self, out: &mut impl Write) -> io::Result<()> {
+        let microseconds = |ticks: u64| 1e6 * ticks_to_seconds(ticks, self.frequency);
+        writeln!(out, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")?;
+        for (index, span) in self.spans.iter().enumerate() {
+            let separator = if index + 1 < self.spans.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\"ts\":{:.3},\"dur\":{:.3}}}{separator}",
+                JsonString(span.label),
+                microseconds(span.start),
+                microseconds(span.duration)
+            )?;
+        }
+        writeln!(out, "]}}")
+    }
+
+    /// Writes the ticks spent in each stack of zones, not counting those nested deeper, as
+    /// folded stack lines like `outer;inner 1234`, for inferno or flamegraph.pl.
+    pub fn write_folded_stacks(&self, out: &mut impl Write) -> io::Result<()> {
+        // Each stack's total, in the order it was first reached.
+        let mut stacks: Vec<(String, u64)> = Vec::new();
+        let mut slots = HashMap::new();
+        // The stacks of the spans open around the one reached, by depth.
+        let mut open: Vec<usize> = Vec::new();
+        for span in &self.spans {
+            open.truncate(span.depth as usize);
+            // Semicolons separate frames, so one in a label would split it.
+            let label = span.label.replace(';', ":");
+            let stack = match open.last() {
+                Some(&parent) => format!("{};{label}", stacks[parent].0),
+                None => label,
+            };
+            let slot = *slots.entry(stack.clone()).or_insert_with(|| {
+                stacks.push((stack, 0));
+                stacks.len() - 1
+            });
+            stacks[slot].1 += span.duration;
+            if let Some(&parent) = open.last() {
+                stacks[parent].1 = stacks[parent].1.saturating_sub(span.duration);
+            }
+            open.push(slot);
+        }
+        for (stack, ticks) in stacks {
+            writeln!(out, "{stack} {ticks}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Text escaped to go between a JSON string's quotes.
+struct JsonString<'a>(&'a str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |ticks: u64| 100.0 * ticks as f64 / self.total.max(1) as f64;
+        writeln!(
+            f,
+            "Total time: {:.4}ms (CPU freq {})",
+            1000.0 * ticks_to_seconds(self.total, self.frequency),
+            self.frequency
+        )?;
+        for zone in &self.zones {
+            write!(
+                f,
+                "  {}[{}]: {} ({:.2}%",
+                zone.label,
+                zone.hits,
+                zone.exclusive,
+                percent(zone.exclusive)
+            )?;
+            if zone.inclusive != zone.exclusive {
+                write!(f, ", {:.2}% w/children", percent(zone.inclusive))?;
+            }
+            write!(f, ")")?;
+            if zone.hits > 1 {
+                write!(
+                    f,
+                    "  {} ({:.4}ms) per hit",
+                    zone.ticks_per_hit(),
+                    1000.0 * ticks_to_seconds(zone.ticks_per_hit(), self.frequency)
+                )?;
+            }
+            if zone.bytes != 0 {
+                let seconds = ticks_to_seconds(zone.inclusive, self.frequency);
+                write!(
+                    f,
+                    "  {:.3}MB at {}",
+                    zone.bytes as f64 / MEGABYTE,
+                    Bandwidth(zone.bytes as f64 / seconds)
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "profiler"))]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+
+    fn inner() {
+        time_function!();
+        std::hint::black_box((0..1000).sum::<u64>());
+    }
+
+    #[test]
+    fn nested_zones_split_their_time() {
+        begin();
+        {
+            time_block!("outer");
+            for _ in 0..3 {
+                inner();
+            }
+        }
+        let report = end();
+        let labels: Vec<_> = report.zones.iter().map(|zone| zone.label).collect();
+        assert_eq!(labels, ["outer", "inner"]);
+        let (outer, inner) = (&report.zones[0], &report.zones[1]);
+        assert_eq!((outer.hits, inner.hits), (1, 3));
+        assert_eq!(inner.inclusive, inner.exclusive);
+        assert_eq!(outer.exclusive + inner.inclusive, outer.inclusive);
+        assert!(outer.inclusive <= report.total);
+        assert_eq!(inner.ticks_per_hit(), inner.inclusive / 3);
+        let text = report.to_string();
+        let inner_line = text.lines().find(|line| line.contains("inner[3]")).unwrap();
+        assert!(inner_line.ends_with(" per hit"), "{inner_line}");
+    }
+
+    fn recurse(depth: u32) {
+        time_function!();
+        std::hint::black_box((0..100).sum::<u64>());
+        if depth > 0 {
+            recurse(depth - 1);
+        }
+    }
+
+    #[test]
+    fn recursive_zones_count_their_time_once() {
+        begin();
+        {
+            time_block!("outer");
+            recurse(2);
+            recurse(3);
+        }
+        let report = end();
+        let (outer, recurse) = (&report.zones[0], &report.zones[1]);
+        assert_eq!(recurse.hits, 7);
+        assert_eq!(recurse.inclusive, recurse.exclusive);
+        assert_eq!(outer.exclusive + recurse.inclusive, outer.inclusive);
+    }
+
+    #[test]
+    fn bandwidth_zones_add_up_their_bytes() {
+        begin();
+        for _ in 0..2 {
+            time_bandwidth!("copy", 3 * 1024 * 1024);
+        }
+        let report = end();
+        assert_eq!(report.zones[0].bytes, 6 * 1024 * 1024);
+    }
+
+    #[test]
+    fn traces_list_every_span_as_it_opened() {
+        begin_tracing();
+        {
+            time_block!("outer \"quoted\"");
+            recurse(1);
+        }
+        let report = end();
+        let spans: Vec<_> = report
+            .spans
+            .iter()
+            .map(|span| (span.label, span.depth))
+            .collect();
+        assert_eq!(
+            spans,
+            [("outer \"quoted\"", 0), ("recurse", 1), ("recurse", 2)]
+        );
+
+        let mut trace = Vec::new();
+        report.write_chrome_trace(&mut trace).unwrap();
+        let trace = crate::json::parse(&trace).unwrap();
+        let events = trace.get("traceEvents").and_then(Value::as_array).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0].get("name"),
+            Some(&Value::String("outer \"quoted\"".into()))
+        );
+
+        let mut folded = Vec::new();
+        report.write_folded_stacks(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        let stacks: Vec<_> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap())
+            .collect();
+        assert_eq!(
+            stacks.iter().map(|stack| stack.0).collect::<Vec<_>>(),
+            [
+                "outer \"quoted\"",
+                "outer \"quoted\";recurse",
+                "outer \"quoted\";recurse;recurse"
+            ]
+        );
+        let total: u64 = stacks
+            .iter()
+            .map(|stack| stack.1.parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(total, report.spans[0].duration);
+    }
+}
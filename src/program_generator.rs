@@ -0,0 +1,221 @@
+use crate::{
+    decoder::Decoder,
+    instruction::{AddressBase, Instruction, Mnemonic, Operand, Register},
+};
+
+/// A program from `ProgramGenerator`: code to load at CS:0 and run until IP leaves it.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub code: Vec<u8>,
+    /// The most instructions running the program can take, if every loop runs in full and
+    /// no branch skips anything.
+    pub max_instructions: usize,
+}
+
+/// Generates random 8086 programs that are well formed however their instructions turn out:
+/// control only ever moves forward, or back round a loop counted down from a fixed count.
+///
+/// The instructions themselves are random, of the kinds the simulator runs, except for any
+/// that could break that promise or leave the stack: nothing writes SP or the segment
+/// registers, jumps anywhere, pushes or pops, or writes memory through SS or CS. They should
+/// be run with DS and ES apart from CS, so that memory writes miss the code.
+pub struct ProgramGenerator {
+    /// SplitMix64 state.
+    state: u64,
+}
+
+/// The most instructions in a block, which keeps a loop round one within LOOP's reach.
+const MAX_BLOCK: usize = 8;
+/// The most times a loop goes round.
+const MAX_ITERATIONS: u16 = 8;
+
+impl ProgramGenerator {
+    /// The same seed always generates the same programs.
+    pub fn new(seed: u64) -> Self {
+        ProgramGenerator { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 to `below`, exclusive.
+    fn below(&mut self, below: u64) -> u64 {
+        self.next() % below
+    }
+
+    /// A program of `blocks` blocks of instructions, each run once, skipped over by a
+    /// conditional jump, or looped round.
+    pub fn program(&mut self, blocks: usize) -> Program {
+        let mut program = Program {
+            code: Vec::new(),
+            max_instructions: 0,
+        };
+        for _ in 0..blocks {
+            let count = 1 + self.below(MAX_BLOCK as u64) as usize;
+            let mut block = Vec::new();
+            for _ in 0..count {
+                block.extend_from_slice(&self.instruction());
+            }
+            let code = &mut program.code;
+            match self.below(3) {
+                0 => {
+                    code.extend_from_slice(&block);
+                    program.max_instructions += count;
+                }
+                // jcc past the block
+                1 => {
+                    code.push(0x70 | self.below(16) as u8);
+                    code.push(block.len() as u8);
+                    code.extend_from_slice(&block);
+                    program.max_instructions += 1 + count;
+                }
+                // mov cx, n; top: push cx; block; pop cx; loop top
+                _ => {
+                    let iterations = 1 + self.below(MAX_ITERATIONS as u64) as u16;
+                    code.push(0xB9);
+                    code.extend_from_slice(&iterations.to_le_bytes());
+                    code.push(0x51);
+                    code.extend_from_slice(&block);
+                    code.push(0x59);
+                    code.push(0xE2);
+                    code.push((-(block.len() as i16 + 4)) as u8);
+                    program.max_instructions += 1 + iterations as usize * (count + 3);
+                }
+            }
+        }
+        program
+    }
+
+    /// The bytes of one random instruction that's safe to run anywhere in a block.
+    fn instruction(&mut self) -> Vec<u8> {
+        loop {
+            let bytes = self.next().to_le_bytes();
+            if let Ok(Some(instruction)) = Decoder::new(&bytes).next_instruction() {
+                if is_safe(&instruction) {
+                    return bytes[..instruction.length].to_vec();
+                }
+            }
+        }
+    }
+}
+
+/// Whether `instruction` keeps to the rules `ProgramGenerator` promises.
+fn is_safe(instruction: &Instruction) -> bool {
+    use Mnemonic::*;
+    // What the simulator runs, less control flow, the stack, and the faults of DIV and IDIV.
+    let allowed = matches!(
+        instruction.mnemonic,
+        Mov | Add
+            | Sub
+            | Cmp
+            | Shl
+            | Shr
+            | Sar
+            | Rol
+            | Ror
+            | Rcl
+            | Rcr
+            | Mul
+            | Imul
+            | In
+            | Out
+            | Movs
+            | Cmps
+            | Stos
+            | Lods
+            | Scas
+            | Clc
+            | Stc
+            | Cmc
+            | Cld
+            | Std
+            | Cli
+            | Sti
+    );
+    // A repeated string instruction can run 65535 times; that's for other tests.
+    if !allowed || instruction.rep.is_some() {
+        return false;
+    }
+    if matches!(
+        instruction.segment_override,
+        Some(Register::Ss | Register::Cs)
+    ) {
+        return false;
+    }
+    let written = &instruction.operands[..1];
+    instruction
+        .operands
+        .iter()
+        .flatten()
+        .all(|operand| match operand {
+            // Addresses based on BP are in SS unless overridden.
+            Operand::Memory(address) => {
+                !matches!(
+                    address.base,
+                    AddressBase::Bp | AddressBase::BpSi | AddressBase::BpDi
+                ) || instruction.segment_override.is_some()
+            }
+            _ => true,
+        })
+        && written.iter().flatten().all(|operand| match operand {
+            Operand::Register(register) => *register != Register::Sp && !register.is_segment(),
+            _ => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{physical_address, Cpu};
+
+    const CODE_SEGMENT: u16 = 0;
+    const DATA_SEGMENT: u16 = 0x1000;
+    const STACK_SEGMENT: u16 = 0x2000;
+    const STACK: std::ops::Range<u32> = 0xF000..0x10000;
+
+    #[test]
+    fn random_programs_run_to_the_end_without_leaving_the_stack() {
+        let mut generator = ProgramGenerator::new(0x8086);
+        for index in 0..200 {
+            let program = generator.program(16);
+            let mut cpu = Cpu::with_memory(0x30000);
+            cpu.memory
+                .load(physical_address(CODE_SEGMENT, 0), &program.code);
+            cpu.registers.set(Register::Cs, CODE_SEGMENT);
+            cpu.registers.set(Register::Ds, DATA_SEGMENT);
+            cpu.registers.set(Register::Es, DATA_SEGMENT);
+            cpu.registers.set(Register::Ss, STACK_SEGMENT);
+            cpu.registers.set(Register::Sp, 0);
+            cpu.stack = Some(STACK);
+
+            let mut executed = 0;
+            while (cpu.ip as usize) < program.code.len() {
+                assert!(
+                    executed < program.max_instructions,
+                    "program {index} ran past {} instructions",
+                    program.max_instructions
+                );
+                let instruction = cpu.fetch().unwrap().unwrap();
+                if let Err(error) = cpu.execute(&instruction) {
+                    panic!("program {index}: {error}");
+                }
+                let sp = match cpu.registers.get(Register::Sp) {
+                    0 => 0x10000,
+                    sp => sp as u32,
+                };
+                assert!(
+                    (STACK.start..=STACK.end).contains(&sp),
+                    "program {index}: SP {sp:#x} left the stack"
+                );
+                assert_eq!(cpu.registers.get(Register::Ss), STACK_SEGMENT);
+                executed += 1;
+            }
+            assert_eq!(cpu.ip as usize, program.code.len(), "program {index}");
+        }
+    }
+}
@@ -0,0 +1,920 @@
+use crate::{
+    atomic_file::write_atomically,
+    export::write_csv_field,
+    failure::Failure,
+    hardware_counters::{open_hardware_counters, Event, EventCounts, HardwareCounters},
+    metrics::{
+        estimate_cpu_timer_frequency, read_cpu_timer, ticks_to_seconds, Bandwidth, GIGABYTE,
+    },
+    os_memory::Pages,
+    os_metrics::read_os_page_fault_count,
+};
+use std::{
+    fmt,
+    io::{self, Write},
+    ops::{AddAssign, Deref, DerefMut},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+/// What one trial, or several added up, took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Measurement {
+    /// CPU timer ticks between `begin_time` and `end_time`.
+    pub ticks: u64,
+    /// Bytes the trial said it processed, with `count_bytes`.
+    pub bytes: u64,
+    /// Page faults the process took between `begin_time` and `end_time`.
+    pub page_faults: u64,
+    /// Hardware events between `begin_time` and `end_time`, if the tester was counting them.
+    pub events: EventCounts,
+}
+
+impl Measurement {
+    /// Bytes processed for each page fault, if there were any.
+    pub fn bytes_per_fault(&self) -> Option<f64> {
+        (self.page_faults != 0).then(|| self.bytes as f64 / self.page_faults as f64)
+    }
+}
+
+impl AddAssign for Measurement {
+    fn add_assign(&mut self, other: Measurement) {
+        self.ticks += other.ticks;
+        self.bytes += other.bytes;
+        self.page_faults += other.page_faults;
+        for (events, other) in self.events.iter_mut().zip(other.events) {
+            *events += other;
+        }
+    }
+}
+
+/// The trials of one wave so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Results {
+    /// CPU timer ticks a second.
+    pub frequency: u64,
+    pub count: u64,
+    pub total: Measurement,
+    pub min: Measurement,
+    pub max: Measurement,
+    /// Which of `Measurement::events` were counted.
+    pub counted: [bool; Event::COUNT],
+}
+
+impl Results {
+    /// Adds `other`'s trials to these, as if they'd been one wave.
+    pub fn merge(&mut self, other: &Results) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.count += other.count;
+        self.total += other.total;
+        if other.min.ticks < self.min.ticks {
+            self.min = other.min;
+        }
+        if other.max.ticks > self.max.ticks {
+            self.max = other.max;
+        }
+    }
+
+    /// The mean trial, or nothing if there haven't been any.
+    pub fn average(&self) -> Measurement {
+        match self.count {
+            0 => Measurement::default(),
+            count => Measurement {
+                ticks: self.total.ticks / count,
+                bytes: self.total.bytes / count,
+                page_faults: self.total.page_faults / count,
+                events: self.total.events.map(|events| events / count),
+            },
+        }
+    }
+
+    /// `measurement` as `label: ticks (ms) bandwidth PF: faults (KB/fault)`, followed by the
+    /// events counted.
+    fn line(&self, label: &str, measurement: Measurement) -> String {
+        let seconds = ticks_to_seconds(measurement.ticks, self.frequency);
+        let mut line = format!("{label}: {} ({:.6}ms)", measurement.ticks, 1000.0 * seconds);
+        if measurement.bytes != 0 && seconds > 0.0 {
+            line += &format!(" {}", Bandwidth(measurement.bytes as f64 / seconds));
+        }
+        if let Some(bytes_per_fault) = measurement.bytes_per_fault() {
+            line += &format!(
+                " PF: {} ({:.4}KB/fault)",
+                measurement.page_faults,
+                bytes_per_fault / 1024.0
+            );
+        }
+        let mut events = Event::ALL
+            .into_iter()
+            .filter(|&event| self.counted[event as usize])
+            .map(|event| format!("{} {}", measurement.events[event as usize], event.name()));
+        if let Some(first) = events.next() {
+            line += &format!(" | {first}");
+            for event in events {
+                line += &format!(", {event}");
+            }
+        }
+        if self.counted[Event::Cycles as usize] && self.counted[Event::Instructions as usize] {
+            let cycles = measurement.events[Event::Cycles as usize];
+            if cycles != 0 {
+                let instructions = measurement.events[Event::Instructions as usize];
+                line += &format!(" ({:.2} IPC)", instructions as f64 / cycles as f64);
+            }
+        }
+        line
+    }
+}
+
+impl fmt::Display for Results {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.line("Min", self.min))?;
+        writeln!(f, "{}", self.line("Max", self.max))?;
+        writeln!(f, "{}", self.line("Avg", self.average()))
+    }
+}
+
+/// The header row for `RepetitionTester::write_csv`. GB are 2^30 bytes, as the results print.
+/// `bytes_per_fault` is empty for trials without any faults, and the hardware events for
+/// testers not counting them.
+pub const CSV_HEADER: &str = "test,trial,ticks,seconds,bytes,gb_per_second,page_faults,\
+    bytes_per_fault,cycles,instructions,branch_misses,cache_misses";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum State {
+    Testing,
+    Completed,
+    Error(String),
+}
+
+/// Runs a test over and over until it stops getting faster, keeping its fastest, slowest, and
+/// average times. After `new_wave`, loop while `is_testing`, each time calling `begin_time` and
+/// `end_time` around the work and `count_bytes` with what it processed. A wave ends once a whole
+/// window passes without a new fastest trial.
+pub struct RepetitionTester {
+    state: State,
+    /// Print each new fastest trial over the last, and the results at the end.
+    live: bool,
+    /// Bytes each trial should say it processed.
+    target_bytes: u64,
+    /// Ticks to go on for without a new fastest trial.
+    window: u64,
+    /// When the wave started, or its fastest trial so far finished.
+    window_start: u64,
+    /// `begin_time` and `end_time` calls in the trial so far, which should pair up.
+    opened: u32,
+    closed: u32,
+    trial: Measurement,
+    results: Results,
+    /// Every trial of the wave, in order, if `keep_trials` asked for them.
+    trials: Option<Vec<Measurement>>,
+    /// Where hardware events are counted from, if `count_events` gave any.
+    counters: Option<Box<dyn HardwareCounters>>,
+}
+
+impl RepetitionTester {
+    /// A tester timing with the CPU timer at `frequency` ticks a second, printing as it goes
+    /// if `live`.
+    pub fn new(frequency: u64, live: bool) -> RepetitionTester {
+        RepetitionTester {
+            state: State::Completed,
+            live,
+            target_bytes: 0,
+            window: 0,
+            window_start: 0,
+            opened: 0,
+            closed: 0,
+            trial: Measurement::default(),
+            results: Results {
+                frequency,
+                ..Results::default()
+            },
+            trials: None,
+            counters: None,
+        }
+    }
+
+    /// Keeps every trial from now on, not just the fastest and slowest, for `write_csv`.
+    pub fn keep_trials(&mut self) {
+        self.trials.get_or_insert_with(Vec::new);
+    }
+
+    /// Counts the events `counters` can in every trial from the next wave on.
+    pub fn count_events(&mut self, counters: Box<dyn HardwareCounters>) {
+        self.counters = Some(counters);
+    }
+
+    /// Starts testing afresh, with trials that each process `target_bytes`, until `window`
+    /// passes without a new fastest one.
+    pub fn new_wave(&mut self, target_bytes: u64, window: Duration) {
+        let frequency = self.results.frequency;
+        let counted = match &self.counters {
+            Some(counters) => Event::ALL.map(|event| counters.counts(event)),
+            None => [false; Event::COUNT],
+        };
+        *self = RepetitionTester {
+            state: State::Testing,
+            target_bytes,
+            window: (window.as_secs_f64() * frequency as f64) as u64,
+            window_start: read_cpu_timer(),
+            trials: self.trials.take().map(|mut trials| {
+                trials.clear();
+                trials
+            }),
+            counters: self.counters.take(),
+            ..RepetitionTester::new(frequency, self.live)
+        };
+        self.results.counted = counted;
+    }
+
+    /// Starts, or restarts, timing the trial. Time between `end_time` and the next
+    /// `begin_time` isn't counted.
+    #[inline(always)]
+    pub fn begin_time(&mut self) {
+        self.opened += 1;
+        if let Some(counters) = &mut self.counters {
+            let events = counters.read();
+            for (trial, events) in self.trial.events.iter_mut().zip(events) {
+                *trial = trial.wrapping_sub(events);
+            }
+        }
+        let faults = read_os_page_fault_count();
+        self.trial.page_faults = self.trial.page_faults.wrapping_sub(faults);
+        self.trial.ticks = self.trial.ticks.wrapping_sub(read_cpu_timer());
+    }
+
+    #[inline(always)]
+    pub fn end_time(&mut self) {
+        self.trial.ticks = self.trial.ticks.wrapping_add(read_cpu_timer());
+        let faults = read_os_page_fault_count();
+        self.trial.page_faults = self.trial.page_faults.wrapping_add(faults);
+        if let Some(counters) = &mut self.counters {
+            let events = counters.read();
+            for (trial, events) in self.trial.events.iter_mut().zip(events) {
+                *trial = trial.wrapping_add(events);
+            }
+        }
+        self.closed += 1;
+    }
+
+    /// Counts `bytes` as processed in the trial.
+    pub fn count_bytes(&mut self, bytes: u64) {
+        self.trial.bytes += bytes;
+    }
+
+    /// Stops the wave, because the test couldn't do what it was timing.
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.state = State::Error(message.into());
+    }
+
+    /// What stopped the wave, if it went wrong.
+    pub fn error_message(&self) -> Option<&str> {
+        match &self.state {
+            State::Error(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    pub fn results(&self) -> &Results {
+        &self.results
+    }
+
+    /// The wave's trials so far, if they're being kept.
+    pub fn trials(&self) -> &[Measurement] {
+        self.trials.as_deref().unwrap_or_default()
+    }
+
+    /// Writes the wave's trials as CSV rows under `CSV_HEADER`, each labelled `test`.
+    pub fn write_csv(&self, out: &mut impl Write, test: &str) -> io::Result<()> {
+        for (index, trial) in self.trials().iter().enumerate() {
+            let seconds = ticks_to_seconds(trial.ticks, self.results.frequency);
+            let bandwidth = if seconds > 0.0 {
+                trial.bytes as f64 / seconds / GIGABYTE
+            } else {
+                0.0
+            };
+            write_csv_field(out, test)?;
+            write!(
+                out,
+                ",{index},{},{seconds:e},{},{bandwidth},{},",
+                trial.ticks, trial.bytes, trial.page_faults
+            )?;
+            if let Some(bytes_per_fault) = trial.bytes_per_fault() {
+                write!(out, "{bytes_per_fault}")?;
+            }
+            for event in Event::ALL {
+                write!(out, ",")?;
+                if self.results.counted[event as usize] {
+                    write!(out, "{}", trial.events[event as usize])?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the trial just run, if any, and says whether to run another.
+    pub fn is_testing(&mut self) -> bool {
+        if self.state != State::Testing {
+            return false;
+        }
+        let now = read_cpu_timer();
+        if self.opened != 0 {
+            if self.opened != self.closed {
+                self.error("begin_time and end_time weren't called in pairs");
+            } else if self.trial.bytes != self.target_bytes {
+                self.error(format!(
+                    "processed {} bytes but expected {}",
+                    self.trial.bytes, self.target_bytes
+                ));
+            } else {
+                self.record(now);
+            }
+        }
+        if self.state == State::Testing && now.wrapping_sub(self.window_start) > self.window {
+            self.state = State::Completed;
+            if self.live {
+                print!("\r{:<60}\r{}", "", self.results);
+                let _ = io::stdout().flush();
+            }
+        }
+        self.trial = Measurement::default();
+        self.opened = 0;
+        self.closed = 0;
+        self.state == State::Testing
+    }
+
+    fn record(&mut self, now: u64) {
+        let trial = self.trial;
+        let results = &mut self.results;
+        results.count += 1;
+        results.total += trial;
+        if let Some(trials) = &mut self.trials {
+            trials.push(trial);
+        }
+        if trial.ticks > results.max.ticks {
+            results.max = trial;
+        }
+        if results.count == 1 || trial.ticks < results.min.ticks {
+            results.min = trial;
+            self.window_start = now;
+            if self.live {
+                print!("\r{:<60}", results.line("Min", trial));
+                let _ = io::stdout().flush();
+            }
+        }
+    }
+}
+
+/// Where a test's trials get the buffer they work on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Allocation {
+    /// One buffer, allocated once and used by every trial, so only the first faults.
+    Reuse,
+    /// A new buffer from the heap for every trial, which may reuse memory freed before.
+    Fresh,
+    /// New pages from the OS for every trial, which all fault.
+    Mapped,
+    /// New large pages from the OS for every trial, which fault far fewer times than `Mapped`
+    /// for the same memory. Ordinary pages where the OS won't give us large ones.
+    Large,
+}
+
+impl Allocation {
+    pub const ALL: [Allocation; 4] = [
+        Allocation::Reuse,
+        Allocation::Fresh,
+        Allocation::Mapped,
+        Allocation::Large,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Allocation::Reuse => "reuse",
+            Allocation::Fresh => "fresh",
+            Allocation::Mapped => "mapped",
+            Allocation::Large => "large",
+        }
+    }
+}
+
+/// A buffer from `Buffers`.
+pub enum Buffer {
+    Heap(Vec<u8>),
+    Pages(Pages),
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Heap(bytes) => bytes,
+            Buffer::Pages(pages) => pages,
+        }
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Heap(bytes) => bytes,
+            Buffer::Pages(pages) => pages,
+        }
+    }
+}
+
+/// Hands out zeroed buffers of one size, allocated as an `Allocation` says. Take one with
+/// `get` before `begin_time` and give it back with `put` after `end_time`, so only touching
+/// the memory is timed, not allocating it.
+pub struct Buffers {
+    allocation: Allocation,
+    size: usize,
+    kept: Option<Buffer>,
+}
+
+impl Buffers {
+    pub fn new(allocation: Allocation, size: usize) -> Buffers {
+        Buffers {
+            allocation,
+            size,
+            kept: None,
+        }
+    }
+
+    pub fn get(&mut self) -> io::Result<Buffer> {
+        if let Some(buffer) = self.kept.take() {
+            return Ok(buffer);
+        }
+        Ok(match self.allocation {
+            Allocation::Reuse | Allocation::Fresh => Buffer::Heap(vec![0; self.size]),
+            Allocation::Mapped => Buffer::Pages(Pages::new(self.size)?),
+            Allocation::Large => Buffer::Pages(Pages::new_preferring_large(self.size)?),
+        })
+    }
+
+    pub fn put(&mut self, buffer: Buffer) {
+        if self.allocation == Allocation::Reuse {
+            self.kept = Some(buffer);
+        }
+    }
+}
+
+/// Several tests' results side by side, a row each.
+#[derive(Default)]
+pub struct Comparison {
+    rows: Vec<(String, Results)>,
+}
+
+impl Comparison {
+    pub fn add(&mut self, test: impl Into<String>, results: &Results) {
+        self.rows.push((test.into(), *results));
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .rows
+            .iter()
+            .map(|(test, _)| test.len())
+            .chain([4])
+            .max()
+            .unwrap_or_default();
+        writeln!(
+            f,
+            "{:<width$}  {:>12}  {:>12}  {:>12}  {:>12}  {:>10}",
+            "test", "min ms", "min", "avg", "max ms", "PF/trial"
+        )?;
+        for (test, results) in &self.rows {
+            let milliseconds = |measurement: Measurement| {
+                1000.0 * ticks_to_seconds(measurement.ticks, results.frequency)
+            };
+            let bandwidth = |measurement: Measurement| {
+                let seconds = ticks_to_seconds(measurement.ticks, results.frequency);
+                if seconds > 0.0 && measurement.bytes > 0 {
+                    Bandwidth(measurement.bytes as f64 / seconds).to_string()
+                } else {
+                    "-".to_string()
+                }
+            };
+            let faults = match results.count {
+                0 => 0.0,
+                count => results.total.page_faults as f64 / count as f64,
+            };
+            writeln!(
+                f,
+                "{test:<width$}  {:>12.6}  {:>12}  {:>12}  {:>12.6}  {faults:>10.1}",
+                milliseconds(results.min),
+                bandwidth(results.min),
+                bandwidth(results.average()),
+                milliseconds(results.max),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A test of one implementation of a task: it runs a wave on the tester it's given, as in the
+/// loop described on `RepetitionTester`, after `new_wave` has started it.
+pub type Test<'a> = Box<dyn FnMut(&mut RepetitionTester) + 'a>;
+
+/// Several implementations of the same task, registered by name and tested in turn, round
+/// after round, so each gets more than one chance at a fastest trial however the machine's
+/// state drifts. Each keeps its trials from every round, for one table comparing them.
+pub struct TestSet<'a> {
+    target_bytes: u64,
+    tests: Vec<(String, Test<'a>)>,
+    errors: Vec<(String, String)>,
+}
+
+impl<'a> TestSet<'a> {
+    /// A set of tests that each process `target_bytes` a trial.
+    pub fn new(target_bytes: u64) -> TestSet<'a> {
+        TestSet {
+            target_bytes,
+            tests: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, test: impl FnMut(&mut RepetitionTester) + 'a) {
+        self.tests.push((name.into(), Box::new(test)));
+    }
+
+    /// Tests each implementation in turn `rounds` times over, with waves that end once `window`
+    /// passes without a new fastest trial, calling `after_wave` after each wave that works.
+    /// An implementation whose wave goes wrong isn't tested again, and is left out of the
+    /// comparison; `errors` says why.
+    pub fn run(
+        &mut self,
+        tester: &mut RepetitionTester,
+        window: Duration,
+        rounds: u32,
+        mut after_wave: impl FnMut(&str, &RepetitionTester),
+    ) -> Comparison {
+        let mut results: Vec<Option<Results>> = vec![Some(Results::default()); self.tests.len()];
+        for round in 0..rounds {
+            for ((name, test), results) in self.tests.iter_mut().zip(&mut results) {
+                let Some(merged) = results else {
+                    continue;
+                };
+                if tester.live {
+                    match rounds {
+                        1 => println!("\n--- {name} ---"),
+                        _ => println!("\n--- {name} (round {} of {rounds}) ---", round + 1),
+                    }
+                }
+                tester.new_wave(self.target_bytes, window);
+                test(tester);
+                match tester.error_message() {
+                    Some(message) => {
+                        self.errors.push((name.clone(), message.to_string()));
+                        *results = None;
+                    }
+                    None => {
+                        merged.merge(tester.results());
+                        after_wave(name, tester);
+                    }
+                }
+            }
+        }
+        let mut comparison = Comparison::default();
+        for ((name, _), results) in self.tests.iter().zip(&results) {
+            if let Some(results) = results {
+                comparison.add(name.as_str(), results);
+            }
+        }
+        comparison
+    }
+
+    /// The implementations that went wrong, and how.
+    pub fn errors(&self) -> &[(String, String)] {
+        &self.errors
+    }
+}
+
+/// Options every benchmark binary takes, flattened into its arguments.
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Seconds each test goes on for without getting any faster.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10.0)]
+    pub window: f64,
+
+    /// Write every trial of every test to this CSV file.
+    #[arg(long, value_name = "PATH")]
+    pub csv: Option<PathBuf>,
+
+    /// Count cycles, instructions, branch misses, and cache misses in every trial, where the
+    /// OS and CPU let us.
+    #[arg(long)]
+    pub counters: bool,
+}
+
+impl BenchArgs {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs_f64(self.window.max(0.0))
+    }
+
+    /// A `Bench` that runs for the window, writes the CSV file and counts events as asked.
+    pub fn bench(&self) -> Bench {
+        let mut bench = Bench::new(self.window(), self.csv.clone());
+        if let Some(counters) = self.hardware_counters() {
+            bench.count_events(counters);
+        }
+        bench
+    }
+
+    /// A `RepetitionTester` for a `TestSet`, keeping trials if there's a CSV file to write
+    /// and counting events as asked.
+    pub fn tester(&self) -> RepetitionTester {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(100));
+        let mut tester = RepetitionTester::new(frequency, true);
+        if self.csv.is_some() {
+            tester.keep_trials();
+        }
+        if let Some(counters) = self.hardware_counters() {
+            tester.count_events(counters);
+        }
+        tester
+    }
+
+    /// The hardware counters if `--counters` asked for them, or `None`, saying why, if they
+    /// can't be had.
+    fn hardware_counters(&self) -> Option<Box<dyn HardwareCounters>> {
+        if !self.counters {
+            return None;
+        }
+        match open_hardware_counters() {
+            Ok(counters) => Some(counters),
+            Err(error) => {
+                eprintln!("can't count hardware events: {error}");
+                None
+            }
+        }
+    }
+}
+
+/// Runs tests one after another and compares them at the end.
+pub struct Bench {
+    tester: RepetitionTester,
+    window: Duration,
+    comparison: Comparison,
+    /// Where to write the trials, and the rows so far.
+    csv: Option<(PathBuf, Vec<u8>)>,
+    failed: bool,
+}
+
+impl Bench {
+    /// Runs each test until it goes `window` without getting faster, keeping every trial to
+    /// write to `csv` if there is one.
+    pub fn new(window: Duration, csv: Option<PathBuf>) -> Bench {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(100));
+        let mut tester = RepetitionTester::new(frequency, true);
+        let csv = csv.map(|path| {
+            tester.keep_trials();
+            (path, format!("{CSV_HEADER}\n").into_bytes())
+        });
+        Bench {
+            tester,
+            window,
+            comparison: Comparison::default(),
+            csv,
+            failed: false,
+        }
+    }
+
+    /// Counts hardware events in every test from now on, as `RepetitionTester::count_events`.
+    pub fn count_events(&mut self, counters: Box<dyn HardwareCounters>) {
+        self.tester.count_events(counters);
+    }
+
+    /// Runs a wave of the test `name`, timing each call of `test`, which processes `bytes`.
+    pub fn run(&mut self, name: &str, bytes: u64, mut test: impl FnMut()) -> Option<Results> {
+        println!("\n--- {name} ---");
+        let tester = &mut self.tester;
+        tester.new_wave(bytes, self.window);
+        while tester.is_testing() {
+            tester.begin_time();
+            test();
+            tester.end_time();
+            tester.count_bytes(bytes);
+        }
+        if let Some(message) = tester.error_message() {
+            eprintln!("{name}: {message}");
+            self.failed = true;
+            return None;
+        }
+        self.comparison.add(name, tester.results());
+        if let Some((_, rows)) = &mut self.csv {
+            let _ = tester.write_csv(rows, name);
+        }
+        Some(*tester.results())
+    }
+
+    /// Prints the comparison and writes the CSV file, if asked for.
+    pub fn finish(self) -> ExitCode {
+        println!();
+        print!("{}", self.comparison);
+        if let Some((path, rows)) = &self.csv {
+            if let Err(error) = write_atomically(path, |out| out.write_all(rows)) {
+                eprintln!("{}: {error}", path.display());
+                return Failure::Io.into();
+            }
+        }
+        if self.failed {
+            Failure::Invalid.into()
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::estimate_cpu_timer_frequency;
+
+    #[test]
+    fn waves_run_until_no_trial_is_faster() {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(10));
+        let mut tester = RepetitionTester::new(frequency, false);
+        tester.keep_trials();
+        let buffer = vec![1u8; 4096];
+        tester.new_wave(buffer.len() as u64, Duration::from_millis(20));
+        while tester.is_testing() {
+            tester.begin_time();
+            std::hint::black_box(buffer.iter().map(|&byte| byte as u64).sum::<u64>());
+            tester.end_time();
+            tester.count_bytes(buffer.len() as u64);
+        }
+        assert_eq!(tester.error_message(), None);
+        let results = tester.results();
+        assert!(results.count > 0);
+        assert!(results.min.ticks <= results.average().ticks);
+        assert!(results.average().ticks <= results.max.ticks);
+        assert_eq!(results.min.bytes, 4096);
+        assert_eq!(tester.trials().len() as u64, results.count);
+        let mut csv = Vec::new();
+        tester.write_csv(&mut csv, "sum, bytes").unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count() as u64, results.count);
+        assert!(csv.starts_with("\"sum, bytes\",0,"));
+
+        tester.new_wave(4096, Duration::from_secs(10));
+        while tester.is_testing() {
+            tester.begin_time();
+            tester.end_time();
+            tester.count_bytes(100);
+        }
+        assert_eq!(
+            tester.error_message(),
+            Some("processed 100 bytes but expected 4096")
+        );
+    }
+
+    #[test]
+    fn hardware_events_are_counted_per_trial() {
+        /// Counts go up by 10 cycles, 20 instructions, and 10 branch misses a read.
+        struct Ticking(u64);
+
+        impl HardwareCounters for Ticking {
+            fn counts(&self, event: Event) -> bool {
+                event != Event::CacheMisses
+            }
+
+            fn read(&mut self) -> EventCounts {
+                self.0 += 10;
+                [self.0, 2 * self.0, self.0, 0]
+            }
+        }
+
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(10));
+        let mut tester = RepetitionTester::new(frequency, false);
+        tester.keep_trials();
+        tester.count_events(Box::new(Ticking(0)));
+        tester.new_wave(0, Duration::from_millis(5));
+        while tester.is_testing() {
+            tester.begin_time();
+            tester.end_time();
+        }
+        let results = tester.results();
+        assert_eq!(results.counted, [true, true, true, false]);
+        assert_eq!(results.min.events, [10, 20, 10, 0]);
+        assert!(results
+            .to_string()
+            .contains("| 10 cycles, 20 instructions, 10 branch misses (2.00 IPC)"));
+        let mut csv = Vec::new();
+        tester.write_csv(&mut csv, "nothing").unwrap();
+        assert!(String::from_utf8(csv).unwrap().ends_with(",10,20,10,\n"));
+        assert_eq!(CSV_HEADER.split(',').count(), 12);
+    }
+
+    #[test]
+    fn test_sets_compare_every_round_of_each_implementation() {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(10));
+        let mut tester = RepetitionTester::new(frequency, false);
+        let data = vec![1u8; 4096];
+        let mut set = TestSet::new(data.len() as u64);
+        set.add("sum", |tester: &mut RepetitionTester| {
+            while tester.is_testing() {
+                tester.begin_time();
+                std::hint::black_box(data.iter().map(|&byte| byte as u64).sum::<u64>());
+                tester.end_time();
+                tester.count_bytes(data.len() as u64);
+            }
+        });
+        set.add("short", |tester: &mut RepetitionTester| {
+            while tester.is_testing() {
+                tester.begin_time();
+                tester.end_time();
+                tester.count_bytes(1);
+            }
+        });
+        let mut waves = Vec::new();
+        let comparison = set.run(&mut tester, Duration::from_millis(5), 2, |name, tester| {
+            waves.push((name.to_string(), tester.results().count));
+        });
+        let names: Vec<_> = waves.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["sum", "sum"]);
+        assert_eq!(
+            set.errors(),
+            [(
+                "short".to_string(),
+                "processed 1 bytes but expected 4096".to_string()
+            )]
+        );
+        let table = comparison.to_string();
+        let tests: Vec<_> = table
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(tests, ["test", "sum"]);
+
+        let mut merged = Results::default();
+        let (one, two) = (
+            Results {
+                count: 2,
+                min: Measurement {
+                    ticks: 5,
+                    ..Measurement::default()
+                },
+                max: Measurement {
+                    ticks: 9,
+                    ..Measurement::default()
+                },
+                ..Results::default()
+            },
+            Results {
+                count: 1,
+                min: Measurement {
+                    ticks: 3,
+                    ..Measurement::default()
+                },
+                max: Measurement {
+                    ticks: 3,
+                    ..Measurement::default()
+                },
+                ..Results::default()
+            },
+        );
+        merged.merge(&one);
+        merged.merge(&two);
+        assert_eq!(
+            (merged.count, merged.min.ticks, merged.max.ticks),
+            (3, 3, 9)
+        );
+    }
+
+    #[test]
+    fn allocations_compare_in_one_table() {
+        let frequency = estimate_cpu_timer_frequency(Duration::from_millis(10));
+        let mut tester = RepetitionTester::new(frequency, false);
+        let mut comparison = Comparison::default();
+        const SIZE: usize = 1 << 20;
+        for allocation in Allocation::ALL {
+            let mut buffers = Buffers::new(allocation, SIZE);
+            tester.new_wave(SIZE as u64, Duration::from_millis(20));
+            while tester.is_testing() {
+                let mut buffer = buffers.get().unwrap();
+                tester.begin_time();
+                buffer.fill(1);
+                tester.end_time();
+                tester.count_bytes(buffer.len() as u64);
+                buffers.put(buffer);
+            }
+            comparison.add(allocation.name(), tester.results());
+        }
+        let table = comparison.to_string();
+        let tests: Vec<_> = table
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(tests, ["test", "reuse", "fresh", "mapped", "large"]);
+    }
+}
@@ -0,0 +1,1716 @@
+use crate::{
+    decoder::{DecodeError, Decoder},
+    flags::Flags,
+    instruction::{AddressBase, EffectiveAddress, Instruction, Mnemonic, Operand, Register, Rep},
+    memory::Memory,
+    metadata::timing,
+};
+use std::{
+    cell::RefCell, collections::BTreeMap, error::Error, fmt, ops::Range, rc::Rc, str::FromStr,
+};
+
+/// The 8086 register file: eight general registers and four segment registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Registers {
+    words: [u16; 12],
+}
+
+impl Registers {
+    /// Every 16-bit register, in the order they are dumped.
+    pub const ALL: [Register; 12] = [
+        Register::Ax,
+        Register::Bx,
+        Register::Cx,
+        Register::Dx,
+        Register::Sp,
+        Register::Bp,
+        Register::Si,
+        Register::Di,
+        Register::Es,
+        Register::Cs,
+        Register::Ss,
+        Register::Ds,
+    ];
+
+    // Index of the word holding `register`, and the shift of its byte within it if it's a
+    // byte register.
+    fn slot(register: Register) -> (usize, Option<u32>) {
+        match register {
+            Register::Al => (0, Some(0)),
+            Register::Ah => (0, Some(8)),
+            Register::Bl => (1, Some(0)),
+            Register::Bh => (1, Some(8)),
+            Register::Cl => (2, Some(0)),
+            Register::Ch => (2, Some(8)),
+            Register::Dl => (3, Some(0)),
+            Register::Dh => (3, Some(8)),
+            Register::Ax => (0, None),
+            Register::Bx => (1, None),
+            Register::Cx => (2, None),
+            Register::Dx => (3, None),
+            Register::Sp => (4, None),
+            Register::Bp => (5, None),
+            Register::Si => (6, None),
+            Register::Di => (7, None),
+            Register::Es => (8, None),
+            Register::Cs => (9, None),
+            Register::Ss => (10, None),
+            Register::Ds => (11, None),
+        }
+    }
+
+    pub fn get(&self, register: Register) -> u16 {
+        match Registers::slot(register) {
+            (index, None) => self.words[index],
+            (index, Some(shift)) => (self.words[index] >> shift) & 0xFF,
+        }
+    }
+
+    /// Sets `register`, truncating `value` to a byte for the 8-bit registers.
+    pub fn set(&mut self, register: Register, value: u16) {
+        match Registers::slot(register) {
+            (index, None) => self.words[index] = value,
+            (index, Some(shift)) => {
+                let word = &mut self.words[index];
+                *word = (*word & !(0xFF << shift)) | ((value & 0xFF) << shift);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExecError {
+    /// The instruction at `offset` isn't one the simulator can execute yet.
+    Unsupported { offset: usize, mnemonic: Mnemonic },
+    /// The instruction at `offset` pushed below the bottom of `Cpu::stack`.
+    StackOverflow { offset: usize },
+    /// The instruction at `offset` popped above the top of `Cpu::stack`.
+    StackUnderflow { offset: usize },
+    /// DIV or IDIV at `offset` divided by zero.
+    DivideByZero { offset: usize },
+    /// DIV or IDIV at `offset` had a quotient too large for its destination.
+    DivideOverflow { offset: usize },
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::Unsupported { offset, mnemonic } => {
+                write!(f, "can't execute {} at offset {offset}", mnemonic.name())
+            }
+            ExecError::StackOverflow { offset } => write!(f, "stack overflow at offset {offset}"),
+            ExecError::StackUnderflow { offset } => {
+                write!(f, "stack underflow at offset {offset}")
+            }
+            ExecError::DivideByZero { offset } => write!(f, "divide by zero at offset {offset}"),
+            ExecError::DivideOverflow { offset } => {
+                write!(f, "division overflow at offset {offset}")
+            }
+        }
+    }
+}
+
+impl Error for ExecError {}
+
+/// Where IN and OUT go. Implement it to attach virtual devices, such as a timer or a serial
+/// port, and set `Cpu::io` to it.
+pub trait IoBus: fmt::Debug {
+    /// A byte, or a word if `wide`, from `port`.
+    fn read(&mut self, port: u16, wide: bool) -> u16;
+    /// Sends a byte, or a word if `wide`, to `port`.
+    fn write(&mut self, port: u16, wide: bool, value: u16);
+}
+
+/// Lets an embedder keep a handle to a device it has given the CPU, to inspect it afterwards.
+impl<T: IoBus> IoBus for Rc<RefCell<T>> {
+    fn read(&mut self, port: u16, wide: bool) -> u16 {
+        self.borrow_mut().read(port, wide)
+    }
+
+    fn write(&mut self, port: u16, wide: bool, value: u16) {
+        self.borrow_mut().write(port, wide, value)
+    }
+}
+
+/// One IN or OUT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortAccess {
+    pub port: u16,
+    pub wide: bool,
+    pub write: bool,
+    /// The value read or written.
+    pub value: u16,
+}
+
+/// The default bus: no devices, just a record of every access. Reads see all ones, as from
+/// a port nothing drives.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PortLog {
+    pub accesses: Vec<PortAccess>,
+}
+
+impl IoBus for PortLog {
+    fn read(&mut self, port: u16, wide: bool) -> u16 {
+        let value = if wide { 0xFFFF } else { 0xFF };
+        self.accesses.push(PortAccess {
+            port,
+            wide,
+            write: false,
+            value,
+        });
+        value
+    }
+
+    fn write(&mut self, port: u16, wide: bool, value: u16) {
+        self.accesses.push(PortAccess {
+            port,
+            wide,
+            write: true,
+            value,
+        });
+    }
+}
+
+/// Host code run for an interrupt instead of the guest's handler. It sees the CPU as the guest
+/// handler would after IRET, with IP past the INT, and returns whether it handled the
+/// interrupt; if not, the interrupt vectors into guest memory as usual.
+pub type InterruptHook = Box<dyn FnMut(&mut Cpu) -> bool>;
+
+// The hooked interrupt numbers, which is all that can be shown of them.
+#[derive(Default)]
+struct InterruptHooks(BTreeMap<u8, InterruptHook>);
+
+impl fmt::Debug for InterruptHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Executes decoded instructions against simulated 8086 state.
+#[derive(Debug)]
+pub struct Cpu {
+    pub registers: Registers,
+    pub flags: Flags,
+    /// Offset of the next instruction to execute.
+    pub ip: u16,
+    pub memory: Memory,
+    /// The memory accesses made by the last instruction executed, in order.
+    pub accesses: Vec<Access>,
+    /// Offsets within SS the stack may occupy, the end being where it starts out empty (0x10000
+    /// for an SP of 0). Pushing below it or popping past it is an error; `None` allows either.
+    pub stack: Option<Range<u32>>,
+    /// The devices IN and OUT talk to.
+    pub io: Box<dyn IoBus>,
+    /// Set when the program terminates, such as through DOS, to its exit code. Callers stop
+    /// executing once it's set.
+    pub exit_code: Option<u8>,
+    hooks: InterruptHooks,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu {
+            registers: Registers::default(),
+            flags: Flags::default(),
+            ip: 0,
+            memory: Memory::default(),
+            accesses: Vec::new(),
+            stack: None,
+            io: Box::new(PortLog::default()),
+            exit_code: None,
+            hooks: InterruptHooks::default(),
+        }
+    }
+}
+
+/// One read or write of simulated memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub address: usize,
+    /// 1 or 2 bytes.
+    pub size: usize,
+    pub write: bool,
+    /// What the address held beforehand: for writes, the value overwritten.
+    pub previous: u16,
+}
+
+impl Access {
+    /// Whether the access touches any byte of `range`.
+    pub fn overlaps(&self, range: &Range<usize>) -> bool {
+        self.address < range.end && range.start < self.address + self.size
+    }
+}
+
+/// Which processor's bus to model when estimating clocks. They share the execution unit; the
+/// 8088's 8-bit bus needs two transfers for every word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Model {
+    #[default]
+    I8086,
+    I8088,
+}
+
+impl Model {
+    /// Bytes the bus interface unit prefetches ahead of the execution unit.
+    pub fn queue_size(self) -> u32 {
+        match self {
+            Model::I8086 => 6,
+            Model::I8088 => 4,
+        }
+    }
+
+    /// Bytes moved by one 4-clock bus cycle.
+    pub fn bus_width(self) -> u32 {
+        match self {
+            Model::I8086 => 2,
+            Model::I8088 => 1,
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "8086" => Ok(Model::I8086),
+            "8088" => Ok(Model::I8088),
+            _ => Err(format!("unknown model {text:?} (expected 8086 or 8088)")),
+        }
+    }
+}
+
+/// Estimated clocks for one execution of an instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clocks {
+    /// Base clocks, including repetitions and whether a conditional transfer is taken.
+    pub base: u32,
+    /// Effective address calculation.
+    pub ea: u32,
+    /// Extra bus cycles for word transfers: those at odd addresses on the 8086, and all of them
+    /// on the 8088.
+    pub penalty: u32,
+    /// Time spent waiting for the prefetch queue to deliver the instruction, when one is modeled.
+    pub wait: u32,
+}
+
+impl Clocks {
+    pub fn total(&self) -> u32 {
+        self.base + self.ea + self.penalty + self.wait
+    }
+}
+
+/// A model of the bus interface unit's prefetch queue. The queue fills whenever the execution
+/// unit leaves the bus idle; an instruction that isn't fully queued when it's reached waits for
+/// the missing bytes, and a taken branch throws the queue away.
+///
+/// It ignores the 8086's alignment of code fetches and the overlap between the end of one
+/// instruction and the decoding of the next, so it's an estimate, not a cycle-exact model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchQueue {
+    model: Model,
+    /// Bytes queued ahead of the execution unit.
+    queued: u32,
+    /// Idle bus clocks left over from short gaps, too few for a whole bus cycle yet.
+    idle: u32,
+}
+
+impl PrefetchQueue {
+    /// An empty queue, as after reset.
+    pub fn new(model: Model) -> Self {
+        PrefetchQueue {
+            model,
+            queued: 0,
+            idle: 0,
+        }
+    }
+
+    pub fn queued(&self) -> u32 {
+        self.queued
+    }
+
+    /// Runs `instruction` through the queue, returning `clocks` with the time spent waiting
+    /// for it to be fetched filled in. `branched` says whether it transferred control
+    /// elsewhere, flushing the queue.
+    pub fn advance(
+        &mut self,
+        instruction: &Instruction,
+        mut clocks: Clocks,
+        branched: bool,
+    ) -> Clocks {
+        let width = self.model.bus_width();
+        let length = instruction.length as u32;
+        if self.queued < length {
+            let cycles = (length - self.queued).div_ceil(width);
+            clocks.wait = 4 * cycles;
+            self.queued += cycles * width;
+            self.idle = 0;
+        }
+        self.queued -= length;
+
+        // Whatever part of the instruction doesn't use the bus for its own data goes to
+        // fetching, as long as there is room in the queue.
+        let busy = 4 * timing(instruction).transfers + clocks.penalty;
+        self.idle += (clocks.total() - clocks.wait).saturating_sub(busy);
+        let room = (self.model.queue_size() - self.queued) / width;
+        let cycles = (self.idle / 4).min(room);
+        self.queued += cycles * width;
+        self.idle -= cycles * 4;
+        if self.queued + width > self.model.queue_size() {
+            self.idle = 0;
+        }
+
+        if branched {
+            self.queued = 0;
+            self.idle = 0;
+        }
+        clocks
+    }
+}
+
+// Long enough for any instruction the decoder accepts, short of a run of redundant prefixes.
+const FETCH_WINDOW: usize = 16;
+
+/// The interrupt raised after each instruction while TF is set.
+pub const SINGLE_STEP_INTERRUPT: u8 = 1;
+
+/// The 20-bit address `segment:offset` refers to.
+pub fn physical_address(segment: u16, offset: u16) -> usize {
+    ((segment as usize) << 4) + offset as usize
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Cpu::default()
+    }
+
+    /// A CPU with `size` bytes of memory instead of the full megabyte.
+    pub fn with_memory(size: usize) -> Self {
+        Cpu {
+            memory: Memory::new(size),
+            ..Cpu::default()
+        }
+    }
+
+    /// Runs `hook` whenever interrupt `number` is raised, replacing any hook it already had.
+    pub fn hook_interrupt(&mut self, number: u8, hook: impl FnMut(&mut Cpu) -> bool + 'static) {
+        self.hooks.0.insert(number, Box::new(hook));
+    }
+
+    /// Removes the hook for interrupt `number`, returning it if there was one.
+    pub fn unhook_interrupt(&mut self, number: u8) -> Option<InterruptHook> {
+        self.hooks.0.remove(&number)
+    }
+
+    /// The `(segment, offset)` of interrupt `number`'s handler, from the vector table at
+    /// 0000:0000.
+    pub fn vector(&self, number: u8) -> (u16, u16) {
+        let address = number as usize * 4;
+        (
+            self.memory.read_u16(address + 2),
+            self.memory.read_u16(address),
+        )
+    }
+
+    /// Points interrupt `number` at `segment:offset`.
+    pub fn set_vector(&mut self, number: u8, segment: u16, offset: u16) {
+        let address = number as usize * 4;
+        self.memory.write_u16(address, offset);
+        self.memory.write_u16(address + 2, segment);
+    }
+
+    /// Physical address of the next instruction, CS:IP.
+    pub fn instruction_address(&self) -> usize {
+        physical_address(self.registers.get(Register::Cs), self.ip)
+    }
+
+    /// Decodes the instruction at CS:IP, or `None` if memory ends before one starts.
+    pub fn fetch(&self) -> Result<Option<Instruction>, DecodeError> {
+        let code = self.memory.slice(self.instruction_address(), FETCH_WINDOW);
+        Decoder::with_origin(code, self.ip as usize).next_instruction()
+    }
+
+    /// Offset of `address` within its segment: the sum of its registers and displacement.
+    pub fn effective_address(&self, address: &EffectiveAddress) -> u16 {
+        address
+            .base
+            .registers()
+            .iter()
+            .fold(address.displacement as u16, |sum, &register| {
+                sum.wrapping_add(self.registers.get(register))
+            })
+    }
+
+    /// Physical address of a memory operand of `instruction`. BP-based addresses default to
+    /// the stack segment and everything else to the data segment.
+    pub fn operand_address(&self, instruction: &Instruction, address: &EffectiveAddress) -> usize {
+        let segment = instruction.segment_override.unwrap_or(match address.base {
+            AddressBase::Bp | AddressBase::BpSi | AddressBase::BpDi => Register::Ss,
+            _ => Register::Ds,
+        });
+        physical_address(self.registers.get(segment), self.effective_address(address))
+    }
+
+    /// Executes `instruction`, which is assumed to be the one at `ip`, leaving `ip` at the next
+    /// instruction to run. On an error, `ip` is left at `instruction`.
+    ///
+    /// With TF set as the instruction starts, it's followed by interrupt 1, so the instruction
+    /// that sets TF runs untrapped and the one that clears it is still trapped. An interrupt the
+    /// instruction raises itself clears TF on the way into its handler and isn't trapped.
+    pub fn execute(&mut self, instruction: &Instruction) -> Result<(), ExecError> {
+        let ip = self.ip;
+        let trap = self.flags.contains(Flags::TRAP);
+        let result = self.execute_at_end(instruction);
+        if result.is_err() {
+            self.ip = ip;
+            return result;
+        }
+        let interrupted = matches!(
+            instruction.mnemonic,
+            Mnemonic::Int | Mnemonic::Int3 | Mnemonic::Into
+        ) && !self.flags.contains(Flags::TRAP);
+        if trap && !interrupted {
+            self.interrupt(instruction, SINGLE_STEP_INTERRUPT)?;
+        }
+        Ok(())
+    }
+
+    // `execute`, with IP already pointing past the instruction as its operands expect.
+    fn execute_at_end(&mut self, instruction: &Instruction) -> Result<(), ExecError> {
+        self.ip = instruction.end() as u16;
+        self.accesses.clear();
+        let unsupported = || ExecError::Unsupported {
+            offset: instruction.offset,
+            mnemonic: instruction.mnemonic,
+        };
+
+        let wide = instruction.wide;
+        match (instruction.mnemonic, instruction.operands) {
+            (Mnemonic::Mov, [Some(destination), Some(source)]) => {
+                let value = self.read(instruction, &source).ok_or_else(unsupported)?;
+                self.write(instruction, &destination, value)
+                    .ok_or_else(unsupported)?;
+            }
+            (
+                mnemonic @ (Mnemonic::Add | Mnemonic::Sub | Mnemonic::Cmp),
+                [Some(destination), Some(source)],
+            ) => {
+                let a = self
+                    .read(instruction, &destination)
+                    .ok_or_else(unsupported)?;
+                let b = self.read(instruction, &source).ok_or_else(unsupported)?;
+                let result = if mnemonic == Mnemonic::Add {
+                    self.add(a, b, wide)
+                } else {
+                    self.subtract(a, b, wide)
+                };
+                if mnemonic != Mnemonic::Cmp {
+                    self.write(instruction, &destination, result)
+                        .ok_or_else(unsupported)?;
+                }
+            }
+            (mnemonic, [Some(Operand::Relative(displacement)), None])
+                if mnemonic == Mnemonic::Jmp || self.condition(mnemonic).is_some() =>
+            {
+                if mnemonic == Mnemonic::Jmp || self.condition(mnemonic) == Some(true) {
+                    self.ip = self.ip.wrapping_add(displacement as u16);
+                }
+            }
+            (mnemonic @ (Mnemonic::Call | Mnemonic::Jmp), [Some(target), None]) => {
+                let (segment, offset) = match target {
+                    Operand::Relative(displacement) => {
+                        (None, self.ip.wrapping_add(displacement as u16))
+                    }
+                    Operand::Far { segment, offset } => (Some(segment), offset),
+                    Operand::Memory(address) if instruction.far => {
+                        let address = self.operand_address(instruction, &address);
+                        let offset = self.load_recorded(address, true);
+                        (Some(self.load_recorded(address + 2, true)), offset)
+                    }
+                    operand => (
+                        None,
+                        self.read(instruction, &operand).ok_or_else(unsupported)?,
+                    ),
+                };
+                if mnemonic == Mnemonic::Call {
+                    if segment.is_some() {
+                        self.push(instruction, self.registers.get(Register::Cs))?;
+                    }
+                    self.push(instruction, self.ip)?;
+                }
+                if let Some(segment) = segment {
+                    self.registers.set(Register::Cs, segment);
+                }
+                self.ip = offset;
+            }
+            (mnemonic @ (Mnemonic::Ret | Mnemonic::Retf), [count, None]) => {
+                self.ip = self.pop(instruction)?;
+                if mnemonic == Mnemonic::Retf {
+                    let segment = self.pop(instruction)?;
+                    self.registers.set(Register::Cs, segment);
+                }
+                // RET n also discards n bytes of arguments.
+                if let Some(Operand::Immediate(count)) = count {
+                    let sp = self.registers.get(Register::Sp);
+                    self.registers
+                        .set(Register::Sp, sp.wrapping_add(count as u16));
+                }
+            }
+            (
+                mnemonic @ (Mnemonic::Movs
+                | Mnemonic::Cmps
+                | Mnemonic::Stos
+                | Mnemonic::Lods
+                | Mnemonic::Scas),
+                _,
+            ) => self.string(instruction, mnemonic),
+            (
+                mnemonic @ (Mnemonic::Shl
+                | Mnemonic::Shr
+                | Mnemonic::Sar
+                | Mnemonic::Rol
+                | Mnemonic::Ror
+                | Mnemonic::Rcl
+                | Mnemonic::Rcr),
+                [Some(destination), Some(count)],
+            ) => {
+                let value = self
+                    .read(instruction, &destination)
+                    .ok_or_else(unsupported)?;
+                let count = self.read(instruction, &count).ok_or_else(unsupported)? as u8;
+                let result = self.shift(mnemonic, value, count, wide);
+                self.write(instruction, &destination, result)
+                    .ok_or_else(unsupported)?;
+            }
+            (mnemonic @ (Mnemonic::Mul | Mnemonic::Imul), [Some(source), None]) => {
+                let source = self.read(instruction, &source).ok_or_else(unsupported)?;
+                self.multiply(source, wide, mnemonic == Mnemonic::Imul);
+            }
+            (mnemonic @ (Mnemonic::Div | Mnemonic::Idiv), [Some(source), None]) => {
+                let source = self.read(instruction, &source).ok_or_else(unsupported)?;
+                self.divide(instruction, source, mnemonic == Mnemonic::Idiv)?;
+            }
+            (Mnemonic::In, [Some(destination), Some(port)]) => {
+                let port = self.read(instruction, &port).ok_or_else(unsupported)?;
+                let value = self.io.read(port, wide);
+                self.write(instruction, &destination, value)
+                    .ok_or_else(unsupported)?;
+            }
+            (Mnemonic::Out, [Some(port), Some(source)]) => {
+                let port = self.read(instruction, &port).ok_or_else(unsupported)?;
+                let value = self.read(instruction, &source).ok_or_else(unsupported)?;
+                self.io.write(port, wide, value);
+            }
+            (Mnemonic::Int, [Some(Operand::Immediate(number)), None]) => {
+                self.interrupt(instruction, number as u8)?
+            }
+            (Mnemonic::Int3, _) => self.interrupt(instruction, 3)?,
+            (Mnemonic::Into, _) => {
+                if self.flags.contains(Flags::OVERFLOW) {
+                    self.interrupt(instruction, 4)?;
+                }
+            }
+            (Mnemonic::Iret, _) => {
+                self.ip = self.pop(instruction)?;
+                let segment = self.pop(instruction)?;
+                self.registers.set(Register::Cs, segment);
+                self.flags = Flags(self.pop(instruction)? & Flags::ALL.0);
+            }
+            (Mnemonic::Push, [Some(source), None]) => {
+                let value = self.read(instruction, &source).ok_or_else(unsupported)?;
+                self.push(instruction, value)?;
+            }
+            (Mnemonic::Pop, [Some(destination), None]) => {
+                let value = self.pop(instruction)?;
+                self.write(instruction, &destination, value)
+                    .ok_or_else(unsupported)?;
+            }
+            (Mnemonic::Pushf, _) => self.push(instruction, self.flags.0)?,
+            (Mnemonic::Popf, _) => self.flags = Flags(self.pop(instruction)? & Flags::ALL.0),
+            (
+                mnemonic @ (Mnemonic::Loop | Mnemonic::Loopz | Mnemonic::Loopnz),
+                [Some(Operand::Relative(displacement)), None],
+            ) => {
+                // LOOP decrements CX without touching the flags.
+                let count = self.registers.get(Register::Cx).wrapping_sub(1);
+                self.registers.set(Register::Cx, count);
+                let zero = self.flags.contains(Flags::ZERO);
+                let taken = count != 0
+                    && match mnemonic {
+                        Mnemonic::Loopz => zero,
+                        Mnemonic::Loopnz => !zero,
+                        _ => true,
+                    };
+                if taken {
+                    self.ip = self.ip.wrapping_add(displacement as u16);
+                }
+            }
+            (Mnemonic::Clc, _) => self.flags.set(Flags::CARRY, false),
+            (Mnemonic::Stc, _) => self.flags.set(Flags::CARRY, true),
+            (Mnemonic::Cmc, _) => self
+                .flags
+                .set(Flags::CARRY, !self.flags.contains(Flags::CARRY)),
+            (Mnemonic::Cld, _) => self.flags.set(Flags::DIRECTION, false),
+            (Mnemonic::Std, _) => self.flags.set(Flags::DIRECTION, true),
+            (Mnemonic::Cli, _) => self.flags.set(Flags::INTERRUPT, false),
+            (Mnemonic::Sti, _) => self.flags.set(Flags::INTERRUPT, true),
+            _ => return Err(unsupported()),
+        }
+        Ok(())
+    }
+
+    /// Estimated clocks for executing `instruction` next, so call it before `execute`: the count
+    /// depends on the registers and flags the instruction sees.
+    ///
+    /// Data-dependent instructions (MUL/DIV) are charged their minimum.
+    pub fn clocks(&self, instruction: &Instruction, model: Model) -> Clocks {
+        let timing = timing(instruction);
+        let count = |register| self.registers.get(register) as u32;
+
+        let taken = match instruction.mnemonic {
+            Mnemonic::Loop | Mnemonic::Loopz | Mnemonic::Loopnz => {
+                let zero = self.flags.contains(Flags::ZERO);
+                count(Register::Cx) != 1
+                    && match instruction.mnemonic {
+                        Mnemonic::Loopz => zero,
+                        Mnemonic::Loopnz => !zero,
+                        _ => true,
+                    }
+            }
+            Mnemonic::Into => self.flags.contains(Flags::OVERFLOW),
+            mnemonic => self.condition(mnemonic).unwrap_or(true),
+        };
+        let mut base = match timing.not_taken {
+            Some(not_taken) if !taken => not_taken,
+            _ => timing.base,
+        };
+        if timing.per_repeat != 0 {
+            let repeats = if instruction.rep.is_some() {
+                count(Register::Cx)
+            } else {
+                count(Register::Cl)
+            };
+            base += timing.per_repeat * repeats;
+        }
+
+        // Each word transfer the bus can't do at once takes two bus cycles instead of one.
+        let address = instruction
+            .operands
+            .iter()
+            .flatten()
+            .find_map(|operand| match operand {
+                Operand::Memory(address) => Some(self.operand_address(instruction, address)),
+                _ => None,
+            });
+        let split = match (model, address) {
+            (Model::I8088, Some(_)) => true,
+            (Model::I8086, Some(address)) => address % 2 == 1,
+            (_, None) => false,
+        };
+        let penalty = if instruction.wide && split {
+            4 * timing.transfers
+        } else {
+            0
+        };
+
+        Clocks {
+            base,
+            ea: timing.ea,
+            penalty,
+            wait: 0,
+        }
+    }
+
+    /// Whether the conditional jump `mnemonic` would be taken, or `None` if it isn't one.
+    pub fn condition(&self, mnemonic: Mnemonic) -> Option<bool> {
+        let flag = |flag| self.flags.contains(flag);
+        let (carry, zero, sign, overflow) = (
+            flag(Flags::CARRY),
+            flag(Flags::ZERO),
+            flag(Flags::SIGN),
+            flag(Flags::OVERFLOW),
+        );
+        Some(match mnemonic {
+            Mnemonic::Jo => overflow,
+            Mnemonic::Jno => !overflow,
+            Mnemonic::Jb => carry,
+            Mnemonic::Jnb => !carry,
+            Mnemonic::Je => zero,
+            Mnemonic::Jne => !zero,
+            Mnemonic::Jbe => carry || zero,
+            Mnemonic::Ja => !carry && !zero,
+            Mnemonic::Js => sign,
+            Mnemonic::Jns => !sign,
+            Mnemonic::Jp => flag(Flags::PARITY),
+            Mnemonic::Jnp => !flag(Flags::PARITY),
+            Mnemonic::Jl => sign != overflow,
+            Mnemonic::Jnl => sign == overflow,
+            Mnemonic::Jle => zero || sign != overflow,
+            Mnemonic::Jg => !zero && sign == overflow,
+            Mnemonic::Jcxz => self.registers.get(Register::Cx) == 0,
+            _ => return None,
+        })
+    }
+
+    // Sets ZF, SF, and PF from `result`, which has already been truncated to the operand size.
+    fn set_result_flags(&mut self, result: u16, wide: bool) {
+        let sign = if wide { 0x8000 } else { 0x80 };
+        self.flags.set(Flags::ZERO, result == 0);
+        self.flags.set(Flags::SIGN, result & sign != 0);
+        // Parity only ever looks at the low byte.
+        self.flags
+            .set(Flags::PARITY, (result as u8).count_ones().is_multiple_of(2));
+    }
+
+    // Operands are taken at the operation's width: a byte immediate arrives sign-extended.
+    fn add(&mut self, a: u16, b: u16, wide: bool) -> u16 {
+        let (mask, sign) = if wide { (0xFFFF, 0x8000) } else { (0xFF, 0x80) };
+        let (a, b) = (a & mask as u16, b & mask as u16);
+        let full = a as u32 + b as u32;
+        let result = (full & mask) as u16;
+
+        self.flags.set(Flags::CARRY, full > mask);
+        self.flags
+            .set(Flags::AUX_CARRY, (a ^ b ^ result) & 0x10 != 0);
+        // Overflow when both inputs have the same sign and the result doesn't.
+        self.flags
+            .set(Flags::OVERFLOW, (a ^ result) & (b ^ result) & sign != 0);
+        self.set_result_flags(result, wide);
+        result
+    }
+
+    fn subtract(&mut self, a: u16, b: u16, wide: bool) -> u16 {
+        let (mask, sign) = if wide { (0xFFFF, 0x8000) } else { (0xFF, 0x80) };
+        let (a, b) = (a & mask as u16, b & mask as u16);
+        let result = a.wrapping_sub(b) & mask as u16;
+
+        self.flags.set(Flags::CARRY, b > a);
+        self.flags
+            .set(Flags::AUX_CARRY, (a ^ b ^ result) & 0x10 != 0);
+        // Overflow when the inputs have different signs and the result's differs from `a`'s.
+        self.flags
+            .set(Flags::OVERFLOW, (a ^ b) & (a ^ result) & sign != 0);
+        self.set_result_flags(result, wide);
+        result
+    }
+
+    // Shifts or rotates `value` one bit at a time, `count` times; the 8086 doesn't mask the
+    // count. A count of 0 changes nothing, flags included. OF is only defined for a count of 1,
+    // but comes from the last step regardless.
+    fn shift(&mut self, mnemonic: Mnemonic, value: u16, count: u8, wide: bool) -> u16 {
+        if count == 0 {
+            return value;
+        }
+        let (mask, sign) = if wide { (0xFFFF, 0x8000) } else { (0xFF, 0x80) };
+        let mut value = value & mask;
+        let mut carry = self.flags.contains(Flags::CARRY);
+        let mut overflow = false;
+        for _ in 0..count {
+            let (high, low) = (value & sign != 0, value & 1 != 0);
+            let (result, carry_out) = match mnemonic {
+                Mnemonic::Shl => (value << 1, high),
+                Mnemonic::Shr => (value >> 1, low),
+                Mnemonic::Sar => ((value >> 1) | (value & sign), low),
+                Mnemonic::Rol => ((value << 1) | high as u16, high),
+                Mnemonic::Ror => ((value >> 1) | if low { sign } else { 0 }, low),
+                Mnemonic::Rcl => ((value << 1) | carry as u16, high),
+                _ => ((value >> 1) | if carry { sign } else { 0 }, low),
+            };
+            value = result & mask;
+            carry = carry_out;
+            let top = value & sign != 0;
+            overflow = match mnemonic {
+                // Left: whether the sign changed, that is, the new sign differs from CF.
+                Mnemonic::Shl | Mnemonic::Rol | Mnemonic::Rcl => top != carry,
+                Mnemonic::Shr => high,
+                Mnemonic::Sar => false,
+                // Right rotates: whether the top two bits of the result differ.
+                _ => top != (value & (sign >> 1) != 0),
+            };
+        }
+
+        self.flags.set(Flags::CARRY, carry);
+        self.flags.set(Flags::OVERFLOW, overflow);
+        // Rotates leave the result flags alone.
+        if matches!(mnemonic, Mnemonic::Shl | Mnemonic::Shr | Mnemonic::Sar) {
+            self.set_result_flags(value, wide);
+        }
+        value
+    }
+
+    // AL * source into AX, or AX * source into DX:AX. CF and OF say whether the high half is
+    // needed: whether it's non-zero, or for IMUL, more than the sign extension of the low half.
+    fn multiply(&mut self, source: u16, wide: bool, signed: bool) {
+        let (width, high) = if wide {
+            (16, Register::Dx)
+        } else {
+            (8, Register::Ah)
+        };
+        let accumulator = self
+            .registers
+            .get(if wide { Register::Ax } else { Register::Al });
+        let extend = |value: u16| -> i32 {
+            match (signed, wide) {
+                (false, _) => value as i32,
+                (true, true) => value as i16 as i32,
+                (true, false) => value as u8 as i8 as i32,
+            }
+        };
+        let product = extend(accumulator).wrapping_mul(extend(source)) as u32;
+
+        let low = product & ((1 << width) - 1);
+        let low_extended = if signed {
+            extend(low as u16) as u32
+        } else {
+            low
+        };
+        if wide {
+            self.registers.set(Register::Ax, low as u16);
+            self.registers.set(high, (product >> 16) as u16);
+        } else {
+            self.registers.set(Register::Ax, product as u16);
+        }
+        let overflow = product != low_extended;
+        self.flags.set(Flags::CARRY, overflow);
+        self.flags.set(Flags::OVERFLOW, overflow);
+    }
+
+    // AX / source into AL rem AH, or DX:AX / source into AX rem DX. The remainder takes the
+    // dividend's sign. A zero divisor or a quotient that doesn't fit is an error, as the 8086's
+    // divide interrupt would be; signed quotients must be within -127..=127 or -32767..=32767.
+    fn divide(
+        &mut self,
+        instruction: &Instruction,
+        source: u16,
+        signed: bool,
+    ) -> Result<(), ExecError> {
+        let offset = instruction.offset;
+        let wide = instruction.wide;
+        if source == 0 {
+            return Err(ExecError::DivideByZero { offset });
+        }
+        let ax = self.registers.get(Register::Ax) as u32;
+        let dividend = if wide {
+            ((self.registers.get(Register::Dx) as u32) << 16) | ax
+        } else {
+            ax
+        };
+
+        let (quotient, remainder, fits) = if signed {
+            let (dividend, divisor, limit) = if wide {
+                (dividend as i32 as i64, source as i16 as i64, 0x7FFF)
+            } else {
+                (
+                    dividend as u16 as i16 as i64,
+                    source as u8 as i8 as i64,
+                    0x7F,
+                )
+            };
+            let quotient = dividend / divisor;
+            let remainder = dividend % divisor;
+            (quotient as u32, remainder as u32, quotient.abs() <= limit)
+        } else {
+            let divisor = source as u32;
+            let limit = if wide { 0xFFFF } else { 0xFF };
+            let quotient = dividend / divisor;
+            (quotient, dividend % divisor, quotient <= limit)
+        };
+        if !fits {
+            return Err(ExecError::DivideOverflow { offset });
+        }
+
+        if wide {
+            self.registers.set(Register::Ax, quotient as u16);
+            self.registers.set(Register::Dx, remainder as u16);
+        } else {
+            self.registers.set(Register::Al, quotient as u16);
+            self.registers.set(Register::Ah, remainder as u16);
+        }
+        Ok(())
+    }
+
+    // A string instruction, repeated while CX is non-zero under a REP prefix. CMPS and SCAS
+    // also stop when ZF no longer matches the prefix: REPE stops on a difference, REPNE on a
+    // match.
+    fn string(&mut self, instruction: &Instruction, mnemonic: Mnemonic) {
+        let wide = instruction.wide;
+        let step = match (wide, self.flags.contains(Flags::DIRECTION)) {
+            (false, false) => 1,
+            (true, false) => 2,
+            (false, true) => 1u16.wrapping_neg(),
+            (true, true) => 2u16.wrapping_neg(),
+        };
+        // The source may be overridden; the destination is always ES:DI.
+        let source_segment = instruction.segment_override.unwrap_or(Register::Ds);
+        let accumulator = if wide { Register::Ax } else { Register::Al };
+
+        loop {
+            if instruction.rep.is_some() && self.registers.get(Register::Cx) == 0 {
+                break;
+            }
+            let (si, di) = (
+                self.registers.get(Register::Si),
+                self.registers.get(Register::Di),
+            );
+            let source = physical_address(self.registers.get(source_segment), si);
+            let destination = physical_address(self.registers.get(Register::Es), di);
+            let (uses_si, uses_di) = match mnemonic {
+                Mnemonic::Movs => {
+                    let value = self.load_recorded(source, wide);
+                    self.store(destination, wide, value);
+                    (true, true)
+                }
+                Mnemonic::Cmps => {
+                    let a = self.load_recorded(source, wide);
+                    let b = self.load_recorded(destination, wide);
+                    self.subtract(a, b, wide);
+                    (true, true)
+                }
+                Mnemonic::Stos => {
+                    self.store(destination, wide, self.registers.get(accumulator));
+                    (false, true)
+                }
+                Mnemonic::Lods => {
+                    let value = self.load_recorded(source, wide);
+                    self.registers.set(accumulator, value);
+                    (true, false)
+                }
+                _ => {
+                    let b = self.load_recorded(destination, wide);
+                    self.subtract(self.registers.get(accumulator), b, wide);
+                    (false, true)
+                }
+            };
+            if uses_si {
+                self.registers.set(Register::Si, si.wrapping_add(step));
+            }
+            if uses_di {
+                self.registers.set(Register::Di, di.wrapping_add(step));
+            }
+
+            let Some(rep) = instruction.rep else {
+                break;
+            };
+            let count = self.registers.get(Register::Cx).wrapping_sub(1);
+            self.registers.set(Register::Cx, count);
+            if matches!(mnemonic, Mnemonic::Cmps | Mnemonic::Scas) {
+                let zero = self.flags.contains(Flags::ZERO);
+                if zero != (rep == Rep::Rep) {
+                    break;
+                }
+            }
+        }
+    }
+
+    // A word or byte of memory, recorded as a read.
+    fn load_recorded(&mut self, address: usize, wide: bool) -> u16 {
+        let value = self.load(address, wide);
+        self.accesses.push(Access {
+            address,
+            size: 1 + wide as usize,
+            write: false,
+            previous: value,
+        });
+        value
+    }
+
+    // Writes a word or byte of memory, recording the write.
+    fn store(&mut self, address: usize, wide: bool, value: u16) {
+        self.accesses.push(Access {
+            address,
+            size: 1 + wide as usize,
+            write: true,
+            previous: self.load(address, wide),
+        });
+        if wide {
+            self.memory.write_u16(address, value);
+        } else {
+            self.memory.write_u8(address, value as u8);
+        }
+    }
+
+    // SP as a distance from the bottom of the stack segment, where an SP of 0 is 0x10000: the
+    // top of an empty stack, as the first push wraps it to 0xFFFE.
+    fn stack_position(&self) -> u32 {
+        match self.registers.get(Register::Sp) {
+            0 => 0x10000,
+            sp => sp as u32,
+        }
+    }
+
+    // Raises interrupt `number`: runs its hook, or if there's none or it declines, pushes the
+    // flags, CS, and IP, clears IF and TF, and jumps through the vector table.
+    fn interrupt(&mut self, instruction: &Instruction, number: u8) -> Result<(), ExecError> {
+        // Take the hook out while it runs, as it gets the whole CPU. It may have hooked the
+        // number again itself, in which case its replacement stays.
+        if let Some(mut hook) = self.hooks.0.remove(&number) {
+            let handled = hook(self);
+            self.hooks.0.entry(number).or_insert(hook);
+            if handled {
+                return Ok(());
+            }
+        }
+
+        self.push(instruction, self.flags.0)?;
+        self.push(instruction, self.registers.get(Register::Cs))?;
+        self.push(instruction, self.ip)?;
+        self.flags.set(Flags::INTERRUPT, false);
+        self.flags.set(Flags::TRAP, false);
+        let address = number as usize * 4;
+        self.ip = self.load_recorded(address, true);
+        let segment = self.load_recorded(address + 2, true);
+        self.registers.set(Register::Cs, segment);
+        Ok(())
+    }
+
+    fn push(&mut self, instruction: &Instruction, value: u16) -> Result<(), ExecError> {
+        if let Some(stack) = &self.stack {
+            if self.stack_position() < stack.start + 2 {
+                return Err(ExecError::StackOverflow {
+                    offset: instruction.offset,
+                });
+            }
+        }
+        let sp = self.registers.get(Register::Sp).wrapping_sub(2);
+        self.registers.set(Register::Sp, sp);
+        let address = physical_address(self.registers.get(Register::Ss), sp);
+        self.store(address, true, value);
+        Ok(())
+    }
+
+    fn pop(&mut self, instruction: &Instruction) -> Result<u16, ExecError> {
+        if let Some(stack) = &self.stack {
+            if self.stack_position() + 2 > stack.end {
+                return Err(ExecError::StackUnderflow {
+                    offset: instruction.offset,
+                });
+            }
+        }
+        let sp = self.registers.get(Register::Sp);
+        let address = physical_address(self.registers.get(Register::Ss), sp);
+        let value = self.load_recorded(address, true);
+        self.registers.set(Register::Sp, sp.wrapping_add(2));
+        Ok(value)
+    }
+
+    // A word or byte of memory, without recording an access.
+    fn load(&self, address: usize, wide: bool) -> u16 {
+        if wide {
+            self.memory.read_u16(address)
+        } else {
+            self.memory.read_u8(address) as u16
+        }
+    }
+
+    fn write(&mut self, instruction: &Instruction, operand: &Operand, value: u16) -> Option<()> {
+        match operand {
+            Operand::Register(register) => self.registers.set(*register, value),
+            Operand::Memory(address) => {
+                let address = self.operand_address(instruction, address);
+                self.store(address, instruction.wide, value);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn read(&mut self, instruction: &Instruction, operand: &Operand) -> Option<u16> {
+        match operand {
+            Operand::Register(register) => Some(self.registers.get(*register)),
+            Operand::Memory(address) => {
+                let address = self.operand_address(instruction, address);
+                Some(self.load_recorded(address, instruction.wide))
+            }
+            Operand::Immediate(value) => Some(*value as u16),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode;
+
+    // Runs `code` from a fresh CPU whose registers and flags `setup` has prepared.
+    fn run(code: &[u8], setup: impl FnOnce(&mut Cpu)) -> Cpu {
+        let mut cpu = Cpu::with_memory(0x1000);
+        setup(&mut cpu);
+        for instruction in decode(code).unwrap() {
+            cpu.execute(&instruction).unwrap();
+        }
+        cpu
+    }
+
+    #[test]
+    fn add_sub_and_cmp_set_the_arithmetic_flags() {
+        // (code, register, value before, value after, status flags after)
+        let cases: [(&[u8], Register, u16, u16, Flags); 9] = [
+            // add al, -128
+            (
+                &[0x04, 0x80],
+                Register::Al,
+                0x01,
+                0x81,
+                Flags::SIGN | Flags::PARITY,
+            ),
+            // cmp al, -128: 0xF0 - 0x80 doesn't borrow.
+            (&[0x3C, 0x80], Register::Al, 0xF0, 0xF0, Flags::NONE),
+            // sub al, -1
+            (
+                &[0x2C, 0xFF],
+                Register::Al,
+                0x00,
+                0x01,
+                Flags::CARRY | Flags::AUX_CARRY,
+            ),
+            // cmp al, 0x7F
+            (
+                &[0x3C, 0x7F],
+                Register::Al,
+                0x80,
+                0x80,
+                Flags::OVERFLOW | Flags::AUX_CARRY,
+            ),
+            // add cl, 0x90
+            (
+                &[0x80, 0xC1, 0x90],
+                Register::Cl,
+                0x90,
+                0x20,
+                Flags::CARRY | Flags::OVERFLOW,
+            ),
+            // add ax, -1
+            (
+                &[0x83, 0xC0, 0xFF],
+                Register::Ax,
+                0x0001,
+                0x0000,
+                Flags::CARRY | Flags::AUX_CARRY | Flags::ZERO | Flags::PARITY,
+            ),
+            // add bx, -128
+            (
+                &[0x83, 0xC3, 0x80],
+                Register::Bx,
+                0x0100,
+                0x0080,
+                Flags::CARRY,
+            ),
+            // cmp ax, -128: 0x0010 - 0xFF80 borrows.
+            (
+                &[0x83, 0xF8, 0x80],
+                Register::Ax,
+                0x0010,
+                0x0010,
+                Flags::CARRY | Flags::PARITY,
+            ),
+            // sub dx, 1
+            (
+                &[0x83, 0xEA, 0x01],
+                Register::Dx,
+                0x8000,
+                0x7FFF,
+                Flags::OVERFLOW | Flags::AUX_CARRY | Flags::PARITY,
+            ),
+        ];
+        for (code, register, before, after, flags) in cases {
+            let cpu = run(code, |cpu| cpu.registers.set(register, before));
+            assert_eq!(cpu.registers.get(register), after, "{code:02X?}");
+            assert_eq!(cpu.flags & Flags::STATUS, flags, "{code:02X?}");
+        }
+    }
+
+    #[test]
+    fn byte_arithmetic_on_memory_ignores_the_sign_extension() {
+        // add byte [bx], -128; cmp byte [bx], -1
+        let mut cpu = run(&[0x80, 0x07, 0x80, 0x80, 0x3F, 0xFF], |cpu| {
+            cpu.registers.set(Register::Bx, 0x100);
+            cpu.store(0x100, false, 0x7F);
+        });
+        assert_eq!(cpu.load_recorded(0x100, false), 0xFF);
+        // 0xFF - 0xFF: equal, so no borrow.
+        assert_eq!(cpu.flags & Flags::STATUS, Flags::ZERO | Flags::PARITY);
+    }
+
+    #[test]
+    fn shl_by_one_sets_carry_and_overflow() {
+        // shl al, 1
+        let cpu = run(&[0xD0, 0xE0], |cpu| cpu.registers.set(Register::Al, 0x81));
+        assert_eq!(cpu.registers.get(Register::Al), 0x02);
+        assert!(cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::OVERFLOW));
+        assert!(!cpu.flags.contains(Flags::ZERO));
+    }
+
+    #[test]
+    fn shl_by_cl_shifts_out_every_bit() {
+        // shl ax, cl
+        let cpu = run(&[0xD3, 0xE0], |cpu| {
+            cpu.registers.set(Register::Ax, 0x8001);
+            cpu.registers.set(Register::Cl, 16);
+        });
+        // The last bit shifted out is the original bit 0.
+        assert_eq!(cpu.registers.get(Register::Ax), 0);
+        assert!(cpu.flags.contains(Flags::ZERO));
+        assert!(cpu.flags.contains(Flags::CARRY));
+    }
+
+    #[test]
+    fn shr_by_one_sets_overflow_from_the_old_sign() {
+        // shr bl, 1
+        let cpu = run(&[0xD0, 0xEB], |cpu| cpu.registers.set(Register::Bl, 0x81));
+        assert_eq!(cpu.registers.get(Register::Bl), 0x40);
+        assert!(cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn sar_keeps_the_sign() {
+        // sar dx, cl
+        let cpu = run(&[0xD3, 0xFA], |cpu| {
+            cpu.registers.set(Register::Dx, 0x8004);
+            cpu.registers.set(Register::Cl, 3);
+        });
+        assert_eq!(cpu.registers.get(Register::Dx), 0xF000);
+        assert!(cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::SIGN));
+        assert!(!cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn rol_wraps_the_top_bit_into_carry_and_bit_zero() {
+        // rol al, 1
+        let cpu = run(&[0xD0, 0xC0], |cpu| cpu.registers.set(Register::Al, 0x80));
+        assert_eq!(cpu.registers.get(Register::Al), 0x01);
+        assert!(cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn ror_leaves_the_result_flags_alone() {
+        // ror ax, cl
+        let cpu = run(&[0xD3, 0xC8], |cpu| {
+            cpu.registers.set(Register::Ax, 0x0001);
+            cpu.registers.set(Register::Cl, 4);
+            cpu.flags.set(Flags::ZERO, true);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0x1000);
+        assert!(!cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::ZERO));
+    }
+
+    #[test]
+    fn rcl_rotates_through_carry() {
+        // rcl al, 1
+        let cpu = run(&[0xD0, 0xD0], |cpu| {
+            cpu.registers.set(Register::Al, 0x40);
+            cpu.flags.set(Flags::CARRY, true);
+        });
+        assert_eq!(cpu.registers.get(Register::Al), 0x81);
+        assert!(!cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn rcr_rotates_through_carry() {
+        // rcr bx, cl
+        let cpu = run(&[0xD3, 0xDB], |cpu| {
+            cpu.registers.set(Register::Bx, 0x0003);
+            cpu.registers.set(Register::Cl, 2);
+        });
+        // 0x0003 -> 0x0001 with CF set -> 0x8000 with CF set.
+        assert_eq!(cpu.registers.get(Register::Bx), 0x8000);
+        assert!(cpu.flags.contains(Flags::CARRY));
+        assert!(cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn a_zero_count_changes_nothing() {
+        // shl word [0x100], cl
+        let cpu = run(&[0xD3, 0x26, 0x00, 0x01], |cpu| {
+            cpu.memory.write_u16(0x100, 0x1234);
+            cpu.flags.set(Flags::CARRY, true);
+        });
+        assert_eq!(cpu.memory.read_u16(0x100), 0x1234);
+        assert_eq!(cpu.flags, Flags::CARRY);
+    }
+
+    #[test]
+    fn mul_sets_carry_and_overflow_when_the_high_half_is_used() {
+        // (code, AL or AX, BL or CX, AX after, DX after, CF and OF)
+        let cases = [
+            // mul bl
+            ([0xF6, 0xE3], 0x0F, 0x10, 0x00F0, 0, false),
+            ([0xF6, 0xE3], 0x10, 0x10, 0x0100, 0, true),
+            // mul cx
+            ([0xF7, 0xE1], 0x1234, 0x0002, 0x2468, 0, false),
+            ([0xF7, 0xE1], 0x1000, 0x0010, 0x0000, 0x0001, true),
+        ];
+        for (code, accumulator, source, ax, dx, carry) in cases {
+            let cpu = run(&code, |cpu| {
+                cpu.registers.set(Register::Ax, accumulator);
+                cpu.registers.set(Register::Bx, source);
+                cpu.registers.set(Register::Cx, source);
+            });
+            assert_eq!(
+                cpu.registers.get(Register::Ax),
+                ax,
+                "{code:02X?} {accumulator:#x}"
+            );
+            assert_eq!(
+                cpu.registers.get(Register::Dx),
+                dx,
+                "{code:02X?} {accumulator:#x}"
+            );
+            assert_eq!(cpu.flags.contains(Flags::CARRY), carry);
+            assert_eq!(cpu.flags.contains(Flags::OVERFLOW), carry);
+        }
+    }
+
+    #[test]
+    fn imul_only_overflows_past_the_sign_extended_low_half() {
+        // imul bl: -2 * 3 fits in AL.
+        let cpu = run(&[0xF6, 0xEB], |cpu| {
+            cpu.registers.set(Register::Al, 0xFE);
+            cpu.registers.set(Register::Bl, 3);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0xFFFA);
+        assert!(!cpu.flags.contains(Flags::CARRY) && !cpu.flags.contains(Flags::OVERFLOW));
+
+        // imul bl: -128 * 2 doesn't.
+        let cpu = run(&[0xF6, 0xEB], |cpu| {
+            cpu.registers.set(Register::Al, 0x80);
+            cpu.registers.set(Register::Bl, 2);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0xFF00);
+        assert!(cpu.flags.contains(Flags::CARRY) && cpu.flags.contains(Flags::OVERFLOW));
+
+        // imul cx: -1 * -32768 is positive, so it needs DX's sign bit clear.
+        let cpu = run(&[0xF7, 0xE9], |cpu| {
+            cpu.registers.set(Register::Ax, 0xFFFF);
+            cpu.registers.set(Register::Cx, 0x8000);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0x8000);
+        assert_eq!(cpu.registers.get(Register::Dx), 0x0000);
+        assert!(cpu.flags.contains(Flags::CARRY) && cpu.flags.contains(Flags::OVERFLOW));
+    }
+
+    #[test]
+    fn idiv_rounds_towards_zero_and_keeps_the_dividends_sign() {
+        // idiv bl: -7 / 2
+        let cpu = run(&[0xF6, 0xFB], |cpu| {
+            cpu.registers.set(Register::Ax, (-7i16) as u16);
+            cpu.registers.set(Register::Bl, 2);
+        });
+        assert_eq!(cpu.registers.get(Register::Al), (-3i8) as u8 as u16);
+        assert_eq!(cpu.registers.get(Register::Ah), (-1i8) as u8 as u16);
+
+        // idiv cx: -1000 / 7
+        let cpu = run(&[0xF7, 0xF9], |cpu| {
+            cpu.registers.set(Register::Dx, 0xFFFF);
+            cpu.registers.set(Register::Ax, (-1000i16) as u16);
+            cpu.registers.set(Register::Cx, 7);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), (-142i16) as u16);
+        assert_eq!(cpu.registers.get(Register::Dx), (-6i16) as u16);
+    }
+
+    #[test]
+    fn dividing_by_zero_or_past_the_destination_fails() {
+        // Runs `code` with AX and BL set, expecting it to fail.
+        fn fails(code: &[u8], ax: u16, bl: u16) -> ExecError {
+            let mut cpu = Cpu::with_memory(0x1000);
+            cpu.registers.set(Register::Ax, ax);
+            cpu.registers.set(Register::Bl, bl);
+            cpu.execute(&decode(code).unwrap()[0]).unwrap_err()
+        }
+        // div bl
+        assert!(matches!(
+            fails(&[0xF6, 0xF3], 0x1234, 0),
+            ExecError::DivideByZero { offset: 0 }
+        ));
+        assert!(matches!(
+            fails(&[0xF6, 0xF3], 0x1000, 0x10),
+            ExecError::DivideOverflow { offset: 0 }
+        ));
+        // idiv bl: -128 / -1 is 128, one past the largest byte.
+        assert!(matches!(
+            fails(&[0xF6, 0xFB], 0xFF80, 0xFF),
+            ExecError::DivideOverflow { offset: 0 }
+        ));
+    }
+
+    #[test]
+    fn rep_stosw_fills_words_in_either_direction() {
+        for (direction, di, filled, di_after) in
+            [(false, 0x100, 0x100, 0x106), (true, 0x104, 0x100, 0x0FE)]
+        {
+            // rep stosw
+            let cpu = run(&[0xF3, 0xAB], |cpu| {
+                cpu.registers.set(Register::Ax, 0xABCD);
+                cpu.registers.set(Register::Di, di);
+                cpu.registers.set(Register::Cx, 3);
+                cpu.flags.set(Flags::DIRECTION, direction);
+            });
+            for address in (filled..filled + 6).step_by(2) {
+                assert_eq!(cpu.memory.read_u16(address), 0xABCD, "DF={direction}");
+            }
+            assert_eq!(cpu.memory.read_u16(filled - 2), 0, "DF={direction}");
+            assert_eq!(cpu.memory.read_u16(filled + 6), 0, "DF={direction}");
+            assert_eq!(cpu.registers.get(Register::Di), di_after, "DF={direction}");
+            assert_eq!(cpu.registers.get(Register::Cx), 0);
+        }
+    }
+
+    #[test]
+    fn rep_movsb_copies_bytes_in_either_direction() {
+        for (direction, start, after) in [(false, 0, 3), (true, 2, 0xFFFF)] {
+            // rep movsb
+            let cpu = run(&[0xF3, 0xA4], |cpu| {
+                cpu.memory.load(0x200, b"abc");
+                cpu.registers.set(Register::Si, 0x200 + start);
+                cpu.registers.set(Register::Di, 0x300 + start);
+                cpu.registers.set(Register::Cx, 3);
+                cpu.flags.set(Flags::DIRECTION, direction);
+            });
+            assert_eq!(cpu.memory.slice(0x300, 4), b"abc\0", "DF={direction}");
+            assert_eq!(
+                cpu.registers.get(Register::Si),
+                0x200u16.wrapping_add(after),
+                "DF={direction}"
+            );
+            assert_eq!(
+                cpu.registers.get(Register::Di),
+                0x300u16.wrapping_add(after),
+                "DF={direction}"
+            );
+            assert_eq!(cpu.registers.get(Register::Cx), 0);
+        }
+    }
+
+    #[test]
+    fn repne_scasb_stops_just_past_a_match() {
+        // repne scasb
+        let cpu = run(&[0xF2, 0xAE], |cpu| {
+            cpu.memory.load(0x100, b"hello");
+            cpu.registers.set(Register::Al, b'l' as u16);
+            cpu.registers.set(Register::Di, 0x100);
+            cpu.registers.set(Register::Cx, 5);
+        });
+        assert_eq!(cpu.registers.get(Register::Di), 0x103);
+        assert_eq!(cpu.registers.get(Register::Cx), 2);
+        assert!(cpu.flags.contains(Flags::ZERO));
+    }
+
+    #[test]
+    fn repe_cmpsb_stops_just_past_a_difference() {
+        // repe cmpsb
+        let cpu = run(&[0xF3, 0xA6], |cpu| {
+            cpu.memory.load(0x200, b"abcd");
+            cpu.memory.load(0x300, b"abxd");
+            cpu.registers.set(Register::Si, 0x200);
+            cpu.registers.set(Register::Di, 0x300);
+            cpu.registers.set(Register::Cx, 4);
+        });
+        assert_eq!(cpu.registers.get(Register::Si), 0x203);
+        assert_eq!(cpu.registers.get(Register::Di), 0x303);
+        assert_eq!(cpu.registers.get(Register::Cx), 1);
+        assert!(!cpu.flags.contains(Flags::ZERO));
+        // 'c' - 'x' borrows.
+        assert!(cpu.flags.contains(Flags::CARRY));
+    }
+
+    #[test]
+    fn push_and_pop_go_through_ss_sp() {
+        // push ax; push cx; pop ax; pop cx
+        let cpu = run(&[0x50, 0x51, 0x58, 0x59], |cpu| {
+            cpu.registers.set(Register::Ss, 0x0010);
+            cpu.registers.set(Register::Sp, 0x0800);
+            cpu.registers.set(Register::Ax, 0x1234);
+            cpu.registers.set(Register::Cx, 0x5678);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0x5678);
+        assert_eq!(cpu.registers.get(Register::Cx), 0x1234);
+        assert_eq!(cpu.registers.get(Register::Sp), 0x0800);
+        assert_eq!(cpu.memory.read_u16(0x100 + 0x7FE), 0x1234);
+        assert_eq!(cpu.memory.read_u16(0x100 + 0x7FC), 0x5678);
+    }
+
+    #[test]
+    fn call_pushes_the_return_address_and_ret_n_drops_arguments() {
+        // call 8; nop x5; ret 4
+        let code = [
+            0xE8, 0x05, 0x00, 0x90, 0x90, 0x90, 0x90, 0x90, 0xC2, 0x04, 0x00,
+        ];
+        let mut cpu = Cpu::with_memory(0x1000);
+        cpu.memory.load(0, &code);
+        cpu.registers.set(Register::Sp, 0x0800);
+
+        let call = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&call).unwrap();
+        assert_eq!(cpu.ip, 8);
+        assert_eq!(cpu.registers.get(Register::Sp), 0x07FE);
+        assert_eq!(cpu.memory.read_u16(0x7FE), 3);
+
+        let ret = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&ret).unwrap();
+        assert_eq!(cpu.ip, 3);
+        assert_eq!(cpu.registers.get(Register::Sp), 0x0804);
+    }
+
+    #[test]
+    fn the_stack_limits_catch_overflow_and_underflow() {
+        // push ax; push ax
+        let pushes = decode(&[0x50, 0x50]).unwrap();
+        let mut cpu = Cpu::with_memory(0x1000);
+        cpu.registers.set(Register::Sp, 0x0800);
+        cpu.stack = Some(0x07FE..0x0800);
+        cpu.execute(&pushes[0]).unwrap();
+        assert!(matches!(
+            cpu.execute(&pushes[1]),
+            Err(ExecError::StackOverflow { offset: 1 })
+        ));
+        assert_eq!(cpu.registers.get(Register::Sp), 0x07FE);
+
+        // pop bx; ret
+        let pops = decode(&[0x5B, 0xC3]).unwrap();
+        cpu.execute(&pops[0]).unwrap();
+        assert!(matches!(
+            cpu.execute(&pops[1]),
+            Err(ExecError::StackUnderflow { offset: 1 })
+        ));
+        assert_eq!(cpu.registers.get(Register::Sp), 0x0800);
+
+        // Without limits the same pop goes ahead.
+        cpu.stack = None;
+        cpu.execute(&pops[0]).unwrap();
+        assert_eq!(cpu.registers.get(Register::Sp), 0x0802);
+    }
+
+    #[test]
+    fn in_and_out_go_through_the_bus() {
+        let log = Rc::new(RefCell::new(PortLog::default()));
+        // out 0x42, al; in ax, dx
+        let cpu = run(&[0xE6, 0x42, 0xED], |cpu| {
+            cpu.io = Box::new(log.clone());
+            cpu.registers.set(Register::Al, 0x12);
+            cpu.registers.set(Register::Dx, 0x3F8);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0xFFFF);
+        assert_eq!(
+            log.borrow().accesses,
+            [
+                PortAccess {
+                    port: 0x42,
+                    wide: false,
+                    write: true,
+                    value: 0x12,
+                },
+                PortAccess {
+                    port: 0x3F8,
+                    wide: true,
+                    write: false,
+                    value: 0xFFFF,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn int_vectors_through_the_table_and_iret_returns() {
+        // int 0x21; ... at 0x200: iret
+        let mut cpu = Cpu::with_memory(0x1000);
+        cpu.memory.load(0x100, &[0xCD, 0x21]);
+        cpu.memory.load(0x200, &[0xCF]);
+        cpu.set_vector(0x21, 0x0020, 0x0000);
+        cpu.ip = 0x100;
+        cpu.registers.set(Register::Sp, 0x800);
+        cpu.flags = Flags::INTERRUPT | Flags::CARRY;
+
+        let int = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&int).unwrap();
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0x0020, 0x0000));
+        assert_eq!(cpu.flags, Flags::CARRY);
+        assert_eq!(cpu.memory.read_u16(0x7FA), 0x102);
+        assert_eq!(
+            cpu.memory.read_u16(0x7FE),
+            (Flags::INTERRUPT | Flags::CARRY).0
+        );
+
+        let iret = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&iret).unwrap();
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0, 0x102));
+        assert_eq!(cpu.flags, Flags::INTERRUPT | Flags::CARRY);
+        assert_eq!(cpu.registers.get(Register::Sp), 0x800);
+    }
+
+    #[test]
+    fn a_hook_runs_instead_of_the_vector() {
+        // int 0x10; int 0x11
+        let cpu = run(&[0xCD, 0x10, 0xCD, 0x11], |cpu| {
+            cpu.registers.set(Register::Sp, 0x800);
+            cpu.hook_interrupt(0x10, |cpu| {
+                cpu.registers.set(Register::Ax, 0x1234);
+                true
+            });
+            // Declining leaves the interrupt to the guest.
+            cpu.hook_interrupt(0x11, |_| false);
+            cpu.set_vector(0x11, 0x0040, 0x0010);
+        });
+        assert_eq!(cpu.registers.get(Register::Ax), 0x1234);
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0x0040, 0x0010));
+        assert_eq!(cpu.registers.get(Register::Sp), 0x7FA);
+    }
+
+    #[test]
+    fn into_only_interrupts_on_overflow() {
+        // into
+        let cpu = run(&[0xCE], |cpu| cpu.set_vector(4, 0x0010, 0x0000));
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0, 1));
+    }
+
+    #[test]
+    fn the_trap_flag_interrupts_after_the_next_instruction() {
+        // popf; mov ax, 1; mov bx, 2
+        let mut cpu = Cpu::with_memory(0x1000);
+        cpu.memory
+            .load(0x100, &[0x9D, 0xB8, 0x01, 0x00, 0xBB, 0x02, 0x00]);
+        cpu.memory.write_u16(0x800, Flags::TRAP.0);
+        cpu.set_vector(SINGLE_STEP_INTERRUPT, 0x0020, 0x0000);
+        cpu.ip = 0x100;
+        cpu.registers.set(Register::Sp, 0x800);
+
+        // Setting TF doesn't trap the popf itself.
+        let popf = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&popf).unwrap();
+        assert_eq!(cpu.ip, 0x101);
+        assert_eq!(cpu.flags, Flags::TRAP);
+
+        let mov = cpu.fetch().unwrap().unwrap();
+        cpu.execute(&mov).unwrap();
+        assert_eq!(cpu.registers.get(Register::Ax), 1);
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0x0020, 0x0000));
+        assert_eq!(cpu.flags, Flags::default());
+        assert_eq!(cpu.memory.read_u16(0x7FC), 0x104);
+        assert_eq!(cpu.memory.read_u16(0x800), Flags::TRAP.0);
+    }
+
+    #[test]
+    fn a_hooked_single_step_sees_every_instruction() {
+        // mov ax, 1; mov bx, 2; int 0x10
+        let steps = Rc::new(RefCell::new(Vec::new()));
+        let seen = steps.clone();
+        let cpu = run(&[0xB8, 0x01, 0x00, 0xBB, 0x02, 0x00, 0xCD, 0x10], |cpu| {
+            cpu.registers.set(Register::Sp, 0x800);
+            cpu.flags = Flags::TRAP;
+            cpu.hook_interrupt(SINGLE_STEP_INTERRUPT, move |cpu| {
+                seen.borrow_mut().push(cpu.ip);
+                true
+            });
+            cpu.set_vector(0x10, 0x0040, 0x0000);
+        });
+        // The int clears TF as it enters its handler, so it isn't stepped.
+        assert_eq!(*steps.borrow(), [3, 6]);
+        assert_eq!((cpu.registers.get(Register::Cs), cpu.ip), (0x0040, 0x0000));
+    }
+}
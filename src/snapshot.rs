@@ -0,0 +1,126 @@
+use crate::{
+    cursor::Cursor,
+    flags::Flags,
+    memory::{Memory, ADDRESS_SPACE},
+    simulator::{Cpu, Registers},
+};
+use std::io::{self, Read, Write};
+
+/// Identifies a snapshot file, and which version of the layout it uses.
+const MAGIC: &[u8; 8] = b"CESNAP01";
+
+/// The machine state a program sees: registers, flags, IP, and memory. Devices, interrupt
+/// hooks, and the stack bounds belong to the host and aren't part of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub registers: Registers,
+    pub flags: Flags,
+    pub ip: u16,
+    pub memory: Memory,
+}
+
+impl Snapshot {
+    /// Writes the snapshot: the magic bytes, then every register in `Registers::ALL` order,
+    /// IP, flags, the memory size, and memory, all little-endian.
+    pub fn write(&self, out: &mut (impl Write + ?Sized)) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        for register in Registers::ALL {
+            out.write_all(&self.registers.get(register).to_le_bytes())?;
+        }
+        out.write_all(&self.ip.to_le_bytes())?;
+        out.write_all(&self.flags.0.to_le_bytes())?;
+        out.write_all(&(self.memory.len() as u32).to_le_bytes())?;
+        out.write_all(self.memory.as_slice())
+    }
+
+    /// Reads a snapshot written by `write`.
+    pub fn read(input: &mut (impl Read + ?Sized)) -> io::Result<Snapshot> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut file = Vec::new();
+        input.read_to_end(&mut file)?;
+        let mut cursor = Cursor::new(&file);
+        let truncated = || io::Error::from(io::ErrorKind::UnexpectedEof);
+
+        if cursor.read_bytes(MAGIC.len()).ok_or_else(truncated)? != MAGIC {
+            return Err(invalid("not a snapshot"));
+        }
+        let mut registers = Registers::default();
+        for register in Registers::ALL {
+            registers.set(register, cursor.read_u16_le().ok_or_else(truncated)?);
+        }
+        let ip = cursor.read_u16_le().ok_or_else(truncated)?;
+        let flags = Flags(cursor.read_u16_le().ok_or_else(truncated)? & Flags::ALL.0);
+
+        let size = cursor.read_u32_le().ok_or_else(truncated)? as usize;
+        if size == 0 || size > ADDRESS_SPACE {
+            return Err(invalid("snapshot memory size out of range"));
+        }
+        let mut memory = Memory::new(size);
+        memory.load(0, cursor.read_bytes(size).ok_or_else(truncated)?);
+
+        Ok(Snapshot {
+            registers,
+            flags,
+            ip,
+            memory,
+        })
+    }
+}
+
+impl Cpu {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            registers: self.registers,
+            flags: self.flags,
+            ip: self.ip,
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Puts the machine back as `snapshot` captured it, keeping the host's devices and hooks.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.registers = snapshot.registers;
+        self.flags = snapshot.flags;
+        self.ip = snapshot.ip;
+        self.memory = snapshot.memory.clone();
+        self.accesses.clear();
+        self.exit_code = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Register;
+
+    #[test]
+    fn a_snapshot_survives_writing_and_reading() {
+        let mut cpu = Cpu::with_memory(0x100);
+        cpu.registers.set(Register::Cx, 0x1234);
+        cpu.registers.set(Register::Ds, 0xB800);
+        cpu.flags = Flags::ZERO | Flags::DIRECTION;
+        cpu.ip = 0x42;
+        cpu.memory.write_u16(0xFF, 0xBEEF);
+
+        let mut file = Vec::new();
+        cpu.snapshot().write(&mut file).unwrap();
+        let mut restored = Cpu::new();
+        restored.restore(&Snapshot::read(&mut &file[..]).unwrap());
+        assert_eq!(restored.snapshot(), cpu.snapshot());
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let error = Snapshot::read(&mut &b"MZ\0\0\0\0\0\0"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_snapshots_are_rejected() {
+        let mut file = Vec::new();
+        Cpu::with_memory(0x100).snapshot().write(&mut file).unwrap();
+        file.pop();
+        let error = Snapshot::read(&mut &file[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
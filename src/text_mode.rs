@@ -0,0 +1,73 @@
+use crate::memory::Memory;
+use std::io::{self, Write};
+
+/// Physical address of CGA text-mode video memory.
+pub const TEXT_MEMORY: usize = 0xB8000;
+pub const COLUMNS: usize = 80;
+pub const ROWS: usize = 25;
+
+/// Code page 437, the PC's character set, as Unicode. NUL shows as a space.
+const CP437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', //
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼', //
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', //
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?', //
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', //
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_', //
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', //
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂', //
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ', //
+];
+
+/// ANSI color numbers for the CGA's eight base colors, which list blue and red the other way
+/// round. Brown shows as ANSI yellow.
+const ANSI_COLORS: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+/// The escape sequence for a CGA attribute byte: foreground in the low nibble, background in
+/// the next three bits. The top bit, blink, is ignored.
+fn attribute_escape(attribute: u8) -> String {
+    let foreground = ANSI_COLORS[(attribute & 0x07) as usize];
+    let foreground = if attribute & 0x08 != 0 {
+        90 + foreground
+    } else {
+        30 + foreground
+    };
+    let background = 40 + ANSI_COLORS[((attribute >> 4) & 0x07) as usize];
+    format!("\x1b[{foreground};{background}m")
+}
+
+/// Writes the 80x25 text screen held in `memory` at 0xB8000, a character byte then an
+/// attribute byte per cell. With `color`, attributes become ANSI escapes; without, trailing
+/// spaces are trimmed from each row.
+pub fn write_text_screen(
+    out: &mut (impl Write + ?Sized),
+    memory: &Memory,
+    color: bool,
+) -> io::Result<()> {
+    let cells = memory.slice(TEXT_MEMORY, COLUMNS * ROWS * 2);
+    for row in cells.chunks(COLUMNS * 2) {
+        let mut line = String::new();
+        let mut attribute = None;
+        for cell in row.chunks_exact(2) {
+            if color && attribute != Some(cell[1]) {
+                attribute = Some(cell[1]);
+                line += &attribute_escape(cell[1]);
+            }
+            line.push(CP437[cell[0] as usize]);
+        }
+        if color {
+            line += "\x1b[0m";
+        } else {
+            line.truncate(line.trim_end().len());
+        }
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
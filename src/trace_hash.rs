@@ -0,0 +1,57 @@
+use crate::{
+    instruction::Instruction,
+    simulator::{Cpu, Registers},
+};
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+pub(crate) const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// A digest of an execution trace: each instruction executed and the state it left. It's
+/// 64-bit FNV-1a over a fixed little-endian encoding, so a run hashes the same on every
+/// platform and build, and two runs match only if they executed the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceHash(u64);
+
+impl Default for TraceHash {
+    fn default() -> Self {
+        TraceHash(FNV_OFFSET_BASIS)
+    }
+}
+
+impl TraceHash {
+    pub fn new() -> Self {
+        TraceHash::default()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Adds `instruction`, the one at CS:IP, so call it before executing it: its address and
+    /// its bytes as they are in memory.
+    pub fn instruction(&mut self, cpu: &Cpu, instruction: &Instruction) {
+        let address = cpu.instruction_address();
+        self.write(&(address as u32).to_le_bytes());
+        self.write(cpu.memory.slice(address, instruction.length));
+    }
+
+    /// Adds the state the last instruction left: every register, IP, the flags, and the
+    /// memory it wrote.
+    pub fn state(&mut self, cpu: &Cpu) {
+        for register in Registers::ALL {
+            self.write(&cpu.registers.get(register).to_le_bytes());
+        }
+        self.write(&cpu.ip.to_le_bytes());
+        self.write(&cpu.flags.0.to_le_bytes());
+        for access in cpu.accesses.iter().filter(|access| access.write) {
+            self.write(&(access.address as u32).to_le_bytes());
+            self.write(cpu.memory.slice(access.address, access.size));
+        }
+    }
+
+    pub fn digest(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::listings;
+use computer_enhance::assembler::assemble;
+use std::{fs, process::Command};
+
+/// `ce disasm` options whose output should all assemble back to the original bytes.
+const VARIANTS: [&[&str]; 7] = [
+    &[],
+    &["--distance-keywords"],
+    &["--case", "lower", "--radix", "hex"],
+    &["--radix", "suffix", "--size-keywords", "always"],
+    &["--origin", "0x100"],
+    &["--clocks", "--explain"],
+    &["--syntax", "ndisasm"],
+];
+
+/// The course listing binaries, in name order.
+#[test]
+fn listings_assemble_from_their_sources() {
+    for binary in listings(None) {
+        let source = fs::read_to_string(binary.with_extension("asm")).unwrap();
+        let assembled =
+            assemble(&source).unwrap_or_else(|error| panic!("{}: {error}", binary.display()));
+        assert!(
+            assembled == fs::read(&binary).unwrap(),
+            "{} assembles differently",
+            binary.display()
+        );
+    }
+}
+
+#[test]
+fn listings_assemble_from_their_disassembly() {
+    for binary in listings(None) {
+        let original = fs::read(&binary).unwrap();
+        for options in VARIANTS {
+            let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+                .arg("disasm")
+                .args(options)
+                .arg(&binary)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            let disassembly = String::from_utf8(output.stdout).unwrap();
+            let assembled = assemble(&disassembly)
+                .unwrap_or_else(|error| panic!("{} with {options:?}: {error}", binary.display()));
+            assert!(
+                assembled == original,
+                "{} with {options:?} assembles differently",
+                binary.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The files in listings/ with `extension`, or with none for the assembled binaries, sorted by
+/// name.
+pub fn listings(extension: Option<&str>) -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("listings");
+    let mut listings: Vec<PathBuf> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|extension| extension.to_str()) == extension)
+        .collect();
+    listings.sort();
+    listings
+}
@@ -0,0 +1,101 @@
+use std::{
+    env, fs,
+    process::{self, Command, Output},
+};
+
+/// Runs `ce exec` with `args` on a file holding `image`.
+fn exec(name: &str, image: &[u8], args: &[&str]) -> Output {
+    let path = env::temp_dir().join(format!("ce-exec-{}-{name}", process::id()));
+    fs::write(&path, image).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+        .arg("exec")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn a_com_program_can_keep_its_string_amongst_its_code() {
+    let image = [
+        0xBA, 0x0C, 0x01, // mov dx, message
+        0xB4, 0x09, // mov ah, 9
+        0xCD, 0x21, // int 21h
+        0xB8, 0x00, 0x4C, // mov ax, 0x4c00
+        0xCD, 0x21, // int 21h
+        b'h', b'i', b'$', // message: db "hi$", which doesn't decode
+    ];
+    let output = exec("string.com", &image, &["--com", "--dos"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // What the program prints is kept off the trace's lines.
+    assert!(stdout.contains("\nhi\nINT 33 ; "), "{stdout}");
+}
+
+#[test]
+fn a_boot_sector_can_keep_its_string_amongst_its_code() {
+    let mut image = vec![
+        0xBE, 0x0B, 0x7C, // mov si, message
+        0xAC, // next: lodsb
+        0x3C, 0x00, // cmp al, 0
+        0x75, 0xFB, // jne next
+        0xE9, 0xF5, 0x01, // jmp 0x7e00, past the end of the sector
+    ];
+    // message: db "Hello", 0, which doesn't decode
+    image.extend_from_slice(b"Hello\0");
+    image.resize(510, 0);
+    image.extend_from_slice(&[0x55, 0xAA]);
+    let output = exec("hello.img", &image, &["--boot"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Every byte of the string was read, the zero too.
+    assert!(stdout.contains("si: 0x7c11"), "{stdout}");
+    assert!(stdout.contains("ip: 0x7e00"), "{stdout}");
+}
+
+#[test]
+fn coverage_leaves_data_amongst_the_code_untouched() {
+    let image = [
+        0xB8, 0x00, 0x4C, // mov ax, 0x4c00
+        0xCD, 0x21, // int 21h
+        b'h', b'i', b'$', // db "hi$"
+    ];
+    let output = exec("coverage.com", &image, &["--com", "--dos", "--coverage"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains(
+            "  0x10100..0x10105 executed (5 bytes)\n  0x10105..0x10108 untouched (3 bytes)\n"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn code_patched_beside_data_is_listed_as_patched() {
+    let image = [
+        0xC6, 0x06, 0x06, 0x01, 0x02, // mov byte [0x106], 2
+        0xB8, 0x01, 0x00, // mov ax, 1, patched to mov ax, 2
+        0xC3, // ret
+        b'h', b'i', b'$', // db "hi$"
+    ];
+    let output = exec(
+        "patched.com",
+        &image,
+        &["--com", "--coverage", "--coverage-listing"],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("ax: 0x0002"), "{stdout}");
+    assert!(stdout.contains("* 0105: MOV AX, 2\n"), "{stdout}");
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::listings;
+use std::{fs, path::Path, process::Command};
+
+/// Runs each listing that has a reference trace beside it through `ce exec --trace reference`,
+/// and compares the output with the trace byte for byte. The reference names the listing by
+/// the path it was run from, `test\`, where ours names it as given.
+#[test]
+fn listings_execute_as_their_reference_traces() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("listings");
+    let traces = listings(Some("txt"));
+    assert!(!traces.is_empty());
+
+    for trace in traces {
+        let listing = trace.file_stem().unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+            .current_dir(&directory)
+            .args(["exec", "--trace", "reference"])
+            .arg(listing)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            trace.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let expected = fs::read_to_string(&trace)
+            .unwrap()
+            .replacen("test\\", "", 1);
+        assert!(
+            output.stdout == expected.as_bytes(),
+            "{} differs:\n{}",
+            trace.display(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{self, Command, Stdio},
+};
+
+/// `$data#checksum`.
+fn packet(data: &str) -> String {
+    let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    format!("${data}#{sum:02x}")
+}
+
+/// Reads up to and including the next packet's checksum, acks and all.
+fn read_reply(stream: &mut TcpStream) -> String {
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    while reply.len() < 3 || reply[reply.len() - 3] != b'#' {
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+    String::from_utf8(reply).unwrap()
+}
+
+#[test]
+fn an_interrupt_stops_a_program_that_never_ends() {
+    let path = env::temp_dir().join(format!("ce-gdb-{}.bin", process::id()));
+    // jmp $
+    fs::write(&path, [0xEB, 0xFE]).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ce"))
+        .args(["debug", "--gdb", "127.0.0.1:0"])
+        .arg(&path)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let address = line
+        .trim()
+        .strip_prefix("waiting for gdb on ")
+        .unwrap_or_else(|| panic!("unexpected startup line {line:?}"));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(packet("c").as_bytes()).unwrap();
+    // GDB's Ctrl-C, outside any packet.
+    stream.write_all(&[0x03]).unwrap();
+    assert_eq!(read_reply(&mut stream), format!("+{}", packet("S02")));
+
+    stream.write_all(packet("D").as_bytes()).unwrap();
+    assert_eq!(read_reply(&mut stream), format!("+{}", packet("OK")));
+    assert!(child.wait().unwrap().success());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::listings;
+use std::{env, fs, path::Path, process::Command};
+
+/// Each snapshot's file name suffix, and the `ce disasm` options it's taken with.
+const VARIANTS: [(&str, &[&str]); 3] = [
+    ("", &[]),
+    (".annotated", &["--clocks", "--explain"]),
+    (".ndisasm", &["--format", "ndisasm"]),
+];
+
+/// The course listing binaries, relative to the package, in name order.
+/// Compares each listing's disassembly with its snapshot in tests/fixtures. With
+/// `UPDATE_GOLDEN=1` the snapshots are rewritten instead, for when the output changes on
+/// purpose; review the diff before committing them.
+#[test]
+fn listings_disassemble_as_their_snapshots() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bless = env::var_os("UPDATE_GOLDEN").is_some_and(|value| value == "1");
+    let mut mismatched = Vec::new();
+
+    for listing in listings(None) {
+        // Relative to the package, so the snapshots don't depend on where it's checked out.
+        let listing = listing.strip_prefix(root).unwrap();
+        for (suffix, options) in VARIANTS {
+            let name = listing.file_name().unwrap().to_string_lossy();
+            let snapshot = root.join(format!("tests/fixtures/{name}{suffix}.txt"));
+            let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+                .current_dir(root)
+                .arg("disasm")
+                .args(options)
+                .arg(listing)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "disassembling {name} failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let actual = String::from_utf8(output.stdout).unwrap();
+
+            if bless {
+                fs::write(&snapshot, &actual).unwrap();
+            } else if fs::read_to_string(&snapshot).ok().as_deref() != Some(actual.as_str()) {
+                mismatched.push(snapshot.display().to_string());
+            }
+        }
+    }
+
+    assert!(
+        mismatched.is_empty(),
+        "disassembly differs from {}; rerun with UPDATE_GOLDEN=1 if that's intended",
+        mismatched.join(", ")
+    );
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use common::listings;
+use computer_enhance::{
+    decoder::{decode, Decoder},
+    instruction::{AddressBase, Instruction, Mnemonic, Operand},
+};
+use iced_x86::{DecoderOptions, OpKind, Register as IcedRegister};
+use std::fs;
+
+/// The name iced-x86 gives `instruction`, encoded as `bytes`, where the two differ.
+fn iced_mnemonic(instruction: &Instruction, bytes: &[u8]) -> String {
+    let name = match instruction.mnemonic {
+        Mnemonic::Jnb => "jae",
+        Mnemonic::Jnl => "jge",
+        Mnemonic::Loopz => "loope",
+        Mnemonic::Loopnz => "loopne",
+        Mnemonic::Xlat => "xlatb",
+        // 0x90 is `xchg ax, ax`, which iced calls `nop`.
+        Mnemonic::Xchg if bytes.last() == Some(&0x90) => "nop",
+        // iced names string instructions by the size of element they move.
+        Mnemonic::Movs | Mnemonic::Cmps | Mnemonic::Scas | Mnemonic::Lods | Mnemonic::Stos => {
+            let size = if instruction.wide { "w" } else { "b" };
+            return format!("{}{size}", instruction.mnemonic.name());
+        }
+        mnemonic => mnemonic.name(),
+    };
+    name.to_string()
+}
+
+/// Whether iced decodes `bytes`, which start an instruction this crate decodes, as something
+/// other than an 8086 would: instructions later CPUs gave those opcodes to, the x87
+/// instructions ESC stands for, and segment registers past DS, which the 8086 takes as ES to
+/// DS again but later CPUs as FS and GS.
+fn decoded_differently(bytes: &[u8]) -> bool {
+    let mut opcode = bytes.iter().copied();
+    let first =
+        opcode.find(|&byte| !matches!(byte, 0x26 | 0x2E | 0x36 | 0x3E | 0xF0 | 0xF2 | 0xF3));
+    let reg = opcode.next().map(|mod_reg_r_m| mod_reg_r_m >> 3 & 0b111);
+    matches!(
+        (first, reg),
+        (
+            Some(0x0F | 0x60..=0x6F | 0xC0 | 0xC1 | 0xC8 | 0xC9 | 0xD8..=0xDF),
+            _
+        ) | (Some(0x8C | 0x8E), Some(4..=7))
+    )
+}
+
+fn iced_register(register: IcedRegister) -> String {
+    format!("{register:?}").to_lowercase()
+}
+
+/// An operand of this crate's as text that can be compared with iced's. Data is given at the
+/// instruction's width, since the two sign- or zero-extend bytes differently.
+fn ours(instruction: &Instruction, operand: Operand) -> String {
+    match operand {
+        Operand::Register(register) => register.name().to_string(),
+        Operand::Memory(address) => {
+            let registers = match address.base {
+                AddressBase::BxSi => "bx+si",
+                AddressBase::BxDi => "bx+di",
+                AddressBase::BpSi => "bp+si",
+                AddressBase::BpDi => "bp+di",
+                AddressBase::Si => "si",
+                AddressBase::Di => "di",
+                AddressBase::Bp => "bp",
+                AddressBase::Bx => "bx",
+                AddressBase::Direct => "",
+            };
+            let segment = instruction
+                .segment_override
+                .map_or(String::new(), |segment| format!("{}:", segment.name()));
+            format!(
+                "{segment}[{registers}+{:#06x}]",
+                address.displacement as u16
+            )
+        }
+        Operand::Immediate(value) if instruction.wide => format!("{:#06x}", value as u16),
+        Operand::Immediate(value) => format!("{:#04x}", value as u8),
+        Operand::Relative(displacement) => {
+            format!(
+                "{:#06x}",
+                (instruction.end() as i64 + displacement as i64) as u16
+            )
+        }
+        Operand::Far { segment, offset } => format!("{segment:04x}:{offset:04x}"),
+    }
+}
+
+/// iced's operands as `ours` writes them. iced lists some that this crate leaves implicit: the
+/// accumulator and memory that string instructions and XLAT use, and AAM and AAD's base, and
+/// those are left out.
+fn theirs(ours: &Instruction, theirs: &iced_x86::Instruction) -> Vec<String> {
+    use Mnemonic::*;
+    if matches!(
+        ours.mnemonic,
+        Movs | Cmps | Scas | Lods | Stos | Xlat | Aam | Aad
+    ) {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    for index in 0..theirs.op_count() {
+        let text = match theirs.op_kind(index) {
+            OpKind::Register => iced_register(theirs.op_register(index)),
+            OpKind::Memory => {
+                let registers: Vec<String> = [theirs.memory_base(), theirs.memory_index()]
+                    .into_iter()
+                    .filter(|&register| register != IcedRegister::None)
+                    .map(iced_register)
+                    .collect();
+                let segment = match theirs.segment_prefix() {
+                    IcedRegister::None => String::new(),
+                    segment => format!("{}:", iced_register(segment)),
+                };
+                format!(
+                    "{segment}[{}+{:#06x}]",
+                    registers.join("+"),
+                    theirs.memory_displacement32() as u16
+                )
+            }
+            OpKind::NearBranch16 => format!("{:#06x}", theirs.near_branch16()),
+            OpKind::FarBranch16 => format!(
+                "{:04x}:{:04x}",
+                theirs.far_branch_selector(),
+                theirs.far_branch16()
+            ),
+            _ if ours.wide => format!("{:#06x}", theirs.immediate(index) as u16),
+            _ => format!("{:#04x}", theirs.immediate(index) as u8),
+        };
+        operands.push(text);
+    }
+    operands
+}
+
+/// Compares this crate's decoding of `code` with iced's, instruction by instruction, and
+/// describes the first few that differ in length, mnemonic, or operands.
+fn mismatches(code: &[u8], name: &str) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for instruction in decode(code).unwrap() {
+        let bytes = &code[instruction.offset..instruction.end()];
+        if decoded_differently(bytes) {
+            continue;
+        }
+        let decoded = iced_x86::Decoder::with_ip(
+            16,
+            &code[instruction.offset..],
+            instruction.offset as u64,
+            DecoderOptions::NO_INVALID_CHECK,
+        )
+        .decode();
+
+        let expected = iced_mnemonic(&instruction, bytes);
+        let mnemonic = format!("{:?}", decoded.mnemonic()).to_lowercase();
+        let mut operands: Vec<String> = instruction
+            .operands
+            .into_iter()
+            .flatten()
+            .map(|operand| ours(&instruction, operand))
+            .collect();
+        let mut their_operands = theirs(&instruction, &decoded);
+        // `xchg ax, ax` is a NOP, which iced lists without them.
+        if expected == "nop" {
+            operands.clear();
+        }
+        // Either order means the same exchange.
+        if instruction.mnemonic == Mnemonic::Xchg {
+            operands.sort();
+            their_operands.sort();
+        }
+        if decoded.len() != instruction.length || mnemonic != expected || operands != their_operands
+        {
+            mismatches.push(format!(
+                "{name} {:#x} {bytes:02x?}: we decode {expected} {operands:?} ({} bytes), iced \
+                 {mnemonic} {their_operands:?} ({} bytes)",
+                instruction.offset,
+                instruction.length,
+                decoded.len()
+            ));
+        }
+    }
+    mismatches.truncate(60);
+    mismatches
+}
+
+/// Instructions this crate decodes from random bytes, back to back: each starts where a
+/// run of random bytes does, and is kept with just the bytes it decodes from.
+fn random_instructions(count: usize) -> Vec<u8> {
+    // xorshift64, seeded so failures reproduce.
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut code = Vec::new();
+    let mut kept = 0;
+    while kept < count {
+        let bytes = random().to_le_bytes();
+        if let Ok(Some(instruction)) = Decoder::new(&bytes).next_instruction() {
+            if !decoded_differently(&bytes) {
+                code.extend_from_slice(&bytes[..instruction.length]);
+                kept += 1;
+            }
+        }
+    }
+    code
+}
+
+#[test]
+fn listings_decode_as_iced_does() {
+    let mut mismatched = Vec::new();
+    for path in listings(None) {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        mismatched.extend(mismatches(&fs::read(&path).unwrap(), &name));
+    }
+    assert!(mismatched.is_empty(), "{}", mismatched.join("\n"));
+}
+
+#[test]
+fn random_code_decodes_as_iced_does() {
+    let mismatched = mismatches(&random_instructions(20_000), "random");
+    assert!(mismatched.is_empty(), "{}", mismatched.join("\n"));
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::listings;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// NASM to assemble with: `$NASM`, or `nasm` on the PATH. `None` if it can't be run, in which
+/// case the tests here pass without checking anything.
+fn nasm() -> Option<PathBuf> {
+    let nasm = env::var_os("NASM").map_or_else(|| PathBuf::from("nasm"), PathBuf::from);
+    match Command::new(&nasm).arg("-v").output() {
+        Ok(output) if output.status.success() => Some(nasm),
+        _ => {
+            eprintln!("skipping: {} can't be run", nasm.display());
+            None
+        }
+    }
+}
+
+fn assemble(nasm: &Path, source: &Path, binary: &Path) -> Vec<u8> {
+    let output = Command::new(nasm)
+        .args(["-f", "bin", "-o"])
+        .arg(binary)
+        .arg(source)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed on {}:\n{}",
+        nasm.display(),
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    fs::read(binary).unwrap()
+}
+
+/// The course listings' sources, in name order.
+#[test]
+fn listings_reassemble_from_their_disassembly() {
+    let Some(nasm) = nasm() else { return };
+    let directory = env::temp_dir().join(format!("ce-round-trip-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    for source in listings(Some("asm")) {
+        let name = source.file_stem().unwrap().to_string_lossy();
+        let original = directory.join(format!("{name}.bin"));
+        let disassembly = directory.join(format!("{name}.asm"));
+        let reassembled = directory.join(format!("{name}.out"));
+
+        let expected = assemble(&nasm, &source, &original);
+        let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+            .arg("disasm")
+            .arg(&original)
+            .arg("-o")
+            .arg(&disassembly)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "disassembling {name} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let actual = assemble(&nasm, &disassembly, &reassembled);
+        assert!(
+            expected == actual,
+            "{name} reassembles differently: see {}",
+            disassembly.display()
+        );
+    }
+
+    let _ = fs::remove_dir_all(&directory);
+}
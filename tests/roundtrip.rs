@@ -0,0 +1,70 @@
+use std::{
+    env, fs,
+    path::Path,
+    process::{self, Command},
+};
+
+fn roundtrip(path: &Path) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ce"))
+        .arg("roundtrip")
+        .arg(path)
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn listings_round_trip() {
+    let listing =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("listings/listing_0042_completionist_decode");
+    let (status, stdout) = roundtrip(&listing);
+    assert_eq!(status, Some(0), "{stdout}");
+    assert!(
+        stdout.ends_with(": OK (852 bytes, 329 instructions)\n"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn longer_encodings_are_listed() {
+    let path = env::temp_dir().join(format!("ce-roundtrip-{}.bin", process::id()));
+    // mov ax, [bx + 0]; add ax, word 5; hlt
+    fs::write(&path, [0x8B, 0x47, 0x00, 0x81, 0xC0, 0x05, 0x00, 0xF4]).unwrap();
+    let (status, stdout) = roundtrip(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(status, Some(1), "{stdout}");
+    assert!(
+        stdout.contains(": 2 of 3 instructions reassemble differently (8 bytes in, 6 bytes out)"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(
+            "  0x0003 ADD AX, 5\n    original:    81 c0 05 00\n    reassembled: 83 c0 05\n"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn differences_are_found_from_each_line_of_text() {
+    let path = env::temp_dir().join(format!("ce-roundtrip-label-{}.bin", process::id()));
+    // jnz past; mov ax, [bx + 0]; past: hlt
+    fs::write(&path, [0x75, 0x03, 0x8B, 0x47, 0x00, 0xF4]).unwrap();
+    let (status, stdout) = roundtrip(&path);
+    fs::remove_file(&path).unwrap();
+
+    // The jump's label is defined for its line alone, so only the MOV differs, though the
+    // whole program reassembled is a byte shorter.
+    assert_eq!(status, Some(1), "{stdout}");
+    assert!(
+        stdout.ends_with(
+            ": 1 of 3 instructions reassemble differently (6 bytes in, 5 bytes out)\n  0x0002 \
+             MOV AX, [BX]\n    original:    8b 47 00\n    reassembled: 8b 07\n"
+        ),
+        "{stdout}"
+    );
+}
@@ -0,0 +1,114 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Child, ChildStderr, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// `ce serve` on a free port, killed when dropped.
+struct Server {
+    child: Child,
+    address: String,
+    /// Kept open so that logging to it doesn't fail.
+    _stderr: BufReader<ChildStderr>,
+}
+
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ce"))
+            .args(["serve", "--listen", "127.0.0.1:0"])
+            .args(args)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+        stderr.read_line(&mut line).unwrap();
+        let address = line
+            .trim()
+            .strip_prefix("listening on http://")
+            .and_then(|url| url.strip_suffix("/disasm"))
+            .unwrap_or_else(|| panic!("unexpected startup line {line:?}"))
+            .to_string();
+        Server {
+            child,
+            address,
+            _stderr: stderr,
+        }
+    }
+
+    /// Sends a request and returns the status and body of the response.
+    fn request(&self, method: &str, target: &str, body: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(&self.address).unwrap();
+        write!(
+            stream,
+            "{method} {target} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n",
+            self.address,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn disassembles_posted_bytes_to_json() {
+    let server = Server::start(&[]);
+
+    // mov cx, bx; jne $
+    let (status, body) = server.request("POST", "/disasm", &[0x89, 0xD9, 0x75, 0xFE]);
+    assert_eq!(status, 200, "{body}");
+    assert!(body.contains("\"text\": \"MOV CX, BX\""), "{body}");
+    assert!(body.contains("\"target\": 2"), "{body}");
+
+    let (status, body) = server.request("POST", "/disasm?encoding=base64&origin=0x100", b"idk=");
+    assert_eq!(status, 200, "{body}");
+    assert!(body.contains("\"offset\": 256"), "{body}");
+
+    let (status, body) = server.request("POST", "/disasm", &[0x89]);
+    assert_eq!(status, 422);
+    assert!(body.starts_with("{\"error\": "), "{body}");
+
+    assert_eq!(
+        server.request("POST", "/disasm?encoding=base64", b"i!").0,
+        400
+    );
+    assert_eq!(server.request("GET", "/disasm", b"").0, 405);
+    assert_eq!(server.request("POST", "/elsewhere", b"").0, 404);
+}
+
+#[test]
+fn a_request_sent_a_little_at_a_time_is_cut_off() {
+    let server = Server::start(&["--timeout", "1"]);
+    let mut stream = TcpStream::connect(&server.address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let start = Instant::now();
+    write!(stream, "POST /disasm HTTP/1.1\r\n").unwrap();
+    // A header every 200ms, so no one read waits long, but the request never ends.
+    let mut response = Vec::new();
+    while start.elapsed() < Duration::from_secs(5) {
+        match stream.read_to_end(&mut response) {
+            Ok(_) => break,
+            Err(_) => {
+                let _ = write!(stream, "X-Slow: 1\r\n");
+            }
+        }
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
@@ -0,0 +1,468 @@
+mod common;
+
+use common::listings;
+use computer_enhance::{
+    decoder::Decoder,
+    instruction::{Instruction, Operand, Rep},
+};
+use std::{
+    env,
+    ffi::{c_char, c_void, CStr, CString},
+    fs,
+    path::Path,
+};
+
+// The structures of sim86_shared.h, the header of the reference decoder's shared library.
+// They match the version below; anything else is skipped rather than misread.
+const SIM86_VERSION: u32 = 4;
+
+const INST_LOCK: u32 = 0x1;
+const INST_REP: u32 = 0x2;
+const INST_SEGMENT: u32 = 0x4;
+const INST_WIDE: u32 = 0x8;
+const INST_FAR: u32 = 0x10;
+const INST_REPNE: u32 = 0x20;
+
+const OPERAND_NONE: u32 = 0;
+const OPERAND_REGISTER: u32 = 1;
+const OPERAND_MEMORY: u32 = 2;
+const OPERAND_IMMEDIATE: u32 = 3;
+
+const IMMEDIATE_RELATIVE_JUMP_DISPLACEMENT: u32 = 0x1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct RegisterAccess {
+    index: u32,
+    offset: u32,
+    count: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct EffectiveAddressTerm {
+    register: RegisterAccess,
+    scale: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct EffectiveAddressExpression {
+    terms: [EffectiveAddressTerm; 2],
+    explicit_segment: u32,
+    displacement: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Immediate {
+    value: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union OperandPayload {
+    address: EffectiveAddressExpression,
+    register: RegisterAccess,
+    immediate: Immediate,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstructionOperand {
+    kind: u32,
+    payload: OperandPayload,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Sim86Instruction {
+    address: u32,
+    size: u32,
+    op: u32,
+    flags: u32,
+    operands: [InstructionOperand; 2],
+    segment_override: u32,
+}
+
+impl Default for Sim86Instruction {
+    fn default() -> Self {
+        let operand = InstructionOperand {
+            kind: OPERAND_NONE,
+            payload: OperandPayload {
+                address: EffectiveAddressExpression::default(),
+            },
+        };
+        Sim86Instruction {
+            address: 0,
+            size: 0,
+            op: 0,
+            flags: 0,
+            operands: [operand; 2],
+            segment_override: 0,
+        }
+    }
+}
+
+/// The library's entry points.
+struct Sim86 {
+    decode: unsafe extern "C" fn(u32, *const u8, *mut Sim86Instruction),
+    register_name: unsafe extern "C" fn(*const RegisterAccess) -> *const c_char,
+    mnemonic: unsafe extern "C" fn(u32) -> *const c_char,
+}
+
+impl Sim86 {
+    /// Loads the library at `path`, or says why it can't be used.
+    fn load(path: &Path) -> Result<Sim86, String> {
+        let library = platform::open(path)?;
+        let symbol = |name: &str| platform::symbol(library, name);
+        // SAFETY: the symbols are the library's functions with the signatures its header gives.
+        unsafe {
+            let version: unsafe extern "C" fn() -> u32 =
+                std::mem::transmute(symbol("Sim86_GetVersion")?);
+            if version() != SIM86_VERSION {
+                return Err(format!(
+                    "it's version {}, and only version {SIM86_VERSION} is understood",
+                    version()
+                ));
+            }
+            let decode: unsafe extern "C" fn(u32, *const u8, *mut Sim86Instruction) =
+                std::mem::transmute(symbol("Sim86_Decode8086Instruction")?);
+            let register_name: unsafe extern "C" fn(*const RegisterAccess) -> *const c_char =
+                std::mem::transmute(symbol("Sim86_RegisterNameFromOperand")?);
+            let mnemonic: unsafe extern "C" fn(u32) -> *const c_char =
+                std::mem::transmute(symbol("Sim86_MnemonicFromOperationType")?);
+            Ok(Sim86 {
+                decode,
+                register_name,
+                mnemonic,
+            })
+        }
+    }
+
+    /// The library's decoding of the instruction at the start of `bytes`, in the form
+    /// `describe` gives, or `None` if it couldn't decode one.
+    fn describe(&self, bytes: &[u8]) -> Option<String> {
+        let mut decoded = Sim86Instruction::default();
+        // SAFETY: the library reads at most `bytes.len()` bytes and writes one instruction.
+        unsafe { (self.decode)(bytes.len() as u32, bytes.as_ptr(), &mut decoded) };
+        if decoded.op == 0 || decoded.size == 0 {
+            return None;
+        }
+        let string = |pointer: *const c_char| {
+            // SAFETY: the library returns static NUL-terminated names.
+            unsafe { CStr::from_ptr(pointer) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        // SAFETY: as `string`, for the register access given.
+        let register = |access: &RegisterAccess| string(unsafe { (self.register_name)(access) });
+
+        let wide = decoded.flags & INST_WIDE != 0;
+        let mut operands = Vec::new();
+        for operand in &decoded.operands {
+            // SAFETY: `kind` says which member of the payload is set.
+            let text = unsafe {
+                match operand.kind {
+                    OPERAND_REGISTER => register(&operand.payload.register),
+                    OPERAND_MEMORY => {
+                        let address = operand.payload.address;
+                        let mut terms: Vec<String> = address
+                            .terms
+                            .iter()
+                            .filter(|term| term.register.index != 0)
+                            .map(|term| register(&term.register))
+                            .collect();
+                        terms.push(format!("{}", address.displacement as u16));
+                        format!("[{}]", terms.join("+"))
+                    }
+                    OPERAND_IMMEDIATE => {
+                        let immediate = operand.payload.immediate;
+                        if immediate.flags & IMMEDIATE_RELATIVE_JUMP_DISPLACEMENT != 0 {
+                            format!("${:+}", immediate.value)
+                        } else {
+                            format!("{}", immediate.value as u16 & mask(wide))
+                        }
+                    }
+                    _ => continue,
+                }
+            };
+            operands.push(text);
+        }
+        let segment = (decoded.flags & INST_SEGMENT != 0).then(|| {
+            register(&RegisterAccess {
+                index: decoded.segment_override,
+                offset: 0,
+                count: 2,
+            })
+        });
+        Some(describe(
+            decoded.size as usize,
+            &string(unsafe { (self.mnemonic)(decoded.op) }),
+            [
+                decoded.flags & INST_LOCK != 0,
+                decoded.flags & INST_REP != 0,
+                decoded.flags & INST_REPNE != 0,
+                wide,
+                decoded.flags & INST_FAR != 0,
+            ],
+            segment,
+            &operands,
+        ))
+    }
+}
+
+fn mask(wide: bool) -> u16 {
+    if wide {
+        0xFFFF
+    } else {
+        0xFF
+    }
+}
+
+/// One line holding every field the two decoders are compared on.
+fn describe(
+    size: usize,
+    mnemonic: &str,
+    [lock, rep, repne, wide, far]: [bool; 5],
+    segment: Option<String>,
+    operands: &[String],
+) -> String {
+    format!(
+        "{size} bytes: {mnemonic} {} | lock={lock} rep={rep} repne={repne} wide={wide} \
+         far={far} segment={}",
+        operands.join(", "),
+        segment.as_deref().unwrap_or("-")
+    )
+}
+
+/// This crate's decoding of `instruction` in the form `Sim86::describe` gives.
+fn describe_ours(instruction: &Instruction) -> String {
+    let operands: Vec<String> = instruction
+        .operands
+        .iter()
+        .flatten()
+        .map(|operand| match *operand {
+            Operand::Register(register) => register.name().to_string(),
+            Operand::Memory(address) => {
+                let mut terms: Vec<String> = (address.base.registers().iter())
+                    .map(|register| register.name().to_string())
+                    .collect();
+                terms.push(format!("{}", address.displacement as u16));
+                format!("[{}]", terms.join("+"))
+            }
+            Operand::Immediate(value) => format!("{}", value as u16 & mask(instruction.wide)),
+            Operand::Relative(displacement) => format!("${displacement:+}"),
+            Operand::Far { segment, offset } => format!("{segment}:{offset}"),
+        })
+        .collect();
+    describe(
+        instruction.length,
+        instruction.mnemonic.name(),
+        [
+            instruction.lock,
+            instruction.rep == Some(Rep::Rep),
+            instruction.rep == Some(Rep::Repne),
+            instruction.wide,
+            instruction.far,
+        ],
+        instruction
+            .segment_override
+            .map(|register| register.name().to_string()),
+        &operands,
+    )
+}
+
+/// The library named by `$SIM86_LIBRARY`, or `None` if that's unset or it can't be used, in
+/// which case the tests here pass without checking anything.
+fn sim86() -> Option<Sim86> {
+    let Some(path) = env::var_os("SIM86_LIBRARY") else {
+        eprintln!("skipping: set SIM86_LIBRARY to the path of the sim86 shared library");
+        return None;
+    };
+    match Sim86::load(Path::new(&path)) {
+        Ok(sim86) => Some(sim86),
+        Err(message) => {
+            eprintln!("skipping: {}: {message}", Path::new(&path).display());
+            None
+        }
+    }
+}
+
+/// The length and mnemonic at the start of a description.
+fn head(description: &str) -> String {
+    description
+        .splitn(4, ' ')
+        .take(3)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compares this crate's decoding of `code` with the library's, instruction by instruction,
+/// and describes the first few that differ.
+fn mismatches(sim86: &Sim86, code: &[u8], name: &str) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let mut decoder = Decoder::new(code);
+    while let Some(instruction) = decoder.next_instruction().unwrap() {
+        let mut ours = describe_ours(&instruction);
+        let mut theirs = sim86.describe(&code[instruction.offset..]);
+        // The library's layout of a direct far target isn't part of its header's contract, so
+        // those are compared on length and mnemonic alone.
+        if (instruction.operands.iter()).any(|operand| matches!(operand, Some(Operand::Far { .. })))
+        {
+            ours = head(&ours);
+            theirs = theirs.as_deref().map(head);
+        }
+        if theirs.as_ref() != Some(&ours) {
+            mismatches.push(format!(
+                "{name} {:#x}:\n  ours:   {ours}\n  theirs: {}",
+                instruction.offset,
+                theirs.as_deref().unwrap_or("nothing")
+            ));
+        }
+    }
+    mismatches.truncate(20);
+    mismatches
+}
+
+/// Instructions this crate decodes from random bytes, back to back.
+fn random_instructions(count: usize) -> Vec<u8> {
+    // xorshift64, seeded so failures reproduce.
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut code = Vec::new();
+    for _ in 0..count {
+        loop {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let bytes = state.to_le_bytes();
+            if let Ok(Some(instruction)) = Decoder::new(&bytes).next_instruction() {
+                code.extend_from_slice(&bytes[..instruction.length]);
+                break;
+            }
+        }
+    }
+    code
+}
+
+#[test]
+fn listings_decode_as_the_reference_library_does() {
+    let Some(sim86) = sim86() else { return };
+    let mut mismatched = Vec::new();
+    for path in listings(None) {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        mismatched.extend(mismatches(&sim86, &fs::read(&path).unwrap(), &name));
+    }
+    assert!(mismatched.is_empty(), "{}", mismatched.join("\n"));
+}
+
+#[test]
+fn random_code_decodes_as_the_reference_library_does() {
+    let Some(sim86) = sim86() else { return };
+    let mismatched = mismatches(&sim86, &random_instructions(20_000), "random");
+    assert!(mismatched.is_empty(), "{}", mismatched.join("\n"));
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::*;
+    use std::{ffi::c_int, os::unix::ffi::OsStrExt};
+
+    const RTLD_NOW: c_int = 2;
+
+    #[link(name = "dl")]
+    extern "C" {
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *const c_char;
+    }
+
+    fn error() -> String {
+        // SAFETY: dlerror returns a NUL-terminated message, or null if there's none.
+        unsafe {
+            let message = dlerror();
+            if message.is_null() {
+                "unknown error".to_string()
+            } else {
+                CStr::from_ptr(message).to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    /// Loads the library at `path`, which stays loaded for the rest of the process.
+    pub fn open(path: &Path) -> Result<*mut c_void, String> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|error| error.to_string())?;
+        // SAFETY: `path` is NUL-terminated.
+        let library = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+        if library.is_null() {
+            Err(error())
+        } else {
+            Ok(library)
+        }
+    }
+
+    pub fn symbol(library: *mut c_void, name: &str) -> Result<*mut c_void, String> {
+        let name = CString::new(name).unwrap();
+        // SAFETY: `library` came from dlopen and `name` is NUL-terminated.
+        let symbol = unsafe { dlsym(library, name.as_ptr()) };
+        if symbol.is_null() {
+            Err(error())
+        } else {
+            Ok(symbol)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::{iter, os::windows::ffi::OsStrExt};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    /// Loads the library at `path`, which stays loaded for the rest of the process.
+    pub fn open(path: &Path) -> Result<*mut c_void, String> {
+        let path: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        // SAFETY: `path` is NUL-terminated.
+        let library = unsafe { LoadLibraryW(path.as_ptr()) };
+        if library.is_null() {
+            Err(std::io::Error::last_os_error().to_string())
+        } else {
+            Ok(library)
+        }
+    }
+
+    pub fn symbol(library: *mut c_void, name: &str) -> Result<*mut c_void, String> {
+        let name = CString::new(name).unwrap();
+        // SAFETY: `library` came from LoadLibraryW and `name` is NUL-terminated.
+        let symbol = unsafe { GetProcAddress(library, name.as_ptr()) };
+        if symbol.is_null() {
+            Err(std::io::Error::last_os_error().to_string())
+        } else {
+            Ok(symbol)
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::*;
+
+    pub fn open(_: &Path) -> Result<*mut c_void, String> {
+        Err("loading libraries isn't supported on this OS".to_string())
+    }
+
+    pub fn symbol(_: *mut c_void, _: &str) -> Result<*mut c_void, String> {
+        unreachable!()
+    }
+}